@@ -1,7 +1,9 @@
-use corrmatch::lowlevel::rotate_u8_bilinear_masked;
+use corrmatch::lowlevel::{
+    rotate_u8_bilinear_masked, scan_masked_zncc_scalar_full, scan_zncc_scalar_full,
+};
 use corrmatch::{
-    CompileConfig, CompileConfigNoRot, CompiledTemplate, ImageView, MatchConfig, Matcher, Metric,
-    RotationMode, Template,
+    CompileConfig, CompileConfigNoRot, CompiledTemplate, FillStrategy, ImagePyramid, ImageView,
+    MatchConfig, Matcher, Metric, RotationMode, Template, TemplateBatch,
 };
 use criterion::{criterion_group, criterion_main, Criterion};
 use std::hint::black_box;
@@ -35,6 +37,7 @@ fn extract_patch(
     out
 }
 
+#[allow(deprecated)]
 fn bench_matcher(c: &mut Criterion) {
     let img_width = 512;
     let img_height = 512;
@@ -89,6 +92,44 @@ fn bench_matcher(c: &mut Criterion) {
         b.iter(|| black_box(matcher_ssd_unmasked.match_image(image_view).unwrap()));
     });
 
+    // Low-amplitude noise keeps the background above `min_var_i`, so only the
+    // variance-ratio gate can skip it. A shallow pyramid keeps the coarse
+    // scan dominant.
+    let mut flat = (0..img_width * img_height)
+        .map(|i| 128 + ((i * 7 + i / img_width * 3) % 5) as u8)
+        .collect::<Vec<_>>();
+    for y in 0..tpl_height {
+        let row = (tpl_y0 + y) * img_width + tpl_x0;
+        flat[row..row + tpl_width].copy_from_slice(&tpl_data[y * tpl_width..(y + 1) * tpl_width]);
+    }
+    let flat_view = ImageView::from_slice(&flat, img_width, img_height).unwrap();
+    for (name, ratio) in [
+        ("zncc_unmasked_flat_background", None),
+        ("zncc_unmasked_flat_background_variance_gate", Some(4.0)),
+    ] {
+        let matcher = Matcher::new(
+            CompiledTemplate::compile_unrotated(&template, CompileConfigNoRot { max_levels: 2 })
+                .unwrap(),
+        )
+        .with_config(MatchConfig {
+            metric: Metric::Zncc,
+            rotation: RotationMode::Disabled,
+            parallel: false,
+            max_image_levels: 2,
+            beam_width: 6,
+            coarse_per_angle_topk: 3,
+            refine_per_angle_topk: 3,
+            roi_radius: 6,
+            nms_radius: 4,
+            angle_half_range_steps: 1,
+            coarse_variance_ratio: ratio,
+            ..MatchConfig::default()
+        });
+        c.bench_function(name, |b| {
+            b.iter(|| black_box(matcher.match_image(flat_view).unwrap()));
+        });
+    }
+
     if cfg!(feature = "rayon") {
         let matcher_unmasked_par = Matcher::new(
             CompiledTemplate::compile_unrotated(&template, CompileConfigNoRot { max_levels: 4 })
@@ -134,6 +175,7 @@ fn bench_matcher(c: &mut Criterion) {
             min_step_deg: 7.5,
             fill_value: 0,
             precompute_coarsest: true,
+            ..CompileConfig::default()
         },
     )
     .unwrap();
@@ -150,6 +192,32 @@ fn bench_matcher(c: &mut Criterion) {
         ..MatchConfig::default()
     });
 
+    let zero_idx = matcher_rot
+        .compiled()
+        .angle_grid(0)
+        .unwrap()
+        .nearest_index(0.0);
+    let masked_zero = matcher_rot
+        .compiled()
+        .rotated_zncc_plan(0, zero_idx)
+        .unwrap();
+    let unmasked_zero = matcher_rot.compiled().unmasked_zncc_plan(0).unwrap();
+    c.bench_function("zncc_zero_angle_slot_masked_kernel", |b| {
+        b.iter(|| {
+            black_box(
+                scan_masked_zncc_scalar_full(image_view, masked_zero, zero_idx, 3, 1e-8, -1.0)
+                    .unwrap(),
+            )
+        });
+    });
+    c.bench_function("zncc_zero_angle_slot_unmasked_kernel", |b| {
+        b.iter(|| {
+            black_box(
+                scan_zncc_scalar_full(image_view, unmasked_zero, zero_idx, 3, 1e-8, -1.0).unwrap(),
+            )
+        });
+    });
+
     c.bench_function("zncc_masked_rotation_on", |b| {
         b.iter(|| black_box(matcher_rot.match_image(image_rot_view).unwrap()));
     });
@@ -163,6 +231,7 @@ fn bench_matcher(c: &mut Criterion) {
                 min_step_deg: 7.5,
                 fill_value: 0,
                 precompute_coarsest: true,
+                ..CompileConfig::default()
             },
         )
         .unwrap(),
@@ -184,6 +253,69 @@ fn bench_matcher(c: &mut Criterion) {
         b.iter(|| black_box(matcher_ssd_rot.match_image(image_rot_view).unwrap()));
     });
 
+    let compiled_rot_packed = CompiledTemplate::compile_rotated(
+        &template,
+        CompileConfig {
+            max_levels: 4,
+            coarse_step_deg: 30.0,
+            min_step_deg: 7.5,
+            fill_strategy: FillStrategy::Constant(0),
+            precompute_coarsest: true,
+            compact_mask: true,
+            ..CompileConfig::default()
+        },
+    )
+    .unwrap();
+    let rot_idx = compiled_rot_packed
+        .angle_grid(0)
+        .unwrap()
+        .nearest_index(rotated_angle);
+    let masked_rot = matcher_rot
+        .compiled()
+        .rotated_zncc_plan(0, rot_idx)
+        .unwrap();
+    let masked_rot_packed = compiled_rot_packed.rotated_zncc_plan(0, rot_idx).unwrap();
+    c.bench_function("zncc_rotated_slot_masked_kernel", |b| {
+        b.iter(|| {
+            black_box(
+                scan_masked_zncc_scalar_full(image_rot_view, masked_rot, rot_idx, 3, 1e-8, -1.0)
+                    .unwrap(),
+            )
+        });
+    });
+    c.bench_function("zncc_rotated_slot_masked_kernel_compact_mask", |b| {
+        b.iter(|| {
+            black_box(
+                scan_masked_zncc_scalar_full(
+                    image_rot_view,
+                    masked_rot_packed,
+                    rot_idx,
+                    3,
+                    1e-8,
+                    -1.0,
+                )
+                .unwrap(),
+            )
+        });
+    });
+
+    let matcher_rot_packed = Matcher::new(compiled_rot_packed).with_config(MatchConfig {
+        metric: Metric::Zncc,
+        rotation: RotationMode::Enabled,
+        parallel: false,
+        max_image_levels: 4,
+        beam_width: 6,
+        coarse_per_angle_topk: 3,
+        refine_per_angle_topk: 3,
+        roi_radius: 6,
+        nms_radius: 4,
+        angle_half_range_steps: 1,
+        ..MatchConfig::default()
+    });
+    c.bench_function("zncc_masked_rotation_on_compact_mask", |b| {
+        b.iter(|| black_box(matcher_rot_packed.match_image(image_rot_view).unwrap()));
+    });
+
     if cfg!(feature = "rayon") {
         let matcher_rot_par = Matcher::new(
             CompiledTemplate::compile_rotated(
@@ -194,6 +326,7 @@ fn bench_matcher(c: &mut Criterion) {
                     min_step_deg: 7.5,
                     fill_value: 0,
                     precompute_coarsest: true,
+                    ..CompileConfig::default()
                 },
             )
             .unwrap(),
@@ -217,5 +350,89 @@ fn bench_matcher(c: &mut Criterion) {
     }
 }
 
-criterion_group!(benches, bench_matcher);
+fn bench_pyramid(c: &mut Criterion) {
+    // A 4K frame, where the level-0 downsample dominates pyramid construction.
+    let img_width = 3840;
+    let img_height = 2160;
+    let image = make_image(img_width, img_height);
+    let image_view = ImageView::from_slice(&image, img_width, img_height).unwrap();
+
+    c.bench_function("pyramid_4k", |b| {
+        b.iter(|| black_box(ImagePyramid::build_u8(image_view, 6).unwrap()));
+    });
+
+    #[cfg(feature = "rayon")]
+    c.bench_function("pyramid_4k_parallel", |b| {
+        b.iter(|| black_box(ImagePyramid::build_u8_par(image_view, 6).unwrap()));
+    });
+}
+
+fn bench_many_small_templates(c: &mut Criterion) {
+    // Fiducial-style workload: 200 tiny templates matched against one frame.
+    let img_width = 320;
+    let img_height = 240;
+    let image = make_image(img_width, img_height);
+    let image_view = ImageView::from_slice(&image, img_width, img_height).unwrap();
+
+    let size = 16;
+    let templates: Vec<Template> = (0..200)
+        .map(|i| {
+            let x0 = (i * 37) % (img_width - size);
+            let y0 = (i * 23) % (img_height - size);
+            let data = extract_patch(&image, img_width, x0, y0, size, size);
+            Template::new(data, size, size).unwrap()
+        })
+        .collect();
+    let compile = CompileConfigNoRot { max_levels: 2 };
+    let cfg = MatchConfig {
+        max_image_levels: 2,
+        ..MatchConfig::default()
+    };
+
+    let matchers: Vec<Matcher> = templates
+        .iter()
+        .map(|template| {
+            let compiled = CompiledTemplate::compile_unrotated(template, compile.clone()).unwrap();
+            Matcher::new(compiled).with_config(cfg.clone())
+        })
+        .collect();
+    let batch = TemplateBatch::compile_unrotated(&templates, compile, cfg.clone()).unwrap();
+
+    let mut group = c.benchmark_group("many_small_templates");
+    group.sample_size(10);
+    group.bench_function("per_template", |b| {
+        b.iter(|| {
+            for matcher in &matchers {
+                let _ = black_box(matcher.match_image(image_view));
+            }
+        });
+    });
+    group.bench_function("batch", |b| {
+        b.iter(|| black_box(batch.match_image(image_view).unwrap()));
+    });
+
+    #[cfg(feature = "rayon")]
+    {
+        let batch_par = TemplateBatch::compile_unrotated(
+            &templates,
+            CompileConfigNoRot { max_levels: 2 },
+            MatchConfig {
+                parallel: true,
+                ..cfg
+            },
+        )
+        .unwrap();
+        group.bench_function("batch_parallel", |b| {
+            b.iter(|| black_box(batch_par.match_image(image_view).unwrap()));
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    bench_matcher,
+    bench_pyramid,
+    bench_many_small_templates
+);
 criterion_main!(benches);
@@ -0,0 +1,72 @@
+/*
+ * C ABI for the corrmatch template matching library.
+ *
+ * Images are 8-bit grayscale buffers addressed by width, height, and stride
+ * (bytes between row starts). Failures are reported as CORRMATCH_ERR_* codes;
+ * corrmatch_last_error() returns the message for the latest failure on the
+ * calling thread.
+ */
+#ifndef CORRMATCH_H
+#define CORRMATCH_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+#define CORRMATCH_OK 0
+#define CORRMATCH_ERR_NULL_POINTER 1
+#define CORRMATCH_ERR_INVALID_INPUT 2
+#define CORRMATCH_ERR_NO_MATCH 3
+#define CORRMATCH_ERR_UNSUPPORTED 4
+#define CORRMATCH_ERR_INTERNAL 5
+
+#define CORRMATCH_METRIC_ZNCC 0
+#define CORRMATCH_METRIC_SSD 1
+
+/* Opaque matcher handle; release with corrmatch_free(). */
+typedef struct CorrmatchMatcher CorrmatchMatcher;
+
+/* Compile and match settings; start from corrmatch_config_default(). */
+typedef struct CorrmatchConfig {
+    int32_t metric;        /* CORRMATCH_METRIC_ZNCC or CORRMATCH_METRIC_SSD */
+    int32_t rotation;      /* nonzero to search over rotations */
+    uint32_t max_levels;   /* pyramid levels for template and image */
+    float coarse_step_deg; /* rotation step at the coarsest level */
+    float min_step_deg;    /* rotation step at the finest level */
+    float min_score;       /* matches below this score are rejected */
+    int32_t parallel;      /* nonzero to search in parallel */
+} CorrmatchConfig;
+
+/* Best match; the pose is valid only when status == CORRMATCH_OK. */
+typedef struct CorrmatchMatch {
+    int32_t status;
+    float x;
+    float y;
+    float angle_deg;
+    float score;
+} CorrmatchMatch;
+
+CorrmatchConfig corrmatch_config_default(void);
+
+/* Compiles a template. On success *out owns a handle; on failure *out is NULL.
+ * A NULL config uses the defaults. */
+int32_t corrmatch_compile(const uint8_t *data, size_t width, size_t height, size_t stride,
+                          const CorrmatchConfig *config, CorrmatchMatcher **out);
+
+CorrmatchMatch corrmatch_match(const CorrmatchMatcher *matcher, const uint8_t *data,
+                               size_t width, size_t height, size_t stride);
+
+/* Releases a handle; NULL is ignored. */
+void corrmatch_free(CorrmatchMatcher *matcher);
+
+/* Valid until the next failing call on the same thread; do not free. */
+const char *corrmatch_last_error(void);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* CORRMATCH_H */
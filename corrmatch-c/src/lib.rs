@@ -0,0 +1,345 @@
+//! C ABI for the corrmatch template matching library.
+//!
+//! A template is compiled into an opaque `CorrmatchMatcher` handle that wraps
+//! a `Matcher`. Images are passed as raw 8-bit grayscale buffers with an
+//! explicit stride. Every entry point reports failures through an integer
+//! status code; the message for the most recent failure on the calling thread
+//! is available from `corrmatch_last_error`. The matching C header lives in
+//! `include/corrmatch.h`.
+
+use std::cell::RefCell;
+use std::ffi::{c_char, CString};
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::ptr;
+
+use corrmatch::{
+    CompileConfig, CompileConfigNoRot, CompiledTemplate, CorrMatchError, CorrMatchResult,
+    ImageView, MatchConfig, Matcher, Metric, RotationMode, Template,
+};
+
+/// The call succeeded.
+pub const CORRMATCH_OK: i32 = 0;
+/// A required pointer argument was null.
+pub const CORRMATCH_ERR_NULL_POINTER: i32 = 1;
+/// Image dimensions, buffers, or configuration values were rejected.
+pub const CORRMATCH_ERR_INVALID_INPUT: i32 = 2;
+/// The search finished without a candidate above `min_score`.
+pub const CORRMATCH_ERR_NO_MATCH: i32 = 3;
+/// The requested mode is unavailable for this template or build.
+pub const CORRMATCH_ERR_UNSUPPORTED: i32 = 4;
+/// An unexpected internal error occurred.
+pub const CORRMATCH_ERR_INTERNAL: i32 = 5;
+
+/// ZNCC metric selector for `CorrmatchConfig.metric`.
+pub const CORRMATCH_METRIC_ZNCC: i32 = 0;
+/// SSD metric selector for `CorrmatchConfig.metric`.
+pub const CORRMATCH_METRIC_SSD: i32 = 1;
+
+thread_local! {
+    static LAST_ERROR: RefCell<CString> = RefCell::new(CString::default());
+}
+
+/// Compile and match settings accepted by `corrmatch_compile`.
+///
+/// Start from `corrmatch_config_default` and override individual fields.
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+pub struct CorrmatchConfig {
+    /// `CORRMATCH_METRIC_ZNCC` or `CORRMATCH_METRIC_SSD`.
+    pub metric: i32,
+    /// Nonzero to search over rotations.
+    pub rotation: i32,
+    /// Maximum pyramid levels for both the template and the image.
+    pub max_levels: u32,
+    /// Rotation step at the coarsest level, in degrees.
+    pub coarse_step_deg: f32,
+    /// Rotation step at the finest level, in degrees.
+    pub min_step_deg: f32,
+    /// Matches scoring below this value are rejected.
+    pub min_score: f32,
+    /// Nonzero to run the search in parallel.
+    pub parallel: i32,
+}
+
+impl Default for CorrmatchConfig {
+    fn default() -> Self {
+        let compile = CompileConfig::default();
+        let matching = MatchConfig::default();
+        Self {
+            metric: CORRMATCH_METRIC_ZNCC,
+            rotation: 0,
+            max_levels: compile.max_levels.min(u32::MAX as usize) as u32,
+            coarse_step_deg: compile.coarse_step_deg,
+            min_step_deg: compile.min_step_deg,
+            min_score: matching.min_score,
+            parallel: 0,
+        }
+    }
+}
+
+impl CorrmatchConfig {
+    fn match_config(&self) -> CorrMatchResult<MatchConfig> {
+        let metric = match self.metric {
+            CORRMATCH_METRIC_ZNCC => Metric::Zncc,
+            CORRMATCH_METRIC_SSD => Metric::Ssd,
+            _ => {
+                return Err(CorrMatchError::InvalidConfig {
+                    reason: "metric must be CORRMATCH_METRIC_ZNCC or CORRMATCH_METRIC_SSD",
+                })
+            }
+        };
+        let cfg = MatchConfig {
+            metric,
+            rotation: if self.rotation != 0 {
+                RotationMode::Enabled
+            } else {
+                RotationMode::Disabled
+            },
+            parallel: self.parallel != 0,
+            max_image_levels: self.max_levels as usize,
+            min_score: self.min_score,
+            ..MatchConfig::default()
+        };
+        cfg.validate()?;
+        Ok(cfg)
+    }
+}
+
+/// Opaque matcher handle created by `corrmatch_compile`.
+pub struct CorrmatchMatcher {
+    matcher: Matcher,
+}
+
+/// Result of `corrmatch_match`.
+///
+/// The pose fields are only meaningful when `status` is `CORRMATCH_OK`.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct CorrmatchMatch {
+    /// `CORRMATCH_OK` or one of the `CORRMATCH_ERR_*` codes.
+    pub status: i32,
+    /// Top-left x coordinate of the template placement.
+    pub x: f32,
+    /// Top-left y coordinate of the template placement.
+    pub y: f32,
+    /// Rotation angle in degrees.
+    pub angle_deg: f32,
+    /// Match score (ZNCC in [-1, 1] or negative SSD).
+    pub score: f32,
+}
+
+/// Maps a library error onto a status code.
+fn status_of(err: &CorrMatchError) -> i32 {
+    match err {
+        CorrMatchError::InvalidDimensions { .. }
+        | CorrMatchError::InvalidStride { .. }
+        | CorrMatchError::BufferTooSmall { .. }
+        | CorrMatchError::RoiOutOfBounds { .. }
+        | CorrMatchError::ImageSmallerThanTemplate { .. }
+        | CorrMatchError::InsufficientPyramidLevels { .. }
+        | CorrMatchError::DegenerateTemplate { .. }
+        | CorrMatchError::LowTemplateVariance { .. }
+        | CorrMatchError::InvalidAngleGrid { .. }
+        | CorrMatchError::InvalidConfig { .. } => CORRMATCH_ERR_INVALID_INPUT,
+        CorrMatchError::NoCandidates { .. } => CORRMATCH_ERR_NO_MATCH,
+        CorrMatchError::UnsupportedMetric { .. }
+        | CorrMatchError::RotationUnavailable { .. }
+        | CorrMatchError::RotationNotCompiled
+        | CorrMatchError::ParallelUnavailable => CORRMATCH_ERR_UNSUPPORTED,
+        CorrMatchError::IndexOutOfBounds { .. } | CorrMatchError::ImageIo { .. } => {
+            CORRMATCH_ERR_INTERNAL
+        }
+    }
+}
+
+fn set_last_error(message: &str) {
+    // Interior NULs would truncate the C string; replace them instead.
+    let message = CString::new(message.replace('\0', " ")).unwrap_or_default();
+    LAST_ERROR.with(|slot| *slot.borrow_mut() = message);
+}
+
+/// Runs `f`, recording any error or panic as the thread's last error.
+fn guard<T>(f: impl FnOnce() -> Result<T, i32>) -> Result<T, i32> {
+    match catch_unwind(AssertUnwindSafe(f)) {
+        Ok(result) => result,
+        Err(_) => {
+            set_last_error("panic inside corrmatch");
+            Err(CORRMATCH_ERR_INTERNAL)
+        }
+    }
+}
+
+fn fail(err: CorrMatchError) -> i32 {
+    set_last_error(&err.to_string());
+    status_of(&err)
+}
+
+fn null_pointer(name: &str) -> i32 {
+    set_last_error(&format!("{name} must not be null"));
+    CORRMATCH_ERR_NULL_POINTER
+}
+
+/// Borrows a caller-owned buffer as an image view.
+///
+/// # Safety
+/// `data` must be non-null and point to at least
+/// `(height - 1) * stride + width` readable bytes when the dimensions are
+/// valid.
+unsafe fn image_view<'a>(
+    data: *const u8,
+    width: usize,
+    height: usize,
+    stride: usize,
+) -> CorrMatchResult<ImageView<'a, u8>> {
+    if width == 0 || height == 0 || stride < width {
+        // Let the library report the invalid dimensions or stride.
+        return ImageView::new(&[], width, height, stride);
+    }
+    let len = (height - 1)
+        .checked_mul(stride)
+        .and_then(|rows| rows.checked_add(width))
+        .ok_or(CorrMatchError::InvalidDimensions { width, height })?;
+    // SAFETY: the caller guarantees `len` readable bytes at `data`.
+    let data = unsafe { std::slice::from_raw_parts(data, len) };
+    ImageView::new(data, width, height, stride)
+}
+
+/// Returns the default configuration.
+#[no_mangle]
+pub extern "C" fn corrmatch_config_default() -> CorrmatchConfig {
+    CorrmatchConfig::default()
+}
+
+/// Compiles a grayscale template into a matcher handle.
+///
+/// On success `*out` receives a handle that must be released with
+/// `corrmatch_free`; on failure `*out` is set to null. A null `config` uses
+/// the defaults.
+///
+/// # Safety
+/// `data` must point to at least `(height - 1) * stride + width` readable
+/// bytes, `config` must be null or point to a valid `CorrmatchConfig`, and
+/// `out` must be a valid pointer to writable storage for a handle.
+#[no_mangle]
+pub unsafe extern "C" fn corrmatch_compile(
+    data: *const u8,
+    width: usize,
+    height: usize,
+    stride: usize,
+    config: *const CorrmatchConfig,
+    out: *mut *mut CorrmatchMatcher,
+) -> i32 {
+    if out.is_null() {
+        return null_pointer("out");
+    }
+    // SAFETY: `out` is non-null and writable per the contract above.
+    unsafe { *out = ptr::null_mut() };
+    if data.is_null() {
+        return null_pointer("data");
+    }
+    // SAFETY: `config` is null or valid per the contract above.
+    let config = unsafe { config.as_ref() }.copied().unwrap_or_default();
+    let result = guard(|| {
+        // SAFETY: `data` is non-null and sized per the contract above.
+        let view = unsafe { image_view(data, width, height, stride) }.map_err(fail)?;
+        let cfg = config.match_config().map_err(fail)?;
+        let mut pixels = Vec::with_capacity(width * height);
+        for y in 0..height {
+            pixels.extend_from_slice(view.row(y).expect("row within bounds"));
+        }
+        let template = Template::new(pixels, width, height).map_err(fail)?;
+        let max_levels = config.max_levels as usize;
+        let compiled = match cfg.rotation {
+            RotationMode::Enabled => CompiledTemplate::compile_rotated(
+                &template,
+                CompileConfig {
+                    max_levels,
+                    coarse_step_deg: config.coarse_step_deg,
+                    min_step_deg: config.min_step_deg,
+                    parallel: cfg.parallel,
+                    ..CompileConfig::default()
+                },
+            ),
+            RotationMode::Disabled => {
+                CompiledTemplate::compile_unrotated(&template, CompileConfigNoRot { max_levels })
+            }
+        }
+        .map_err(fail)?;
+        Ok(Box::new(CorrmatchMatcher {
+            matcher: Matcher::new(compiled).with_config(cfg),
+        }))
+    });
+    match result {
+        Ok(handle) => {
+            // SAFETY: `out` is non-null and writable per the contract above.
+            unsafe { *out = Box::into_raw(handle) };
+            CORRMATCH_OK
+        }
+        Err(status) => status,
+    }
+}
+
+/// Matches a compiled template against a grayscale image.
+///
+/// Returns the best match; check `status` before reading the pose.
+///
+/// # Safety
+/// `matcher` must be a live handle from `corrmatch_compile`, and `data` must
+/// point to at least `(height - 1) * stride + width` readable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn corrmatch_match(
+    matcher: *const CorrmatchMatcher,
+    data: *const u8,
+    width: usize,
+    height: usize,
+    stride: usize,
+) -> CorrmatchMatch {
+    let failed = |status| CorrmatchMatch {
+        status,
+        ..CorrmatchMatch::default()
+    };
+    // SAFETY: `matcher` is null or a live handle per the contract above.
+    let Some(handle) = (unsafe { matcher.as_ref() }) else {
+        return failed(null_pointer("matcher"));
+    };
+    if data.is_null() {
+        return failed(null_pointer("data"));
+    }
+    let result = guard(|| {
+        // SAFETY: `data` is non-null and sized per the contract above.
+        let view = unsafe { image_view(data, width, height, stride) }.map_err(fail)?;
+        handle.matcher.match_image(view).map_err(fail)
+    });
+    match result {
+        Ok(m) => CorrmatchMatch {
+            status: CORRMATCH_OK,
+            x: m.x,
+            y: m.y,
+            angle_deg: m.angle_deg,
+            score: m.score,
+        },
+        Err(status) => failed(status),
+    }
+}
+
+/// Releases a matcher handle. Passing null is a no-op.
+///
+/// # Safety
+/// `matcher` must be null or a handle from `corrmatch_compile` that has not
+/// been freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn corrmatch_free(matcher: *mut CorrmatchMatcher) {
+    if !matcher.is_null() {
+        // SAFETY: the handle came from `Box::into_raw` and is freed once.
+        drop(unsafe { Box::from_raw(matcher) });
+    }
+}
+
+/// Returns the message for the most recent failure on the calling thread.
+///
+/// The string is empty if no call has failed yet. It stays valid until the
+/// next failing call on the same thread and must not be freed.
+#[no_mangle]
+pub extern "C" fn corrmatch_last_error() -> *const c_char {
+    LAST_ERROR.with(|slot| slot.borrow().as_ptr())
+}
@@ -0,0 +1,80 @@
+use std::ffi::CStr;
+use std::ptr;
+
+use corrmatch_c::{
+    corrmatch_compile, corrmatch_config_default, corrmatch_free, corrmatch_last_error,
+    corrmatch_match, CorrmatchMatcher, CORRMATCH_ERR_INVALID_INPUT, CORRMATCH_ERR_NULL_POINTER,
+    CORRMATCH_OK,
+};
+
+fn make_template(width: usize, height: usize) -> Vec<u8> {
+    let mut data = Vec::with_capacity(width * height);
+    for y in 0..height {
+        for x in 0..width {
+            let value = ((x * 13) ^ (y * 7) ^ (x * y)) & 0xFF;
+            data.push(value as u8);
+        }
+    }
+    data
+}
+
+fn last_error() -> String {
+    unsafe { CStr::from_ptr(corrmatch_last_error()) }
+        .to_string_lossy()
+        .into_owned()
+}
+
+#[test]
+fn compile_and_match_through_the_c_abi() {
+    let (tpl_width, tpl_height) = (32, 24);
+    let tpl = make_template(tpl_width, tpl_height);
+
+    // Padded rows exercise the stride handling.
+    let (img_width, img_height, stride) = (96, 72, 100);
+    let mut image = vec![0u8; stride * img_height];
+    let (x0, y0) = (41, 27);
+    for y in 0..tpl_height {
+        let row = (y0 + y) * stride + x0;
+        image[row..row + tpl_width].copy_from_slice(&tpl[y * tpl_width..(y + 1) * tpl_width]);
+    }
+
+    let config = corrmatch_config_default();
+    let mut handle: *mut CorrmatchMatcher = ptr::null_mut();
+    let status = unsafe {
+        corrmatch_compile(
+            tpl.as_ptr(),
+            tpl_width,
+            tpl_height,
+            tpl_width,
+            &config,
+            &mut handle,
+        )
+    };
+    assert_eq!(status, CORRMATCH_OK, "{}", last_error());
+    assert!(!handle.is_null());
+
+    let m = unsafe { corrmatch_match(handle, image.as_ptr(), img_width, img_height, stride) };
+    assert_eq!(m.status, CORRMATCH_OK, "{}", last_error());
+    assert!((m.x - x0 as f32).abs() < 0.5 && (m.y - y0 as f32).abs() < 0.5);
+    assert!(m.score > 0.99);
+
+    let bad_stride = unsafe { corrmatch_match(handle, image.as_ptr(), img_width, img_height, 10) };
+    assert_eq!(bad_stride.status, CORRMATCH_ERR_INVALID_INPUT);
+    assert!(last_error().contains("stride"), "{}", last_error());
+
+    unsafe { corrmatch_free(handle) };
+}
+
+#[test]
+fn null_arguments_are_reported() {
+    let mut handle: *mut CorrmatchMatcher = ptr::null_mut();
+    let status = unsafe { corrmatch_compile(ptr::null(), 8, 8, 8, ptr::null(), &mut handle) };
+    assert_eq!(status, CORRMATCH_ERR_NULL_POINTER);
+    assert!(handle.is_null());
+    assert!(last_error().contains("data"));
+
+    let data = [0u8; 64];
+    let m = unsafe { corrmatch_match(ptr::null(), data.as_ptr(), 8, 8, 8) };
+    assert_eq!(m.status, CORRMATCH_ERR_NULL_POINTER);
+    unsafe { corrmatch_free(ptr::null_mut()) };
+}
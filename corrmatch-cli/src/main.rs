@@ -1,16 +1,22 @@
-use clap::Parser;
+use clap::{Parser, ValueEnum};
 use corrmatch::io::load_gray_image;
 use corrmatch::{
-    CompileConfig, CompileConfigNoRot, CompiledTemplate, Match, MatchConfig, Matcher, Metric,
-    RotationMode, Template,
+    AngleRefine, AngleSchedule, CompileConfig, CompileConfigNoRot, CompileOptions,
+    CompiledTemplate, CorrMatchResult, FillStrategy, Match, MatchConfig, Matcher, Metric,
+    PyramidFilter, RotationMode, ScoreAgg, ScoreClamp, Template, TieBreak,
 };
 use serde::{Deserialize, Serialize};
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 const SCHEMA_JSON: &str = include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/config.schema.json"));
 const EXAMPLE_JSON: &str =
     include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/config.example.json"));
+const IMAGE_EXTENSIONS: &[&str] = &["png", "jpg", "jpeg"];
+/// Category assigned to every COCO detection.
+const COCO_CATEGORY_ID: u32 = 1;
+/// Intensity of the match outlines drawn by `--visualize`.
+const OVERLAY_VALUE: u8 = 255;
 
 #[derive(Parser, Debug)]
 #[command(author, version, about = "CorrMatch CLI (JSON config driven)")]
@@ -24,6 +30,30 @@ struct Cli {
     /// Print an example config and exit.
     #[arg(long)]
     print_example: bool,
+    /// Include the rotated template corners in each match record.
+    #[arg(long)]
+    with_corners: bool,
+    /// Output format; overrides `output_format` in the config.
+    #[arg(long, value_enum)]
+    output_format: Option<OutputFormat>,
+    /// Write the grayscale input image with each match's rotated outline
+    /// drawn on it (single image only).
+    #[arg(long, value_name = "PATH")]
+    visualize: Option<PathBuf>,
+    /// Warn when the coarse rotation step is wider than the template's
+    /// correlation lobe; tracing the lobe rotates the template many times.
+    #[arg(long)]
+    check_coarse_step: bool,
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq, ValueEnum)]
+#[serde(rename_all = "snake_case")]
+enum OutputFormat {
+    /// Best match and top-k records (per image in batch mode).
+    #[default]
+    Json,
+    /// A flat list of COCO-style detections.
+    Coco,
 }
 
 #[derive(Debug, Deserialize)]
@@ -31,15 +61,7 @@ struct Cli {
 enum MetricConfig {
     Zncc,
     Ssd,
-}
-
-impl From<MetricConfig> for Metric {
-    fn from(value: MetricConfig) -> Self {
-        match value {
-            MetricConfig::Zncc => Metric::Zncc,
-            MetricConfig::Ssd => Metric::Ssd,
-        }
-    }
+    Combined,
 }
 
 #[derive(Debug, Deserialize)]
@@ -58,25 +80,151 @@ impl From<RotationModeConfig> for RotationMode {
     }
 }
 
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum ScoreClampConfig {
+    None,
+    ZeroToOne,
+}
+
+impl From<ScoreClampConfig> for ScoreClamp {
+    fn from(value: ScoreClampConfig) -> Self {
+        match value {
+            ScoreClampConfig::None => ScoreClamp::None,
+            ScoreClampConfig::ZeroToOne => ScoreClamp::ZeroToOne,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum ScoreAggConfig {
+    Finest,
+    MinAcrossLevels,
+    MeanAcrossLevels,
+}
+
+impl From<ScoreAggConfig> for ScoreAgg {
+    fn from(value: ScoreAggConfig) -> Self {
+        match value {
+            ScoreAggConfig::Finest => ScoreAgg::Finest,
+            ScoreAggConfig::MinAcrossLevels => ScoreAgg::MinAcrossLevels,
+            ScoreAggConfig::MeanAcrossLevels => ScoreAgg::MeanAcrossLevels,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum PyramidFilterConfig {
+    Box,
+    Opencv,
+}
+
+impl From<PyramidFilterConfig> for PyramidFilter {
+    fn from(value: PyramidFilterConfig) -> Self {
+        match value {
+            PyramidFilterConfig::Box => PyramidFilter::Box2x2,
+            PyramidFilterConfig::Opencv => PyramidFilter::OpenCvCompatible,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum AngleRefineConfig {
+    Quadratic,
+    GoldenSection { iters: usize },
+}
+
+impl From<AngleRefineConfig> for AngleRefine {
+    fn from(value: AngleRefineConfig) -> Self {
+        match value {
+            AngleRefineConfig::Quadratic => AngleRefine::Quadratic,
+            AngleRefineConfig::GoldenSection { iters } => AngleRefine::GoldenSection { iters },
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum TieBreakConfig {
+    TopLeftFirst,
+    NearestToCenter,
+    MostCentralAngle,
+}
+
+impl From<TieBreakConfig> for TieBreak {
+    fn from(value: TieBreakConfig) -> Self {
+        match value {
+            TieBreakConfig::TopLeftFirst => TieBreak::TopLeftFirst,
+            TieBreakConfig::NearestToCenter => TieBreak::NearestToCenter,
+            TieBreakConfig::MostCentralAngle => TieBreak::MostCentralAngle,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum FillStrategyConfig {
+    Constant,
+    EdgeClamp,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum AngleScheduleConfig {
+    Halving,
+    Geometric,
+}
+
 #[derive(Debug, Deserialize)]
 #[serde(default)]
 struct CompileConfigJson {
     max_levels: usize,
+    min_template_dim: usize,
     coarse_step_deg: f32,
     min_step_deg: f32,
+    angle_schedule: AngleScheduleConfig,
+    angle_ratio: f32,
+    fill_strategy: FillStrategyConfig,
     fill_value: u8,
     precompute_coarsest: bool,
+    angle_steps: Option<Vec<f32>>,
+    strict_coarse_step: bool,
+    compact_plans: bool,
+    compact_mask: bool,
+    min_template_variance: f32,
+    rotation_cache_capacity: Option<usize>,
 }
 
 impl Default for CompileConfigJson {
     fn default() -> Self {
         let cfg = CompileConfig::default();
+        let (fill_strategy, fill_value) = match cfg.fill_strategy {
+            FillStrategy::Constant(value) => (FillStrategyConfig::Constant, value),
+            FillStrategy::EdgeClamp => (FillStrategyConfig::EdgeClamp, 0),
+        };
+        let (angle_schedule, angle_ratio) = match cfg.angle_schedule {
+            AngleSchedule::Halving => (AngleScheduleConfig::Halving, 2.0),
+            AngleSchedule::Geometric { ratio } => (AngleScheduleConfig::Geometric, ratio),
+        };
         Self {
             max_levels: cfg.max_levels,
+            min_template_dim: cfg.min_template_dim,
             coarse_step_deg: cfg.coarse_step_deg,
             min_step_deg: cfg.min_step_deg,
-            fill_value: cfg.fill_value,
+            angle_schedule,
+            angle_ratio,
+            fill_strategy,
+            fill_value,
             precompute_coarsest: cfg.precompute_coarsest,
+            angle_steps: cfg.angle_steps,
+            strict_coarse_step: cfg.strict_coarse_step,
+            compact_plans: cfg.compact_plans,
+            compact_mask: cfg.compact_mask,
+            min_template_variance: CompileOptions::default().min_template_variance,
+            rotation_cache_capacity: cfg.rotation_cache_capacity,
         }
     }
 }
@@ -85,16 +233,44 @@ impl Default for CompileConfigJson {
 #[serde(default)]
 struct MatchConfigJson {
     metric: MetricConfig,
+    zncc_weight: f32,
+    ssd_weight: f32,
     rotation: RotationModeConfig,
     parallel: bool,
     max_image_levels: usize,
+    require_full_pyramid: bool,
+    pyramid_filter: PyramidFilterConfig,
+    initial_downscale: usize,
     beam_width: usize,
-    per_angle_topk: usize,
+    min_instances: usize,
+    /// Deprecated alias that sets both per-angle top-k values.
+    per_angle_topk: Option<usize>,
+    coarse_per_angle_topk: Option<usize>,
+    refine_per_angle_topk: Option<usize>,
     nms_radius: usize,
+    nms_keep_ratio: f32,
+    result_min_separation: Option<usize>,
+    nms_angle_separation_deg: Option<f32>,
+    tie_break: TieBreakConfig,
     roi_radius: usize,
+    level_stride: usize,
+    radius_in_level0_pixels: bool,
+    keep_runner_up_positions: bool,
     angle_half_range_steps: usize,
     min_var_i: f32,
+    min_contrast: Option<f32>,
+    coarse_variance_ratio: Option<f32>,
     min_score: f32,
+    retry_on_empty: bool,
+    allow_partial: bool,
+    score_aggregation: ScoreAggConfig,
+    score_clamp: ScoreClampConfig,
+    final_refinement: bool,
+    subpixel_resample: bool,
+    verify_full_res: bool,
+    angle_refine: AngleRefineConfig,
+    allow_inverted: bool,
+    deterministic_accumulation: bool,
 }
 
 impl Default for MatchConfigJson {
@@ -102,16 +278,43 @@ impl Default for MatchConfigJson {
         let cfg = MatchConfig::default();
         Self {
             metric: MetricConfig::Zncc,
+            zncc_weight: 0.5,
+            ssd_weight: 0.5,
             rotation: RotationModeConfig::Disabled,
             parallel: cfg.parallel,
             max_image_levels: cfg.max_image_levels,
+            require_full_pyramid: cfg.require_full_pyramid,
+            pyramid_filter: PyramidFilterConfig::Box,
+            initial_downscale: cfg.initial_downscale,
             beam_width: cfg.beam_width,
-            per_angle_topk: cfg.per_angle_topk,
+            min_instances: cfg.min_instances,
+            per_angle_topk: None,
+            coarse_per_angle_topk: None,
+            refine_per_angle_topk: None,
             nms_radius: cfg.nms_radius,
+            nms_keep_ratio: cfg.nms_keep_ratio,
+            result_min_separation: cfg.result_min_separation,
+            nms_angle_separation_deg: cfg.nms_angle_separation_deg,
+            tie_break: TieBreakConfig::TopLeftFirst,
             roi_radius: cfg.roi_radius,
+            level_stride: cfg.level_stride,
+            radius_in_level0_pixels: cfg.radius_in_level0_pixels,
+            keep_runner_up_positions: cfg.keep_runner_up_positions,
             angle_half_range_steps: cfg.angle_half_range_steps,
             min_var_i: cfg.min_var_i,
+            min_contrast: cfg.min_contrast,
+            coarse_variance_ratio: cfg.coarse_variance_ratio,
             min_score: cfg.min_score,
+            retry_on_empty: cfg.retry_on_empty,
+            allow_partial: cfg.allow_partial,
+            score_aggregation: ScoreAggConfig::Finest,
+            score_clamp: ScoreClampConfig::None,
+            final_refinement: cfg.final_refinement,
+            subpixel_resample: cfg.subpixel_resample,
+            verify_full_res: cfg.verify_full_res,
+            angle_refine: AngleRefineConfig::Quadratic,
+            allow_inverted: cfg.allow_inverted,
+            deterministic_accumulation: cfg.deterministic_accumulation,
         }
     }
 }
@@ -123,6 +326,8 @@ struct Config {
     template_path: String,
     output_path: Option<String>,
     topk: usize,
+    with_corners: bool,
+    output_format: OutputFormat,
     compile: CompileConfigJson,
     #[serde(rename = "match")]
     match_cfg: MatchConfigJson,
@@ -135,6 +340,8 @@ impl Default for Config {
             template_path: String::new(),
             output_path: None,
             topk: 1,
+            with_corners: false,
+            output_format: OutputFormat::Json,
             compile: CompileConfigJson::default(),
             match_cfg: MatchConfigJson::default(),
         }
@@ -147,15 +354,31 @@ struct MatchRecord {
     y: f32,
     angle_deg: f32,
     score: f32,
+    /// Rotated template corners, clockwise from the template's top-left.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    corners: Option<[[f32; 2]; 4]>,
 }
 
-impl From<Match> for MatchRecord {
-    fn from(value: Match) -> Self {
+impl MatchRecord {
+    /// Builds a record, adding corners when the template size is given.
+    fn new(value: Match, template_size: Option<(usize, usize)>) -> Self {
         Self {
             x: value.x,
             y: value.y,
             angle_deg: value.angle_deg,
             score: value.score,
+            corners: template_size.map(|(width, height)| value.corners(width, height)),
+        }
+    }
+
+    /// Rebuilds the match the record was made from.
+    fn to_match(&self) -> Match {
+        Match {
+            x: self.x,
+            y: self.y,
+            angle_deg: self.angle_deg,
+            score: self.score,
+            peak_sharpness: None,
         }
     }
 }
@@ -166,6 +389,249 @@ struct Output {
     topk: Vec<MatchRecord>,
 }
 
+impl Output {
+    fn from_matches(matches: Vec<Match>, template_size: Option<(usize, usize)>) -> Self {
+        let record = |m: Match| MatchRecord::new(m, template_size);
+        let best = matches.first().copied().map(record);
+        let topk = matches.into_iter().map(record).collect();
+        Self { best, topk }
+    }
+}
+
+/// Detection in the COCO results format.
+#[derive(Debug, Serialize)]
+struct CocoDetection {
+    /// Index of the image in the (sorted) batch; 0 for a single image.
+    image_id: usize,
+    category_id: u32,
+    /// Axis-aligned `[x, y, width, height]` of the rotated footprint.
+    bbox: [f32; 4],
+    score: f32,
+}
+
+impl CocoDetection {
+    /// Converts the top-k records of one image.
+    ///
+    /// ZNCC scores are mapped to [0, 1] by clamping, or by magnitude when
+    /// polarity-inverted matches are allowed.
+    fn from_output(
+        output: &Output,
+        image_id: usize,
+        (tpl_width, tpl_height): (usize, usize),
+        allow_inverted: bool,
+    ) -> Vec<Self> {
+        output
+            .topk
+            .iter()
+            .map(|record| {
+                let (min_x, min_y, max_x, max_y) = record.to_match().aabb(tpl_width, tpl_height);
+                let score = if allow_inverted {
+                    record.score.abs()
+                } else {
+                    record.score.clamp(0.0, 1.0)
+                };
+                Self {
+                    image_id,
+                    category_id: COCO_CATEGORY_ID,
+                    bbox: [min_x, min_y, max_x - min_x, max_y - min_y],
+                    score,
+                }
+            })
+            .collect()
+    }
+}
+
+/// Per-image result emitted in batch mode.
+#[derive(Debug, Serialize)]
+struct BatchRecord {
+    image_path: String,
+    best: Option<MatchRecord>,
+    topk: Vec<MatchRecord>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+/// Lists supported image files in a directory, sorted by path.
+fn list_images(dir: &Path) -> std::io::Result<Vec<PathBuf>> {
+    let mut paths = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        let supported = path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .is_some_and(|ext| IMAGE_EXTENSIONS.contains(&ext.to_ascii_lowercase().as_str()));
+        if path.is_file() && supported {
+            paths.push(path);
+        }
+    }
+    paths.sort();
+    Ok(paths)
+}
+
+/// Matches one image; `template_size` enables corner output.
+fn match_file(
+    matcher: &Matcher,
+    path: &Path,
+    topk: usize,
+    template_size: Option<(usize, usize)>,
+) -> CorrMatchResult<Output> {
+    let image = load_gray_image(path)?;
+    // `match_image_topk` rejects `allow_partial`; a single match supports it.
+    let matches = if topk == 1 && matcher.config().allow_partial {
+        vec![matcher.match_image(image.view())?]
+    } else {
+        matcher.match_image_topk(image.view(), topk)?
+    };
+    Ok(Output::from_matches(matches, template_size))
+}
+
+fn run_batch(
+    matcher: &Matcher,
+    dir: &Path,
+    topk: usize,
+    template_size: Option<(usize, usize)>,
+) -> std::io::Result<Vec<BatchRecord>> {
+    let records = list_images(dir)?
+        .into_iter()
+        .map(|path| {
+            let image_path = path.display().to_string();
+            match match_file(matcher, &path, topk, template_size) {
+                Ok(output) => BatchRecord {
+                    image_path,
+                    best: output.best,
+                    topk: output.topk,
+                    error: None,
+                },
+                Err(err) => BatchRecord {
+                    image_path,
+                    best: None,
+                    topk: Vec::new(),
+                    error: Some(err.to_string()),
+                },
+            }
+        })
+        .collect();
+    Ok(records)
+}
+
+/// Sets the pixels on the line from `from` to `to` (Bresenham), skipping
+/// those outside the `width` x `height` buffer.
+fn draw_line(pixels: &mut [u8], width: usize, height: usize, from: [i64; 2], to: [i64; 2]) {
+    let [mut x, mut y] = from;
+    let [x1, y1] = to;
+    let dx = (x1 - x).abs();
+    let dy = -(y1 - y).abs();
+    let sx = if x < x1 { 1 } else { -1 };
+    let sy = if y < y1 { 1 } else { -1 };
+    let mut err = dx + dy;
+    loop {
+        if (0..width as i64).contains(&x) && (0..height as i64).contains(&y) {
+            pixels[y as usize * width + x as usize] = OVERLAY_VALUE;
+        }
+        if x == x1 && y == y1 {
+            break;
+        }
+        let e2 = 2 * err;
+        if e2 >= dy {
+            err += dy;
+            x += sx;
+        }
+        if e2 <= dx {
+            err += dx;
+            y += sy;
+        }
+    }
+}
+
+/// Saves the image at `image_path` to `out` with the rotated outline of each
+/// record drawn on it.
+fn save_overlay(
+    image_path: &Path,
+    records: &[MatchRecord],
+    (tpl_width, tpl_height): (usize, usize),
+    out: &Path,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let image = load_gray_image(image_path)?;
+    let (width, height) = (image.width(), image.height());
+    let mut pixels = image.data().to_vec();
+    for record in records {
+        // Corners are pixel edges. The centers of the footprint's border
+        // pixels trace the footprint shrunk by half a pixel on each side,
+        // which has the same center; pixel `i` has its center at `i + 0.5`.
+        let border = Match {
+            x: record.x + 0.5,
+            y: record.y + 0.5,
+            ..record.to_match()
+        };
+        let corners = border
+            .corners(tpl_width.saturating_sub(1), tpl_height.saturating_sub(1))
+            .map(|[x, y]| [(x - 0.5).round() as i64, (y - 0.5).round() as i64]);
+        for (i, &corner) in corners.iter().enumerate() {
+            draw_line(&mut pixels, width, height, corner, corners[(i + 1) % 4]);
+        }
+    }
+    image::GrayImage::from_raw(width as u32, height as u32, pixels)
+        .ok_or("overlay buffer does not match the image size")?
+        .save(out)?;
+    Ok(())
+}
+
+/// Matches one image or every image in a directory into COCO detections.
+///
+/// Batch images that fail to match are reported on stderr and skipped. A
+/// single image is also drawn to `visualize` when given.
+fn run_coco(
+    matcher: &Matcher,
+    image_path: &Path,
+    topk: usize,
+    template_size: (usize, usize),
+    allow_inverted: bool,
+    visualize: Option<&Path>,
+) -> Result<Vec<CocoDetection>, Box<dyn std::error::Error>> {
+    if !image_path.is_dir() {
+        let output = match_file(matcher, image_path, topk, None)?;
+        if let Some(out) = visualize {
+            save_overlay(image_path, &output.topk, template_size, out)?;
+        }
+        return Ok(CocoDetection::from_output(
+            &output,
+            0,
+            template_size,
+            allow_inverted,
+        ));
+    }
+    let mut detections = Vec::new();
+    for (image_id, path) in list_images(image_path)?.into_iter().enumerate() {
+        match match_file(matcher, &path, topk, None) {
+            Ok(output) => detections.extend(CocoDetection::from_output(
+                &output,
+                image_id,
+                template_size,
+                allow_inverted,
+            )),
+            Err(err) => eprintln!("warning: {}: {err}", path.display()),
+        }
+    }
+    Ok(detections)
+}
+
+/// Warns when the coarsest rotation step may skip the template's true angle.
+fn warn_on_coarse_step(compiled: &CompiledTemplate) -> CorrMatchResult<()> {
+    let Some(recommended) = compiled.recommended_max_coarse_step()? else {
+        return Ok(());
+    };
+    let coarsest = compiled.num_levels().saturating_sub(1);
+    let step = compiled
+        .angle_grid(coarsest)
+        .map_or(0.0, |grid| grid.step_deg());
+    if step > recommended {
+        eprintln!(
+            "warning: coarse rotation step {step:.1} deg exceeds the recommended {recommended:.1} deg for this template; matches may be missed"
+        );
+    }
+    Ok(())
+}
+
 fn main() -> Result<(), Box<dyn std::error::Error>> {
     let cli = Cli::parse();
     if cli.print_schema {
@@ -185,8 +651,15 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     if config.topk == 0 {
         return Err("topk must be at least 1".into());
     }
+    let output_format = cli.output_format.unwrap_or(config.output_format);
+    if output_format == OutputFormat::Coco && !matches!(config.match_cfg.metric, MetricConfig::Zncc)
+    {
+        return Err("coco output requires the zncc metric".into());
+    }
+    if cli.visualize.is_some() && Path::new(&config.image_path).is_dir() {
+        return Err("--visualize needs a single image, not a directory".into());
+    }
 
-    let image = load_gray_image(&config.image_path)?;
     let template_img = load_gray_image(&config.template_path)?;
     let template = Template::new(
         template_img.data().to_vec(),
@@ -194,45 +667,141 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         template_img.height(),
     )?;
 
+    let opts =
+        CompileOptions::new().with_min_template_variance(config.compile.min_template_variance);
     let compiled = match config.match_cfg.rotation {
-        RotationModeConfig::Enabled => CompiledTemplate::compile_rotated(
+        RotationModeConfig::Enabled => CompiledTemplate::compile_with(
             &template,
             CompileConfig {
                 max_levels: config.compile.max_levels,
+                min_template_dim: config.compile.min_template_dim,
                 coarse_step_deg: config.compile.coarse_step_deg,
                 min_step_deg: config.compile.min_step_deg,
-                fill_value: config.compile.fill_value,
+                angle_schedule: match config.compile.angle_schedule {
+                    AngleScheduleConfig::Halving => AngleSchedule::Halving,
+                    AngleScheduleConfig::Geometric => AngleSchedule::Geometric {
+                        ratio: config.compile.angle_ratio,
+                    },
+                },
+                fill_strategy: match config.compile.fill_strategy {
+                    FillStrategyConfig::Constant => {
+                        FillStrategy::Constant(config.compile.fill_value)
+                    }
+                    FillStrategyConfig::EdgeClamp => FillStrategy::EdgeClamp,
+                },
                 precompute_coarsest: config.compile.precompute_coarsest,
+                parallel: config.match_cfg.parallel,
+                angle_steps: config.compile.angle_steps,
+                strict_coarse_step: config.compile.strict_coarse_step,
+                compact_plans: config.compile.compact_plans,
+                compact_mask: config.compile.compact_mask,
+                rotation_cache_capacity: config.compile.rotation_cache_capacity,
+                ..CompileConfig::default()
             },
+            opts,
         )?,
-        RotationModeConfig::Disabled => CompiledTemplate::compile_unrotated(
+        RotationModeConfig::Disabled => CompiledTemplate::compile_unrotated_with(
             &template,
             CompileConfigNoRot {
                 max_levels: config.compile.max_levels,
             },
+            opts,
         )?,
     };
 
+    // `strict_coarse_step` already rejected a wide step during compilation.
+    if cli.check_coarse_step && !config.compile.strict_coarse_step {
+        warn_on_coarse_step(&compiled)?;
+    }
+
+    let defaults = MatchConfig::default();
+    // Like the deprecated `MatchConfig::per_angle_topk`, a nonzero alias
+    // overrides both split fields.
+    let per_angle_alias = config.match_cfg.per_angle_topk.filter(|&k| k != 0);
     let matcher = Matcher::new(compiled).with_config(MatchConfig {
-        metric: config.match_cfg.metric.into(),
+        metric: match config.match_cfg.metric {
+            MetricConfig::Zncc => Metric::Zncc,
+            MetricConfig::Ssd => Metric::Ssd,
+            MetricConfig::Combined => Metric::Combined {
+                zncc_weight: config.match_cfg.zncc_weight,
+                ssd_weight: config.match_cfg.ssd_weight,
+            },
+        },
         rotation: config.match_cfg.rotation.into(),
         parallel: config.match_cfg.parallel,
         max_image_levels: config.match_cfg.max_image_levels,
+        require_full_pyramid: config.match_cfg.require_full_pyramid,
+        pyramid_filter: config.match_cfg.pyramid_filter.into(),
+        initial_downscale: config.match_cfg.initial_downscale,
         beam_width: config.match_cfg.beam_width,
-        per_angle_topk: config.match_cfg.per_angle_topk,
+        min_instances: config.match_cfg.min_instances,
+        coarse_per_angle_topk: per_angle_alias
+            .or(config.match_cfg.coarse_per_angle_topk)
+            .unwrap_or(defaults.coarse_per_angle_topk),
+        refine_per_angle_topk: per_angle_alias
+            .or(config.match_cfg.refine_per_angle_topk)
+            .unwrap_or(defaults.refine_per_angle_topk),
         nms_radius: config.match_cfg.nms_radius,
+        nms_keep_ratio: config.match_cfg.nms_keep_ratio,
+        result_min_separation: config.match_cfg.result_min_separation,
+        nms_angle_separation_deg: config.match_cfg.nms_angle_separation_deg,
+        tie_break: config.match_cfg.tie_break.into(),
         roi_radius: config.match_cfg.roi_radius,
+        level_stride: config.match_cfg.level_stride,
+        radius_in_level0_pixels: config.match_cfg.radius_in_level0_pixels,
+        keep_runner_up_positions: config.match_cfg.keep_runner_up_positions,
         angle_half_range_steps: config.match_cfg.angle_half_range_steps,
         min_var_i: config.match_cfg.min_var_i,
+        min_contrast: config.match_cfg.min_contrast,
+        coarse_variance_ratio: config.match_cfg.coarse_variance_ratio,
         min_score: config.match_cfg.min_score,
+        retry_on_empty: config.match_cfg.retry_on_empty,
+        allow_partial: config.match_cfg.allow_partial,
+        score_aggregation: config.match_cfg.score_aggregation.into(),
+        score_clamp: config.match_cfg.score_clamp.into(),
+        final_refinement: config.match_cfg.final_refinement,
+        subpixel_resample: config.match_cfg.subpixel_resample,
+        verify_full_res: config.match_cfg.verify_full_res,
+        angle_refine: config.match_cfg.angle_refine.into(),
+        allow_inverted: config.match_cfg.allow_inverted,
+        // Matches come from `match_image_topk`, which does not report sharpness.
+        compute_psr: false,
+        deterministic_accumulation: config.match_cfg.deterministic_accumulation,
+        ..defaults
     });
 
-    let image_view = image.view();
-    let matches = matcher.match_image_topk(image_view, config.topk)?;
-    let best = matches.first().copied().map(MatchRecord::from);
-    let topk = matches.into_iter().map(MatchRecord::from).collect();
-    let output = Output { best, topk };
-    let json = serde_json::to_string_pretty(&output)?;
+    let template_size =
+        (cli.with_corners || config.with_corners).then(|| (template.width(), template.height()));
+    let image_path = Path::new(&config.image_path);
+    let json = match output_format {
+        OutputFormat::Json if image_path.is_dir() => {
+            let records = run_batch(&matcher, image_path, config.topk, template_size)?;
+            serde_json::to_string_pretty(&records)?
+        }
+        OutputFormat::Json => {
+            let output = match_file(&matcher, image_path, config.topk, template_size)?;
+            if let Some(out) = &cli.visualize {
+                save_overlay(
+                    image_path,
+                    &output.topk,
+                    (template.width(), template.height()),
+                    out,
+                )?;
+            }
+            serde_json::to_string_pretty(&output)?
+        }
+        OutputFormat::Coco => {
+            let detections = run_coco(
+                &matcher,
+                image_path,
+                config.topk,
+                (template.width(), template.height()),
+                config.match_cfg.allow_inverted,
+                cli.visualize.as_deref(),
+            )?;
+            serde_json::to_string_pretty(&detections)?
+        }
+    };
 
     match config.output_path {
         Some(path) => fs::write(path, json)?,
@@ -241,3 +810,136 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{draw_line, run_batch, save_overlay, MatchRecord, OVERLAY_VALUE};
+    use corrmatch::{CompileConfigNoRot, CompiledTemplate, MatchConfig, Matcher, Template};
+    use std::fs;
+    use std::path::{Path, PathBuf};
+
+    /// Creates an empty scratch directory unique to this test process.
+    fn scratch_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("corrmatch-cli-{name}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn make_template(width: usize, height: usize) -> Vec<u8> {
+        (0..width * height)
+            .map(|i| {
+                let (x, y) = (i % width, i / width);
+                ((x * 37 + y * 91 + x * y * 7) % 251) as u8
+            })
+            .collect()
+    }
+
+    #[test]
+    fn run_batch_matches_every_image_and_reports_load_errors() {
+        let dir = scratch_dir("batch");
+        let (tpl_width, tpl_height) = (24, 20);
+        let tpl_data = make_template(tpl_width, tpl_height);
+        let (width, height) = (64, 48);
+        let placements = [(5, 7), (30, 20)];
+        for (name, (x0, y0)) in ["a.png", "b.png"].into_iter().zip(placements) {
+            let mut pixels = vec![0u8; width * height];
+            for y in 0..tpl_height {
+                let row = (y0 + y) * width + x0;
+                pixels[row..row + tpl_width]
+                    .copy_from_slice(&tpl_data[y * tpl_width..(y + 1) * tpl_width]);
+            }
+            image::GrayImage::from_raw(width as u32, height as u32, pixels)
+                .unwrap()
+                .save(dir.join(name))
+                .unwrap();
+        }
+        // A corrupt image is reported in place; other files are skipped.
+        fs::write(dir.join("c.png"), b"not a png").unwrap();
+        fs::write(dir.join("notes.txt"), b"not an image").unwrap();
+
+        let template = Template::new(tpl_data, tpl_width, tpl_height).unwrap();
+        let compiled =
+            CompiledTemplate::compile_unrotated(&template, CompileConfigNoRot { max_levels: 2 })
+                .unwrap();
+        let matcher = Matcher::new(compiled).with_config(MatchConfig {
+            max_image_levels: 2,
+            ..MatchConfig::default()
+        });
+        let records = run_batch(&matcher, &dir, 1, None);
+        fs::remove_dir_all(&dir).unwrap();
+        let records = records.unwrap();
+
+        let names: Vec<_> = records
+            .iter()
+            .map(|record| Path::new(&record.image_path).file_name().unwrap())
+            .collect();
+        assert_eq!(names, ["a.png", "b.png", "c.png"]);
+        for (record, (x0, y0)) in records.iter().zip(placements) {
+            assert!(record.error.is_none(), "{record:?}");
+            let best = record.best.as_ref().unwrap();
+            assert!((best.x - x0 as f32).abs() <= 0.5, "{record:?}");
+            assert!((best.y - y0 as f32).abs() <= 0.5, "{record:?}");
+            assert_eq!(record.topk.len(), 1);
+        }
+        assert!(records[2].best.is_none() && records[2].topk.is_empty());
+        assert!(records[2].error.is_some());
+    }
+
+    #[test]
+    fn draw_line_clips_a_rectangle_at_the_border() {
+        let (width, height) = (6, 5);
+        let mut pixels = vec![0u8; width * height];
+        // The rectangle's left edge and bottom row fall outside the buffer.
+        let corners = [[-2, 1], [4, 1], [4, 5], [-2, 5]];
+        for (i, &corner) in corners.iter().enumerate() {
+            draw_line(&mut pixels, width, height, corner, corners[(i + 1) % 4]);
+        }
+        let on = OVERLAY_VALUE;
+        let expected = [
+            [0, 0, 0, 0, 0, 0],
+            [on, on, on, on, on, 0],
+            [0, 0, 0, 0, on, 0],
+            [0, 0, 0, 0, on, 0],
+            [0, 0, 0, 0, on, 0],
+        ];
+        assert_eq!(pixels, expected.as_flattened());
+    }
+
+    #[test]
+    fn save_overlay_outlines_the_footprint_border_pixels() {
+        let dir = scratch_dir("overlay");
+        let (width, height) = (8, 6);
+        let input = dir.join("input.png");
+        image::GrayImage::from_raw(width as u32, height as u32, vec![10; width * height])
+            .unwrap()
+            .save(&input)
+            .unwrap();
+        let record = |x, y, angle_deg| MatchRecord {
+            x,
+            y,
+            angle_deg,
+            score: 1.0,
+            corners: None,
+        };
+        // A 4x3 template at (1, 2) covers columns 1..=4 and rows 2..=4; the
+        // second placement runs off the right edge.
+        let records = [record(1.0, 2.0, 0.0), record(6.0, 0.0, 0.0)];
+        let output = dir.join("overlay.png");
+        let saved = save_overlay(&input, &records, (4, 3), &output);
+        let pixels = image::open(&output).map(|image| image.into_luma8().into_raw());
+        fs::remove_dir_all(&dir).unwrap();
+        saved.unwrap();
+
+        let (on, bg) = (OVERLAY_VALUE, 10);
+        let expected = [
+            [bg, bg, bg, bg, bg, bg, on, on],
+            [bg, bg, bg, bg, bg, bg, on, bg],
+            [bg, on, on, on, on, bg, on, on],
+            [bg, on, bg, bg, on, bg, bg, bg],
+            [bg, on, on, on, on, bg, bg, bg],
+            [bg, bg, bg, bg, bg, bg, bg, bg],
+        ];
+        assert_eq!(pixels.unwrap(), expected.as_flattened());
+    }
+}
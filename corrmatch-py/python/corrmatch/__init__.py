@@ -35,6 +35,9 @@ from ._corrmatch import (
     CompiledTemplate,
     Matcher,
     match_template,
+    load_image,
+    NoMatchError,
+    RotationNotCompiledError,
     __version__,
 )
 
@@ -46,5 +49,8 @@ __all__ = [
     "CompiledTemplate",
     "Matcher",
     "match_template",
+    "load_image",
+    "NoMatchError",
+    "RotationNotCompiledError",
     "__version__",
 ]
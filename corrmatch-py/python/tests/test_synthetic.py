@@ -89,6 +89,13 @@ class TestSyntheticCases:
             assert result.score >= threshold, \
                 f"Score {result.score:.4f} below threshold {threshold}"
 
+    def test_load_image_matches_reference_loader(self, synthetic_case: SyntheticCase):
+        """Test that corrmatch.load_image decodes like the PIL reference."""
+        image = corrmatch.load_image(str(synthetic_case.image_path))
+        assert image.dtype == np.uint8
+        assert image.ndim == 2
+        np.testing.assert_array_equal(image, load_image(synthetic_case.image_path))
+
     def test_match_absent(self, synthetic_case: SyntheticCase):
         """Test that matcher doesn't find template when absent."""
         if synthetic_case.expected_present:
@@ -124,6 +131,72 @@ class TestBasicFunctionality:
         compiled = tpl.compile()
         assert compiled.num_levels > 0
 
+    def test_warm_up(self):
+        """Test that warm_up precomputes rotations without changing results."""
+        data = np.random.randint(0, 256, (32, 32), dtype=np.uint8)
+        compiled = corrmatch.Template(data).compile(
+            corrmatch.CompileConfig(coarse_step_deg=30.0, precompute_coarsest=False)
+        )
+        compiled.warm_up()
+        pixels, mask = compiled.rotated_template_image(0, 0)
+        assert pixels.shape == mask.shape == (32, 32)
+
+    def test_template_from_region(self):
+        """Test that a template can be cut from an image crop."""
+        image = np.random.randint(0, 256, (40, 60), dtype=np.uint8)
+        tpl = corrmatch.Template.from_region(image, 10, 5, 24, 16)
+        assert (tpl.width, tpl.height) == (24, 16)
+        compiled = tpl.compile_no_rotation(max_levels=1)
+        np.testing.assert_array_equal(compiled.level_image(0), image[5:21, 10:34])
+        with pytest.raises(ValueError):
+            corrmatch.Template.from_region(image, 50, 5, 24, 16)
+
+    def test_level_image(self):
+        """Test that level images match the template and level sizes."""
+        data = np.random.randint(0, 256, (32, 48), dtype=np.uint8)
+        compiled = corrmatch.Template(data).compile_no_rotation()
+        np.testing.assert_array_equal(compiled.level_image(0), data)
+        assert compiled.level_image(1).shape == (16, 24)
+        with pytest.raises(IndexError):
+            compiled.level_image(compiled.num_levels)
+
+    def test_angle_grid_introspection(self):
+        """Test per-level angle step and count accessors."""
+        data = np.random.randint(0, 256, (32, 32), dtype=np.uint8)
+        tpl = corrmatch.Template(data)
+        compiled = tpl.compile(corrmatch.CompileConfig(coarse_step_deg=30.0))
+        coarsest = compiled.num_levels - 1
+        assert compiled.angle_step_deg(coarsest) == pytest.approx(30.0)
+        assert compiled.num_angles(coarsest) == 12
+        assert compiled.num_angles(0) >= compiled.num_angles(coarsest)
+        angles = compiled.angles(coarsest)
+        assert len(angles) == 12
+        assert angles[0] == pytest.approx(-180.0)
+        assert angles[1] - angles[0] == pytest.approx(30.0)
+        with pytest.raises(IndexError):
+            compiled.num_angles(compiled.num_levels)
+        with pytest.raises(IndexError):
+            compiled.angles(compiled.num_levels)
+
+        unrotated = tpl.compile_no_rotation()
+        assert unrotated.angle_step_deg(0) is None
+        assert unrotated.num_angles(0) is None
+        assert unrotated.angles(0) is None
+
+    def test_recommended_max_coarse_step(self):
+        """Test the coarse-step estimate and the strict compile check."""
+        data = np.random.RandomState(7).randint(0, 256, (32, 32), dtype=np.uint8)
+        tpl = corrmatch.Template(data)
+        compiled = tpl.compile(corrmatch.CompileConfig(coarse_step_deg=30.0))
+        recommended = compiled.recommended_max_coarse_step()
+        assert 0.0 < recommended < 180.0
+        assert tpl.compile_no_rotation().recommended_max_coarse_step() is None
+
+        with pytest.raises(ValueError):
+            tpl.compile(
+                corrmatch.CompileConfig(coarse_step_deg=90.0, strict_coarse_step=True)
+            )
+
     def test_compile_no_rotation(self):
         """Test compiling template without rotation."""
         data = np.random.randint(0, 256, (32, 32), dtype=np.uint8)
@@ -170,14 +243,168 @@ class TestBasicFunctionality:
         for r in results:
             assert r.score > 0.9
 
+    def test_explain_shape(self):
+        """Test that explain returns a contribution map shaped like the template."""
+        image = np.zeros((96, 96), dtype=np.uint8)
+        template = np.random.randint(50, 200, (20, 28), dtype=np.uint8)
+        image[30:50, 24:52] = template
+
+        compiled = corrmatch.Template(template).compile_no_rotation(max_levels=3)
+        matcher = compiled.matcher()
+        result = matcher.match_image(image)
+        contributions = matcher.explain(image, result)
+
+        assert contributions.shape == template.shape
+        assert contributions.dtype == np.float32
+        assert contributions.sum() > 0.0
+
+    def test_refine_seed(self):
+        """Test refining a rough external pose without a full search."""
+        image = np.zeros((96, 96), dtype=np.uint8)
+        template = np.random.randint(50, 200, (20, 28), dtype=np.uint8)
+        image[30:50, 24:52] = template
+
+        matcher = corrmatch.Template(template).compile_no_rotation(max_levels=3).matcher()
+        seed = corrmatch.Match(24.4, 29.7)
+        result = matcher.refine(image, seed)
+
+        assert abs(result.x - 24) < 0.5
+        assert abs(result.y - 30) < 0.5
+        assert result.score > 0.95
+
+    def test_score_pose(self):
+        """Test scoring a single placement without a search."""
+        image = np.zeros((96, 96), dtype=np.uint8)
+        template = np.random.randint(50, 200, (20, 28), dtype=np.uint8)
+        image[30:50, 24:52] = template
+
+        matcher = corrmatch.Template(template).compile_no_rotation(max_levels=3).matcher()
+
+        assert matcher.score_pose(image, 24, 30) > 0.99
+        assert matcher.score_pose(image, 40, 30) < 0.5
+
+    def test_match_image_masked(self):
+        """Test that masked-out image pixels are excluded from scoring."""
+        image = np.random.randint(0, 256, (96, 96), dtype=np.uint8)
+        template = np.random.randint(50, 200, (20, 28), dtype=np.uint8)
+        image[30:50, 24:52] = template
+        image[32:42, 30:40] = 255
+        mask = np.ones_like(image)
+        mask[32:42, 30:40] = 0
+
+        matcher = corrmatch.Template(template).compile_no_rotation(max_levels=3).matcher()
+        result = matcher.match_image_masked(image, mask)
+
+        assert abs(result.x - 24) < 0.5
+        assert abs(result.y - 30) < 0.5
+        assert result.score > 0.99
+        from_bool = matcher.match_image_masked(image, mask.astype(bool))
+        assert (from_bool.x, from_bool.y) == (result.x, result.y)
+        with pytest.raises(ValueError):
+            matcher.match_image_masked(image, mask[:, :50])
+
+    def test_match_image_strided(self):
+        """Test matching a flat buffer whose rows are padded past the width."""
+        image = np.random.randint(0, 256, (96, 96), dtype=np.uint8)
+        template = np.random.randint(50, 200, (20, 28), dtype=np.uint8)
+        image[30:50, 24:52] = template
+        padded = np.zeros((96, 104), dtype=np.uint8)
+        padded[:, :96] = image
+
+        matcher = corrmatch.Template(template).compile_no_rotation(max_levels=3).matcher()
+        result = matcher.match_image_strided(padded.tobytes(), 96, 96, 104)
+
+        assert abs(result.x - 24) < 0.5
+        assert abs(result.y - 30) < 0.5
+        assert result.score > 0.99
+        with pytest.raises(ValueError):
+            matcher.match_image_strided(padded.tobytes(), 96, 96, 90)
+
+    def test_allow_inverted(self):
+        """Test that an intensity-inverted copy is found with a negative score."""
+        image = np.full((96, 96), 128, dtype=np.uint8)
+        template = np.random.randint(50, 200, (20, 28), dtype=np.uint8)
+        image[30:50, 24:52] = 255 - template
+
+        compiled = corrmatch.Template(template).compile_no_rotation(max_levels=3)
+        matcher = compiled.matcher(corrmatch.MatchConfig(allow_inverted=True))
+        result = matcher.match_image(image)
+
+        assert abs(result.x - 24) < 0.5
+        assert abs(result.y - 30) < 0.5
+        assert result.score < -0.95
+
+        with pytest.raises(ValueError):
+            corrmatch.MatchConfig(metric="ssd", allow_inverted=True)
+
+    def test_peak_sharpness(self):
+        """Test that a repeated instance gives a peak sharpness near 1."""
+        template = np.random.randint(50, 200, (24, 32), dtype=np.uint8)
+        image = np.zeros((104, 144), dtype=np.uint8)
+        image[40:64, 56:88] = template
+
+        compiled = corrmatch.Template(template).compile_no_rotation(max_levels=3)
+        config = corrmatch.MatchConfig(max_image_levels=3, compute_psr=True)
+        matcher = compiled.matcher(config)
+        assert matcher.match_image(image).peak_sharpness > 1.5
+
+        image[8:32, 8:40] = template
+        assert abs(matcher.match_image(image).peak_sharpness - 1.0) < 1e-3
+
+        plain = compiled.matcher(corrmatch.MatchConfig(max_image_levels=3))
+        assert plain.match_image(image).peak_sharpness is None
+
     def test_config_validation(self):
         """Test that invalid configs are rejected."""
-        with pytest.raises(RuntimeError):
+        with pytest.raises(ValueError):
             corrmatch.MatchConfig(beam_width=0)
 
-        with pytest.raises(RuntimeError):
+        with pytest.raises(ValueError):
             corrmatch.MatchConfig(per_angle_topk=0)
 
+        with pytest.raises(ValueError):
+            corrmatch.MatchConfig(coarse_per_angle_topk=4, refine_per_angle_topk=0)
+
+        with pytest.raises(ValueError):
+            corrmatch.MatchConfig(min_contrast=-1.0)
+
+    def test_detect(self):
+        """Test presence decisions above and below the threshold."""
+        image = np.zeros((128, 128), dtype=np.uint8)
+        template = np.random.randint(50, 200, (32, 32), dtype=np.uint8)
+        image[60:92, 40:72] = template
+        matcher = corrmatch.Template(template).compile_no_rotation(max_levels=3).matcher()
+
+        found = matcher.detect(image, 0.9)
+        assert found.present and found
+        assert abs(found.best.x - 40) <= 1.0 and abs(found.best.y - 60) <= 1.0
+
+        missed = matcher.detect(image, 1.5)
+        assert not missed.present
+        assert missed.best is not None
+
+        empty = matcher.detect(np.zeros((128, 128), dtype=np.uint8), 0.5)
+        assert not empty.present and empty.best is None
+
+    def test_no_match_error(self):
+        """Test that a search without candidates raises NoMatchError."""
+        template = np.tile(np.arange(16, dtype=np.uint8) * 8, (16, 1))
+        image = np.zeros((64, 64), dtype=np.uint8)
+        with pytest.raises(corrmatch.NoMatchError):
+            corrmatch.match_template(image, template)
+        assert issubclass(corrmatch.NoMatchError, RuntimeError)
+
+    def test_rotation_not_compiled_error(self):
+        """Test that rotation search on an unrotated template names the fix."""
+        template = np.tile(np.arange(16, dtype=np.uint8) * 8, (16, 1))
+        image = np.zeros((64, 64), dtype=np.uint8)
+        matcher = corrmatch.Template(template).compile_no_rotation().matcher(
+            corrmatch.MatchConfig(rotation="enabled")
+        )
+        with pytest.raises(corrmatch.RotationNotCompiledError, match="Template.compile()"):
+            matcher.match_image(image)
+        assert issubclass(corrmatch.RotationNotCompiledError, RuntimeError)
+
     def test_invalid_metric(self):
         """Test that invalid metric raises error."""
         with pytest.raises(ValueError):
@@ -188,6 +415,12 @@ class TestBasicFunctionality:
         with pytest.raises(ValueError):
             corrmatch.MatchConfig(rotation="invalid")
 
+    def test_fill_strategy(self):
+        """Test that the fill strategy is parsed and validated."""
+        corrmatch.CompileConfig(fill_strategy="edge_clamp")
+        with pytest.raises(ValueError):
+            corrmatch.CompileConfig(fill_strategy="invalid")
+
 
 class TestRotation:
     """Tests specifically for rotation matching."""
@@ -2,20 +2,95 @@
 //!
 //! This module exposes the high-level corrmatch API to Python via PyO3.
 
-use numpy::{PyReadonlyArray2, PyUntypedArrayMethods};
-use pyo3::exceptions::{PyRuntimeError, PyValueError};
+use numpy::ndarray::Array2;
+use numpy::{IntoPyArray, PyArray2, PyReadonlyArray2, PyUntypedArrayMethods};
+use pyo3::create_exception;
+use pyo3::exceptions::{PyIOError, PyIndexError, PyRuntimeError, PyValueError};
 use pyo3::prelude::*;
+use std::borrow::Cow;
+use std::sync::Arc;
 
+use corrmatch::lowlevel::AngleGrid;
 use corrmatch::{
+    AngleRefine as RustAngleRefine, AngleSchedule as RustAngleSchedule,
     CompileConfig as RustCompileConfig, CompileConfigNoRot as RustCompileConfigNoRot,
-    CompiledTemplate as RustCompiledTemplate, CorrMatchError, ImageView, Match as RustMatch,
+    CompileOptions as RustCompileOptions, CompiledTemplate as RustCompiledTemplate, CorrMatchError,
+    FillStrategy as RustFillStrategy, ImageView, Match as RustMatch,
     MatchConfig as RustMatchConfig, Matcher as RustMatcher, Metric as RustMetric,
-    RotationMode as RustRotationMode, Template as RustTemplate,
+    PyramidFilter as RustPyramidFilter, RotationMode as RustRotationMode, ScoreAgg as RustScoreAgg,
+    ScoreClamp as RustScoreClamp, Template as RustTemplate, TieBreak as RustTieBreak,
 };
 
+create_exception!(
+    corrmatch,
+    NoMatchError,
+    PyRuntimeError,
+    "Raised when the search produces no candidate match."
+);
+
+create_exception!(
+    corrmatch,
+    RotationNotCompiledError,
+    PyRuntimeError,
+    "Raised when rotation search is requested for a template compiled without rotation."
+);
+
 /// Convert a CorrMatchError to a Python exception.
+///
+/// Input and configuration problems raise `ValueError`, missing matches raise
+/// `NoMatchError` (a `RuntimeError` subclass), rotation search on a template
+/// compiled without rotation raises `RotationNotCompiledError` (also a
+/// `RuntimeError` subclass), and I/O failures raise `OSError`.
 fn to_py_err(err: CorrMatchError) -> PyErr {
-    PyRuntimeError::new_err(err.to_string())
+    let msg = err.to_string();
+    match err {
+        CorrMatchError::InvalidDimensions { .. }
+        | CorrMatchError::InvalidStride { .. }
+        | CorrMatchError::BufferTooSmall { .. }
+        | CorrMatchError::RoiOutOfBounds { .. }
+        | CorrMatchError::ImageSmallerThanTemplate { .. }
+        | CorrMatchError::InsufficientPyramidLevels { .. }
+        | CorrMatchError::DegenerateTemplate { .. }
+        | CorrMatchError::LowTemplateVariance { .. }
+        | CorrMatchError::InvalidAngleGrid { .. }
+        | CorrMatchError::UnsupportedMetric { .. }
+        | CorrMatchError::InvalidConfig { .. } => PyValueError::new_err(msg),
+        CorrMatchError::IndexOutOfBounds { .. } => PyIndexError::new_err(msg),
+        CorrMatchError::NoCandidates { .. } => NoMatchError::new_err(msg),
+        CorrMatchError::ImageIo { .. } => PyIOError::new_err(msg),
+        CorrMatchError::RotationNotCompiled => RotationNotCompiledError::new_err(
+            "rotation='enabled' needs a template compiled with rotation: use \
+             Template.compile() instead of Template.compile_no_rotation(), or \
+             set rotation='disabled'",
+        ),
+        CorrMatchError::RotationUnavailable { .. } | CorrMatchError::ParallelUnavailable => {
+            PyRuntimeError::new_err(msg)
+        }
+    }
+}
+
+/// Validity mask passed from numpy as either `uint8` or `bool`.
+#[derive(FromPyObject)]
+enum MaskArray<'py> {
+    U8(PyReadonlyArray2<'py, u8>),
+    Bool(PyReadonlyArray2<'py, bool>),
+}
+
+impl MaskArray<'_> {
+    fn shape(&self) -> &[usize] {
+        match self {
+            Self::U8(mask) => mask.shape(),
+            Self::Bool(mask) => mask.shape(),
+        }
+    }
+
+    /// Returns the mask as 0/1-style bytes, converting `bool` entries.
+    fn to_bytes(&self) -> PyResult<Cow<'_, [u8]>> {
+        Ok(match self {
+            Self::U8(mask) => Cow::Borrowed(mask.as_slice()?),
+            Self::Bool(mask) => Cow::Owned(mask.as_slice()?.iter().map(|&v| u8::from(v)).collect()),
+        })
+    }
 }
 
 /// Match result containing position, angle, and score.
@@ -34,10 +109,41 @@ pub struct Match {
     /// Match score (ZNCC in [-1, 1] or negative SSD).
     #[pyo3(get)]
     pub score: f32,
+    /// Peak-to-sidelobe ratio when `compute_psr` is enabled, else None.
+    #[pyo3(get)]
+    pub peak_sharpness: Option<f32>,
 }
 
 #[pymethods]
 impl Match {
+    /// Create a Match, e.g. as a seed pose for `Matcher.refine`.
+    #[new]
+    #[pyo3(signature = (x, y, angle_deg=0.0, score=0.0))]
+    fn new(x: f32, y: f32, angle_deg: f32, score: f32) -> Self {
+        Self {
+            x,
+            y,
+            angle_deg,
+            score,
+            peak_sharpness: None,
+        }
+    }
+
+    /// Axis-aligned bounding box (min_x, min_y, max_x, max_y) of the rotated template.
+    fn aabb(&self, tpl_width: usize, tpl_height: usize) -> (f32, f32, f32, f32) {
+        self.to_rust().aabb(tpl_width, tpl_height)
+    }
+
+    /// Four (x, y) corners of the rotated template, clockwise from its top-left.
+    fn corners(&self, tpl_width: usize, tpl_height: usize) -> [[f32; 2]; 4] {
+        self.to_rust().corners(tpl_width, tpl_height)
+    }
+
+    /// Intersection-over-union of the bounding boxes of two matches.
+    fn iou(&self, other: &Match, tpl_width: usize, tpl_height: usize) -> f32 {
+        self.to_rust().iou(&other.to_rust(), tpl_width, tpl_height)
+    }
+
     fn __repr__(&self) -> String {
         format!(
             "Match(x={:.2}, y={:.2}, angle_deg={:.2}, score={:.4})",
@@ -46,6 +152,18 @@ impl Match {
     }
 }
 
+impl Match {
+    fn to_rust(&self) -> RustMatch {
+        RustMatch {
+            x: self.x,
+            y: self.y,
+            angle_deg: self.angle_deg,
+            score: self.score,
+            peak_sharpness: self.peak_sharpness,
+        }
+    }
+}
+
 impl From<RustMatch> for Match {
     fn from(m: RustMatch) -> Self {
         Self {
@@ -53,15 +171,50 @@ impl From<RustMatch> for Match {
             y: m.y,
             angle_deg: m.angle_deg,
             score: m.score,
+            peak_sharpness: m.peak_sharpness,
+        }
+    }
+}
+
+/// Presence decision returned by `Matcher.detect`.
+#[pyclass]
+#[derive(Clone)]
+pub struct Detection {
+    /// Whether the best match reaches the detection threshold.
+    #[pyo3(get)]
+    pub present: bool,
+    /// Best match found, even below the threshold; None without candidates.
+    #[pyo3(get)]
+    pub best: Option<Match>,
+}
+
+#[pymethods]
+impl Detection {
+    fn __bool__(&self) -> bool {
+        self.present
+    }
+
+    fn __repr__(&self) -> String {
+        match &self.best {
+            Some(best) => format!(
+                "Detection(present={}, best={})",
+                self.present,
+                best.__repr__()
+            ),
+            None => format!("Detection(present={}, best=None)", self.present),
         }
     }
 }
 
+/// 2D uint8 numpy array returned to Python.
+type U8Array<'py> = Bound<'py, PyArray2<u8>>;
+
 /// Configuration for compiling a template with rotation support.
 #[pyclass]
 #[derive(Clone)]
 pub struct CompileConfig {
     inner: RustCompileConfig,
+    options: RustCompileOptions,
 }
 
 #[pymethods]
@@ -72,40 +225,101 @@ impl CompileConfig {
     ///     max_levels: Maximum pyramid levels (default: 6)
     ///     coarse_step_deg: Coarse rotation step in degrees (default: 10.0)
     ///     min_step_deg: Minimum rotation step in degrees (default: 0.5)
-    ///     fill_value: Fill value for out-of-bounds rotations (default: 0)
+    ///     fill_value: Fill value for out-of-bounds rotations when
+    ///         fill_strategy is "constant" (default: 0)
     ///     precompute_coarsest: Precompute coarsest level rotations (default: True)
+    ///     parallel: Precompute rotations in parallel (default: False)
+    ///     angle_steps: Optional per-level rotation steps, finest level first (default: None)
+    ///     min_template_dim: Smallest longer template side in coarser levels (default: 3)
+    ///     strict_coarse_step: Reject a coarsest step larger than the
+    ///         template's recommended maximum (default: False)
+    ///     compact_plans: Store ZNCC plans in half precision (default: False)
+    ///     fill_strategy: "constant" or "edge_clamp"; ZNCC masks filled
+    ///         pixels out, SSD scores edge-clamped ones (default: "constant")
+    ///     min_template_variance: Reject templates whose per-pixel intensity
+    ///         variance is below this floor; 0 disables (default: 0.0)
+    ///     angle_schedule: "halving" or "geometric"; how the rotation step
+    ///         shrinks toward level 0 (default: "halving")
+    ///     angle_ratio: Step ratio between levels for the "geometric"
+    ///         schedule; must be greater than 1 (default: 2.0)
+    ///     rotation_cache_capacity: Maximum number of rotations kept below
+    ///         the coarsest level; None keeps all (default: None)
+    ///     compact_mask: Store rotated masks with one bit per pixel
+    ///         (default: False)
     #[new]
-    #[pyo3(signature = (max_levels=6, coarse_step_deg=10.0, min_step_deg=0.5, fill_value=0, precompute_coarsest=true))]
+    #[pyo3(signature = (max_levels=6, coarse_step_deg=10.0, min_step_deg=0.5, fill_value=0, precompute_coarsest=true, parallel=false, angle_steps=None, min_template_dim=3, strict_coarse_step=false, compact_plans=false, fill_strategy="constant", min_template_variance=0.0, angle_schedule="halving", angle_ratio=2.0, rotation_cache_capacity=None, compact_mask=false))]
+    #[allow(clippy::too_many_arguments)]
     fn new(
         max_levels: usize,
         coarse_step_deg: f32,
         min_step_deg: f32,
         fill_value: u8,
         precompute_coarsest: bool,
+        parallel: bool,
+        angle_steps: Option<Vec<f32>>,
+        min_template_dim: usize,
+        strict_coarse_step: bool,
+        compact_plans: bool,
+        fill_strategy: &str,
+        min_template_variance: f32,
+        angle_schedule: &str,
+        angle_ratio: f32,
+        rotation_cache_capacity: Option<usize>,
+        compact_mask: bool,
     ) -> PyResult<Self> {
+        let fill_strategy = match fill_strategy.to_lowercase().as_str() {
+            "constant" => RustFillStrategy::Constant(fill_value),
+            "edge_clamp" => RustFillStrategy::EdgeClamp,
+            _ => {
+                return Err(PyValueError::new_err(
+                    "fill_strategy must be 'constant' or 'edge_clamp'",
+                ))
+            }
+        };
+        let angle_schedule = match angle_schedule.to_lowercase().as_str() {
+            "halving" => RustAngleSchedule::Halving,
+            "geometric" => RustAngleSchedule::Geometric { ratio: angle_ratio },
+            _ => {
+                return Err(PyValueError::new_err(
+                    "angle_schedule must be 'halving' or 'geometric'",
+                ))
+            }
+        };
         let inner = RustCompileConfig {
             max_levels,
+            min_template_dim,
             coarse_step_deg,
             min_step_deg,
-            fill_value,
+            angle_schedule,
+            fill_strategy,
             precompute_coarsest,
+            parallel,
+            angle_steps,
+            strict_coarse_step,
+            compact_plans,
+            compact_mask,
+            rotation_cache_capacity,
+            ..RustCompileConfig::default()
         };
         inner.validate().map_err(to_py_err)?;
-        Ok(Self { inner })
+        let options = RustCompileOptions::new().with_min_template_variance(min_template_variance);
+        options.validate().map_err(to_py_err)?;
+        Ok(Self { inner, options })
     }
 
     /// Validate the configuration.
     fn validate(&self) -> PyResult<()> {
-        self.inner.validate().map_err(to_py_err)
+        self.inner.validate().map_err(to_py_err)?;
+        self.options.validate().map_err(to_py_err)
     }
 
     fn __repr__(&self) -> String {
         format!(
-            "CompileConfig(max_levels={}, coarse_step_deg={}, min_step_deg={}, fill_value={}, precompute_coarsest={})",
+            "CompileConfig(max_levels={}, coarse_step_deg={}, min_step_deg={}, fill_strategy={:?}, precompute_coarsest={})",
             self.inner.max_levels,
             self.inner.coarse_step_deg,
             self.inner.min_step_deg,
-            self.inner.fill_value,
+            self.inner.fill_strategy,
             self.inner.precompute_coarsest
         )
     }
@@ -123,17 +337,74 @@ impl MatchConfig {
     /// Create a new MatchConfig.
     ///
     /// Args:
-    ///     metric: "zncc" or "ssd" (default: "zncc")
+    ///     metric: "zncc", "ssd", or "combined" (default: "zncc")
     ///     rotation: "enabled" or "disabled" (default: "disabled")
     ///     parallel: Enable parallel execution (default: False)
     ///     max_image_levels: Maximum image pyramid levels (default: 6)
     ///     beam_width: Candidates kept per level (default: 8)
-    ///     per_angle_topk: Top peaks per angle at coarsest level (default: 3)
+    ///     per_angle_topk: Deprecated; a nonzero value overrides both per-angle
+    ///         top-k values (default: None)
     ///     nms_radius: Non-maximum suppression radius (default: 6)
     ///     roi_radius: Refinement ROI radius (default: 8)
+    ///     radius_in_level0_pixels: Scale radii per pyramid level (default: False)
+    ///     keep_runner_up_positions: Keep separated clusters past beam_width (default: False)
     ///     angle_half_range_steps: Angle search half-range in steps (default: 1)
     ///     min_var_i: Minimum image patch variance for ZNCC (default: 1e-8)
-    ///     min_score: Minimum score threshold (default: -inf)
+    ///     min_score: Minimum raw score threshold, applied before clamping (default: -inf)
+    ///     score_clamp: "none" or "zero_to_one" to clamp ZNCC scores (default: "none")
+    ///     initial_downscale: Power-of-two factor; the search stops at that
+    ///         pyramid level, trading precision for speed (default: 1)
+    ///     final_refinement: Run the subpixel/subangle fit (default: True)
+    ///     subpixel_resample: Re-fit the position on bilinearly resampled ZNCC
+    ///         scores; ZNCC only (default: False)
+    ///     nms_keep_ratio: Keep nearby local maxima scoring within this ratio
+    ///         of the suppressing peak; 0 disables (default: 0.0)
+    ///     coarse_per_angle_topk: Top peaks per angle at the coarsest level (default: 3)
+    ///     refine_per_angle_topk: Top peaks per angle in each refinement ROI (default: 3)
+    ///     min_contrast: Minimum image-window standard deviation in intensity
+    ///         units; overrides min_var_i when set (default: None)
+    ///     pyramid_filter: "box" (2x2 average) or "opencv" to match cv2.pyrDown
+    ///         image pyramids (default: "box")
+    ///     allow_inverted: Also find polarity-inverted instances, which are
+    ///         reported with negative scores; ZNCC only (default: False)
+    ///     level_stride: Pyramid levels descended per refinement step; larger
+    ///         values skip levels for speed at some cost in robustness (default: 1)
+    ///     tie_break: Preference among equally scored peaks: "top_left_first",
+    ///         "nearest_to_center", or "most_central_angle" (default: "top_left_first")
+    ///     compute_psr: Set Match.peak_sharpness, the ratio of the best score to
+    ///         the best spatially distinct runner-up, in match_image (default: False)
+    ///     angle_refine: "quadratic" (parabola over grid neighbors) or
+    ///         "golden_section" (search within one grid step) (default: "quadratic")
+    ///     golden_section_iters: Iterations for "golden_section" (default: 12)
+    ///     result_min_separation: Minimum pixel separation between match_topk
+    ///         results; None uses nms_radius (default: None)
+    ///     verify_full_res: Replace each reported score with the full-resolution
+    ///         score at the rounded pose, e.g. with initial_downscale (default: False)
+    ///     retry_on_empty: Re-run a search level that finds nothing without
+    ///         min_score and with doubled per-angle Top-K (default: False)
+    ///     allow_partial: Also consider placements where the template extends
+    ///         past the image border, scored over the overlap; not supported
+    ///         by match_image_topk (default: False)
+    ///     score_aggregation: "finest", "min_across_levels", or
+    ///         "mean_across_levels"; combines the final score with the
+    ///         candidate's coarser-level scores (default: "finest")
+    ///     min_instances: Keep at least this many spatially distinct
+    ///         candidates per level, overriding beam_width (default: 0)
+    ///     nms_angle_separation_deg: Keep nearby peaks whose angles differ by
+    ///         more than this many degrees, so match_topk reports each
+    ///         orientation of a symmetric part; None suppresses by position
+    ///         only (default: None)
+    ///     require_full_pyramid: Fail instead of starting finer when the image
+    ///         pyramid has fewer levels than the template (default: False)
+    ///     zncc_weight: Weight of the ZNCC term of the "combined" metric
+    ///         (default: 0.5)
+    ///     ssd_weight: Weight of the normalized SSD term, 1 - SSE / (n * 255²),
+    ///         of the "combined" metric (default: 0.5)
+    ///     deterministic_accumulation: Sum kernel windows with Kahan
+    ///         compensation for reproducible scores; slower (default: False)
+    ///     coarse_variance_ratio: Skip coarse-scan placements whose window
+    ///         variance differs from the template's by more than this factor;
+    ///         ZNCC only (default: None)
     #[new]
     #[pyo3(signature = (
         metric = "zncc",
@@ -141,12 +412,41 @@ impl MatchConfig {
         parallel = false,
         max_image_levels = 6,
         beam_width = 8,
-        per_angle_topk = 3,
+        per_angle_topk = None,
         nms_radius = 6,
         roi_radius = 8,
+        radius_in_level0_pixels = false,
+        keep_runner_up_positions = false,
         angle_half_range_steps = 1,
         min_var_i = 1e-8,
-        min_score = f32::NEG_INFINITY
+        min_score = f32::NEG_INFINITY,
+        score_clamp = "none",
+        initial_downscale = 1,
+        final_refinement = true,
+        subpixel_resample = false,
+        nms_keep_ratio = 0.0,
+        coarse_per_angle_topk = None,
+        refine_per_angle_topk = None,
+        min_contrast = None,
+        pyramid_filter = "box",
+        allow_inverted = false,
+        level_stride = 1,
+        tie_break = "top_left_first",
+        compute_psr = false,
+        angle_refine = "quadratic",
+        golden_section_iters = 12,
+        result_min_separation = None,
+        verify_full_res = false,
+        retry_on_empty = false,
+        allow_partial = false,
+        score_aggregation = "finest",
+        min_instances = 0,
+        nms_angle_separation_deg = None,
+        require_full_pyramid = false,
+        zncc_weight = 0.5,
+        ssd_weight = 0.5,
+        deterministic_accumulation = false,
+        coarse_variance_ratio = None
     ))]
     #[allow(clippy::too_many_arguments)]
     fn new(
@@ -155,17 +455,54 @@ impl MatchConfig {
         parallel: bool,
         max_image_levels: usize,
         beam_width: usize,
-        per_angle_topk: usize,
+        per_angle_topk: Option<usize>,
         nms_radius: usize,
         roi_radius: usize,
+        radius_in_level0_pixels: bool,
+        keep_runner_up_positions: bool,
         angle_half_range_steps: usize,
         min_var_i: f32,
         min_score: f32,
+        score_clamp: &str,
+        initial_downscale: usize,
+        final_refinement: bool,
+        subpixel_resample: bool,
+        nms_keep_ratio: f32,
+        coarse_per_angle_topk: Option<usize>,
+        refine_per_angle_topk: Option<usize>,
+        min_contrast: Option<f32>,
+        pyramid_filter: &str,
+        allow_inverted: bool,
+        level_stride: usize,
+        tie_break: &str,
+        compute_psr: bool,
+        angle_refine: &str,
+        golden_section_iters: usize,
+        result_min_separation: Option<usize>,
+        verify_full_res: bool,
+        retry_on_empty: bool,
+        allow_partial: bool,
+        score_aggregation: &str,
+        min_instances: usize,
+        nms_angle_separation_deg: Option<f32>,
+        require_full_pyramid: bool,
+        zncc_weight: f32,
+        ssd_weight: f32,
+        deterministic_accumulation: bool,
+        coarse_variance_ratio: Option<f32>,
     ) -> PyResult<Self> {
         let metric = match metric.to_lowercase().as_str() {
             "zncc" => RustMetric::Zncc,
             "ssd" => RustMetric::Ssd,
-            _ => return Err(PyValueError::new_err("metric must be 'zncc' or 'ssd'")),
+            "combined" => RustMetric::Combined {
+                zncc_weight,
+                ssd_weight,
+            },
+            _ => {
+                return Err(PyValueError::new_err(
+                    "metric must be 'zncc', 'ssd', or 'combined'",
+                ))
+            }
         };
         let rotation = match rotation.to_lowercase().as_str() {
             "enabled" => RustRotationMode::Enabled,
@@ -176,18 +513,96 @@ impl MatchConfig {
                 ))
             }
         };
+        let score_clamp = match score_clamp.to_lowercase().as_str() {
+            "none" => RustScoreClamp::None,
+            "zero_to_one" => RustScoreClamp::ZeroToOne,
+            _ => {
+                return Err(PyValueError::new_err(
+                    "score_clamp must be 'none' or 'zero_to_one'",
+                ))
+            }
+        };
+        let score_aggregation = match score_aggregation.to_lowercase().as_str() {
+            "finest" => RustScoreAgg::Finest,
+            "min_across_levels" => RustScoreAgg::MinAcrossLevels,
+            "mean_across_levels" => RustScoreAgg::MeanAcrossLevels,
+            _ => return Err(PyValueError::new_err(
+                "score_aggregation must be 'finest', 'min_across_levels', or 'mean_across_levels'",
+            )),
+        };
+        let pyramid_filter = match pyramid_filter.to_lowercase().as_str() {
+            "box" => RustPyramidFilter::Box2x2,
+            "opencv" => RustPyramidFilter::OpenCvCompatible,
+            _ => {
+                return Err(PyValueError::new_err(
+                    "pyramid_filter must be 'box' or 'opencv'",
+                ))
+            }
+        };
+        let angle_refine = match angle_refine.to_lowercase().as_str() {
+            "quadratic" => RustAngleRefine::Quadratic,
+            "golden_section" => RustAngleRefine::GoldenSection {
+                iters: golden_section_iters,
+            },
+            _ => {
+                return Err(PyValueError::new_err(
+                    "angle_refine must be 'quadratic' or 'golden_section'",
+                ))
+            }
+        };
+        let tie_break = match tie_break.to_lowercase().as_str() {
+            "top_left_first" => RustTieBreak::TopLeftFirst,
+            "nearest_to_center" => RustTieBreak::NearestToCenter,
+            "most_central_angle" => RustTieBreak::MostCentralAngle,
+            _ => return Err(PyValueError::new_err(
+                "tie_break must be 'top_left_first', 'nearest_to_center', or 'most_central_angle'",
+            )),
+        };
+        let defaults = RustMatchConfig::default();
+        // Like the deprecated Rust field, a nonzero alias overrides both.
+        let per_angle_alias = per_angle_topk.filter(|&k| k != 0);
         let inner = RustMatchConfig {
             metric,
             rotation,
             parallel,
             max_image_levels,
+            require_full_pyramid,
+            pyramid_filter,
+            initial_downscale,
             beam_width,
-            per_angle_topk,
+            min_instances,
+            coarse_per_angle_topk: per_angle_alias
+                .or(coarse_per_angle_topk)
+                .unwrap_or(defaults.coarse_per_angle_topk),
+            refine_per_angle_topk: per_angle_alias
+                .or(refine_per_angle_topk)
+                .unwrap_or(defaults.refine_per_angle_topk),
             nms_radius,
+            nms_keep_ratio,
+            result_min_separation,
+            nms_angle_separation_deg,
+            tie_break,
             roi_radius,
+            level_stride,
+            radius_in_level0_pixels,
+            keep_runner_up_positions,
             angle_half_range_steps,
             min_var_i,
+            min_contrast,
+            coarse_variance_ratio,
             min_score,
+            retry_on_empty,
+            allow_partial,
+            score_aggregation,
+            score_clamp,
+            final_refinement,
+            subpixel_resample,
+            verify_full_res,
+            angle_refine,
+            allow_inverted,
+            compute_psr,
+            deterministic_accumulation,
+            ..defaults
         };
         inner.validate().map_err(to_py_err)?;
         Ok(Self { inner })
@@ -202,6 +617,7 @@ impl MatchConfig {
         let metric = match self.inner.metric {
             RustMetric::Zncc => "zncc",
             RustMetric::Ssd => "ssd",
+            RustMetric::Combined { .. } => "combined",
         };
         let rotation = match self.inner.rotation {
             RustRotationMode::Enabled => "enabled",
@@ -239,6 +655,29 @@ impl Template {
         Ok(Self { inner })
     }
 
+    /// Create a template from a rectangular crop of an image.
+    ///
+    /// Args:
+    ///     image: 2D uint8 numpy array (height x width)
+    ///     x: Left edge of the crop
+    ///     y: Top edge of the crop
+    ///     w: Crop width
+    ///     h: Crop height
+    #[staticmethod]
+    fn from_region(
+        image: PyReadonlyArray2<'_, u8>,
+        x: usize,
+        y: usize,
+        w: usize,
+        h: usize,
+    ) -> PyResult<Self> {
+        let shape = image.shape();
+        let view =
+            ImageView::from_slice(image.as_slice()?, shape[1], shape[0]).map_err(to_py_err)?;
+        let inner = RustTemplate::from_region(view, x, y, w, h).map_err(to_py_err)?;
+        Ok(Self { inner })
+    }
+
     /// Load a template from an image file.
     ///
     /// Args:
@@ -257,27 +696,43 @@ impl Template {
     ///     config: CompileConfig (default: CompileConfig())
     #[pyo3(signature = (config = None))]
     fn compile(&self, config: Option<CompileConfig>) -> PyResult<CompiledTemplate> {
-        let cfg = config.map(|c| c.inner).unwrap_or_default();
+        let (cfg, options) = config.map(|c| (c.inner, c.options)).unwrap_or_default();
         cfg.validate().map_err(to_py_err)?;
         let compiled =
-            RustCompiledTemplate::compile_rotated(&self.inner, cfg).map_err(to_py_err)?;
-        let num_levels = compiled.num_levels();
-        let matcher = RustMatcher::new(compiled);
-        Ok(CompiledTemplate::new_with_matcher(matcher, num_levels))
+            RustCompiledTemplate::compile_with(&self.inner, cfg, options).map_err(to_py_err)?;
+        Ok(CompiledTemplate::new(compiled))
     }
 
     /// Compile the template without rotation support (faster).
     ///
     /// Args:
     ///     max_levels: Maximum pyramid levels (default: 6)
-    #[pyo3(signature = (max_levels = 6))]
-    fn compile_no_rotation(&self, max_levels: usize) -> PyResult<CompiledTemplate> {
+    ///     min_template_variance: Reject templates whose per-pixel intensity
+    ///         variance is below this floor; 0 disables (default: 0.0)
+    ///     mask: Optional 2D uint8 or bool numpy array of the template's
+    ///         shape; nonzero (or True) marks pixels used for scoring
+    ///         (default: None)
+    #[pyo3(signature = (max_levels = 6, min_template_variance = 0.0, mask = None))]
+    fn compile_no_rotation(
+        &self,
+        max_levels: usize,
+        min_template_variance: f32,
+        mask: Option<MaskArray<'_>>,
+    ) -> PyResult<CompiledTemplate> {
+        let mut options =
+            RustCompileOptions::new().with_min_template_variance(min_template_variance);
+        if let Some(mask) = mask {
+            if mask.shape() != [self.inner.height(), self.inner.width()] {
+                return Err(PyValueError::new_err(
+                    "mask must have the same shape as the template",
+                ));
+            }
+            options = options.with_mask(mask.to_bytes()?.into_owned());
+        }
         let cfg = RustCompileConfigNoRot { max_levels };
-        let compiled =
-            RustCompiledTemplate::compile_unrotated(&self.inner, cfg).map_err(to_py_err)?;
-        let num_levels = compiled.num_levels();
-        let matcher = RustMatcher::new(compiled);
-        Ok(CompiledTemplate::new_with_matcher(matcher, num_levels))
+        let compiled = RustCompiledTemplate::compile_unrotated_with(&self.inner, cfg, options)
+            .map_err(to_py_err)?;
+        Ok(CompiledTemplate::new(compiled))
     }
 
     /// Get template width.
@@ -299,21 +754,25 @@ impl Template {
 
 /// A compiled template ready for matching.
 ///
-/// This class wraps a Rust Matcher directly since CompiledTemplate
-/// cannot be shared across Python boundaries (no Clone).
+/// The compiled assets are shared, so `matcher()` can be called repeatedly
+/// with different configurations without recompiling the template.
 #[pyclass]
 pub struct CompiledTemplate {
-    // Store the matcher directly since CompiledTemplate is consumed
-    matcher: Option<RustMatcher>,
-    num_levels: usize,
+    inner: Arc<RustCompiledTemplate>,
 }
 
 impl CompiledTemplate {
-    fn new_with_matcher(matcher: RustMatcher, num_levels: usize) -> Self {
+    fn new(compiled: RustCompiledTemplate) -> Self {
         Self {
-            matcher: Some(matcher),
-            num_levels,
+            inner: Arc::new(compiled),
+        }
+    }
+
+    fn level_angle_grid(&self, level: usize) -> PyResult<Option<&AngleGrid>> {
+        if level >= self.inner.num_levels() {
+            return Err(PyIndexError::new_err("level out of range"));
         }
+        Ok(self.inner.angle_grid(level))
     }
 }
 
@@ -322,39 +781,124 @@ impl CompiledTemplate {
     /// Get the number of pyramid levels.
     #[getter]
     fn num_levels(&self) -> usize {
-        self.num_levels
+        self.inner.num_levels()
     }
 
     /// Create a matcher from this compiled template.
     ///
-    /// Note: This consumes the compiled template. Create a new one for
-    /// different configurations.
+    /// The compiled assets (pyramid and rotation cache) are shared between
+    /// all matchers created from this template.
     ///
     /// Args:
     ///     config: MatchConfig (default: MatchConfig())
     #[pyo3(signature = (config = None))]
-    fn matcher(&mut self, config: Option<MatchConfig>) -> PyResult<Matcher> {
-        let inner = self.matcher.take().ok_or_else(|| {
-            PyRuntimeError::new_err("CompiledTemplate already consumed - recompile the template")
-        })?;
-
+    fn matcher(&self, config: Option<MatchConfig>) -> PyResult<Matcher> {
         let cfg = config.map(|c| c.inner).unwrap_or_default();
         cfg.validate().map_err(to_py_err)?;
-        let inner = inner.with_config(cfg);
+        let inner = RustMatcher::from_shared(Arc::clone(&self.inner)).with_config(cfg);
 
         Ok(Matcher { inner })
     }
 
+    /// Get the angle step in degrees used at a pyramid level.
+    ///
+    /// Args:
+    ///     level: Pyramid level (0 is full resolution)
+    ///
+    /// Returns:
+    ///     Step in degrees, or None for templates compiled without rotation
+    fn angle_step_deg(&self, level: usize) -> PyResult<Option<f32>> {
+        Ok(self.level_angle_grid(level)?.map(|grid| grid.step_deg()))
+    }
+
+    /// Get the number of angles searched at a pyramid level.
+    ///
+    /// Args:
+    ///     level: Pyramid level (0 is full resolution)
+    ///
+    /// Returns:
+    ///     Number of grid angles, or None for templates compiled without rotation
+    fn num_angles(&self, level: usize) -> PyResult<Option<usize>> {
+        Ok(self.level_angle_grid(level)?.map(|grid| grid.len()))
+    }
+
+    /// Get the angles searched at a pyramid level.
+    ///
+    /// Args:
+    ///     level: Pyramid level (0 is full resolution)
+    ///
+    /// Returns:
+    ///     Grid angles in degrees, or None for templates compiled without rotation
+    fn angles(&self, level: usize) -> PyResult<Option<Vec<f32>>> {
+        self.level_angle_grid(level)?;
+        Ok(self.inner.angles(level))
+    }
+
+    /// Estimate the largest coarsest-level rotation step that keeps the true
+    /// angle inside the template's correlation lobe.
+    ///
+    /// Returns:
+    ///     Step in degrees, or None for templates compiled without rotation
+    fn recommended_max_coarse_step(&self) -> PyResult<Option<f32>> {
+        self.inner.recommended_max_coarse_step().map_err(to_py_err)
+    }
+
+    /// Compute all lazily cached rotations now instead of during matching.
+    fn warm_up(&self) -> PyResult<()> {
+        self.inner.warm_up().map_err(to_py_err)
+    }
+
+    /// Get the cached rotated template and its mask at a level and angle.
+    ///
+    /// Args:
+    ///     level: Pyramid level (0 is full resolution)
+    ///     angle_idx: Index into the level's angle grid
+    ///
+    /// Returns:
+    ///     Tuple of 2D uint8 arrays (pixels, mask), mask is 1 for valid pixels
+    fn rotated_template_image<'py>(
+        &self,
+        py: Python<'py>,
+        level: usize,
+        angle_idx: usize,
+    ) -> PyResult<(U8Array<'py>, U8Array<'py>)> {
+        let (pixels, mask) = self
+            .inner
+            .rotated_template_image(level, angle_idx)
+            .map_err(to_py_err)?;
+        let (width, height) = self
+            .inner
+            .level_size(level)
+            .ok_or_else(|| PyIndexError::new_err("level out of range"))?;
+        let pixels = Array2::from_shape_vec((height, width), pixels)
+            .map_err(|err| PyRuntimeError::new_err(err.to_string()))?;
+        let mask = Array2::from_shape_vec((height, width), mask)
+            .map_err(|err| PyRuntimeError::new_err(err.to_string()))?;
+        Ok((pixels.into_pyarray(py), mask.into_pyarray(py)))
+    }
+
+    /// Get the unrotated template image stored for a pyramid level.
+    ///
+    /// Args:
+    ///     level: Pyramid level (0 is full resolution)
+    ///
+    /// Returns:
+    ///     2D uint8 array of the downsampled template
+    fn level_image<'py>(&self, py: Python<'py>, level: usize) -> PyResult<U8Array<'py>> {
+        let view = self
+            .inner
+            .level_image(level)
+            .ok_or_else(|| PyIndexError::new_err("level out of range"))?;
+        let pixels: Vec<u8> = (0..view.height())
+            .flat_map(|y| view.row(y).unwrap_or_default().iter().copied())
+            .collect();
+        let pixels = Array2::from_shape_vec((view.height(), view.width()), pixels)
+            .map_err(|err| PyRuntimeError::new_err(err.to_string()))?;
+        Ok(pixels.into_pyarray(py))
+    }
+
     fn __repr__(&self) -> String {
-        let status = if self.matcher.is_some() {
-            "ready"
-        } else {
-            "consumed"
-        };
-        format!(
-            "CompiledTemplate(num_levels={}, status='{}')",
-            self.num_levels, status
-        )
+        format!("CompiledTemplate(num_levels={})", self.inner.num_levels())
     }
 }
 
@@ -384,6 +928,80 @@ impl Matcher {
         Ok(result.into())
     }
 
+    /// Decide whether the template is present in an image.
+    ///
+    /// Args:
+    ///     image: 2D uint8 numpy array (height x width)
+    ///     threshold: Minimum score for the object to count as present
+    ///
+    /// Returns:
+    ///     Detection with `present` and the best match (kept even below the
+    ///     threshold; None when the search found no candidates)
+    fn detect(&self, image: PyReadonlyArray2<'_, u8>, threshold: f32) -> PyResult<Detection> {
+        let shape = image.shape();
+        let view =
+            ImageView::from_slice(image.as_slice()?, shape[1], shape[0]).map_err(to_py_err)?;
+        let detection = self.inner.detect(view, threshold).map_err(to_py_err)?;
+        Ok(Detection {
+            present: detection.present,
+            best: detection.best.map(Match::from),
+        })
+    }
+
+    /// Match the template against a flat grayscale buffer with row padding.
+    ///
+    /// Args:
+    ///     buffer: bytes holding at least (height - 1) * stride + width pixels
+    ///     width: Image width in pixels
+    ///     height: Image height in pixels
+    ///     stride: Bytes between the starts of consecutive rows (>= width)
+    ///
+    /// Returns:
+    ///     Match object with x, y, angle_deg, and score
+    fn match_image_strided(
+        &self,
+        buffer: &[u8],
+        width: usize,
+        height: usize,
+        stride: usize,
+    ) -> PyResult<Match> {
+        let view = ImageView::new(buffer, width, height, stride).map_err(to_py_err)?;
+        let result = self.inner.match_image(view).map_err(to_py_err)?;
+        Ok(result.into())
+    }
+
+    /// Match the template while ignoring invalid image pixels (ZNCC only).
+    ///
+    /// Args:
+    ///     image: 2D uint8 numpy array (height x width)
+    ///     mask: 2D uint8 or bool numpy array of the same shape; nonzero (or
+    ///         True) marks valid pixels
+    ///
+    /// Returns:
+    ///     Match object with x, y, angle_deg, and score
+    fn match_image_masked(
+        &self,
+        image: PyReadonlyArray2<'_, u8>,
+        mask: MaskArray<'_>,
+    ) -> PyResult<Match> {
+        let shape = image.shape();
+        let height = shape[0];
+        let width = shape[1];
+        if mask.shape() != shape {
+            return Err(PyValueError::new_err(
+                "mask must have the same shape as image",
+            ));
+        }
+        let data = image.as_slice()?;
+
+        let view = ImageView::from_slice(data, width, height).map_err(to_py_err)?;
+        let result = self
+            .inner
+            .match_image_masked(view, &mask.to_bytes()?)
+            .map_err(to_py_err)?;
+        Ok(result.into())
+    }
+
     /// Match the template against an image, returning top-k matches.
     ///
     /// Args:
@@ -403,6 +1021,85 @@ impl Matcher {
         Ok(results.into_iter().map(Match::from).collect())
     }
 
+    /// Refine an externally detected pose without running the full search.
+    ///
+    /// Args:
+    ///     image: 2D uint8 numpy array (height x width)
+    ///     seed: Rough pose, e.g. from another detector
+    ///
+    /// Returns:
+    ///     Match with subpixel position and refined angle
+    fn refine(&self, image: PyReadonlyArray2<'_, u8>, seed: &Match) -> PyResult<Match> {
+        let shape = image.shape();
+        let height = shape[0];
+        let width = shape[1];
+        let data = image.as_slice()?;
+
+        let view = ImageView::from_slice(data, width, height).map_err(to_py_err)?;
+        let result = self.inner.refine(view, seed.to_rust()).map_err(to_py_err)?;
+        Ok(Match::from(result))
+    }
+
+    /// Score a single pose without searching.
+    ///
+    /// Args:
+    ///     image: 2D uint8 numpy array (height x width)
+    ///     x: Template top-left column in image pixels
+    ///     y: Template top-left row in image pixels
+    ///     angle_deg: Rotation, snapped to the nearest grid angle (default: 0.0)
+    ///
+    /// Returns:
+    ///     Level-0 score; -inf when the window is too flat for ZNCC
+    #[pyo3(signature = (image, x, y, angle_deg = 0.0))]
+    fn score_pose(
+        &self,
+        image: PyReadonlyArray2<'_, u8>,
+        x: usize,
+        y: usize,
+        angle_deg: f32,
+    ) -> PyResult<f32> {
+        let shape = image.shape();
+        let height = shape[0];
+        let width = shape[1];
+        let data = image.as_slice()?;
+
+        let view = ImageView::from_slice(data, width, height).map_err(to_py_err)?;
+        self.inner
+            .score_pose(view, x, y, angle_deg)
+            .map_err(to_py_err)
+    }
+
+    /// Per-pixel contributions to the ZNCC dot product at a match.
+    ///
+    /// Args:
+    ///     image: 2D uint8 numpy array (height x width)
+    ///     m: Match to explain (typically from match_image)
+    ///
+    /// Returns:
+    ///     2D float32 array with the template's shape, suitable for overlays
+    fn explain<'py>(
+        &self,
+        py: Python<'py>,
+        image: PyReadonlyArray2<'_, u8>,
+        m: &Match,
+    ) -> PyResult<Bound<'py, PyArray2<f32>>> {
+        let shape = image.shape();
+        let height = shape[0];
+        let width = shape[1];
+        let data = image.as_slice()?;
+
+        let view = ImageView::from_slice(data, width, height).map_err(to_py_err)?;
+        let values = self.inner.explain(view, &m.to_rust()).map_err(to_py_err)?;
+        let (tpl_width, tpl_height) = self
+            .inner
+            .compiled()
+            .level_size(0)
+            .ok_or_else(|| PyRuntimeError::new_err("compiled template has no levels"))?;
+        let array = Array2::from_shape_vec((tpl_height, tpl_width), values)
+            .map_err(|err| PyRuntimeError::new_err(err.to_string()))?;
+        Ok(array.into_pyarray(py))
+    }
+
     fn __repr__(&self) -> String {
         "Matcher()".to_string()
     }
@@ -487,16 +1184,40 @@ fn match_template(
     Ok(result.into())
 }
 
+/// Load an image file as a grayscale array.
+///
+/// RGB inputs are converted to luma, as in Template.from_file.
+///
+/// Args:
+///     path: Path to a PNG or JPEG image file
+///
+/// Returns:
+///     2D uint8 numpy array (height x width)
+#[pyfunction]
+fn load_image<'py>(py: Python<'py>, path: &str) -> PyResult<U8Array<'py>> {
+    let owned = corrmatch::io::load_gray_image(path).map_err(to_py_err)?;
+    let pixels = Array2::from_shape_vec((owned.height(), owned.width()), owned.data().to_vec())
+        .map_err(|err| PyRuntimeError::new_err(err.to_string()))?;
+    Ok(pixels.into_pyarray(py))
+}
+
 /// Python module for corrmatch template matching.
 #[pymodule]
 fn _corrmatch(m: &Bound<'_, PyModule>) -> PyResult<()> {
     m.add_class::<Match>()?;
+    m.add_class::<Detection>()?;
     m.add_class::<CompileConfig>()?;
     m.add_class::<MatchConfig>()?;
     m.add_class::<Template>()?;
     m.add_class::<CompiledTemplate>()?;
     m.add_class::<Matcher>()?;
     m.add_function(wrap_pyfunction!(match_template, m)?)?;
+    m.add_function(wrap_pyfunction!(load_image, m)?)?;
+    m.add("NoMatchError", m.py().get_type::<NoMatchError>())?;
+    m.add(
+        "RotationNotCompiledError",
+        m.py().get_type::<RotationNotCompiledError>(),
+    )?;
 
     // Add version
     m.add("__version__", env!("CARGO_PKG_VERSION"))?;
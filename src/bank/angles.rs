@@ -1,8 +1,36 @@
 //! Angle grid generation and lookup for rotation search.
 
+use crate::image::pyramid::level_factor;
 use crate::util::math::wrap_deg;
 use crate::util::{CorrMatchError, CorrMatchResult};
 
+/// How the rotation step shrinks from the coarsest level toward level 0.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum AngleSchedule {
+    /// Halves the step at each finer level, matching the pyramid decimation.
+    #[default]
+    Halving,
+    /// Divides the step by `ratio` at each finer level.
+    ///
+    /// A ratio above 2 reaches a fine final step in fewer levels; the step is
+    /// still clamped to `min_step_deg`.
+    Geometric {
+        /// Step ratio between adjacent levels; must be greater than 1.
+        ratio: f32,
+    },
+}
+
+impl AngleSchedule {
+    /// Returns how much finer the step is `levels_below` levels under the
+    /// coarsest one.
+    pub(crate) fn shrink(self, levels_below: usize) -> f32 {
+        match self {
+            Self::Halving => level_factor(levels_below) as f32,
+            Self::Geometric { ratio } => ratio.powi(levels_below as i32),
+        }
+    }
+}
+
 /// Discrete, circular angle grid in degrees.
 #[derive(Clone, Debug)]
 pub struct AngleGrid {
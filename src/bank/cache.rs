@@ -0,0 +1,108 @@
+//! Bounded cache for rotations below the coarsest level.
+//!
+//! By default every rotation slot lives in a `OnceLock` for the lifetime of
+//! the compiled template. With `CompileConfig::rotation_cache_capacity` the
+//! finer levels instead share a least-recently-used cache: evicted rotations
+//! are rebuilt on the next access, trading recomputation for memory. Callers
+//! receive [`RotatedRef`] handles that keep an evicted rotation alive until
+//! they are dropped.
+
+use super::RotatedTemplate;
+use std::collections::HashMap;
+use std::ops::Deref;
+use std::sync::Arc;
+
+/// Handle to a rotation: borrowed from a permanent slot or shared with the
+/// bounded cache.
+pub(crate) enum RotatedRef<'a> {
+    Pinned(&'a RotatedTemplate),
+    Shared(Arc<RotatedTemplate>),
+}
+
+impl Deref for RotatedRef<'_> {
+    type Target = RotatedTemplate;
+
+    fn deref(&self) -> &RotatedTemplate {
+        match self {
+            Self::Pinned(rotated) => rotated,
+            Self::Shared(rotated) => rotated,
+        }
+    }
+}
+
+/// A plan of a cached rotation; dereferences to the plan.
+///
+/// Rotations held by the bounded cache (see
+/// [`CompileConfig::rotation_cache_capacity`](crate::CompileConfig::rotation_cache_capacity))
+/// can be evicted while the handle is alive; the handle keeps its rotation
+/// valid until it is dropped.
+pub struct PlanRef<'a, P> {
+    rotated: RotatedRef<'a>,
+    plan: fn(&RotatedTemplate) -> &P,
+}
+
+impl<'a, P> PlanRef<'a, P> {
+    pub(crate) fn new(rotated: RotatedRef<'a>, plan: fn(&RotatedTemplate) -> &P) -> Self {
+        Self { rotated, plan }
+    }
+}
+
+impl<P> Deref for PlanRef<'_, P> {
+    type Target = P;
+
+    fn deref(&self) -> &P {
+        (self.plan)(&self.rotated)
+    }
+}
+
+/// Least-recently-used map from `(level, angle_idx)` to rotations.
+pub(crate) struct RotationLru {
+    capacity: usize,
+    tick: u64,
+    entries: HashMap<(usize, usize), (Arc<RotatedTemplate>, u64)>,
+}
+
+impl RotationLru {
+    pub(crate) fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            tick: 0,
+            entries: HashMap::new(),
+        }
+    }
+
+    /// Returns the cached rotation and marks it most recently used.
+    pub(crate) fn get(&mut self, key: (usize, usize)) -> Option<Arc<RotatedTemplate>> {
+        self.tick += 1;
+        let tick = self.tick;
+        self.entries.get_mut(&key).map(|(rotated, used)| {
+            *used = tick;
+            rotated.clone()
+        })
+    }
+
+    /// Stores a rotation, evicting the least recently used one when full.
+    pub(crate) fn insert(&mut self, key: (usize, usize), rotated: Arc<RotatedTemplate>) {
+        if self.capacity == 0 {
+            return;
+        }
+        if !self.entries.contains_key(&key) && self.entries.len() >= self.capacity {
+            let oldest = self
+                .entries
+                .iter()
+                .min_by_key(|(_, (_, used))| *used)
+                .map(|(&oldest, _)| oldest);
+            if let Some(oldest) = oldest {
+                self.entries.remove(&oldest);
+            }
+        }
+        self.tick += 1;
+        self.entries.insert(key, (rotated, self.tick));
+    }
+
+    /// Returns the number of cached rotations.
+    #[cfg(test)]
+    pub(crate) fn len(&self) -> usize {
+        self.entries.len()
+    }
+}
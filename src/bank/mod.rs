@@ -2,23 +2,114 @@
 //!
 //! Compiling template assets once amortizes the cost of building pyramids and
 //! rotated variants across multiple match calls. Each cached rotation stores
-//! precomputed masked plans (ZNCC and SSD) for fast score evaluation.
-//! Rotated templates are cached lazily per level; each angle slot is populated
-//! at most once and stored in a `OnceLock` for thread-safe reuse when parallel
-//! search is introduced later.
+//! precomputed masked plans (ZNCC and SSD) for fast score evaluation, plus
+//! unmasked plans when the rotated mask happens to cover the whole template.
+//! Rotated templates are cached lazily per level; each angle slot is stored in
+//! a `OnceLock`, so concurrent readers always see a fully built entry. If two
+//! threads miss the same empty slot at once, both build the rotation and the
+//! first `set` wins; the build is deterministic, so either copy is identical.
+//! With `CompileConfig::rotation_cache_capacity`, levels below the coarsest
+//! use a bounded least-recently-used cache instead.
 
 mod angles;
+mod cache;
+mod rgb;
 
-pub use angles::AngleGrid;
+pub use angles::{AngleGrid, AngleSchedule};
+pub use cache::PlanRef;
+pub(crate) use cache::RotatedRef;
+pub use rgb::CompiledTemplate3;
 
-use crate::image::pyramid::ImagePyramid;
+use crate::image::pyramid::{level_factor, Downsampler, ImagePyramid};
 use crate::image::{ImageView, OwnedImage};
-use crate::template::rotate::rotate_u8_bilinear_masked;
+use crate::kernel::scalar::ZnccMaskedScalar;
+use crate::kernel::Kernel;
+use crate::template::rotate::{rotate_u8_bilinear_masked_fill, FillStrategy, RotationMode};
 use crate::template::{
-    MaskedSsdTemplatePlan, MaskedTemplatePlan, SsdTemplatePlan, Template, TemplatePlan,
+    MaskedSsdTemplatePlan, MaskedTemplatePlan, PlanMask, SsdTemplatePlan, Template, TemplatePlan,
 };
-use crate::util::{CorrMatchError, CorrMatchResult};
-use std::sync::{Arc, OnceLock};
+use crate::util::{CorrMatchError, CorrMatchResult, TemplateVariance};
+use cache::RotationLru;
+use std::sync::{Arc, Mutex, OnceLock, PoisonError};
+
+#[cfg(feature = "rayon")]
+use rayon::prelude::*;
+
+/// Smallest template side that still yields a usable rotated mask.
+const MIN_ROTATED_DIM: usize = 3;
+
+/// ZNCC a coarsest-level template must keep against itself at the edge of
+/// its angular correlation lobe.
+const LOBE_MIN_SCORE: f32 = 0.5;
+/// Angle increment, in degrees, used to trace the correlation lobe.
+const LOBE_PROBE_STEP_DEG: f32 = 0.5;
+/// Largest lobe half-width traced; wider lobes are reported as this value.
+const LOBE_MAX_HALF_WIDTH_DEG: f32 = 90.0;
+
+/// Shortest template side kept by the levels of [`CompileConfig::for_template`].
+const AUTO_MIN_SHORT_DIM: usize = 8;
+/// Largest coarse step chosen by [`CompileConfig::for_template`].
+const AUTO_MAX_COARSE_STEP_DEG: f32 = 20.0;
+
+/// Returns how many pyramid levels a rotated template of this size may use.
+///
+/// The longer side must stay at least `min_long` pixels and the shorter side
+/// at least `MIN_ROTATED_DIM`, so elongated templates are limited by their
+/// length rather than their thickness. Level 0 is always counted.
+fn rotated_level_budget(width: usize, height: usize, min_long: usize, max_levels: usize) -> usize {
+    let mut levels = 1;
+    let (mut long, mut short) = (width.max(height), width.min(height));
+    while levels < max_levels {
+        long /= 2;
+        short /= 2;
+        if long < min_long || short < MIN_ROTATED_DIM {
+            break;
+        }
+        levels += 1;
+    }
+    levels
+}
+
+/// Traces the angular correlation lobe of `base` downsampled to `level` and
+/// returns twice its half-width (see
+/// [`CompiledTemplateRot::recommended_max_coarse_step`]).
+fn lobe_coarse_step(
+    base: ImageView<'_, u8>,
+    level: usize,
+    fill: FillStrategy,
+) -> CorrMatchResult<f32> {
+    let (reference, _) = rotate_downsample_to_level(base, 0.0, fill, level)?;
+
+    let mut prev_angle = 0.0f32;
+    let mut prev_score = 1.0f32;
+    let probes = (LOBE_MAX_HALF_WIDTH_DEG / LOBE_PROBE_STEP_DEG) as usize;
+    for probe in 1..=probes {
+        let angle = probe as f32 * LOBE_PROBE_STEP_DEG;
+        let (rotated, mask) = rotate_downsample_to_level(base, angle, fill, level)?;
+        let plan = MaskedTemplatePlan::from_rotated_u8(rotated.view(), mask, angle)?;
+        let score = <ZnccMaskedScalar as Kernel>::score_at(reference.view(), &plan, 0, 0, 0.0);
+        if score < LOBE_MIN_SCORE {
+            let score = if score.is_finite() { score } else { 0.0 };
+            let t = ((prev_score - LOBE_MIN_SCORE) / (prev_score - score)).clamp(0.0, 1.0);
+            return Ok(2.0 * (prev_angle + t * (angle - prev_angle)));
+        }
+        prev_angle = angle;
+        prev_score = score;
+    }
+    Ok(2.0 * LOBE_MAX_HALF_WIDTH_DEG)
+}
+
+/// Builds the template pyramid with `downsampler`, or the default box filter.
+fn template_pyramid(
+    tpl: &Template,
+    max_levels: usize,
+    downsampler: Option<&Arc<dyn Downsampler>>,
+) -> CorrMatchResult<ImagePyramid> {
+    match downsampler {
+        Some(downsampler) => ImagePyramid::build_with(tpl.view(), max_levels, downsampler.as_ref()),
+        None => ImagePyramid::build_u8(tpl.view(), max_levels),
+    }
+}
 
 fn trim_degenerate_levels(levels: &mut Vec<OwnedImage>, min_dim: usize) -> CorrMatchResult<()> {
     let mut last_err: Option<CorrMatchError> = None;
@@ -51,6 +142,31 @@ fn trim_degenerate_levels(levels: &mut Vec<OwnedImage>, min_dim: usize) -> CorrM
     }
 }
 
+fn validate_min_template_variance(min_variance: f32) -> CorrMatchResult<()> {
+    if !min_variance.is_finite() || min_variance < 0.0 {
+        return Err(CorrMatchError::InvalidConfig {
+            reason: "min_template_variance must be finite and non-negative",
+        });
+    }
+    Ok(())
+}
+
+/// Rejects a level-0 template whose variance is below `min_variance`.
+pub(crate) fn check_template_variance(
+    plan: &TemplatePlan,
+    min_variance: f32,
+) -> CorrMatchResult<()> {
+    validate_min_template_variance(min_variance)?;
+    let variance = plan.inv_std().powi(-2);
+    if variance < min_variance {
+        return Err(CorrMatchError::LowTemplateVariance {
+            variance: TemplateVariance(variance),
+            min: TemplateVariance(min_variance),
+        });
+    }
+    Ok(())
+}
+
 fn downsample_u8(src: ImageView<'_, u8>) -> CorrMatchResult<OwnedImage> {
     let width = src.width();
     let height = src.height();
@@ -146,10 +262,10 @@ fn downsample_mask(mask: &[u8], width: usize, height: usize) -> CorrMatchResult<
 fn rotate_downsample_to_level(
     base: ImageView<'_, u8>,
     angle: f32,
-    fill: u8,
+    fill: FillStrategy,
     level: usize,
 ) -> CorrMatchResult<(OwnedImage, Vec<u8>)> {
-    let (mut img, mut mask) = rotate_u8_bilinear_masked(base, angle, fill);
+    let (mut img, mut mask) = rotate_u8_bilinear_masked_fill(base, angle, fill);
     for _ in 0..level {
         let view = img.view();
         let next_img = downsample_u8(view)?;
@@ -165,29 +281,138 @@ fn rotate_downsample_to_level(
 pub struct CompileConfig {
     /// Maximum pyramid levels to build.
     pub max_levels: usize,
+    /// Smallest length of the template's longer side kept in coarser levels.
+    ///
+    /// Downsampling stops before the longer side would fall below this size,
+    /// so the level count follows from the template dimensions. The shorter
+    /// side only has to stay at least 3 pixels, which lets elongated templates
+    /// (for example a 256x16 barcode) keep enough levels for a fast coarse
+    /// search. Level 0 is always kept. The default of 3 only drops levels too
+    /// small to rotate; raise it to keep coarse levels more distinctive.
+    pub min_template_dim: usize,
     /// Coarse rotation step in degrees at level 0.
     pub coarse_step_deg: f32,
     /// Minimum rotation step in degrees across levels.
     pub min_step_deg: f32,
-    /// Fill value used for out-of-bounds rotations.
+    /// How the step shrinks from `coarse_step_deg` toward level 0.
+    ///
+    /// With [`AngleSchedule::Geometric`], the unclamped level-0 step must not
+    /// fall below `min_step_deg`; compilation fails otherwise, so a ratio
+    /// never silently overshoots the requested resolution.
+    pub angle_schedule: AngleSchedule,
+    /// Deprecated alias for `fill_strategy: FillStrategy::Constant(fill_value)`.
+    ///
+    /// A nonzero value overrides `fill_strategy`; 0 (the default) leaves it
+    /// in effect.
+    #[deprecated(note = "set `fill_strategy` to `FillStrategy::Constant` instead")]
     pub fill_value: u8,
+    /// Fill used for rotated pixels whose source lies outside the template.
+    ///
+    /// ZNCC always masks those pixels out. [`FillStrategy::EdgeClamp`] makes
+    /// them valid for [`crate::Metric::Ssd`], so SSD compares the replicated
+    /// template edge against the image near the rotated template's border;
+    /// a constant fill stays masked out of SSD too.
+    pub fill_strategy: FillStrategy,
     /// Precompute all rotations for the coarsest level.
     pub precompute_coarsest: bool,
+    /// Precomputes coarsest-level rotations in parallel when the `rayon`
+    /// feature is available.
+    pub parallel: bool,
+    /// Explicit rotation step in degrees for each level, finest (level 0) first.
+    ///
+    /// When set, this overrides the schedule derived from `coarse_step_deg` and
+    /// `min_step_deg`. The length must equal the number of pyramid levels that
+    /// survive compilation.
+    pub angle_steps: Option<Vec<f32>>,
+    /// Rejects a coarsest-level step larger than
+    /// [`CompiledTemplate::recommended_max_coarse_step`].
+    ///
+    /// Tracing the correlation lobe rotates the template up to a few hundred
+    /// times, so this check is off by default.
+    pub strict_coarse_step: bool,
+    /// Stores the zero-mean buffers of ZNCC plans in half precision.
+    ///
+    /// Roughly halves the memory of the ZNCC plans, which dominate when many
+    /// rotations are cached; SSD plans keep full precision. Scores differ from
+    /// full-precision plans by about 1e-4. Requires the `half` feature:
+    /// without it [`Self::validate`] rejects the flag and compiled plans keep
+    /// full precision.
+    pub compact_plans: bool,
+    /// Stores the masks of rotated plans with one bit per pixel.
+    ///
+    /// Cuts mask memory by 8x; kernels extract each bit while scanning, which
+    /// costs a little scan speed. Scores are unchanged.
+    pub compact_mask: bool,
+    /// Maximum number of rotations kept below the coarsest level; `None`
+    /// keeps every rotation once built.
+    ///
+    /// Fine-angle refinement touches a different set of angle slots on each
+    /// frame, so unbounded slots grow toward the full bank. A capacity keeps
+    /// the least recently used rotations only and rebuilds evicted ones on
+    /// demand. Coarsest-level rotations are always kept.
+    pub rotation_cache_capacity: Option<usize>,
 }
 
 impl Default for CompileConfig {
+    #[allow(deprecated)]
     fn default() -> Self {
         Self {
             max_levels: 6,
+            min_template_dim: MIN_ROTATED_DIM,
             coarse_step_deg: 10.0,
             min_step_deg: 0.5,
+            angle_schedule: AngleSchedule::Halving,
             fill_value: 0,
+            fill_strategy: FillStrategy::Constant(0),
             precompute_coarsest: true,
+            parallel: false,
+            angle_steps: None,
+            strict_coarse_step: false,
+            compact_plans: false,
+            compact_mask: false,
+            rotation_cache_capacity: None,
         }
     }
 }
 
 impl CompileConfig {
+    /// Derives the pyramid depth and angle schedule from the template.
+    ///
+    /// Levels are added while the template's shorter side stays at least 8
+    /// pixels. `coarse_step_deg` is half of
+    /// [`CompiledTemplate::recommended_max_coarse_step`] at the coarsest
+    /// level, so no pose falls more than a quarter lobe from a grid angle,
+    /// capped at 20°. `min_step_deg` moves the template corner farthest from
+    /// the center by about one pixel at level 0. The remaining fields keep
+    /// their defaults.
+    ///
+    /// Tracing the lobe rotates the template up to a few hundred times; if it
+    /// fails, for example on a flat template, the default coarse step is kept.
+    pub fn for_template(tpl: &Template) -> Self {
+        let defaults = Self::default();
+        let (width, height) = (tpl.width(), tpl.height());
+        let mut max_levels = 1;
+        let mut short = width.min(height);
+        while short / 2 >= AUTO_MIN_SHORT_DIM {
+            short /= 2;
+            max_levels += 1;
+        }
+
+        let coarsest = max_levels - 1;
+        let coarse_step_deg = lobe_coarse_step(tpl.view(), coarsest, defaults.fill_strategy)
+            .map(|step| (0.5 * step).min(AUTO_MAX_COARSE_STEP_DEG))
+            .unwrap_or(defaults.coarse_step_deg);
+        let radius = 0.5 * (width as f32).hypot(height as f32);
+        let min_step_deg = radius.recip().to_degrees().min(coarse_step_deg);
+
+        Self {
+            max_levels,
+            coarse_step_deg,
+            min_step_deg,
+            ..defaults
+        }
+    }
+
     /// Validates the configuration, returning an error if any parameter is invalid.
     pub fn validate(&self) -> CorrMatchResult<()> {
         if self.max_levels == 0 {
@@ -195,6 +420,11 @@ impl CompileConfig {
                 reason: "max_levels must be at least 1",
             });
         }
+        if self.min_template_dim == 0 {
+            return Err(CorrMatchError::InvalidConfig {
+                reason: "min_template_dim must be at least 1",
+            });
+        }
         if !self.coarse_step_deg.is_finite() || self.coarse_step_deg <= 0.0 {
             return Err(CorrMatchError::InvalidConfig {
                 reason: "coarse_step_deg must be a positive finite value",
@@ -210,8 +440,78 @@ impl CompileConfig {
                 reason: "min_step_deg must not exceed coarse_step_deg",
             });
         }
+        if let AngleSchedule::Geometric { ratio } = self.angle_schedule {
+            if !ratio.is_finite() || ratio <= 1.0 {
+                return Err(CorrMatchError::InvalidConfig {
+                    reason: "angle_schedule ratio must be finite and greater than 1",
+                });
+            }
+        }
+        if let Some(steps) = &self.angle_steps {
+            if steps.is_empty() || steps.len() > self.max_levels {
+                return Err(CorrMatchError::InvalidConfig {
+                    reason: "angle_steps must have between 1 and max_levels entries",
+                });
+            }
+            if steps.iter().any(|step| !step.is_finite() || *step <= 0.0) {
+                return Err(CorrMatchError::InvalidConfig {
+                    reason: "angle_steps must contain positive finite values",
+                });
+            }
+        }
+        #[cfg(not(feature = "rayon"))]
+        if self.parallel {
+            return Err(CorrMatchError::ParallelUnavailable);
+        }
+        #[cfg(not(feature = "half"))]
+        if self.compact_plans {
+            return Err(CorrMatchError::InvalidConfig {
+                reason: "compact_plans requires the `half` feature",
+            });
+        }
         Ok(())
     }
+
+    /// Returns the fill in effect, honouring the deprecated `fill_value`.
+    #[allow(deprecated)]
+    pub(crate) fn fill(&self) -> FillStrategy {
+        match self.fill_value {
+            0 => self.fill_strategy,
+            value => FillStrategy::Constant(value),
+        }
+    }
+}
+
+/// Switches a ZNCC plan to half-precision storage on request.
+///
+/// Without the `half` feature plans keep full precision; `validate` reports
+/// the unsupported `compact_plans` flag.
+trait CompactPlan: Sized {
+    #[cfg(feature = "half")]
+    fn into_compact(self) -> Self;
+
+    fn compact_if(self, compact: bool) -> Self {
+        #[cfg(feature = "half")]
+        if compact {
+            return self.into_compact();
+        }
+        let _ = compact;
+        self
+    }
+}
+
+impl CompactPlan for TemplatePlan {
+    #[cfg(feature = "half")]
+    fn into_compact(self) -> Self {
+        TemplatePlan::into_compact(self)
+    }
+}
+
+impl CompactPlan for MaskedTemplatePlan {
+    #[cfg(feature = "half")]
+    fn into_compact(self) -> Self {
+        MaskedTemplatePlan::into_compact(self)
+    }
 }
 
 /// Configuration for compiling template assets without rotation support.
@@ -227,13 +527,143 @@ impl Default for CompileConfigNoRot {
     }
 }
 
+/// Template-level options accepted by both rotated and unrotated compilation.
+///
+/// Kept apart from [`CompileConfig`] and [`CompileConfigNoRot`] so new options
+/// do not break struct literals of either config. Start from
+/// [`CompileOptions::new`] and pass the result to
+/// [`CompiledTemplate::compile_with`] or
+/// [`CompiledTemplate::compile_unrotated_with`].
+#[derive(Clone, Debug, Default)]
+#[non_exhaustive]
+pub struct CompileOptions {
+    /// Minimum per-pixel intensity variance of the level-0 template.
+    ///
+    /// Templates just above the built-in zero-variance floor compile but
+    /// score unstably; a positive floor rejects them with
+    /// [`CorrMatchError::LowTemplateVariance`]. 0 disables the check.
+    pub min_template_variance: f32,
+    /// Custom filter for the template pyramid; `None` uses the 2x2 box.
+    ///
+    /// Matchers built on the compiled template downsample the image with the
+    /// same filter, overriding `MatchConfig::pyramid_filter`, so both
+    /// pyramids agree. Image masks (`match_image_masked`, `allow_partial`)
+    /// are not supported with a custom downsampler.
+    pub downsampler: Option<Arc<dyn Downsampler>>,
+    /// Template mask, one byte per level-0 pixel in row-major order; nonzero
+    /// marks a pixel that takes part in scoring.
+    ///
+    /// Masking out occluded or cluttered parts of the template does not
+    /// require rotation search: unrotated compilation with a mask builds a
+    /// single-angle (0°) masked bank, and `RotationMode::Disabled` then scans
+    /// it with the masked kernels. Coarser levels keep a pixel only if all
+    /// four of its sources are valid. Rotated compilation rejects a mask with
+    /// `InvalidConfig`.
+    pub mask: Option<Vec<u8>>,
+}
+
+impl CompileOptions {
+    /// Returns the default options.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets [`Self::min_template_variance`].
+    pub fn with_min_template_variance(mut self, min_template_variance: f32) -> Self {
+        self.min_template_variance = min_template_variance;
+        self
+    }
+
+    /// Sets [`Self::downsampler`].
+    pub fn with_downsampler(mut self, downsampler: Arc<dyn Downsampler>) -> Self {
+        self.downsampler = Some(downsampler);
+        self
+    }
+
+    /// Sets [`Self::mask`].
+    pub fn with_mask(mut self, mask: Vec<u8>) -> Self {
+        self.mask = Some(mask);
+        self
+    }
+
+    /// Validates the options, returning an error if any parameter is invalid.
+    pub fn validate(&self) -> CorrMatchResult<()> {
+        validate_min_template_variance(self.min_template_variance)
+    }
+}
+
 pub(crate) struct RotatedTemplate {
     angle_deg: f32,
     zncc: MaskedTemplatePlan,
     ssd: MaskedSsdTemplatePlan,
+    /// Unmasked ZNCC plan, present only when every pixel of the rotated mask
+    /// is valid.
+    full_zncc: Option<TemplatePlan>,
+    /// Unmasked SSD plan, present when the SSD plan scores every pixel.
+    full_ssd: Option<SsdTemplatePlan>,
 }
 
 impl RotatedTemplate {
+    /// Builds the plans of one rotated slot.
+    ///
+    /// With `ssd_scores_fill`, the filled pixels hold replicated edge values
+    /// ([`FillStrategy::EdgeClamp`]) and the SSD plan scores every pixel;
+    /// ZNCC always uses `mask`.
+    fn build(
+        img: OwnedImage,
+        mask: Vec<u8>,
+        angle_deg: f32,
+        ssd_scores_fill: bool,
+        compact: bool,
+        compact_mask: bool,
+    ) -> CorrMatchResult<Self> {
+        let full_mask = mask.iter().all(|&m| m != 0);
+        let full_zncc = if full_mask {
+            Some(TemplatePlan::from_view(img.view())?.compact_if(compact))
+        } else {
+            None
+        };
+        let full_ssd = if full_mask || ssd_scores_fill {
+            Some(SsdTemplatePlan::from_view(img.view())?)
+        } else {
+            None
+        };
+        let ssd_mask: Arc<[u8]> = if ssd_scores_fill {
+            Arc::from(vec![1u8; mask.len()])
+        } else {
+            Arc::from(mask.as_slice())
+        };
+        let mask: Arc<[u8]> = Arc::from(mask);
+        let mut zncc = MaskedTemplatePlan::from_rotated_parts(img.view(), mask.clone(), angle_deg)?
+            .compact_if(compact);
+        let mut ssd =
+            MaskedSsdTemplatePlan::from_rotated_parts(img.view(), ssd_mask.clone(), angle_deg)?;
+        if compact_mask {
+            zncc = zncc.with_mask(PlanMask::packed(&mask));
+            ssd = ssd.with_mask(PlanMask::packed(&ssd_mask));
+        }
+        Ok(Self {
+            angle_deg,
+            zncc,
+            ssd,
+            full_zncc,
+            full_ssd,
+        })
+    }
+
+    /// Returns true when the rotated mask covers the whole template.
+    pub(crate) fn is_full_mask(&self) -> bool {
+        self.full_zncc.is_some()
+    }
+
+    pub(crate) fn full_zncc_plan(&self) -> Option<&TemplatePlan> {
+        self.full_zncc.as_ref()
+    }
+
+    pub(crate) fn full_ssd_plan(&self) -> Option<&SsdTemplatePlan> {
+        self.full_ssd.as_ref()
+    }
+
     pub(crate) fn zncc_plan(&self) -> &MaskedTemplatePlan {
         &self.zncc
     }
@@ -241,6 +671,16 @@ impl RotatedTemplate {
     pub(crate) fn ssd_plan(&self) -> &MaskedSsdTemplatePlan {
         &self.ssd
     }
+
+    /// Returns copies of the rotated pixels and mask.
+    ///
+    /// The SSD plan keeps the raw `u8` values (including fill pixels), so the
+    /// conversion back is exact.
+    pub(crate) fn image_and_mask(&self) -> (Vec<u8>, Vec<u8>) {
+        let pixels = self.ssd.data().iter().map(|&v| v as u8).collect();
+        let len = self.ssd.width() * self.ssd.height();
+        (pixels, self.zncc.plan_mask().to_bytes(len).into_owned())
+    }
 }
 
 struct LevelBank {
@@ -254,29 +694,82 @@ pub struct CompiledTemplateRot {
     banks: Vec<LevelBank>,
     unmasked_zncc: Vec<TemplatePlan>,
     unmasked_ssd: Vec<SsdTemplatePlan>,
+    /// Bounded cache for the finer levels; `None` keeps every slot.
+    lru: Option<Mutex<RotationLru>>,
     cfg: CompileConfig,
+    downsampler: Option<Arc<dyn Downsampler>>,
 }
 
 impl CompiledTemplateRot {
     /// Compiles template assets for matching with rotation support.
     pub fn compile(tpl: &Template, cfg: CompileConfig) -> CorrMatchResult<Self> {
-        let pyramid = ImagePyramid::build_u8(tpl.view(), cfg.max_levels)?;
+        Self::compile_with(tpl, cfg, CompileOptions::default())
+    }
+
+    /// Compiles template assets for matching with rotation support and
+    /// template-level `opts`.
+    pub fn compile_with(
+        tpl: &Template,
+        cfg: CompileConfig,
+        opts: CompileOptions,
+    ) -> CorrMatchResult<Self> {
+        if opts.mask.is_some() {
+            return Err(CorrMatchError::InvalidConfig {
+                reason: "template masks are only supported without rotation",
+            });
+        }
+        let max_levels = rotated_level_budget(
+            tpl.width(),
+            tpl.height(),
+            cfg.min_template_dim,
+            cfg.max_levels,
+        );
+        let pyramid = template_pyramid(tpl, max_levels, opts.downsampler.as_ref())?;
         let mut levels = pyramid.into_levels();
-        trim_degenerate_levels(&mut levels, 3)?;
+        trim_degenerate_levels(&mut levels, MIN_ROTATED_DIM)?;
 
         let mut unmasked_zncc = Vec::with_capacity(levels.len());
         let mut unmasked_ssd = Vec::with_capacity(levels.len());
         for level in levels.iter() {
-            unmasked_zncc.push(TemplatePlan::from_view(level.view())?);
+            let plan = TemplatePlan::from_view(level.view())?;
+            if unmasked_zncc.is_empty() {
+                check_template_variance(&plan, opts.min_template_variance)?;
+            }
+            unmasked_zncc.push(plan.compact_if(cfg.compact_plans));
             unmasked_ssd.push(SsdTemplatePlan::from_view(level.view())?);
         }
 
-        let mut banks = Vec::with_capacity(levels.len());
+        if let Some(steps) = &cfg.angle_steps {
+            cfg.validate()?;
+            if steps.len() != levels.len() {
+                return Err(CorrMatchError::InvalidConfig {
+                    reason: "angle_steps length must match the number of template levels",
+                });
+            }
+        }
+
         let coarsest_idx = levels.len().saturating_sub(1);
+        if cfg.angle_steps.is_none() {
+            if let AngleSchedule::Geometric { .. } = cfg.angle_schedule {
+                cfg.validate()?;
+                let finest = cfg.coarse_step_deg / cfg.angle_schedule.shrink(coarsest_idx);
+                if finest < cfg.min_step_deg {
+                    return Err(CorrMatchError::InvalidConfig {
+                        reason: "angle_schedule ratio takes the level-0 step below min_step_deg",
+                    });
+                }
+            }
+        }
+
+        let mut banks = Vec::with_capacity(levels.len());
         for (level_idx, _level) in levels.iter().enumerate() {
-            let shift = coarsest_idx.saturating_sub(level_idx);
-            let factor = (1u64.checked_shl(shift as u32).unwrap_or(u64::MAX)) as f32;
-            let step = (cfg.coarse_step_deg / factor).max(cfg.min_step_deg);
+            let step = match &cfg.angle_steps {
+                Some(steps) => steps[level_idx],
+                None => {
+                    let shrink = cfg.angle_schedule.shrink(coarsest_idx - level_idx);
+                    (cfg.coarse_step_deg / shrink).max(cfg.min_step_deg)
+                }
+            };
             let grid = AngleGrid::full(step)?;
             let slots = (0..grid.len()).map(|_| OnceLock::new()).collect();
             banks.push(LevelBank { grid, slots });
@@ -296,41 +789,58 @@ impl CompiledTemplateRot {
                     len: levels.len(),
                     context: "level",
                 })?;
-            if let Some(bank) = banks.get_mut(coarsest_idx) {
-                for (idx, angle) in bank.grid.iter().enumerate() {
-                    let (rotated_img, mask) = rotate_downsample_to_level(
-                        base.view(),
-                        angle,
-                        cfg.fill_value,
-                        coarsest_idx,
-                    )?;
+            if let Some(bank) = banks.get(coarsest_idx) {
+                let fill_slot = |idx: usize, slot: &OnceLock<RotatedTemplate>| {
+                    let angle = bank.grid.angle_at(idx);
+                    let (rotated_img, mask) =
+                        rotate_downsample_to_level(base.view(), angle, cfg.fill(), coarsest_idx)?;
                     debug_assert_eq!(rotated_img.width(), coarsest.width());
                     debug_assert_eq!(rotated_img.height(), coarsest.height());
-                    let mask: Arc<[u8]> = Arc::from(mask);
-                    let zncc_plan = MaskedTemplatePlan::from_rotated_parts(
-                        rotated_img.view(),
-                        mask.clone(),
+                    let rotated = RotatedTemplate::build(
+                        rotated_img,
+                        mask,
                         angle,
+                        cfg.fill() == FillStrategy::EdgeClamp,
+                        cfg.compact_plans,
+                        cfg.compact_mask,
                     )?;
-                    let ssd_plan =
-                        MaskedSsdTemplatePlan::from_rotated_parts(rotated_img.view(), mask, angle)?;
-                    let rotated = RotatedTemplate {
-                        angle_deg: angle,
-                        zncc: zncc_plan,
-                        ssd: ssd_plan,
-                    };
-                    let _ = bank.slots[idx].set(rotated);
+                    let _ = slot.set(rotated);
+                    Ok(())
+                };
+                if cfg.parallel && cfg!(feature = "rayon") {
+                    #[cfg(feature = "rayon")]
+                    bank.slots
+                        .par_iter()
+                        .enumerate()
+                        .try_for_each(|(idx, slot)| fill_slot(idx, slot))?;
+                } else {
+                    for (idx, slot) in bank.slots.iter().enumerate() {
+                        fill_slot(idx, slot)?;
+                    }
                 }
             }
         }
 
-        Ok(Self {
+        let compiled = Self {
             levels,
             banks,
             unmasked_zncc,
             unmasked_ssd,
+            lru: cfg
+                .rotation_cache_capacity
+                .map(|capacity| Mutex::new(RotationLru::new(capacity))),
             cfg,
-        })
+            downsampler: opts.downsampler,
+        };
+        if compiled.cfg.strict_coarse_step {
+            let coarsest_step = compiled.banks[coarsest_idx].grid.step_deg();
+            if coarsest_step > compiled.recommended_max_coarse_step()? {
+                return Err(CorrMatchError::InvalidConfig {
+                    reason: "coarse rotation step exceeds the template's recommended maximum",
+                });
+            }
+        }
+        Ok(compiled)
     }
 
     /// Returns the number of pyramid levels.
@@ -345,11 +855,65 @@ impl CompiledTemplateRot {
             .map(|img| (img.width(), img.height()))
     }
 
+    /// Returns the unrotated template image stored for a pyramid level.
+    pub fn level_image(&self, level: usize) -> Option<ImageView<'_, u8>> {
+        self.levels.get(level).map(OwnedImage::view)
+    }
+
     /// Returns the angle grid for a pyramid level.
     pub fn angle_grid(&self, level: usize) -> Option<&AngleGrid> {
         self.banks.get(level).map(|bank| &bank.grid)
     }
 
+    /// Estimates the largest coarsest-level rotation step that cannot skip the
+    /// true angle.
+    ///
+    /// The coarsest template is rotated in small increments and scored against
+    /// its unrotated self until ZNCC drops below 0.5; call that angle the lobe
+    /// half-width. A grid angle is never more than half a step from the true
+    /// angle, so steps up to twice the half-width keep the true pose inside the
+    /// lobe. Small or finely detailed templates have narrow lobes and need
+    /// finer coarse steps. Each call recomputes the estimate.
+    pub fn recommended_max_coarse_step(&self) -> CorrMatchResult<f32> {
+        let level = self.levels.len().saturating_sub(1);
+        let base = self
+            .levels
+            .first()
+            .ok_or(CorrMatchError::IndexOutOfBounds {
+                index: 0,
+                len: self.levels.len(),
+                context: "level",
+            })?;
+        lobe_coarse_step(base.view(), level, self.cfg.fill())
+    }
+
+    /// Builds every rotation slot at every level that is not cached yet.
+    ///
+    /// Runs in parallel when `CompileConfig::parallel` is set and the `rayon`
+    /// feature is available. With `rotation_cache_capacity` set, only the
+    /// coarsest level is built; finer rotations would be evicted anyway.
+    pub fn warm_up(&self) -> CorrMatchResult<()> {
+        let first = match self.lru {
+            Some(_) => self.banks.len().saturating_sub(1),
+            None => 0,
+        };
+        let slots: Vec<(usize, usize)> = self
+            .banks
+            .iter()
+            .enumerate()
+            .skip(first)
+            .flat_map(|(level, bank)| (0..bank.slots.len()).map(move |idx| (level, idx)))
+            .collect();
+        let fill = |&(level, idx): &(usize, usize)| self.rotated(level, idx).map(|_| ());
+        if self.cfg.parallel && cfg!(feature = "rayon") {
+            #[cfg(feature = "rayon")]
+            slots.par_iter().try_for_each(fill)?;
+        } else {
+            slots.iter().try_for_each(fill)?;
+        }
+        Ok(())
+    }
+
     /// Returns an unmasked ZNCC template plan for a given level.
     pub fn unmasked_zncc_plan(&self, level: usize) -> CorrMatchResult<&TemplatePlan> {
         self.unmasked_zncc
@@ -372,11 +936,35 @@ impl CompiledTemplateRot {
             })
     }
 
+    /// Returns a rotation, going through the bounded cache below the
+    /// coarsest level when one is configured.
     pub(crate) fn rotated(
         &self,
         level: usize,
         angle_idx: usize,
+    ) -> CorrMatchResult<RotatedRef<'_>> {
+        self.rotated_slot(level, angle_idx, true)
+    }
+
+    /// Returns a rotation stored in its permanent slot, bypassing the bounded
+    /// cache.
+    pub(crate) fn pinned(
+        &self,
+        level: usize,
+        angle_idx: usize,
     ) -> CorrMatchResult<&RotatedTemplate> {
+        match self.rotated_slot(level, angle_idx, false)? {
+            RotatedRef::Pinned(rotated) => Ok(rotated),
+            RotatedRef::Shared(_) => unreachable!("pinned lookups bypass the bounded cache"),
+        }
+    }
+
+    fn rotated_slot(
+        &self,
+        level: usize,
+        angle_idx: usize,
+        bounded: bool,
+    ) -> CorrMatchResult<RotatedRef<'_>> {
         let bank = self
             .banks
             .get(level)
@@ -406,7 +994,20 @@ impl CompiledTemplateRot {
             debug_assert!((rotated.angle_deg - angle).abs() < 1e-6);
             debug_assert_eq!(rotated.zncc.width(), level_img.width());
             debug_assert_eq!(rotated.zncc.height(), level_img.height());
-            return Ok(rotated);
+            return Ok(RotatedRef::Pinned(rotated));
+        }
+        let lru = self
+            .lru
+            .as_ref()
+            .filter(|_| bounded && level + 1 < self.levels.len());
+        if let Some(lru) = lru {
+            let hit = lru
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .get((level, angle_idx));
+            if let Some(rotated) = hit {
+                return Ok(RotatedRef::Shared(rotated));
+            }
         }
         let base = self
             .levels
@@ -417,20 +1018,48 @@ impl CompiledTemplateRot {
                 context: "level",
             })?;
         let (rotated_img, mask) =
-            rotate_downsample_to_level(base.view(), angle, self.cfg.fill_value, level)?;
+            rotate_downsample_to_level(base.view(), angle, self.cfg.fill(), level)?;
         debug_assert_eq!(rotated_img.width(), level_img.width());
         debug_assert_eq!(rotated_img.height(), level_img.height());
-        let mask: Arc<[u8]> = Arc::from(mask);
-        let zncc_plan =
-            MaskedTemplatePlan::from_rotated_parts(rotated_img.view(), mask.clone(), angle)?;
-        let ssd_plan = MaskedSsdTemplatePlan::from_rotated_parts(rotated_img.view(), mask, angle)?;
-        let rotated = RotatedTemplate {
-            angle_deg: angle,
-            zncc: zncc_plan,
-            ssd: ssd_plan,
-        };
+        let rotated = RotatedTemplate::build(
+            rotated_img,
+            mask,
+            angle,
+            self.cfg.fill() == FillStrategy::EdgeClamp,
+            self.cfg.compact_plans,
+            self.cfg.compact_mask,
+        )?;
+        if let Some(lru) = lru {
+            let rotated = Arc::new(rotated);
+            lru.lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .insert((level, angle_idx), rotated.clone());
+            return Ok(RotatedRef::Shared(rotated));
+        }
         let _ = slot.set(rotated);
-        Ok(slot.get().expect("rotated template should be initialized"))
+        Ok(RotatedRef::Pinned(
+            slot.get().expect("rotated template should be initialized"),
+        ))
+    }
+
+    /// Rotates the template to an arbitrary angle at `level`, bypassing the
+    /// angle grid and its cache.
+    pub(crate) fn rotated_at(
+        &self,
+        level: usize,
+        angle_deg: f32,
+    ) -> CorrMatchResult<RotatedTemplate> {
+        if level >= self.levels.len() {
+            return Err(CorrMatchError::IndexOutOfBounds {
+                index: level,
+                len: self.levels.len(),
+                context: "level",
+            });
+        }
+        let (rotated_img, mask) =
+            rotate_downsample_to_level(self.levels[0].view(), angle_deg, self.cfg.fill(), level)?;
+        let ssd_scores_fill = self.cfg.fill() == FillStrategy::EdgeClamp;
+        RotatedTemplate::build(rotated_img, mask, angle_deg, ssd_scores_fill, false, false)
     }
 }
 
@@ -439,29 +1068,82 @@ pub struct CompiledTemplateNoRot {
     levels: Vec<OwnedImage>,
     unmasked_zncc: Vec<TemplatePlan>,
     unmasked_ssd: Vec<SsdTemplatePlan>,
+    /// Single-angle (0°) banks built from `CompileOptions::mask`; empty
+    /// without a mask.
+    masked: Vec<LevelBank>,
+    downsampler: Option<Arc<dyn Downsampler>>,
 }
 
 impl CompiledTemplateNoRot {
     /// Compiles template assets without rotation support.
     pub fn compile(tpl: &Template, cfg: CompileConfigNoRot) -> CorrMatchResult<Self> {
-        let pyramid = ImagePyramid::build_u8(tpl.view(), cfg.max_levels)?;
+        Self::compile_with(tpl, cfg, CompileOptions::default())
+    }
+
+    /// Compiles template assets without rotation support and with
+    /// template-level `opts`.
+    pub fn compile_with(
+        tpl: &Template,
+        cfg: CompileConfigNoRot,
+        opts: CompileOptions,
+    ) -> CorrMatchResult<Self> {
+        let pyramid = template_pyramid(tpl, cfg.max_levels, opts.downsampler.as_ref())?;
         let mut levels = pyramid.into_levels();
         trim_degenerate_levels(&mut levels, 1)?;
 
         let mut unmasked_zncc = Vec::with_capacity(levels.len());
         let mut unmasked_ssd = Vec::with_capacity(levels.len());
         for level in levels.iter() {
-            unmasked_zncc.push(TemplatePlan::from_view(level.view())?);
+            let plan = TemplatePlan::from_view(level.view())?;
+            if unmasked_zncc.is_empty() {
+                check_template_variance(&plan, opts.min_template_variance)?;
+            }
+            unmasked_zncc.push(plan);
             unmasked_ssd.push(SsdTemplatePlan::from_view(level.view())?);
         }
+        let masked = match &opts.mask {
+            Some(mask) => masked_banks(&levels, mask)?,
+            None => Vec::new(),
+        };
 
         Ok(Self {
             levels,
             unmasked_zncc,
             unmasked_ssd,
+            masked,
+            downsampler: opts.downsampler,
         })
     }
 
+    /// Returns true when the template was compiled with a mask.
+    pub fn is_masked(&self) -> bool {
+        !self.masked.is_empty()
+    }
+
+    fn masked_slot(&self, level: usize, angle_idx: usize) -> CorrMatchResult<&RotatedTemplate> {
+        if self.masked.is_empty() {
+            return Err(CorrMatchError::RotationUnavailable {
+                reason: "compiled without rotation support",
+            });
+        }
+        let bank = self
+            .masked
+            .get(level)
+            .ok_or(CorrMatchError::IndexOutOfBounds {
+                index: level,
+                len: self.masked.len(),
+                context: "level",
+            })?;
+        bank.slots
+            .get(angle_idx)
+            .and_then(OnceLock::get)
+            .ok_or(CorrMatchError::IndexOutOfBounds {
+                index: angle_idx,
+                len: bank.slots.len(),
+                context: "angle_idx",
+            })
+    }
+
     /// Returns the number of pyramid levels.
     pub fn num_levels(&self) -> usize {
         self.levels.len()
@@ -474,6 +1156,11 @@ impl CompiledTemplateNoRot {
             .map(|img| (img.width(), img.height()))
     }
 
+    /// Returns the unrotated template image stored for a pyramid level.
+    pub fn level_image(&self, level: usize) -> Option<ImageView<'_, u8>> {
+        self.levels.get(level).map(OwnedImage::view)
+    }
+
     /// Returns an unmasked ZNCC template plan for a given level.
     pub fn unmasked_zncc_plan(&self, level: usize) -> CorrMatchResult<&TemplatePlan> {
         self.unmasked_zncc
@@ -497,6 +1184,38 @@ impl CompiledTemplateNoRot {
     }
 }
 
+/// Builds the single-angle (0°) masked bank of every template level.
+fn masked_banks(levels: &[OwnedImage], mask: &[u8]) -> CorrMatchResult<Vec<LevelBank>> {
+    let grid = AngleGrid::new(0.0, 360.0, 360.0)?;
+    let mut level_mask: Vec<u8> = mask.iter().map(|&m| u8::from(m != 0)).collect();
+    let mut banks = Vec::with_capacity(levels.len());
+    for (idx, level) in levels.iter().enumerate() {
+        if idx > 0 {
+            let prev = &levels[idx - 1];
+            level_mask = downsample_mask(&level_mask, prev.width(), prev.height())?;
+            if level_mask.len() != level.width() * level.height() {
+                return Err(CorrMatchError::InvalidConfig {
+                    reason: "template masks require a downsampler that rounds odd sizes down",
+                });
+            }
+        }
+        let slot = OnceLock::new();
+        let _ = slot.set(RotatedTemplate::build(
+            OwnedImage::from_view(level.view())?,
+            level_mask.clone(),
+            0.0,
+            false,
+            false,
+            false,
+        )?);
+        banks.push(LevelBank {
+            grid: grid.clone(),
+            slots: vec![slot],
+        });
+    }
+    Ok(banks)
+}
+
 /// Compiled template assets for rotated or unrotated matching.
 ///
 /// Use `Template::compile`/`CompiledTemplate::compile_rotated` when rotation
@@ -520,6 +1239,28 @@ impl CompiledTemplate {
         Ok(Self::Unrotated(CompiledTemplateNoRot::compile(tpl, cfg)?))
     }
 
+    /// Compiles rotation-enabled template assets with template-level `opts`.
+    pub fn compile_with(
+        tpl: &Template,
+        cfg: CompileConfig,
+        opts: CompileOptions,
+    ) -> CorrMatchResult<Self> {
+        Ok(Self::Rotated(CompiledTemplateRot::compile_with(
+            tpl, cfg, opts,
+        )?))
+    }
+
+    /// Compiles rotation-disabled template assets with template-level `opts`.
+    pub fn compile_unrotated_with(
+        tpl: &Template,
+        cfg: CompileConfigNoRot,
+        opts: CompileOptions,
+    ) -> CorrMatchResult<Self> {
+        Ok(Self::Unrotated(CompiledTemplateNoRot::compile_with(
+            tpl, cfg, opts,
+        )?))
+    }
+
     /// Compiles rotation-enabled template assets (backwards-compatible default).
     pub fn compile(tpl: &Template, cfg: CompileConfig) -> CorrMatchResult<Self> {
         Self::compile_rotated(tpl, cfg)
@@ -533,6 +1274,19 @@ impl CompiledTemplate {
         }
     }
 
+    /// Computes all lazily cached rotations up front.
+    ///
+    /// Matching fills rotation slots on first use, which slows the first
+    /// calls when `precompute_coarsest` is off or finer levels are involved.
+    /// Call this once after compiling to move that cost out of the hot path.
+    /// Templates compiled without rotation have nothing to prepare.
+    pub fn warm_up(&self) -> CorrMatchResult<()> {
+        match self {
+            Self::Rotated(rot) => rot.warm_up(),
+            Self::Unrotated(_) => Ok(()),
+        }
+    }
+
     /// Returns the width and height for a pyramid level.
     pub fn level_size(&self, level: usize) -> Option<(usize, usize)> {
         match self {
@@ -541,11 +1295,79 @@ impl CompiledTemplate {
         }
     }
 
+    /// Maps a position at a template pyramid level to level-0 (base)
+    /// coordinates, e.g. to plot coarse-level candidates on the full-size
+    /// image.
+    ///
+    /// Template and image pyramids share the same per-level decimation, see
+    /// [`ImagePyramid::scale_factor`](crate::ImagePyramid::scale_factor).
+    pub fn level_to_base(&self, level: usize, x: f32, y: f32) -> (f32, f32) {
+        let factor = level_factor(level) as f32;
+        (x * factor, y * factor)
+    }
+
+    /// Returns a view of the unrotated template image for a pyramid level.
+    ///
+    /// Level 0 is the template as compiled; coarser levels are its
+    /// box-filtered downsamples, as used to build the plans of that level.
+    pub fn level_image(&self, level: usize) -> Option<ImageView<'_, u8>> {
+        match self {
+            Self::Rotated(rot) => rot.level_image(level),
+            Self::Unrotated(unrot) => unrot.level_image(level),
+        }
+    }
+
     /// Returns the angle grid for a pyramid level.
+    ///
+    /// Templates compiled without rotation have no grid, unless they carry a
+    /// mask: their masked bank has a single 0° angle.
     pub fn angle_grid(&self, level: usize) -> Option<&AngleGrid> {
         match self {
             Self::Rotated(rot) => rot.angle_grid(level),
-            Self::Unrotated(_) => None,
+            Self::Unrotated(unrot) => unrot.masked.get(level).map(|bank| &bank.grid),
+        }
+    }
+
+    /// Returns the angles sampled at a pyramid level, in degrees and grid
+    /// order.
+    ///
+    /// These are the exact values of [`AngleGrid::iter`], wrapped to
+    /// `[-180, 180)`. `None` when [`Self::angle_grid`] has no grid for the
+    /// level.
+    pub fn angles(&self, level: usize) -> Option<Vec<f32>> {
+        self.angle_grid(level).map(|grid| grid.iter().collect())
+    }
+
+    /// Returns the rotation mode a search configured with `requested` runs in.
+    ///
+    /// A template compiled with a mask but without rotation is scanned
+    /// through its single-angle masked bank, i.e. like a rotated template.
+    pub(crate) fn search_rotation(&self, requested: RotationMode) -> RotationMode {
+        match self {
+            Self::Unrotated(unrot) if unrot.is_masked() => RotationMode::Enabled,
+            _ => requested,
+        }
+    }
+
+    /// Returns the custom downsampler the template was compiled with.
+    pub(crate) fn downsampler(&self) -> Option<&Arc<dyn Downsampler>> {
+        match self {
+            Self::Rotated(rot) => rot.downsampler.as_ref(),
+            Self::Unrotated(unrot) => unrot.downsampler.as_ref(),
+        }
+    }
+
+    /// Estimates the largest coarsest-level rotation step that cannot skip the
+    /// true angle.
+    ///
+    /// The coarsest template is rotated in small increments until ZNCC against
+    /// its unrotated self drops below 0.5, and the step may be up to twice that
+    /// angle. Returns `None` for templates compiled without rotation. Each
+    /// call recomputes the estimate.
+    pub fn recommended_max_coarse_step(&self) -> CorrMatchResult<Option<f32>> {
+        match self {
+            Self::Rotated(rot) => rot.recommended_max_coarse_step().map(Some),
+            Self::Unrotated(_) => Ok(None),
         }
     }
 
@@ -570,30 +1392,299 @@ impl CompiledTemplate {
         &self,
         level: usize,
         angle_idx: usize,
-    ) -> CorrMatchResult<&RotatedTemplate> {
+    ) -> CorrMatchResult<RotatedRef<'_>> {
         match self {
             Self::Rotated(rot) => rot.rotated(level, angle_idx),
+            Self::Unrotated(unrot) => unrot.masked_slot(level, angle_idx).map(RotatedRef::Pinned),
+        }
+    }
+
+    /// Returns the template rotated to an off-grid angle at a level.
+    pub(crate) fn rotated_at(
+        &self,
+        level: usize,
+        angle_deg: f32,
+    ) -> CorrMatchResult<RotatedTemplate> {
+        match self {
+            Self::Rotated(rot) => rot.rotated_at(level, angle_deg),
             Self::Unrotated(_) => Err(CorrMatchError::RotationUnavailable {
                 reason: "compiled without rotation support",
             }),
         }
     }
 
+    /// Returns a rotated template entry kept for the template's lifetime.
+    fn pinned(&self, level: usize, angle_idx: usize) -> CorrMatchResult<&RotatedTemplate> {
+        match self {
+            Self::Rotated(rot) => rot.pinned(level, angle_idx),
+            Self::Unrotated(unrot) => unrot.masked_slot(level, angle_idx),
+        }
+    }
+
     /// Returns a masked ZNCC template plan for a given level and angle.
+    ///
+    /// The rotation is kept for the template's lifetime, even below the
+    /// coarsest level with a bounded rotation cache; see
+    /// [`Self::cached_zncc_plan`] to go through the cache instead.
     pub fn rotated_zncc_plan(
         &self,
         level: usize,
         angle_idx: usize,
     ) -> CorrMatchResult<&MaskedTemplatePlan> {
-        Ok(self.rotated(level, angle_idx)?.zncc_plan())
+        Ok(self.pinned(level, angle_idx)?.zncc_plan())
     }
 
     /// Returns a masked SSD template plan for a given level and angle.
+    ///
+    /// The rotation is kept for the template's lifetime, even below the
+    /// coarsest level with a bounded rotation cache; see
+    /// [`Self::cached_ssd_plan`] to go through the cache instead.
     pub fn rotated_ssd_plan(
         &self,
         level: usize,
         angle_idx: usize,
     ) -> CorrMatchResult<&MaskedSsdTemplatePlan> {
-        Ok(self.rotated(level, angle_idx)?.ssd_plan())
+        Ok(self.pinned(level, angle_idx)?.ssd_plan())
+    }
+
+    /// Returns a handle to the masked ZNCC plan for a level and angle, taken
+    /// from the bounded rotation cache when one is configured.
+    ///
+    /// Without [`CompileConfig::rotation_cache_capacity`] this is
+    /// [`Self::rotated_zncc_plan`] behind a handle.
+    pub fn cached_zncc_plan(
+        &self,
+        level: usize,
+        angle_idx: usize,
+    ) -> CorrMatchResult<PlanRef<'_, MaskedTemplatePlan>> {
+        Ok(PlanRef::new(
+            self.rotated(level, angle_idx)?,
+            RotatedTemplate::zncc_plan,
+        ))
+    }
+
+    /// Returns a handle to the masked SSD plan for a level and angle, taken
+    /// from the bounded rotation cache when one is configured.
+    ///
+    /// Without [`CompileConfig::rotation_cache_capacity`] this is
+    /// [`Self::rotated_ssd_plan`] behind a handle.
+    pub fn cached_ssd_plan(
+        &self,
+        level: usize,
+        angle_idx: usize,
+    ) -> CorrMatchResult<PlanRef<'_, MaskedSsdTemplatePlan>> {
+        Ok(PlanRef::new(
+            self.rotated(level, angle_idx)?,
+            RotatedTemplate::ssd_plan,
+        ))
+    }
+
+    /// Returns true when the rotated mask at a level and angle covers every pixel.
+    ///
+    /// Such slots are scanned with the unmasked kernels during coarse search.
+    pub fn rotated_mask_is_full(&self, level: usize, angle_idx: usize) -> CorrMatchResult<bool> {
+        Ok(self.rotated(level, angle_idx)?.is_full_mask())
+    }
+
+    /// Returns the rotated template pixels and mask used at a level and angle.
+    ///
+    /// Both buffers are row-major with the level's width and height (see
+    /// `level_size`); mask entries are 1 for valid pixels and 0 for fill. The
+    /// slot is taken from the rotation cache, so it is computed at most once.
+    pub fn rotated_template_image(
+        &self,
+        level: usize,
+        angle_idx: usize,
+    ) -> CorrMatchResult<(Vec<u8>, Vec<u8>)> {
+        Ok(self.rotated(level, angle_idx)?.image_and_mask())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CompileConfig, CompiledTemplate};
+    use crate::{CorrMatchError, FillStrategy, Template};
+
+    #[test]
+    fn strict_coarse_step_rejects_steps_wider_than_the_lobe() {
+        // Fine texture decorrelates quickly under rotation; a smooth ramp does not.
+        let textured: Vec<u8> = (0..48 * 48)
+            .map(|i| (((i % 48) * 13) ^ ((i / 48) * 7) ^ ((i % 48) * (i / 48))) as u8)
+            .collect();
+        let smooth: Vec<u8> = (0..48 * 48)
+            .map(|i| (((i % 48) + 2 * (i / 48)) * 2) as u8)
+            .collect();
+        let cfg = CompileConfig {
+            max_levels: 2,
+            coarse_step_deg: 60.0,
+            min_step_deg: 30.0,
+            ..CompileConfig::default()
+        };
+
+        let narrow = Template::new(textured, 48, 48).unwrap();
+        let compiled = CompiledTemplate::compile_rotated(&narrow, cfg.clone()).unwrap();
+        let narrow_step = compiled.recommended_max_coarse_step().unwrap().unwrap();
+        assert!(narrow_step < 60.0, "textured lobe step {narrow_step}");
+        let wide = Template::new(smooth, 48, 48).unwrap();
+        let compiled = CompiledTemplate::compile_rotated(&wide, cfg.clone()).unwrap();
+        let wide_step = compiled.recommended_max_coarse_step().unwrap().unwrap();
+        assert!(wide_step > narrow_step, "{wide_step} <= {narrow_step}");
+
+        let strict = CompileConfig {
+            strict_coarse_step: true,
+            ..cfg
+        };
+        assert!(matches!(
+            CompiledTemplate::compile_rotated(&narrow, strict.clone()),
+            Err(CorrMatchError::InvalidConfig { .. })
+        ));
+        let fine = CompileConfig {
+            coarse_step_deg: narrow_step * 0.5,
+            min_step_deg: narrow_step * 0.25,
+            ..strict
+        };
+        assert!(CompiledTemplate::compile_rotated(&narrow, fine).is_ok());
+    }
+
+    #[test]
+    fn for_template_scales_with_size_and_texture() {
+        let xor = |w: usize, h: usize| -> Vec<u8> {
+            (0..w * h)
+                .map(|i| (((i % w) * 13) ^ ((i / w) * 7) ^ ((i % w) * (i / w))) as u8)
+                .collect()
+        };
+        let small = CompileConfig::for_template(&Template::new(xor(24, 20), 24, 20).unwrap());
+        let large = CompileConfig::for_template(&Template::new(xor(256, 160), 256, 160).unwrap());
+        assert_eq!(small.max_levels, 2);
+        assert_eq!(large.max_levels, 5);
+        assert!(large.min_step_deg < small.min_step_deg);
+        for cfg in [&small, &large] {
+            cfg.validate().unwrap();
+            assert!(cfg.min_step_deg <= cfg.coarse_step_deg);
+            assert!(cfg.coarse_step_deg <= 20.0);
+        }
+
+        // Fine texture needs a finer coarse step than a smooth ramp.
+        let smooth: Vec<u8> = (0..48 * 48)
+            .map(|i| (((i % 48) + 2 * (i / 48)) * 2) as u8)
+            .collect();
+        let textured = CompileConfig::for_template(&Template::new(xor(48, 48), 48, 48).unwrap());
+        let ramp = CompileConfig::for_template(&Template::new(smooth, 48, 48).unwrap());
+        assert!(textured.coarse_step_deg < ramp.coarse_step_deg);
+
+        let template = Template::new(xor(48, 48), 48, 48).unwrap();
+        let compiled = CompiledTemplate::compile_rotated(&template, textured.clone()).unwrap();
+        assert_eq!(compiled.num_levels(), textured.max_levels);
+    }
+
+    #[test]
+    fn warm_up_fills_every_rotation_slot() {
+        let data: Vec<u8> = (0..32 * 24).map(|i| (i * 7 % 251) as u8).collect();
+        let template = Template::new(data, 32, 24).unwrap();
+        let compiled = CompiledTemplate::compile_rotated(
+            &template,
+            CompileConfig {
+                max_levels: 2,
+                coarse_step_deg: 30.0,
+                min_step_deg: 15.0,
+                precompute_coarsest: false,
+                ..CompileConfig::default()
+            },
+        )
+        .unwrap();
+        let CompiledTemplate::Rotated(rot) = &compiled else {
+            unreachable!("compiled with rotation");
+        };
+        let filled = |rot: &super::CompiledTemplateRot| {
+            rot.banks
+                .iter()
+                .flat_map(|bank| bank.slots.iter())
+                .filter(|slot| slot.get().is_some())
+                .count()
+        };
+        assert_eq!(filled(rot), 0);
+
+        compiled.warm_up().unwrap();
+        let total: usize = rot.banks.iter().map(|bank| bank.slots.len()).sum();
+        assert_eq!(filled(rot), total);
+    }
+
+    #[test]
+    fn rotation_cache_capacity_evicts_finer_levels_only() {
+        let data: Vec<u8> = (0..32 * 32)
+            .map(|i| (((i % 32) * 13) ^ ((i / 32) * 7)) as u8)
+            .collect();
+        let template = Template::new(data, 32, 32).unwrap();
+        let cfg = CompileConfig {
+            max_levels: 2,
+            coarse_step_deg: 30.0,
+            min_step_deg: 15.0,
+            ..CompileConfig::default()
+        };
+        let unbounded = CompiledTemplate::compile_rotated(&template, cfg.clone()).unwrap();
+        let bounded = CompiledTemplate::compile_rotated(
+            &template,
+            CompileConfig {
+                rotation_cache_capacity: Some(2),
+                ..cfg
+            },
+        )
+        .unwrap();
+        let CompiledTemplate::Rotated(rot) = &bounded else {
+            unreachable!("compiled with rotation");
+        };
+
+        // A handle outlives the eviction of its rotation.
+        let held = bounded.cached_zncc_plan(0, 0).unwrap();
+        for idx in 0..6 {
+            let plan = bounded.cached_zncc_plan(0, idx).unwrap();
+            let expected = unbounded.rotated_zncc_plan(0, idx).unwrap();
+            assert_eq!(plan.t_prime(), expected.t_prime());
+        }
+        assert_eq!(
+            held.t_prime(),
+            unbounded.rotated_zncc_plan(0, 0).unwrap().t_prime()
+        );
+        assert_eq!(rot.lru.as_ref().unwrap().lock().unwrap().len(), 2);
+        assert!(rot.banks[0].slots.iter().all(|slot| slot.get().is_none()));
+        assert!(rot.banks[1].slots.iter().all(|slot| slot.get().is_some()));
+
+        // The plain accessor pins its rotation instead.
+        bounded.rotated_zncc_plan(0, 3).unwrap();
+        assert!(rot.banks[0].slots[3].get().is_some());
+        assert_eq!(rot.lru.as_ref().unwrap().lock().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn rotation_cache_capacity_does_not_preallocate() {
+        let data: Vec<u8> = (0..16 * 16).map(|i| (i * 7 % 251) as u8).collect();
+        let template = Template::new(data, 16, 16).unwrap();
+        let compiled = CompiledTemplate::compile_rotated(
+            &template,
+            CompileConfig {
+                max_levels: 2,
+                rotation_cache_capacity: Some(usize::MAX),
+                ..CompileConfig::default()
+            },
+        )
+        .unwrap();
+        assert!(compiled.cached_zncc_plan(0, 1).is_ok());
+    }
+
+    #[test]
+    #[allow(deprecated)]
+    fn deprecated_fill_value_overrides_fill_strategy_while_nonzero() {
+        assert_eq!(CompileConfig::default().fill(), FillStrategy::Constant(0));
+        let cfg = CompileConfig {
+            fill_value: 7,
+            fill_strategy: FillStrategy::EdgeClamp,
+            ..CompileConfig::default()
+        };
+        assert_eq!(cfg.fill(), FillStrategy::Constant(7));
+        let cfg = CompileConfig {
+            fill_strategy: FillStrategy::EdgeClamp,
+            ..CompileConfig::default()
+        };
+        assert_eq!(cfg.fill(), FillStrategy::EdgeClamp);
     }
 }
@@ -0,0 +1,81 @@
+//! Compiled assets for RGB template matching.
+
+use crate::bank::{check_template_variance, CompileConfigNoRot, CompileOptions};
+use crate::image::pyramid::ImagePyramid;
+use crate::template::{RgbTemplatePlan, Template3};
+use crate::util::{CorrMatchError, CorrMatchResult};
+
+/// Per-level RGB plans for translation-only matching.
+///
+/// Each channel is downsampled independently. Coarse levels where every
+/// channel has become flat are dropped, as for grayscale templates.
+pub struct CompiledTemplate3 {
+    levels: Vec<RgbTemplatePlan>,
+}
+
+impl CompiledTemplate3 {
+    /// Compiles RGB template assets without rotation support.
+    pub fn compile(tpl: &Template3, cfg: CompileConfigNoRot) -> CorrMatchResult<Self> {
+        Self::compile_with(tpl, cfg, CompileOptions::default())
+    }
+
+    /// Compiles RGB template assets without rotation support and with
+    /// template-level `opts`.
+    ///
+    /// `min_template_variance` applies to the most varied channel.
+    pub fn compile_with(
+        tpl: &Template3,
+        cfg: CompileConfigNoRot,
+        opts: CompileOptions,
+    ) -> CorrMatchResult<Self> {
+        let [r, g, b] = tpl.views();
+        let r = ImagePyramid::build_u8(r, cfg.max_levels)?;
+        let g = ImagePyramid::build_u8(g, cfg.max_levels)?;
+        let b = ImagePyramid::build_u8(b, cfg.max_levels)?;
+
+        let mut levels = Vec::with_capacity(r.levels().len());
+        for ((r, g), b) in r.levels().iter().zip(g.levels()).zip(b.levels()) {
+            match RgbTemplatePlan::from_views([r.view(), g.view(), b.view()]) {
+                Ok(plan) => levels.push(plan),
+                Err(err @ CorrMatchError::DegenerateTemplate { .. }) if levels.is_empty() => {
+                    return Err(err);
+                }
+                Err(CorrMatchError::DegenerateTemplate { .. }) => break,
+                Err(err) => return Err(err),
+            }
+        }
+        // The floor applies to the most varied channel; flatter channels are
+        // already left out of the score.
+        if let Some(most_varied) = (0..3)
+            .filter_map(|index| levels[0].channel(index))
+            .min_by(|a, b| a.inv_std().total_cmp(&b.inv_std()))
+        {
+            check_template_variance(most_varied, opts.min_template_variance)?;
+        }
+
+        Ok(Self { levels })
+    }
+
+    /// Returns the number of pyramid levels.
+    pub fn num_levels(&self) -> usize {
+        self.levels.len()
+    }
+
+    /// Returns the width and height for a pyramid level.
+    pub fn level_size(&self, level: usize) -> Option<(usize, usize)> {
+        self.levels
+            .get(level)
+            .map(|plan| (plan.width(), plan.height()))
+    }
+
+    /// Returns the RGB plan for a given level.
+    pub fn plan(&self, level: usize) -> CorrMatchResult<&RgbTemplatePlan> {
+        self.levels
+            .get(level)
+            .ok_or(CorrMatchError::IndexOutOfBounds {
+                index: level,
+                len: self.levels.len(),
+                context: "level",
+            })
+    }
+}
@@ -1,31 +1,189 @@
 //! Non-maximum suppression utilities for candidates.
 
-use crate::candidate::topk::{sort_peaks_desc, Peak};
+use crate::candidate::topk::{rank_key, sort_peaks_desc, Peak, PeakOrder};
+use alloc::borrow::ToOwned;
+use alloc::vec::Vec;
 
 /// Applies 2D non-maximum suppression using Chebyshev distance.
 ///
 /// Peaks are sorted by descending score and kept if they are farther than
 /// `radius` in Chebyshev distance from all previously kept peaks.
 pub fn nms_2d(peaks: &mut [Peak], radius: usize) -> Vec<Peak> {
+    nms_2d_keep_ratio(peaks, radius, 0.0)
+}
+
+/// Applies 2D non-maximum suppression that spares strong nearby peaks.
+///
+/// Behaves like [`nms_2d`], except that a peak within `radius` of kept peaks
+/// survives when it is a local maximum (no peak within one pixel scores
+/// higher) and its score is within `keep_ratio` of every nearby kept peak.
+/// "Within" is measured relative to the kept score's magnitude, so for a
+/// positive score `s` the threshold is `keep_ratio * s`, and it stays
+/// meaningful for negative SSD scores. A ratio of 0 disables the exception.
+pub fn nms_2d_keep_ratio(peaks: &mut [Peak], radius: usize, keep_ratio: f32) -> Vec<Peak> {
+    nms_2d_ranked(peaks, radius, keep_ratio, PeakOrder::new(false))
+}
+
+/// Applies [`nms_2d_keep_ratio`] with peaks ranked by `order`.
+///
+/// When `order` ranks by magnitude, suppression, the keep-ratio threshold,
+/// and the local maximum test all compare score magnitudes; kept peaks
+/// retain their sign. Its tie-break decides which of several equal peaks
+/// suppresses the others, and its angle separation, if any, exempts peaks
+/// at clearly different angles from suppressing each other.
+pub(crate) fn nms_2d_ranked(
+    peaks: &mut [Peak],
+    radius: usize,
+    keep_ratio: f32,
+    order: PeakOrder,
+) -> Vec<Peak> {
+    let key = |peak: &Peak| rank_key(peak.score, order.by_magnitude());
+    sort_peaks_desc(peaks, order);
     if radius == 0 {
-        sort_peaks_desc(peaks);
         return peaks.to_owned();
     }
 
-    sort_peaks_desc(peaks);
     let mut kept: Vec<Peak> = Vec::new();
-
-    'outer: for peak in peaks.iter().copied() {
-        for kept_peak in kept.iter() {
-            let dx = peak.x.max(kept_peak.x) - peak.x.min(kept_peak.x);
-            let dy = peak.y.max(kept_peak.y) - peak.y.min(kept_peak.y);
-            let dist = dx.max(dy);
-            if dist <= radius {
-                continue 'outer;
-            }
+    for peak in peaks.iter().copied() {
+        let mut near = kept
+            .iter()
+            .filter(|kept_peak| {
+                chebyshev(&peak, kept_peak) <= radius && !order.angles_distinct(&peak, kept_peak)
+            })
+            .peekable();
+        if near.peek().is_none() {
+            kept.push(peak);
+            continue;
+        }
+        if keep_ratio <= 0.0 {
+            continue;
+        }
+        let strong = near.all(|kept_peak| {
+            let kept_key = key(kept_peak);
+            let threshold = kept_key - (1.0 - keep_ratio) * kept_key.abs();
+            key(&peak) >= threshold
+        });
+        let local_max = peaks.iter().all(|other| {
+            chebyshev(&peak, other) > 1
+                || order.angles_distinct(&peak, other)
+                || key(other) <= key(&peak)
+        });
+        if strong && local_max {
+            kept.push(peak);
         }
-        kept.push(peak);
     }
 
     kept
 }
+
+fn chebyshev(a: &Peak, b: &Peak) -> usize {
+    a.x.abs_diff(b.x).max(a.y.abs_diff(b.y))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{nms_2d_keep_ratio, nms_2d_ranked};
+    use crate::candidate::topk::{Peak, PeakOrder, TieBreak};
+
+    fn peak(x: usize, y: usize, score: f32) -> Peak {
+        Peak {
+            x,
+            y,
+            score,
+            angle_idx: 0,
+        }
+    }
+
+    #[test]
+    fn keep_ratio_spares_adjacent_equal_peaks() {
+        let peaks = vec![
+            peak(10, 10, 0.9),
+            peak(14, 10, 0.9),
+            peak(13, 10, 0.85),
+            peak(11, 12, 0.4),
+        ];
+
+        let kept = nms_2d_keep_ratio(&mut peaks.clone(), 6, 0.0);
+        assert_eq!(kept, vec![peak(10, 10, 0.9)]);
+
+        let kept = nms_2d_keep_ratio(&mut peaks.clone(), 6, 0.9);
+        assert_eq!(kept, vec![peak(10, 10, 0.9), peak(14, 10, 0.9)]);
+    }
+
+    #[test]
+    fn keep_ratio_uses_score_magnitude_for_negative_scores() {
+        let peaks = vec![peak(0, 0, -100.0), peak(3, 0, -105.0), peak(6, 0, -200.0)];
+        let kept = nms_2d_keep_ratio(&mut peaks.clone(), 4, 0.9);
+        assert_eq!(kept, vec![peak(0, 0, -100.0), peak(3, 0, -105.0)]);
+    }
+
+    #[test]
+    fn magnitude_ranking_keeps_strong_negative_peaks() {
+        let peaks = vec![peak(0, 0, 0.6), peak(2, 0, -0.95), peak(20, 0, 0.3)];
+
+        let kept = nms_2d_ranked(&mut peaks.clone(), 4, 0.0, PeakOrder::new(false));
+        assert_eq!(kept, vec![peak(0, 0, 0.6), peak(20, 0, 0.3)]);
+
+        let kept = nms_2d_ranked(&mut peaks.clone(), 4, 0.0, PeakOrder::new(true));
+        assert_eq!(kept, vec![peak(2, 0, -0.95), peak(20, 0, 0.3)]);
+    }
+
+    #[test]
+    fn tie_break_picks_which_equal_peak_survives() {
+        let tied = |angle_idx| Peak {
+            angle_idx,
+            ..peak(0, 0, 0.8)
+        };
+        let peaks = vec![
+            peak(2, 2, 0.8),
+            peak(5, 4, 0.8),
+            Peak {
+                x: 8,
+                y: 6,
+                ..tied(4)
+            },
+            Peak {
+                x: 3,
+                y: 7,
+                ..tied(2)
+            },
+        ];
+        let order = |tie_break| PeakOrder::new(false).with_tie_break(tie_break, 10, 8, 2);
+
+        let kept = nms_2d_ranked(&mut peaks.clone(), 20, 0.0, order(TieBreak::TopLeftFirst));
+        assert_eq!(kept, vec![peak(2, 2, 0.8)]);
+
+        let kept = nms_2d_ranked(
+            &mut peaks.clone(),
+            20,
+            0.0,
+            order(TieBreak::NearestToCenter),
+        );
+        assert_eq!(kept, vec![peak(5, 4, 0.8)]);
+
+        let kept = nms_2d_ranked(
+            &mut peaks.clone(),
+            20,
+            0.0,
+            order(TieBreak::MostCentralAngle),
+        );
+        assert_eq!((kept[0].x, kept[0].y, kept[0].angle_idx), (3, 7, 2));
+    }
+
+    #[test]
+    fn angle_separation_keeps_colocated_peaks_at_distinct_angles() {
+        let at = |angle_idx, score| Peak {
+            angle_idx,
+            ..peak(10, 10, score)
+        };
+        // 12 steps of 30°: index 11 wraps to 30° from index 0.
+        let peaks = vec![at(0, 0.9), at(3, 0.85), at(11, 0.8), at(1, 0.7)];
+        let order = PeakOrder::new(false).with_angle_separation(30.0, 45.0);
+
+        let kept = nms_2d_ranked(&mut peaks.clone(), 4, 0.0, PeakOrder::new(false));
+        assert_eq!(kept, vec![at(0, 0.9)]);
+
+        let kept = nms_2d_ranked(&mut peaks.clone(), 4, 0.0, order);
+        assert_eq!(kept, vec![at(0, 0.9), at(3, 0.85)]);
+    }
+}
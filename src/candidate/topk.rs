@@ -1,6 +1,7 @@
 //! Top-K candidate tracking for match peaks.
 
-use std::cmp::Ordering;
+use alloc::vec::Vec;
+use core::cmp::Ordering;
 
 /// Peak candidate in image space for a specific rotation angle.
 #[derive(Clone, Copy, Debug, PartialEq)]
@@ -15,31 +16,167 @@ pub struct Peak {
     pub angle_idx: usize,
 }
 
-fn peak_cmp_desc(a: &Peak, b: &Peak) -> Ordering {
-    b.score
-        .total_cmp(&a.score)
-        .then_with(|| a.y.cmp(&b.y))
-        .then_with(|| a.x.cmp(&b.x))
-        .then_with(|| a.angle_idx.cmp(&b.angle_idx))
+/// Returns the value peaks are ranked by: the score itself, or its
+/// magnitude when inverted (negative ZNCC) matches count as well.
+pub(crate) fn rank_key(score: f32, by_magnitude: bool) -> f32 {
+    if by_magnitude {
+        score.abs()
+    } else {
+        score
+    }
+}
+
+/// Preference among peaks with equal scores.
+///
+/// Ties are common on flat-topped correlation plateaus, for example with
+/// repetitive or synthetic patterns. Whatever the preference, remaining ties
+/// fall back to row-major order and then to the angle nearest 0° (the lower
+/// angle index between two equally near angles), so results stay
+/// deterministic. A template with N-fold rotational symmetry is therefore
+/// reported at its symmetric pose closest to 0°.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum TieBreak {
+    /// Prefer the smallest `y`, then the smallest `x`.
+    #[default]
+    TopLeftFirst,
+    /// Prefer the placement whose template center is nearest the image center.
+    NearestToCenter,
+    /// Prefer the angle nearest 0° before comparing positions.
+    MostCentralAngle,
 }
 
-/// Sorts peaks by descending score with deterministic tie-breaking.
-pub(crate) fn sort_peaks_desc(peaks: &mut [Peak]) {
-    peaks.sort_by(peak_cmp_desc);
+/// Total order used to rank peaks: rank key descending, then the
+/// [`TieBreak`] preference, then row-major position and angle distance
+/// from 0°.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct PeakOrder {
+    by_magnitude: bool,
+    tie_break: TieBreak,
+    /// Doubled center of the placement range, keeping odd ranges integral.
+    center2: (usize, usize),
+    /// Index of the angle-grid entry nearest 0°.
+    zero_angle_idx: usize,
+    /// Grid step and minimum angle difference, in degrees, beyond which NMS
+    /// keeps peaks regardless of position.
+    angle_separation: Option<(f32, f32)>,
+}
+
+impl PeakOrder {
+    /// Ranks by score (or `|score|`) and breaks ties top-left first.
+    pub(crate) fn new(by_magnitude: bool) -> Self {
+        Self {
+            by_magnitude,
+            tie_break: TieBreak::TopLeftFirst,
+            center2: (0, 0),
+            zero_angle_idx: 0,
+            angle_separation: None,
+        }
+    }
+
+    /// Applies `tie_break` for placements in `0..=max_x` x `0..=max_y` over
+    /// an angle grid whose entry nearest 0° is `zero_angle_idx`.
+    pub(crate) fn with_tie_break(
+        self,
+        tie_break: TieBreak,
+        max_x: usize,
+        max_y: usize,
+        zero_angle_idx: usize,
+    ) -> Self {
+        Self {
+            tie_break,
+            center2: (max_x, max_y),
+            zero_angle_idx,
+            ..self
+        }
+    }
+
+    #[cfg(feature = "std")]
+    /// Treats peaks on a grid with step `step_deg` whose angles differ by
+    /// more than `min_sep_deg` as distinct instances in NMS.
+    pub(crate) fn with_angle_separation(self, step_deg: f32, min_sep_deg: f32) -> Self {
+        Self {
+            angle_separation: Some((step_deg, min_sep_deg)),
+            ..self
+        }
+    }
+
+    pub(crate) fn by_magnitude(&self) -> bool {
+        self.by_magnitude
+    }
+
+    /// Returns `true` when NMS must not let one peak suppress the other.
+    pub(crate) fn angles_distinct(&self, a: &Peak, b: &Peak) -> bool {
+        self.angle_separation
+            .is_some_and(|(step_deg, min_sep_deg)| {
+                let diff = (a.angle_idx.abs_diff(b.angle_idx) as f32 * step_deg) % 360.0;
+                diff.min(360.0 - diff) > min_sep_deg
+            })
+    }
+
+    /// Returns how far a peak is from the preferred placement (lower wins).
+    fn tie_distance(&self, peak: &Peak) -> usize {
+        match self.tie_break {
+            TieBreak::TopLeftFirst => 0,
+            TieBreak::NearestToCenter => {
+                let dx = (2 * peak.x).abs_diff(self.center2.0);
+                let dy = (2 * peak.y).abs_diff(self.center2.1);
+                dx.saturating_mul(dx).saturating_add(dy.saturating_mul(dy))
+            }
+            TieBreak::MostCentralAngle => self.angle_distance(peak),
+        }
+    }
+
+    /// Returns how many grid steps a peak's angle is from 0°.
+    ///
+    /// Grids start at -180° and increase monotonically, so the index distance
+    /// orders angles by wrapped magnitude.
+    fn angle_distance(&self, peak: &Peak) -> usize {
+        peak.angle_idx.abs_diff(self.zero_angle_idx)
+    }
+
+    /// Orders `a` before `b` when `a` ranks higher.
+    pub(crate) fn cmp(&self, a: &Peak, b: &Peak) -> Ordering {
+        rank_key(b.score, self.by_magnitude)
+            .total_cmp(&rank_key(a.score, self.by_magnitude))
+            .then_with(|| self.tie_distance(a).cmp(&self.tie_distance(b)))
+            .then_with(|| a.y.cmp(&b.y))
+            .then_with(|| a.x.cmp(&b.x))
+            .then_with(|| self.angle_distance(a).cmp(&self.angle_distance(b)))
+            .then_with(|| a.angle_idx.cmp(&b.angle_idx))
+    }
+}
+
+/// Sorts peaks by descending rank with deterministic tie-breaking.
+pub(crate) fn sort_peaks_desc(peaks: &mut [Peak], order: PeakOrder) {
+    peaks.sort_by(|a, b| order.cmp(a, b));
 }
 
 /// Top-K container with O(k) insertion cost.
 pub struct TopK<T> {
     k: usize,
     items: Vec<T>,
+    order: PeakOrder,
 }
 
 impl TopK<Peak> {
     /// Creates a new Top-K collector.
     pub fn new(k: usize) -> Self {
+        Self::ranked(k, false)
+    }
+
+    /// Creates a Top-K collector that optionally ranks peaks by `|score|`.
+    ///
+    /// Retained peaks keep their signed scores either way.
+    pub fn ranked(k: usize, by_magnitude: bool) -> Self {
+        Self::ordered(k, PeakOrder::new(by_magnitude))
+    }
+
+    /// Creates a Top-K collector that ranks peaks by `order`.
+    pub(crate) fn ordered(k: usize, order: PeakOrder) -> Self {
         Self {
             k,
             items: Vec::with_capacity(k),
+            order,
         }
     }
 
@@ -55,19 +192,115 @@ impl TopK<Peak> {
 
         let mut worst_idx = 0usize;
         for (idx, item) in self.items.iter().enumerate().skip(1) {
-            if peak_cmp_desc(item, &self.items[worst_idx]) == Ordering::Greater {
+            if self.order.cmp(item, &self.items[worst_idx]) == Ordering::Greater {
                 worst_idx = idx;
             }
         }
 
-        if peak_cmp_desc(&peak, &self.items[worst_idx]) == Ordering::Less {
+        if self.order.cmp(&peak, &self.items[worst_idx]) == Ordering::Less {
             self.items[worst_idx] = peak;
         }
     }
 
-    /// Returns peaks sorted by descending score.
+    /// Returns peaks sorted by descending rank.
     pub fn into_sorted_desc(mut self) -> Vec<Peak> {
-        sort_peaks_desc(&mut self.items);
+        sort_peaks_desc(&mut self.items, self.order);
         self.items
     }
 }
+
+/// Top-K collector with inline storage for `K` peaks.
+///
+/// Ranks like [`TopK`] but never allocates, so it suits `no_std` targets
+/// without an allocator in the hot path.
+pub struct FixedTopK<const K: usize> {
+    items: [Peak; K],
+    len: usize,
+    order: PeakOrder,
+}
+
+impl<const K: usize> FixedTopK<K> {
+    const EMPTY: Peak = Peak {
+        x: 0,
+        y: 0,
+        score: f32::NEG_INFINITY,
+        angle_idx: 0,
+    };
+
+    /// Creates an empty collector.
+    pub fn new() -> Self {
+        Self::ranked(false)
+    }
+
+    /// Creates a collector that optionally ranks peaks by `|score|`.
+    pub fn ranked(by_magnitude: bool) -> Self {
+        Self {
+            items: [Self::EMPTY; K],
+            len: 0,
+            order: PeakOrder::new(by_magnitude),
+        }
+    }
+
+    /// Pushes a peak, evicting the lowest-ranked one if at capacity.
+    pub fn push(&mut self, peak: Peak) {
+        if K == 0 {
+            return;
+        }
+        if self.len < K {
+            self.items[self.len] = peak;
+            self.len += 1;
+            return;
+        }
+
+        let mut worst_idx = 0usize;
+        for (idx, item) in self.items.iter().enumerate().skip(1) {
+            if self.order.cmp(item, &self.items[worst_idx]) == Ordering::Greater {
+                worst_idx = idx;
+            }
+        }
+
+        if self.order.cmp(&peak, &self.items[worst_idx]) == Ordering::Less {
+            self.items[worst_idx] = peak;
+        }
+    }
+
+    /// Returns the retained peaks sorted by descending rank.
+    pub fn sorted_desc(&mut self) -> &[Peak] {
+        let order = self.order;
+        let peaks = &mut self.items[..self.len];
+        // The order is total, so the in-place unstable sort is deterministic.
+        peaks.sort_unstable_by(|a, b| order.cmp(a, b));
+        peaks
+    }
+}
+
+impl<const K: usize> Default for FixedTopK<K> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{FixedTopK, Peak, TopK};
+
+    #[test]
+    fn fixed_topk_matches_heap_topk() {
+        let scores = [0.2f32, 0.9, -0.95, 0.5, 0.9, 0.1, 0.7];
+        let mut fixed = FixedTopK::<3>::new();
+        let mut heap = TopK::new(3);
+        for (x, &score) in scores.iter().enumerate() {
+            let peak = Peak {
+                x,
+                y: 0,
+                score,
+                angle_idx: 0,
+            };
+            fixed.push(peak);
+            heap.push(peak);
+        }
+        let expected = heap.into_sorted_desc();
+        assert_eq!(fixed.sorted_desc(), expected.as_slice());
+        assert_eq!(FixedTopK::<0>::new().sorted_desc(), &[]);
+    }
+}
@@ -0,0 +1,93 @@
+//! Summed-area tables for constant-time window sums.
+
+use crate::ImageView;
+use alloc::vec;
+use alloc::vec::Vec;
+
+/// Integral images of pixel values and squared pixel values.
+///
+/// Tables have one leading row and column of zeros, so entry `(x, y)` holds
+/// the sum over all pixels strictly above and left of `(x, y)`. Sums are
+/// exact `u64` totals, making window sums independent of summation order.
+pub(crate) struct IntegralImage {
+    stride: usize,
+    sum: Vec<u64>,
+    sum_sq: Vec<u64>,
+}
+
+impl IntegralImage {
+    /// Builds both tables over `image` in one pass.
+    pub(crate) fn new(image: ImageView<'_, u8>) -> Self {
+        let stride = image.width() + 1;
+        let len = stride * (image.height() + 1);
+        let mut sum = vec![0u64; len];
+        let mut sum_sq = vec![0u64; len];
+        for y in 0..image.height() {
+            let row = image.row(y).expect("row within bounds for integral image");
+            let above = y * stride;
+            let here = above + stride;
+            let mut row_sum = 0u64;
+            let mut row_sum_sq = 0u64;
+            for (x, &value) in row.iter().enumerate() {
+                let value = u64::from(value);
+                row_sum += value;
+                row_sum_sq += value * value;
+                sum[here + x + 1] = sum[above + x + 1] + row_sum;
+                sum_sq[here + x + 1] = sum_sq[above + x + 1] + row_sum_sq;
+            }
+        }
+        Self {
+            stride,
+            sum,
+            sum_sq,
+        }
+    }
+
+    /// Returns `(sum, sum of squares)` over the `width` x `height` window
+    /// whose top-left pixel is `(x, y)`.
+    ///
+    /// The caller guarantees the window lies inside the source image.
+    #[inline]
+    pub(crate) fn window_sums(
+        &self,
+        x: usize,
+        y: usize,
+        width: usize,
+        height: usize,
+    ) -> (u64, u64) {
+        let top = y * self.stride;
+        let bottom = (y + height) * self.stride;
+        let rect = |table: &[u64]| {
+            table[bottom + x + width] + table[top + x] - table[top + x + width] - table[bottom + x]
+        };
+        (rect(&self.sum), rect(&self.sum_sq))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::IntegralImage;
+    use crate::ImageView;
+
+    #[test]
+    fn window_sums_match_direct_sums() {
+        let (width, height, stride) = (7, 5, 9);
+        let data: Vec<u8> = (0..stride * height)
+            .map(|i| ((i * 37 + i / stride * 11) % 256) as u8)
+            .collect();
+        let view = ImageView::new(&data, width, height, stride).unwrap();
+        let integral = IntegralImage::new(view);
+
+        for (x, y, w, h) in [(0, 0, 7, 5), (2, 1, 3, 4), (6, 4, 1, 1), (1, 3, 5, 2)] {
+            let mut sum = 0u64;
+            let mut sum_sq = 0u64;
+            for row in y..y + h {
+                for &value in &view.row(row).unwrap()[x..x + w] {
+                    sum += u64::from(value);
+                    sum_sq += u64::from(value) * u64::from(value);
+                }
+            }
+            assert_eq!(integral.window_sums(x, y, w, h), (sum, sum_sq));
+        }
+    }
+}
@@ -10,12 +10,20 @@
 //! `io` module provides convenience loaders.
 
 use crate::util::{CorrMatchError, CorrMatchResult};
+use alloc::vec;
+use alloc::vec::Vec;
 
+pub(crate) mod integral;
 #[cfg(feature = "image-io")]
 pub mod io;
+#[cfg(feature = "std")]
 pub mod pyramid;
 
 /// Borrowed 2D image view with an explicit stride.
+///
+/// Matching accepts padded views directly: kernels, pyramid building, and
+/// refinement read pixels through `row`, which never extends past `width`,
+/// and reported coordinates are relative to the view's top-left corner.
 #[derive(Copy, Clone)]
 pub struct ImageView<'a, T> {
     data: &'a [T],
@@ -86,6 +94,31 @@ impl<'a, T> ImageView<'a, T> {
         self.data.get(start..end)
     }
 
+    /// Returns the element at `(x, y)`, like [`get`](Self::get) without the
+    /// `Option`, for hot loops whose placement bounds are already validated.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `x >= self.width()` or `y >= self.height()`.
+    #[inline]
+    pub fn at(&self, x: usize, y: usize) -> &'a T {
+        assert!(x < self.width && y < self.height, "pixel out of bounds");
+        &self.data[y * self.stride + x]
+    }
+
+    /// Returns row `y` as a slice of length `width`, like [`row`](Self::row)
+    /// without the `Option`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `y >= self.height()`.
+    #[inline]
+    pub fn row_at(&self, y: usize) -> &'a [T] {
+        assert!(y < self.height, "row out of bounds");
+        let start = y * self.stride;
+        &self.data[start..start + self.width]
+    }
+
     /// Returns a zero-copy ROI view into the same backing buffer.
     pub fn roi(
         &self,
@@ -157,6 +190,54 @@ impl<'a, T> ImageView<'a, T> {
 
         ImageView::new(data, width, height, self.stride)
     }
+
+    /// Returns the part of the requested ROI that lies inside the image.
+    ///
+    /// Unlike [`ImageView::roi`], a region extending past the right or bottom
+    /// edge is clipped rather than rejected. Returns `None` when the
+    /// intersection is empty.
+    pub fn roi_clamped(
+        &self,
+        x: usize,
+        y: usize,
+        width: usize,
+        height: usize,
+    ) -> Option<ImageView<'a, T>> {
+        let end_x = x.saturating_add(width).min(self.width);
+        let end_y = y.saturating_add(height).min(self.height);
+        if x >= end_x || y >= end_y {
+            return None;
+        }
+        self.roi(x, y, end_x - x, end_y - y).ok()
+    }
+}
+
+impl ImageView<'_, u8> {
+    /// Decimates the image by keeping every `factor`-th pixel in each
+    /// direction.
+    ///
+    /// Nearest-neighbor sampling takes the top-left pixel of each
+    /// `factor` x `factor` block: cheaper but noisier than the averaging
+    /// `ImagePyramid` levels, for quick previews. The result is
+    /// `width / factor` x `height / factor`; a factor of 0 or one larger
+    /// than either dimension is rejected.
+    pub fn subsample(&self, factor: usize) -> CorrMatchResult<OwnedImage> {
+        if factor == 0 {
+            return Err(CorrMatchError::InvalidConfig {
+                reason: "subsample factor must be at least 1",
+            });
+        }
+        let width = self.width / factor;
+        let height = self.height / factor;
+        if width == 0 || height == 0 {
+            return Err(CorrMatchError::InvalidDimensions { width, height });
+        }
+        let data = (0..height)
+            .filter_map(|y| self.row(y * factor))
+            .flat_map(|row| row.iter().step_by(factor).take(width).copied())
+            .collect();
+        OwnedImage::new(data, width, height)
+    }
 }
 
 fn required_len(width: usize, height: usize, stride: usize) -> CorrMatchResult<usize> {
@@ -207,7 +288,8 @@ impl OwnedImage {
         })
     }
 
-    pub(crate) fn from_view(view: ImageView<'_, u8>) -> CorrMatchResult<Self> {
+    /// Copies a (possibly strided or ROI) view into a contiguous image.
+    pub fn from_view(view: ImageView<'_, u8>) -> CorrMatchResult<Self> {
         let width = view.width();
         let height = view.height();
         let needed = width
@@ -262,3 +344,43 @@ impl OwnedImage {
         self.stride
     }
 }
+
+/// Splits an interleaved RGB buffer into three planar channel images.
+pub(crate) fn split_rgb(
+    data: &[u8],
+    width: usize,
+    height: usize,
+) -> CorrMatchResult<[OwnedImage; 3]> {
+    let count = width
+        .checked_mul(height)
+        .ok_or(CorrMatchError::InvalidDimensions { width, height })?;
+    let needed = count
+        .checked_mul(3)
+        .ok_or(CorrMatchError::InvalidDimensions { width, height })?;
+    if data.len() < needed {
+        return Err(CorrMatchError::BufferTooSmall {
+            needed,
+            got: data.len(),
+        });
+    }
+    if data.len() > needed {
+        return Err(CorrMatchError::InvalidDimensions { width, height });
+    }
+
+    let mut planes = [
+        Vec::with_capacity(count),
+        Vec::with_capacity(count),
+        Vec::with_capacity(count),
+    ];
+    for pixel in data.chunks_exact(3) {
+        for (plane, &value) in planes.iter_mut().zip(pixel) {
+            plane.push(value);
+        }
+    }
+    let [r, g, b] = planes;
+    Ok([
+        OwnedImage::new(r, width, height)?,
+        OwnedImage::new(g, width, height)?,
+        OwnedImage::new(b, width, height)?,
+    ])
+}
@@ -1,11 +1,84 @@
 //! Image pyramid construction for grayscale `u8` images.
 //!
-//! Downsampling uses a 2x2 box filter with integer rounding:
+//! Downsampling uses a 2x2 box filter with integer rounding by default:
 //! `dst = ((a + b + c + d) + 2) / 4`. This is a deterministic baseline
 //! suitable for early scaffolding without introducing blur kernels yet.
+//! [`PyramidFilter::OpenCvCompatible`] reproduces OpenCV's `pyrDown` instead.
 
 use crate::image::{ImageView, OwnedImage};
 use crate::util::{CorrMatchError, CorrMatchResult};
+use core::fmt::Debug;
+
+#[cfg(feature = "rayon")]
+use rayon::prelude::*;
+
+/// Downsampling filter used between image pyramid levels.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PyramidFilter {
+    /// 2x2 box average; coarser levels are `floor(w / 2) x floor(h / 2)`.
+    Box2x2,
+    /// Bit-exact `cv2.pyrDown`: the 5x5 binomial kernel `[1 4 6 4 1] / 16`
+    /// in both directions with `BORDER_REFLECT_101`, rounded as
+    /// `(sum + 128) >> 8`; coarser levels are `ceil(w / 2) x ceil(h / 2)`.
+    ///
+    /// Meant for parity with pipelines that build their pyramids in OpenCV,
+    /// so coarse-level coordinates agree; it is slower than the box filter
+    /// and does not improve accuracy on its own.
+    OpenCvCompatible,
+}
+
+impl PyramidFilter {
+    fn downsampled_size(self, width: usize, height: usize) -> (usize, usize) {
+        match self {
+            Self::Box2x2 => (width / 2, height / 2),
+            Self::OpenCvCompatible => (width.div_ceil(2), height.div_ceil(2)),
+        }
+    }
+
+    /// Returns the inclusive source offsets around `2 * x` that the filter
+    /// reads for destination pixel `x`, along either axis.
+    fn footprint(self) -> (isize, isize) {
+        match self {
+            Self::Box2x2 => (0, 1),
+            Self::OpenCvCompatible => (-2, 2),
+        }
+    }
+}
+
+/// Produces the next, half-resolution pyramid level from the current one.
+///
+/// Implement this to plug a custom anti-aliasing filter into
+/// [`ImagePyramid::build_with`], or into matching via the `downsampler`
+/// field of `CompileOptions`. [`PyramidFilter`] implements it for
+/// the built-in filters.
+///
+/// The output must halve each dimension, rounded either way
+/// (`floor(w / 2)` or `ceil(w / 2)`), because matching maps coordinates
+/// between levels by a factor of two.
+pub trait Downsampler: Debug + Send + Sync {
+    /// Downsamples `src` by a factor of two in each direction.
+    fn downsample(&self, src: ImageView<'_, u8>) -> CorrMatchResult<OwnedImage>;
+}
+
+impl Downsampler for PyramidFilter {
+    fn downsample(&self, src: ImageView<'_, u8>) -> CorrMatchResult<OwnedImage> {
+        let (dst_width, dst_height) = self.downsampled_size(src.width(), src.height());
+        match self {
+            Self::Box2x2 => downsample_box(src, dst_width, dst_height, false),
+            Self::OpenCvCompatible => downsample_pyr_down(src, dst_width, dst_height, false),
+        }
+    }
+}
+
+/// Returns the decimation factor of pyramid level `level` relative to level
+/// 0: every level halves the resolution of the one below it. Saturates at
+/// `usize::MAX` for absurdly deep levels.
+///
+/// All level-to-level coordinate mapping goes through this, for image and
+/// template pyramids alike.
+pub(crate) fn level_factor(level: usize) -> usize {
+    1usize.checked_shl(level as u32).unwrap_or(usize::MAX)
+}
 
 /// Owned image pyramid built from a base level.
 pub struct ImagePyramid {
@@ -17,61 +90,109 @@ impl ImagePyramid {
     ///
     /// `max_levels` is clamped to at least 1 so the base level is always present.
     pub fn build_u8(base: ImageView<'_, u8>, max_levels: usize) -> CorrMatchResult<Self> {
+        Self::build_u8_min_dim(base, max_levels, 1)
+    }
+
+    /// Builds a pyramid whose coarser levels are at least `min_dim` pixels on
+    /// each side.
+    ///
+    /// The base level is always kept, even when it is smaller than `min_dim`.
+    /// Downsampling stops before the first level that would fall below it.
+    pub fn build_u8_min_dim(
+        base: ImageView<'_, u8>,
+        max_levels: usize,
+        min_dim: usize,
+    ) -> CorrMatchResult<Self> {
+        Self::build(base, max_levels, min_dim, PyramidFilter::Box2x2, false)
+    }
+
+    /// Builds a pyramid using the given downsampling filter.
+    pub fn build_u8_filtered(
+        base: ImageView<'_, u8>,
+        max_levels: usize,
+        filter: PyramidFilter,
+    ) -> CorrMatchResult<Self> {
+        Self::build(base, max_levels, 1, filter, false)
+    }
+
+    /// Parallel version of [`ImagePyramid::build_u8`].
+    ///
+    /// Each level is downsampled with its output rows split across the rayon
+    /// thread pool; the levels are byte-identical to the serial build.
+    #[cfg(feature = "rayon")]
+    pub fn build_u8_par(base: ImageView<'_, u8>, max_levels: usize) -> CorrMatchResult<Self> {
+        Self::build(base, max_levels, 1, PyramidFilter::Box2x2, true)
+    }
+
+    /// Parallel version of [`ImagePyramid::build_u8_filtered`].
+    #[cfg(feature = "rayon")]
+    pub fn build_u8_filtered_par(
+        base: ImageView<'_, u8>,
+        max_levels: usize,
+        filter: PyramidFilter,
+    ) -> CorrMatchResult<Self> {
+        Self::build(base, max_levels, 1, filter, true)
+    }
+
+    /// Builds a pyramid with a custom [`Downsampler`].
+    ///
+    /// Downsampling stops at `max_levels` (clamped to at least 1) or once a
+    /// level has a side shorter than 2 pixels. A downsampler whose output
+    /// does not halve the level fails with `InvalidConfig`.
+    pub fn build_with(
+        base: ImageView<'_, u8>,
+        max_levels: usize,
+        downsampler: &dyn Downsampler,
+    ) -> CorrMatchResult<Self> {
         let max_levels = max_levels.max(1);
         let mut levels = Vec::new();
         levels.push(OwnedImage::from_view(base)?);
 
+        let halves = |src: usize, dst: usize| dst == src / 2 || dst == src.div_ceil(2);
         while levels.len() < max_levels {
-            let prev = levels.last().expect("levels is not empty");
-            let src = prev.view();
+            let src = levels.last().expect("levels is not empty").view();
             if src.width() < 2 || src.height() < 2 {
                 break;
             }
-
-            let dst_width = src.width() / 2;
-            let dst_height = src.height() / 2;
-            let dst_len =
-                dst_width
-                    .checked_mul(dst_height)
-                    .ok_or(CorrMatchError::InvalidDimensions {
-                        width: dst_width,
-                        height: dst_height,
-                    })?;
-            let mut dst = vec![0u8; dst_len];
-
-            for y in 0..dst_height {
-                let row0 = src.row(y * 2).ok_or_else(|| {
-                    let needed = (y * 2 + 1)
-                        .checked_mul(src.stride())
-                        .and_then(|v| v.checked_add(src.width()))
-                        .unwrap_or(usize::MAX);
-                    CorrMatchError::BufferTooSmall {
-                        needed,
-                        got: src.as_slice().len(),
-                    }
-                })?;
-                let row1 = src.row(y * 2 + 1).ok_or_else(|| {
-                    let needed = (y * 2 + 2)
-                        .checked_mul(src.stride())
-                        .and_then(|v| v.checked_add(src.width()))
-                        .unwrap_or(usize::MAX);
-                    CorrMatchError::BufferTooSmall {
-                        needed,
-                        got: src.as_slice().len(),
-                    }
-                })?;
-
-                for x in 0..dst_width {
-                    let a = row0[2 * x];
-                    let b = row0[2 * x + 1];
-                    let c = row1[2 * x];
-                    let d = row1[2 * x + 1];
-                    let sum = u16::from(a) + u16::from(b) + u16::from(c) + u16::from(d);
-                    dst[y * dst_width + x] = ((sum + 2) / 4) as u8;
-                }
+            let next = downsampler.downsample(src)?;
+            if !halves(src.width(), next.width()) || !halves(src.height(), next.height()) {
+                return Err(CorrMatchError::InvalidConfig {
+                    reason: "downsampler output must halve each image dimension",
+                });
             }
+            levels.push(next);
+        }
 
-            levels.push(OwnedImage::new(dst, dst_width, dst_height)?);
+        Ok(Self { levels })
+    }
+
+    fn build(
+        base: ImageView<'_, u8>,
+        max_levels: usize,
+        min_dim: usize,
+        filter: PyramidFilter,
+        parallel: bool,
+    ) -> CorrMatchResult<Self> {
+        let max_levels = max_levels.max(1);
+        let min_dim = min_dim.max(1);
+        let mut levels = Vec::new();
+        levels.push(OwnedImage::from_view(base)?);
+
+        while levels.len() < max_levels {
+            let prev = levels.last().expect("levels is not empty");
+            let src = prev.view();
+            let (dst_width, dst_height) = filter.downsampled_size(src.width(), src.height());
+            let shrinks = (dst_width, dst_height) != (src.width(), src.height());
+            if dst_width < min_dim || dst_height < min_dim || !shrinks {
+                break;
+            }
+            let next = match filter {
+                PyramidFilter::Box2x2 => downsample_box(src, dst_width, dst_height, parallel)?,
+                PyramidFilter::OpenCvCompatible => {
+                    downsample_pyr_down(src, dst_width, dst_height, parallel)?
+                }
+            };
+            levels.push(next);
         }
 
         Ok(Self { levels })
@@ -87,7 +208,191 @@ impl ImagePyramid {
         self.levels.get(index).map(|level| level.view())
     }
 
+    /// Returns how many level-0 pixels one pixel of `level` spans along each
+    /// axis, i.e. the factor that maps level coordinates to level 0.
+    ///
+    /// The factor depends only on the level index, not on the filter or on
+    /// whether the level was built.
+    pub fn scale_factor(&self, level: usize) -> f32 {
+        level_factor(level) as f32
+    }
+
     pub(crate) fn into_levels(self) -> Vec<OwnedImage> {
         self.levels
     }
+
+    /// Builds validity masks matching the level sizes of this pyramid.
+    ///
+    /// `mask` marks valid level-0 pixels with nonzero values. A coarser pixel
+    /// is valid (1) only when every finer pixel `filter` reads for it is
+    /// valid, so invalid pixels never contribute to a valid coarse value.
+    pub(crate) fn mask_levels(
+        &self,
+        mask: ImageView<'_, u8>,
+        filter: PyramidFilter,
+    ) -> CorrMatchResult<Vec<OwnedImage>> {
+        let base = self.levels.first().expect("pyramid has a base level");
+        if (mask.width(), mask.height()) != (base.width(), base.height()) {
+            return Err(CorrMatchError::InvalidDimensions {
+                width: mask.width(),
+                height: mask.height(),
+            });
+        }
+        let mut masks = Vec::with_capacity(self.levels.len());
+        let mut data = Vec::with_capacity(mask.width() * mask.height());
+        for y in 0..mask.height() {
+            let row = mask.row(y).expect("row within bounds for mask");
+            data.extend(row.iter().map(|&v| u8::from(v != 0)));
+        }
+        masks.push(OwnedImage::new(data, mask.width(), mask.height())?);
+
+        let (lo, hi) = filter.footprint();
+        for level in &self.levels[1..] {
+            let prev = masks.last().expect("masks is not empty").view();
+            let span = |dst: usize, len: usize| {
+                let center = 2 * dst as isize;
+                let start = (center + lo).max(0) as usize;
+                let end = ((center + hi) as usize).min(len - 1);
+                start..=end
+            };
+            let mut data = Vec::with_capacity(level.width() * level.height());
+            for y in 0..level.height() {
+                for x in 0..level.width() {
+                    let valid = span(y, prev.height()).all(|sy| {
+                        let row = prev.row(sy).expect("row within bounds for mask");
+                        row[span(x, prev.width())].iter().all(|&v| v != 0)
+                    });
+                    data.push(u8::from(valid));
+                }
+            }
+            masks.push(OwnedImage::new(data, level.width(), level.height())?);
+        }
+        Ok(masks)
+    }
+}
+
+/// Fills `dst` one row of `width` elements at a time, in parallel when
+/// requested and the `rayon` feature is available.
+///
+/// Every row is written by exactly one call of `fill_row`, so the result does
+/// not depend on the scheduling.
+fn for_each_row<T: Send>(
+    dst: &mut [T],
+    width: usize,
+    parallel: bool,
+    fill_row: impl Fn(usize, &mut [T]) -> CorrMatchResult<()> + Send + Sync,
+) -> CorrMatchResult<()> {
+    if parallel && cfg!(feature = "rayon") {
+        #[cfg(feature = "rayon")]
+        return dst
+            .par_chunks_mut(width)
+            .enumerate()
+            .try_for_each(|(y, row)| fill_row(y, row));
+    }
+    dst.chunks_mut(width)
+        .enumerate()
+        .try_for_each(|(y, row)| fill_row(y, row))
+}
+
+/// 2x2 box average with rounding.
+fn downsample_box(
+    src: ImageView<'_, u8>,
+    dst_width: usize,
+    dst_height: usize,
+    parallel: bool,
+) -> CorrMatchResult<OwnedImage> {
+    let dst_len = dst_width
+        .checked_mul(dst_height)
+        .ok_or(CorrMatchError::InvalidDimensions {
+            width: dst_width,
+            height: dst_height,
+        })?;
+    let mut dst = vec![0u8; dst_len];
+
+    let source_row = |sy: usize| {
+        src.row(sy).ok_or_else(|| {
+            let needed = (sy + 1)
+                .checked_mul(src.stride())
+                .and_then(|v| v.checked_add(src.width()))
+                .unwrap_or(usize::MAX);
+            CorrMatchError::BufferTooSmall {
+                needed,
+                got: src.as_slice().len(),
+            }
+        })
+    };
+    for_each_row(&mut dst, dst_width, parallel, |y, out| {
+        let row0 = source_row(y * 2)?;
+        let row1 = source_row(y * 2 + 1)?;
+        for (x, value) in out.iter_mut().enumerate() {
+            let a = row0[2 * x];
+            let b = row0[2 * x + 1];
+            let c = row1[2 * x];
+            let d = row1[2 * x + 1];
+            let sum = u16::from(a) + u16::from(b) + u16::from(c) + u16::from(d);
+            *value = ((sum + 2) / 4) as u8;
+        }
+        Ok(())
+    })?;
+
+    OwnedImage::new(dst, dst_width, dst_height)
+}
+
+/// Reflects an out-of-range index like OpenCV's `BORDER_REFLECT_101`.
+fn reflect_101(index: isize, len: usize) -> usize {
+    if len == 1 {
+        return 0;
+    }
+    let last = len as isize - 1;
+    let period = 2 * last;
+    let index = index.rem_euclid(period);
+    (if index > last { period - index } else { index }) as usize
+}
+
+/// Gaussian blur and decimation matching `cv2.pyrDown` bit for bit.
+fn downsample_pyr_down(
+    src: ImageView<'_, u8>,
+    dst_width: usize,
+    dst_height: usize,
+    parallel: bool,
+) -> CorrMatchResult<OwnedImage> {
+    const KERNEL: [u32; 5] = [1, 4, 6, 4, 1];
+    let width = src.width();
+    let height = src.height();
+    let dst_len = dst_width
+        .checked_mul(dst_height)
+        .ok_or(CorrMatchError::InvalidDimensions {
+            width: dst_width,
+            height: dst_height,
+        })?;
+
+    // Horizontal pass on every source row, evaluated at even columns only.
+    let mut rows = vec![0u32; height * dst_width];
+    for_each_row(&mut rows, dst_width, parallel, |y, out| {
+        let row = src.row(y).expect("row within bounds for downsample");
+        for (x, value) in out.iter_mut().enumerate() {
+            let mut sum = 0u32;
+            for (k, &w) in KERNEL.iter().enumerate() {
+                let sx = reflect_101(2 * x as isize + k as isize - 2, width);
+                sum += w * u32::from(row[sx]);
+            }
+            *value = sum;
+        }
+        Ok(())
+    })?;
+
+    let mut dst = vec![0u8; dst_len];
+    for_each_row(&mut dst, dst_width, parallel, |y, out| {
+        for (x, value) in out.iter_mut().enumerate() {
+            let mut sum = 0u32;
+            for (k, &w) in KERNEL.iter().enumerate() {
+                let sy = reflect_101(2 * y as isize + k as isize - 2, height);
+                sum += w * rows[sy * dst_width + x];
+            }
+            *value = ((sum + 128) >> 8) as u8;
+        }
+        Ok(())
+    })?;
+
+    OwnedImage::new(dst, dst_width, dst_height)
 }
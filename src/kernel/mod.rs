@@ -1,11 +1,16 @@
 //! Correlation kernel implementations.
 
-use crate::candidate::topk::Peak;
+use crate::candidate::topk::{rank_key, Peak, PeakOrder, TieBreak, TopK};
 use crate::util::CorrMatchResult;
 use crate::ImageView;
+use alloc::vec::Vec;
 
 /// Scan configuration for kernel evaluations.
+///
+/// Build with [`ScanParams::new`]; the remaining fields start at their
+/// defaults and can be set afterwards.
 #[derive(Clone, Copy, Debug)]
+#[non_exhaustive]
 pub struct ScanParams {
     /// Maximum number of peaks to retain.
     pub topk: usize,
@@ -13,6 +18,72 @@ pub struct ScanParams {
     pub min_var_i: f32,
     /// Minimum score threshold (discard below this value).
     pub min_score: f32,
+    /// Rank peaks and apply `min_score` by `|score|`, so strongly negative
+    /// (inverted) ZNCC peaks are retained; reported scores keep their sign.
+    pub rank_by_magnitude: bool,
+    /// Preference among equally scored placements when the Top-K is full.
+    pub tie_break: TieBreak,
+    /// Accumulates window sums with Kahan compensation, trading speed for
+    /// scores that do not depend on how rounding errors compound.
+    pub compensated: bool,
+    /// Skips windows whose variance differs from the template's by more than
+    /// this factor in either direction (ZNCC only). Unmasked kernels check it
+    /// before the dot product; masked kernels after their fused pass.
+    pub max_var_ratio: Option<f32>,
+}
+
+impl ScanParams {
+    /// Creates scan parameters keeping `topk` peaks at or above `min_score`
+    /// from windows whose variance exceeds `min_var_i`.
+    ///
+    /// Peaks are ranked by signed score with the default tie-break, sums are
+    /// accumulated without compensation and no variance gate is applied.
+    pub fn new(topk: usize, min_var_i: f32, min_score: f32) -> Self {
+        Self {
+            topk,
+            min_var_i,
+            min_score,
+            rank_by_magnitude: false,
+            tie_break: TieBreak::default(),
+            compensated: false,
+            max_var_ratio: None,
+        }
+    }
+
+    /// Ranks peaks and applies `min_score` by `|score|`; see
+    /// [`Self::rank_by_magnitude`].
+    pub fn with_rank_by_magnitude(self, rank_by_magnitude: bool) -> Self {
+        Self {
+            rank_by_magnitude,
+            ..self
+        }
+    }
+
+    /// Sets the preference among equally scored placements; see
+    /// [`Self::tie_break`].
+    pub fn with_tie_break(self, tie_break: TieBreak) -> Self {
+        Self { tie_break, ..self }
+    }
+
+    /// Returns an empty Top-K buffer that ranks peaks like these parameters,
+    /// for placements in `0..=max_x` x `0..=max_y`.
+    pub(crate) fn topk_buffer(&self, max_x: usize, max_y: usize) -> TopK<Peak> {
+        let order =
+            PeakOrder::new(self.rank_by_magnitude).with_tie_break(self.tie_break, max_x, max_y, 0);
+        TopK::ordered(self.topk, order)
+    }
+
+    /// Returns whether a score passes the `min_score` threshold.
+    pub(crate) fn accepts(&self, score: f32) -> bool {
+        score.is_finite() && rank_key(score, self.rank_by_magnitude) >= self.min_score
+    }
+
+    /// Returns whether a window of variance `var_i` fails the `max_var_ratio`
+    /// gate against a template of variance `var_t`.
+    pub(crate) fn rejects_variance(&self, var_i: f32, var_t: f32) -> bool {
+        self.max_var_ratio
+            .is_some_and(|ratio| var_i > var_t * ratio || var_i * ratio < var_t)
+    }
 }
 
 /// Kernel trait for scoring and scan operations.
@@ -28,6 +99,22 @@ pub trait Kernel {
         min_var_i: f32,
     ) -> f32;
 
+    /// Computes the score at a single placement, accumulating with Kahan
+    /// compensation when `compensated` is set (see [`ScanParams::compensated`]).
+    ///
+    /// The default implementation ignores `compensated`.
+    fn score_at_with(
+        image: ImageView<'_, u8>,
+        plan: &Self::Plan,
+        x: usize,
+        y: usize,
+        min_var_i: f32,
+        compensated: bool,
+    ) -> f32 {
+        let _ = compensated;
+        Self::score_at(image, plan, x, y, min_var_i)
+    }
+
     /// Scans the full valid placement range and returns top-K peaks.
     fn scan_full(
         image: ImageView<'_, u8>,
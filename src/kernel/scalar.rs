@@ -1,10 +1,20 @@
 //! Scalar reference kernels for score evaluation.
 
-use crate::candidate::topk::{Peak, TopK};
+use crate::candidate::topk::Peak;
+use crate::image::integral::IntegralImage;
 use crate::kernel::{Kernel, ScanParams};
-use crate::template::{MaskedSsdTemplatePlan, MaskedTemplatePlan, SsdTemplatePlan, TemplatePlan};
+use crate::template::{
+    MaskLookup, MaskedTemplatePlan, PlanMask, PlanValue, PlanValues, TemplatePlan,
+};
+#[cfg(feature = "std")]
+use crate::template::{
+    MaskedSsdTemplatePlan, PackedMask, RgbTemplatePlan, SsdTemplatePlan, ZnccTerms,
+};
+#[cfg(not(feature = "std"))]
+use crate::util::float::FloatExt;
 use crate::util::{CorrMatchError, CorrMatchResult};
 use crate::ImageView;
+use alloc::vec::Vec;
 
 /// Scalar masked ZNCC kernel for rotated templates.
 pub struct ZnccMaskedScalar;
@@ -12,12 +22,595 @@ pub struct ZnccMaskedScalar;
 /// Scalar unmasked ZNCC kernel for rotation-free matching.
 pub struct ZnccUnmaskedScalar;
 
+#[cfg(feature = "std")]
 /// Scalar masked SSD kernel for rotated templates.
 pub struct SsdMaskedScalar;
 
+#[cfg(feature = "std")]
 /// Scalar unmasked SSD kernel for rotation-free matching.
 pub struct SsdUnmaskedScalar;
 
+#[cfg(feature = "std")]
+/// Scalar masked kernel blending ZNCC with normalized SSD for rotated templates.
+///
+/// Both terms are accumulated in one pass over the window, sharing each image
+/// read. It scores with the ZNCC and SSD plans of the same slot, so this does
+/// not implement [`Kernel`], whose methods take a single plan.
+pub struct CombinedMaskedScalar;
+
+#[cfg(feature = "std")]
+/// Scalar unmasked kernel blending ZNCC with normalized SSD.
+///
+/// Window sums come from summed-area tables as in [`ZnccUnmaskedScalar`]; the
+/// dot product and SSE share one pass per placement.
+pub struct CombinedUnmaskedScalar;
+
+#[cfg(feature = "std")]
+/// Weights of the terms of a combined ZNCC + SSD score.
+///
+/// A placement scores `zncc * zncc_score + ssd * (1 - sse / (n * 255²))`,
+/// where `n` counts the valid template pixels, so the SSD term lies in
+/// `[0, 1]` whatever the template size. Placements whose image window is too
+/// flat for ZNCC are rejected.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct CombinedWeights {
+    pub(crate) zncc: f32,
+    pub(crate) ssd: f32,
+}
+
+#[cfg(feature = "std")]
+impl CombinedWeights {
+    /// Creates weights from the fields of `Metric::Combined`.
+    pub(crate) fn new(zncc: f32, ssd: f32) -> Self {
+        Self { zncc, ssd }
+    }
+
+    /// Blends a ZNCC score with the SSE over `n` pixels.
+    #[inline]
+    fn blend(self, zncc: f32, sse: f32, n: f32) -> f32 {
+        self.zncc * zncc + self.ssd * (1.0 - sse / (n * 255.0 * 255.0))
+    }
+}
+
+#[cfg(feature = "std")]
+/// Scalar RGB kernel scoring the mean of per-channel unmasked ZNCC.
+///
+/// Images are passed as three planar channel views of equal size. This does
+/// not implement [`Kernel`], whose methods take a single grayscale view.
+pub struct ZnccRgbScalar;
+
+#[cfg(feature = "std")]
+/// Scalar ZNCC kernel that skips invalid image pixels.
+///
+/// Each placement is scored over the pixels valid in both the template mask
+/// and the image mask window, so template statistics are recomputed per
+/// placement. Like [`ZnccRgbScalar`], this does not implement [`Kernel`],
+/// whose methods take no image mask.
+pub struct ZnccImageMaskedScalar;
+
+#[cfg(feature = "std")]
+/// Minimum share of the template weight that must fall on valid image pixels
+/// for an image-masked placement to be scored.
+///
+/// ZNCC over a handful of pixels is trivially high, so placements mostly
+/// covering invalid pixels are rejected instead.
+const MIN_VALID_WEIGHT_FRACTION: f32 = 0.5;
+
+/// A running float sum used by the kernel accumulations.
+trait Accumulator: Copy + Default {
+    fn accumulate(&mut self, value: f32);
+    fn total(self) -> f32;
+}
+
+impl Accumulator for f32 {
+    #[inline]
+    fn accumulate(&mut self, value: f32) {
+        *self += value;
+    }
+
+    #[inline]
+    fn total(self) -> f32 {
+        self
+    }
+}
+
+/// Kahan-compensated sum, carrying the low-order bits lost by each addition.
+///
+/// The result is within a few ulps of the exact sum whatever the number of
+/// terms, so it no longer depends on how rounding errors compound.
+#[derive(Clone, Copy, Default)]
+struct Kahan {
+    sum: f32,
+    compensation: f32,
+}
+
+impl Accumulator for Kahan {
+    #[inline]
+    fn accumulate(&mut self, value: f32) {
+        let y = value - self.compensation;
+        let t = self.sum + y;
+        self.compensation = (t - self.sum) - y;
+        self.sum = t;
+    }
+
+    #[inline]
+    fn total(self) -> f32 {
+        self.sum
+    }
+}
+
+/// Accumulates `(dot, sum_i, sum_i2)` for a masked ZNCC placement.
+///
+/// Binary plans skip masked-out pixels; weighted plans scale the image sums by
+/// the per-pixel weight. The caller guarantees the placement is in bounds.
+#[inline]
+fn masked_zncc_sums(
+    image: ImageView<'_, u8>,
+    tpl: &MaskedTemplatePlan,
+    x: usize,
+    y: usize,
+    compensated: bool,
+) -> (f32, f32, f32) {
+    if compensated {
+        masked_zncc_sums_acc::<Kahan>(image, tpl, x, y)
+    } else {
+        masked_zncc_sums_acc::<f32>(image, tpl, x, y)
+    }
+}
+
+#[inline]
+fn masked_zncc_sums_acc<A: Accumulator>(
+    image: ImageView<'_, u8>,
+    tpl: &MaskedTemplatePlan,
+    x: usize,
+    y: usize,
+) -> (f32, f32, f32) {
+    match tpl.plan_mask() {
+        PlanMask::Bytes(mask) => masked_zncc_sums_masked::<_, A>(image, tpl, &mask[..], x, y),
+        #[cfg(feature = "std")]
+        PlanMask::Bits { bits, .. } => {
+            masked_zncc_sums_masked::<_, A>(image, tpl, PackedMask(bits), x, y)
+        }
+    }
+}
+
+#[inline]
+fn masked_zncc_sums_masked<M: MaskLookup, A: Accumulator>(
+    image: ImageView<'_, u8>,
+    tpl: &MaskedTemplatePlan,
+    mask: M,
+    x: usize,
+    y: usize,
+) -> (f32, f32, f32) {
+    match tpl.values() {
+        PlanValues::F32(t_prime) => {
+            masked_zncc_sums_with::<_, _, A>(image, tpl, t_prime, mask, x, y)
+        }
+        #[cfg(feature = "half")]
+        PlanValues::F16 {
+            values: t_prime, ..
+        } => masked_zncc_sums_with::<_, _, A>(image, tpl, t_prime, mask, x, y),
+    }
+}
+
+#[inline]
+fn masked_zncc_sums_with<T: PlanValue, M: MaskLookup, A: Accumulator>(
+    image: ImageView<'_, u8>,
+    tpl: &MaskedTemplatePlan,
+    t_prime: &[T],
+    mask: M,
+    x: usize,
+    y: usize,
+) -> (f32, f32, f32) {
+    let tpl_width = tpl.width();
+
+    let mut dot = A::default();
+    let mut sum_i = A::default();
+    let mut sum_i2 = A::default();
+    match tpl.weights() {
+        None => {
+            for ty in 0..tpl.height() {
+                let img_row = image.row(y + ty).expect("row within bounds for scan");
+                let base = ty * tpl_width;
+                for tx in 0..tpl_width {
+                    let idx = base + tx;
+                    if !mask.valid(idx) {
+                        continue;
+                    }
+                    let value = img_row[x + tx] as f32;
+                    dot.accumulate(t_prime[idx].widen() * value);
+                    sum_i.accumulate(value);
+                    sum_i2.accumulate(value * value);
+                }
+            }
+        }
+        Some(weights) => {
+            for ty in 0..tpl.height() {
+                let img_row = image.row(y + ty).expect("row within bounds for scan");
+                let base = ty * tpl_width;
+                for tx in 0..tpl_width {
+                    let idx = base + tx;
+                    if !mask.valid(idx) {
+                        continue;
+                    }
+                    let w = weights[idx];
+                    let value = img_row[x + tx] as f32;
+                    dot.accumulate(t_prime[idx].widen() * value);
+                    sum_i.accumulate(w * value);
+                    sum_i2.accumulate(w * value * value);
+                }
+            }
+        }
+    }
+
+    (dot.total(), sum_i.total(), sum_i2.total())
+}
+
+#[cfg(feature = "std")]
+/// Accumulates the sum of squared differences over the valid pixels of a
+/// masked SSD placement.
+///
+/// The caller guarantees the placement is in bounds.
+#[inline]
+fn masked_sse(
+    image: ImageView<'_, u8>,
+    tpl: &MaskedSsdTemplatePlan,
+    x: usize,
+    y: usize,
+    compensated: bool,
+) -> f32 {
+    if compensated {
+        masked_sse_acc::<Kahan>(image, tpl, x, y)
+    } else {
+        masked_sse_acc::<f32>(image, tpl, x, y)
+    }
+}
+
+#[cfg(feature = "std")]
+#[inline]
+fn masked_sse_acc<A: Accumulator>(
+    image: ImageView<'_, u8>,
+    tpl: &MaskedSsdTemplatePlan,
+    x: usize,
+    y: usize,
+) -> f32 {
+    match tpl.plan_mask() {
+        PlanMask::Bytes(mask) => masked_sse_with::<_, A>(image, tpl, &mask[..], x, y),
+        #[cfg(feature = "std")]
+        PlanMask::Bits { bits, .. } => masked_sse_with::<_, A>(image, tpl, PackedMask(bits), x, y),
+    }
+}
+
+#[cfg(feature = "std")]
+#[inline]
+fn masked_sse_with<M: MaskLookup, A: Accumulator>(
+    image: ImageView<'_, u8>,
+    tpl: &MaskedSsdTemplatePlan,
+    mask: M,
+    x: usize,
+    y: usize,
+) -> f32 {
+    let tpl_width = tpl.width();
+    let data = tpl.data();
+    let mut sse = A::default();
+    for ty in 0..tpl.height() {
+        let img_row = image.row(y + ty).expect("row within bounds for scan");
+        let base = ty * tpl_width;
+        for tx in 0..tpl_width {
+            let idx = base + tx;
+            if !mask.valid(idx) {
+                continue;
+            }
+            let value = img_row[x + tx] as f32;
+            let diff = value - data[idx];
+            sse.accumulate(diff * diff);
+        }
+    }
+    sse.total()
+}
+
+#[cfg(feature = "std")]
+/// Accumulates the sum of squared differences over an unmasked placement.
+///
+/// The caller guarantees the placement is in bounds.
+#[inline]
+fn unmasked_sse(
+    image: ImageView<'_, u8>,
+    tpl: &SsdTemplatePlan,
+    x: usize,
+    y: usize,
+    compensated: bool,
+) -> f32 {
+    if compensated {
+        unmasked_sse_acc::<Kahan>(image, tpl, x, y)
+    } else {
+        unmasked_sse_acc::<f32>(image, tpl, x, y)
+    }
+}
+
+#[cfg(feature = "std")]
+#[inline]
+fn unmasked_sse_acc<A: Accumulator>(
+    image: ImageView<'_, u8>,
+    tpl: &SsdTemplatePlan,
+    x: usize,
+    y: usize,
+) -> f32 {
+    let tpl_width = tpl.width();
+    let data = tpl.data();
+    let mut sse = A::default();
+    for ty in 0..tpl.height() {
+        let img_row = image.row(y + ty).expect("row within bounds for scan");
+        let base = ty * tpl_width;
+        for tx in 0..tpl_width {
+            let idx = base + tx;
+            let value = img_row[x + tx] as f32;
+            let diff = value - data[idx];
+            sse.accumulate(diff * diff);
+        }
+    }
+    sse.total()
+}
+
+/// Accumulates the zero-mean template dot product for an unmasked placement.
+///
+/// The caller guarantees the placement is in bounds.
+#[inline]
+fn unmasked_zncc_dot(
+    image: ImageView<'_, u8>,
+    tpl: &TemplatePlan,
+    x: usize,
+    y: usize,
+    compensated: bool,
+) -> f32 {
+    if compensated {
+        unmasked_zncc_dot_acc::<Kahan>(image, tpl, x, y)
+    } else {
+        unmasked_zncc_dot_acc::<f32>(image, tpl, x, y)
+    }
+}
+
+#[inline]
+fn unmasked_zncc_dot_acc<A: Accumulator>(
+    image: ImageView<'_, u8>,
+    tpl: &TemplatePlan,
+    x: usize,
+    y: usize,
+) -> f32 {
+    match tpl.values() {
+        PlanValues::F32(t_prime) => unmasked_zncc_dot_with::<_, A>(image, tpl, t_prime, x, y),
+        #[cfg(feature = "half")]
+        PlanValues::F16 {
+            values: t_prime, ..
+        } => unmasked_zncc_dot_with::<_, A>(image, tpl, t_prime, x, y),
+    }
+}
+
+#[inline]
+fn unmasked_zncc_dot_with<T: PlanValue, A: Accumulator>(
+    image: ImageView<'_, u8>,
+    tpl: &TemplatePlan,
+    t_prime: &[T],
+    x: usize,
+    y: usize,
+) -> f32 {
+    let tpl_width = tpl.width();
+    let mut dot = A::default();
+    for ty in 0..tpl.height() {
+        let img_row = image.row(y + ty).expect("row within bounds for scan");
+        let t_row = &t_prime[ty * tpl_width..(ty + 1) * tpl_width];
+        for (t, &value) in t_row.iter().zip(&img_row[x..x + tpl_width]) {
+            dot.accumulate(t.widen() * value as f32);
+        }
+    }
+    dot.total()
+}
+
+/// Returns exact `(sum_i, sum_i2)` over an unmasked template window.
+///
+/// Matches [`IntegralImage::window_sums`] so single-placement scores agree
+/// with scans. The caller guarantees the placement is in bounds.
+#[inline]
+fn unmasked_window_sums(
+    image: ImageView<'_, u8>,
+    tpl: &TemplatePlan,
+    x: usize,
+    y: usize,
+) -> (u64, u64) {
+    let mut sum_i = 0u64;
+    let mut sum_i2 = 0u64;
+    for ty in 0..tpl.height() {
+        let img_row = image.row(y + ty).expect("row within bounds for scan");
+        for &value in &img_row[x..x + tpl.width()] {
+            let value = u64::from(value);
+            sum_i += value;
+            sum_i2 += value * value;
+        }
+    }
+    (sum_i, sum_i2)
+}
+
+/// Returns the unnormalized window variance `sum_i2 - sum_i^2 / n`.
+#[inline]
+fn window_variance(sum_i: u64, sum_i2: u64, n: usize) -> f32 {
+    let sum_i = sum_i as f64;
+    (sum_i2 as f64 - sum_i * sum_i / n as f64) as f32
+}
+
+#[cfg(feature = "std")]
+/// Accumulates `(dot, sum_i, sum_i2, sse, n)` for a combined masked placement.
+///
+/// `n` counts the valid template pixels. The SSE uses the SSD plan values over
+/// the ZNCC plan's mask; both plans come from the same rotated slot. The
+/// caller guarantees the placement is in bounds.
+#[inline]
+fn masked_combined_sums(
+    image: ImageView<'_, u8>,
+    zncc: &MaskedTemplatePlan,
+    ssd: &MaskedSsdTemplatePlan,
+    x: usize,
+    y: usize,
+    compensated: bool,
+) -> (f32, f32, f32, f32, usize) {
+    if compensated {
+        masked_combined_sums_acc::<Kahan>(image, zncc, ssd, x, y)
+    } else {
+        masked_combined_sums_acc::<f32>(image, zncc, ssd, x, y)
+    }
+}
+
+#[cfg(feature = "std")]
+#[inline]
+fn masked_combined_sums_acc<A: Accumulator>(
+    image: ImageView<'_, u8>,
+    zncc: &MaskedTemplatePlan,
+    ssd: &MaskedSsdTemplatePlan,
+    x: usize,
+    y: usize,
+) -> (f32, f32, f32, f32, usize) {
+    match zncc.plan_mask() {
+        PlanMask::Bytes(mask) => {
+            masked_combined_sums_masked::<_, A>(image, zncc, ssd, &mask[..], x, y)
+        }
+        #[cfg(feature = "std")]
+        PlanMask::Bits { bits, .. } => {
+            masked_combined_sums_masked::<_, A>(image, zncc, ssd, PackedMask(bits), x, y)
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+#[inline]
+fn masked_combined_sums_masked<M: MaskLookup, A: Accumulator>(
+    image: ImageView<'_, u8>,
+    zncc: &MaskedTemplatePlan,
+    ssd: &MaskedSsdTemplatePlan,
+    mask: M,
+    x: usize,
+    y: usize,
+) -> (f32, f32, f32, f32, usize) {
+    match zncc.values() {
+        PlanValues::F32(t_prime) => {
+            masked_combined_sums_with::<_, _, A>(image, zncc, t_prime, ssd.data(), mask, x, y)
+        }
+        #[cfg(feature = "half")]
+        PlanValues::F16 {
+            values: t_prime, ..
+        } => masked_combined_sums_with::<_, _, A>(image, zncc, t_prime, ssd.data(), mask, x, y),
+    }
+}
+
+#[cfg(feature = "std")]
+#[inline]
+fn masked_combined_sums_with<T: PlanValue, M: MaskLookup, A: Accumulator>(
+    image: ImageView<'_, u8>,
+    zncc: &MaskedTemplatePlan,
+    t_prime: &[T],
+    data: &[f32],
+    mask: M,
+    x: usize,
+    y: usize,
+) -> (f32, f32, f32, f32, usize) {
+    let tpl_width = zncc.width();
+    let weights = zncc.weights();
+
+    let mut dot = A::default();
+    let mut sum_i = A::default();
+    let mut sum_i2 = A::default();
+    let mut sse = A::default();
+    let mut n = 0usize;
+    for ty in 0..zncc.height() {
+        let img_row = image.row(y + ty).expect("row within bounds for scan");
+        let base = ty * tpl_width;
+        for tx in 0..tpl_width {
+            let idx = base + tx;
+            if !mask.valid(idx) {
+                continue;
+            }
+            let w = weights.map_or(1.0, |weights| weights[idx]);
+            let value = img_row[x + tx] as f32;
+            dot.accumulate(t_prime[idx].widen() * value);
+            sum_i.accumulate(w * value);
+            sum_i2.accumulate(w * value * value);
+            let diff = value - data[idx];
+            sse.accumulate(diff * diff);
+            n += 1;
+        }
+    }
+
+    (dot.total(), sum_i.total(), sum_i2.total(), sse.total(), n)
+}
+
+#[cfg(feature = "std")]
+/// Accumulates `(dot, sse)` for an unmasked combined placement.
+///
+/// The caller guarantees the placement is in bounds.
+#[inline]
+fn unmasked_combined_dot_sse(
+    image: ImageView<'_, u8>,
+    zncc: &TemplatePlan,
+    ssd: &SsdTemplatePlan,
+    x: usize,
+    y: usize,
+    compensated: bool,
+) -> (f32, f32) {
+    if compensated {
+        unmasked_combined_dot_sse_acc::<Kahan>(image, zncc, ssd, x, y)
+    } else {
+        unmasked_combined_dot_sse_acc::<f32>(image, zncc, ssd, x, y)
+    }
+}
+
+#[cfg(feature = "std")]
+#[inline]
+fn unmasked_combined_dot_sse_acc<A: Accumulator>(
+    image: ImageView<'_, u8>,
+    zncc: &TemplatePlan,
+    ssd: &SsdTemplatePlan,
+    x: usize,
+    y: usize,
+) -> (f32, f32) {
+    match zncc.values() {
+        PlanValues::F32(t_prime) => {
+            unmasked_combined_dot_sse_with::<_, A>(image, zncc, t_prime, ssd.data(), x, y)
+        }
+        #[cfg(feature = "half")]
+        PlanValues::F16 {
+            values: t_prime, ..
+        } => unmasked_combined_dot_sse_with::<_, A>(image, zncc, t_prime, ssd.data(), x, y),
+    }
+}
+
+#[cfg(feature = "std")]
+#[inline]
+fn unmasked_combined_dot_sse_with<T: PlanValue, A: Accumulator>(
+    image: ImageView<'_, u8>,
+    zncc: &TemplatePlan,
+    t_prime: &[T],
+    data: &[f32],
+    x: usize,
+    y: usize,
+) -> (f32, f32) {
+    let tpl_width = zncc.width();
+    let mut dot = A::default();
+    let mut sse = A::default();
+    for ty in 0..zncc.height() {
+        let img_row = image.row(y + ty).expect("row within bounds for scan");
+        let row = ty * tpl_width..(ty + 1) * tpl_width;
+        for ((t, &d), &value) in t_prime[row.clone()]
+            .iter()
+            .zip(&data[row])
+            .zip(&img_row[x..x + tpl_width])
+        {
+            let value = value as f32;
+            dot.accumulate(t.widen() * value);
+            let diff = value - d;
+            sse.accumulate(diff * diff);
+        }
+    }
+    (dot.total(), sse.total())
+}
+
 impl ZnccMaskedScalar {
     #[allow(clippy::too_many_arguments)]
     fn scan_range(
@@ -66,39 +659,22 @@ impl ZnccMaskedScalar {
         if var_t <= 1e-8 {
             return Ok(Vec::new());
         }
-        let t_prime = tpl.t_prime();
-        let mask = tpl.mask();
 
-        let mut topk_buf = TopK::new(params.topk);
+        let mut topk_buf = params.topk_buffer(max_x, max_y);
         for y in y0..=y1 {
             for x in x0..=x1 {
-                let mut dot = 0.0f32;
-                let mut sum_i = 0.0f32;
-                let mut sum_i2 = 0.0f32;
-
-                for ty in 0..tpl_height {
-                    let img_row = image.row(y + ty).expect("row within bounds for scan");
-                    let base = ty * tpl_width;
-                    for tx in 0..tpl_width {
-                        let idx = base + tx;
-                        if mask[idx] == 0 {
-                            continue;
-                        }
-                        let value = img_row[x + tx] as f32;
-                        dot += t_prime[idx] * value;
-                        sum_i += value;
-                        sum_i2 += value * value;
-                    }
-                }
+                // The masked window sums share one pass with the dot product,
+                // so the variance gate here filters but saves no work.
+                let (dot, sum_i, sum_i2) = masked_zncc_sums(image, tpl, x, y, params.compensated);
 
                 let var_i = sum_i2 - (sum_i * sum_i) / sum_w;
-                if var_i <= params.min_var_i {
+                if var_i <= params.min_var_i || params.rejects_variance(var_i, var_t) {
                     continue;
                 }
 
                 let denom = (var_t * var_i).sqrt();
                 let score = dot / denom;
-                if score.is_finite() && score >= params.min_score {
+                if params.accepts(score) {
                     topk_buf.push(Peak {
                         x,
                         y,
@@ -122,6 +698,17 @@ impl Kernel for ZnccMaskedScalar {
         x: usize,
         y: usize,
         min_var_i: f32,
+    ) -> f32 {
+        Self::score_at_with(image, tpl, x, y, min_var_i, false)
+    }
+
+    fn score_at_with(
+        image: ImageView<'_, u8>,
+        tpl: &Self::Plan,
+        x: usize,
+        y: usize,
+        min_var_i: f32,
+        compensated: bool,
     ) -> f32 {
         let img_width = image.width();
         let img_height = image.height();
@@ -140,27 +727,8 @@ impl Kernel for ZnccMaskedScalar {
         if var_t <= 1e-8 {
             return f32::NEG_INFINITY;
         }
-        let t_prime = tpl.t_prime();
-        let mask = tpl.mask();
-
-        let mut dot = 0.0f32;
-        let mut sum_i = 0.0f32;
-        let mut sum_i2 = 0.0f32;
 
-        for ty in 0..tpl_height {
-            let img_row = image.row(y + ty).expect("row within bounds for score");
-            let base = ty * tpl_width;
-            for tx in 0..tpl_width {
-                let idx = base + tx;
-                if mask[idx] == 0 {
-                    continue;
-                }
-                let value = img_row[x + tx] as f32;
-                dot += t_prime[idx] * value;
-                sum_i += value;
-                sum_i2 += value * value;
-            }
-        }
+        let (dot, sum_i, sum_i2) = masked_zncc_sums(image, tpl, x, y, compensated);
 
         let var_i = sum_i2 - (sum_i * sum_i) / sum_w;
         if var_i <= min_var_i {
@@ -216,15 +784,27 @@ impl Kernel for ZnccMaskedScalar {
     }
 }
 
+#[cfg(feature = "std")]
 impl Kernel for SsdMaskedScalar {
     type Plan = MaskedSsdTemplatePlan;
 
     fn score_at(
+        image: ImageView<'_, u8>,
+        tpl: &Self::Plan,
+        x: usize,
+        y: usize,
+        min_var_i: f32,
+    ) -> f32 {
+        Self::score_at_with(image, tpl, x, y, min_var_i, false)
+    }
+
+    fn score_at_with(
         image: ImageView<'_, u8>,
         tpl: &Self::Plan,
         x: usize,
         y: usize,
         _min_var_i: f32,
+        compensated: bool,
     ) -> f32 {
         let img_width = image.width();
         let img_height = image.height();
@@ -238,24 +818,7 @@ impl Kernel for SsdMaskedScalar {
             return f32::NEG_INFINITY;
         }
 
-        let data = tpl.data();
-        let mask = tpl.mask();
-        let mut sse = 0.0f32;
-
-        for ty in 0..tpl_height {
-            let img_row = image.row(y + ty).expect("row within bounds for score");
-            let base = ty * tpl_width;
-            for tx in 0..tpl_width {
-                let idx = base + tx;
-                if mask[idx] == 0 {
-                    continue;
-                }
-                let value = img_row[x + tx] as f32;
-                let diff = value - data[idx];
-                sse += diff * diff;
-            }
-        }
-
+        let sse = masked_sse(image, tpl, x, y, compensated);
         if sse.is_finite() {
             -sse
         } else {
@@ -303,6 +866,7 @@ impl Kernel for SsdMaskedScalar {
     }
 }
 
+#[cfg(feature = "std")]
 impl SsdMaskedScalar {
     #[allow(clippy::too_many_arguments)]
     fn scan_range(
@@ -346,28 +910,12 @@ impl SsdMaskedScalar {
             return Ok(Vec::new());
         }
 
-        let data = tpl.data();
-        let mask = tpl.mask();
-        let mut topk_buf = TopK::new(params.topk);
+        let mut topk_buf = params.topk_buffer(max_x, max_y);
 
         for y in y0..=y1 {
             for x in x0..=x1 {
-                let mut sse = 0.0f32;
-                for ty in 0..tpl_height {
-                    let img_row = image.row(y + ty).expect("row within bounds for scan");
-                    let base = ty * tpl_width;
-                    for tx in 0..tpl_width {
-                        let idx = base + tx;
-                        if mask[idx] == 0 {
-                            continue;
-                        }
-                        let value = img_row[x + tx] as f32;
-                        let diff = value - data[idx];
-                        sse += diff * diff;
-                    }
-                }
-                let score = -sse;
-                if score.is_finite() && score >= params.min_score {
+                let score = -masked_sse(image, tpl, x, y, params.compensated);
+                if params.accepts(score) {
                     topk_buf.push(Peak {
                         x,
                         y,
@@ -429,36 +977,26 @@ impl ZnccUnmaskedScalar {
         if var_t <= 1e-8 {
             return Ok(Vec::new());
         }
-        let t_prime = tpl.t_prime();
-        let n = (tpl_width * tpl_height) as f32;
+        let n = tpl_width * tpl_height;
+
+        // Window sums come from summed-area tables over the pixels the
+        // placements cover, leaving only the dot product per placement.
+        let region = image.roi(x0, y0, x1 - x0 + tpl_width, y1 - y0 + tpl_height)?;
+        let integral = IntegralImage::new(region);
 
-        let mut topk_buf = TopK::new(params.topk);
+        let mut topk_buf = params.topk_buffer(max_x, max_y);
         for y in y0..=y1 {
             for x in x0..=x1 {
-                let mut dot = 0.0f32;
-                let mut sum_i = 0.0f32;
-                let mut sum_i2 = 0.0f32;
-
-                for ty in 0..tpl_height {
-                    let img_row = image.row(y + ty).expect("row within bounds for scan");
-                    let base = ty * tpl_width;
-                    for tx in 0..tpl_width {
-                        let idx = base + tx;
-                        let value = img_row[x + tx] as f32;
-                        dot += t_prime[idx] * value;
-                        sum_i += value;
-                        sum_i2 += value * value;
-                    }
-                }
-
-                let var_i = sum_i2 - (sum_i * sum_i) / n;
-                if var_i <= params.min_var_i {
+                let (sum_i, sum_i2) = integral.window_sums(x - x0, y - y0, tpl_width, tpl_height);
+                let var_i = window_variance(sum_i, sum_i2, n);
+                if var_i <= params.min_var_i || params.rejects_variance(var_i, var_t) {
                     continue;
                 }
 
+                let dot = unmasked_zncc_dot(image, tpl, x, y, params.compensated);
                 let denom = (var_t * var_i).sqrt();
                 let score = dot / denom;
-                if score.is_finite() && score >= params.min_score {
+                if params.accepts(score) {
                     topk_buf.push(Peak {
                         x,
                         y,
@@ -482,6 +1020,17 @@ impl Kernel for ZnccUnmaskedScalar {
         x: usize,
         y: usize,
         min_var_i: f32,
+    ) -> f32 {
+        Self::score_at_with(image, tpl, x, y, min_var_i, false)
+    }
+
+    fn score_at_with(
+        image: ImageView<'_, u8>,
+        tpl: &Self::Plan,
+        x: usize,
+        y: usize,
+        min_var_i: f32,
+        compensated: bool,
     ) -> f32 {
         let img_width = image.width();
         let img_height = image.height();
@@ -499,29 +1048,12 @@ impl Kernel for ZnccUnmaskedScalar {
         if var_t <= 1e-8 {
             return f32::NEG_INFINITY;
         }
-        let t_prime = tpl.t_prime();
-        let n = (tpl_width * tpl_height) as f32;
-
-        let mut dot = 0.0f32;
-        let mut sum_i = 0.0f32;
-        let mut sum_i2 = 0.0f32;
-
-        for ty in 0..tpl_height {
-            let img_row = image.row(y + ty).expect("row within bounds for score");
-            let base = ty * tpl_width;
-            for tx in 0..tpl_width {
-                let idx = base + tx;
-                let value = img_row[x + tx] as f32;
-                dot += t_prime[idx] * value;
-                sum_i += value;
-                sum_i2 += value * value;
-            }
-        }
-
-        let var_i = sum_i2 - (sum_i * sum_i) / n;
+        let (sum_i, sum_i2) = unmasked_window_sums(image, tpl, x, y);
+        let var_i = window_variance(sum_i, sum_i2, tpl_width * tpl_height);
         if var_i <= min_var_i {
             return f32::NEG_INFINITY;
         }
+        let dot = unmasked_zncc_dot(image, tpl, x, y, compensated);
 
         let denom = (var_t * var_i).sqrt();
         let score = dot / denom;
@@ -572,15 +1104,27 @@ impl Kernel for ZnccUnmaskedScalar {
     }
 }
 
+#[cfg(feature = "std")]
 impl Kernel for SsdUnmaskedScalar {
     type Plan = SsdTemplatePlan;
 
     fn score_at(
+        image: ImageView<'_, u8>,
+        tpl: &Self::Plan,
+        x: usize,
+        y: usize,
+        min_var_i: f32,
+    ) -> f32 {
+        Self::score_at_with(image, tpl, x, y, min_var_i, false)
+    }
+
+    fn score_at_with(
         image: ImageView<'_, u8>,
         tpl: &Self::Plan,
         x: usize,
         y: usize,
         _min_var_i: f32,
+        compensated: bool,
     ) -> f32 {
         let img_width = image.width();
         let img_height = image.height();
@@ -594,19 +1138,7 @@ impl Kernel for SsdUnmaskedScalar {
             return f32::NEG_INFINITY;
         }
 
-        let data = tpl.data();
-        let mut sse = 0.0f32;
-        for ty in 0..tpl_height {
-            let img_row = image.row(y + ty).expect("row within bounds for score");
-            let base = ty * tpl_width;
-            for tx in 0..tpl_width {
-                let idx = base + tx;
-                let value = img_row[x + tx] as f32;
-                let diff = value - data[idx];
-                sse += diff * diff;
-            }
-        }
-
+        let sse = unmasked_sse(image, tpl, x, y, compensated);
         if sse.is_finite() {
             -sse
         } else {
@@ -654,6 +1186,7 @@ impl Kernel for SsdUnmaskedScalar {
     }
 }
 
+#[cfg(feature = "std")]
 impl SsdUnmaskedScalar {
     #[allow(clippy::too_many_arguments)]
     fn scan_range(
@@ -697,23 +1230,11 @@ impl SsdUnmaskedScalar {
             return Ok(Vec::new());
         }
 
-        let data = tpl.data();
-        let mut topk_buf = TopK::new(params.topk);
+        let mut topk_buf = params.topk_buffer(max_x, max_y);
         for y in y0..=y1 {
             for x in x0..=x1 {
-                let mut sse = 0.0f32;
-                for ty in 0..tpl_height {
-                    let img_row = image.row(y + ty).expect("row within bounds for scan");
-                    let base = ty * tpl_width;
-                    for tx in 0..tpl_width {
-                        let idx = base + tx;
-                        let value = img_row[x + tx] as f32;
-                        let diff = value - data[idx];
-                        sse += diff * diff;
-                    }
-                }
-                let score = -sse;
-                if score.is_finite() && score >= params.min_score {
+                let score = -unmasked_sse(image, tpl, x, y, params.compensated);
+                if params.accepts(score) {
                     topk_buf.push(Peak {
                         x,
                         y,
@@ -728,40 +1249,595 @@ impl SsdUnmaskedScalar {
     }
 }
 
-#[cfg(test)]
-mod tests {
-    use super::{Kernel, SsdMaskedScalar, SsdUnmaskedScalar, ZnccUnmaskedScalar};
-    use crate::kernel::ScanParams;
-    use crate::template::{MaskedSsdTemplatePlan, SsdTemplatePlan, TemplatePlan};
-    use crate::ImageView;
+#[cfg(feature = "std")]
+impl CombinedMaskedScalar {
+    /// Computes the combined score at a single placement (top-left coordinates).
+    ///
+    /// Returns `NEG_INFINITY` out of bounds, for a flat template, or when the
+    /// image window variance is at most `min_var_i`. `compensated` selects
+    /// Kahan accumulation as in [`ScanParams::compensated`].
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn score_at(
+        image: ImageView<'_, u8>,
+        zncc: &MaskedTemplatePlan,
+        ssd: &MaskedSsdTemplatePlan,
+        weights: CombinedWeights,
+        x: usize,
+        y: usize,
+        min_var_i: f32,
+        compensated: bool,
+    ) -> f32 {
+        let img_width = image.width();
+        let img_height = image.height();
+        let tpl_width = zncc.width();
+        let tpl_height = zncc.height();
+        debug_assert_eq!((ssd.width(), ssd.height()), (tpl_width, tpl_height));
 
-    #[test]
-    fn unmasked_zncc_scan_matches_bruteforce() {
-        let img_width = 6;
-        let img_height = 5;
-        let mut image = Vec::with_capacity(img_width * img_height);
-        for y in 0..img_height {
-            for x in 0..img_width {
-                image.push(((x * 17 + y * 9 + x * y) & 0xFF) as u8);
-            }
+        if img_width < tpl_width || img_height < tpl_height {
+            return f32::NEG_INFINITY;
         }
-        let tpl_width = 3;
-        let tpl_height = 2;
-        let mut tpl = Vec::with_capacity(tpl_width * tpl_height);
-        for y in 0..tpl_height {
-            for x in 0..tpl_width {
-                tpl.push(((x * 5 + y * 11 + x * y) & 0xFF) as u8);
-            }
+        if x > img_width - tpl_width || y > img_height - tpl_height {
+            return f32::NEG_INFINITY;
+        }
+        let var_t = zncc.var_t();
+        if var_t <= 1e-8 {
+            return f32::NEG_INFINITY;
         }
 
-        let image_view = ImageView::from_slice(&image, img_width, img_height).unwrap();
-        let tpl_view = ImageView::from_slice(&tpl, tpl_width, tpl_height).unwrap();
-        let plan = TemplatePlan::from_view(tpl_view).unwrap();
+        let (dot, sum_i, sum_i2, sse, n) =
+            masked_combined_sums(image, zncc, ssd, x, y, compensated);
+        let var_i = sum_i2 - (sum_i * sum_i) / zncc.sum_w();
+        if var_i <= min_var_i {
+            return f32::NEG_INFINITY;
+        }
 
-        let params = ScanParams {
-            topk: 1,
-            min_var_i: 1e-8,
-            min_score: f32::NEG_INFINITY,
+        let score = weights.blend(dot / (var_t * var_i).sqrt(), sse, n as f32);
+        if score.is_finite() {
+            score
+        } else {
+            f32::NEG_INFINITY
+        }
+    }
+
+    /// Scans the full valid placement range and returns top-K peaks.
+    pub(crate) fn scan_full(
+        image: ImageView<'_, u8>,
+        zncc: &MaskedTemplatePlan,
+        ssd: &MaskedSsdTemplatePlan,
+        weights: CombinedWeights,
+        angle_idx: usize,
+        params: ScanParams,
+    ) -> CorrMatchResult<Vec<Peak>> {
+        Self::scan_roi(
+            image,
+            zncc,
+            ssd,
+            weights,
+            angle_idx,
+            (0, 0, usize::MAX, usize::MAX),
+            params,
+        )
+    }
+
+    /// Scans an inclusive ROI `(x0, y0, x1, y1)` of placement coordinates and
+    /// returns top-K peaks.
+    pub(crate) fn scan_roi(
+        image: ImageView<'_, u8>,
+        zncc: &MaskedTemplatePlan,
+        ssd: &MaskedSsdTemplatePlan,
+        weights: CombinedWeights,
+        angle_idx: usize,
+        (x0, y0, mut x1, mut y1): (usize, usize, usize, usize),
+        params: ScanParams,
+    ) -> CorrMatchResult<Vec<Peak>> {
+        if params.topk == 0 {
+            return Ok(Vec::new());
+        }
+
+        let img_width = image.width();
+        let img_height = image.height();
+        let tpl_width = zncc.width();
+        let tpl_height = zncc.height();
+        debug_assert_eq!((ssd.width(), ssd.height()), (tpl_width, tpl_height));
+
+        if img_width < tpl_width || img_height < tpl_height {
+            return Err(CorrMatchError::RoiOutOfBounds {
+                x: 0,
+                y: 0,
+                width: tpl_width,
+                height: tpl_height,
+                img_width,
+                img_height,
+            });
+        }
+
+        let max_x = img_width - tpl_width;
+        let max_y = img_height - tpl_height;
+        if x0 > max_x || y0 > max_y {
+            return Ok(Vec::new());
+        }
+        x1 = x1.min(max_x);
+        y1 = y1.min(max_y);
+        if x0 > x1 || y0 > y1 {
+            return Ok(Vec::new());
+        }
+
+        let sum_w = zncc.sum_w();
+        let var_t = zncc.var_t();
+        if var_t <= 1e-8 {
+            return Ok(Vec::new());
+        }
+
+        let mut topk_buf = params.topk_buffer(max_x, max_y);
+        for y in y0..=y1 {
+            for x in x0..=x1 {
+                let (dot, sum_i, sum_i2, sse, n) =
+                    masked_combined_sums(image, zncc, ssd, x, y, params.compensated);
+
+                let var_i = sum_i2 - (sum_i * sum_i) / sum_w;
+                if var_i <= params.min_var_i {
+                    continue;
+                }
+
+                let score = weights.blend(dot / (var_t * var_i).sqrt(), sse, n as f32);
+                if params.accepts(score) {
+                    topk_buf.push(Peak {
+                        x,
+                        y,
+                        score,
+                        angle_idx,
+                    });
+                }
+            }
+        }
+
+        Ok(topk_buf.into_sorted_desc())
+    }
+}
+
+#[cfg(feature = "std")]
+impl CombinedUnmaskedScalar {
+    /// Computes the combined score at a single placement (top-left coordinates).
+    ///
+    /// Returns `NEG_INFINITY` out of bounds, for a flat template, or when the
+    /// image window variance is at most `min_var_i`. `compensated` selects
+    /// Kahan accumulation as in [`ScanParams::compensated`].
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn score_at(
+        image: ImageView<'_, u8>,
+        zncc: &TemplatePlan,
+        ssd: &SsdTemplatePlan,
+        weights: CombinedWeights,
+        x: usize,
+        y: usize,
+        min_var_i: f32,
+        compensated: bool,
+    ) -> f32 {
+        let img_width = image.width();
+        let img_height = image.height();
+        let tpl_width = zncc.width();
+        let tpl_height = zncc.height();
+        debug_assert_eq!((ssd.width(), ssd.height()), (tpl_width, tpl_height));
+
+        if img_width < tpl_width || img_height < tpl_height {
+            return f32::NEG_INFINITY;
+        }
+        if x > img_width - tpl_width || y > img_height - tpl_height {
+            return f32::NEG_INFINITY;
+        }
+        let var_t = zncc.var_t();
+        if var_t <= 1e-8 {
+            return f32::NEG_INFINITY;
+        }
+
+        let n = tpl_width * tpl_height;
+        let (sum_i, sum_i2) = unmasked_window_sums(image, zncc, x, y);
+        let var_i = window_variance(sum_i, sum_i2, n);
+        if var_i <= min_var_i {
+            return f32::NEG_INFINITY;
+        }
+        let (dot, sse) = unmasked_combined_dot_sse(image, zncc, ssd, x, y, compensated);
+
+        let score = weights.blend(dot / (var_t * var_i).sqrt(), sse, n as f32);
+        if score.is_finite() {
+            score
+        } else {
+            f32::NEG_INFINITY
+        }
+    }
+
+    /// Scans the full valid placement range and returns top-K peaks.
+    pub(crate) fn scan_full(
+        image: ImageView<'_, u8>,
+        zncc: &TemplatePlan,
+        ssd: &SsdTemplatePlan,
+        weights: CombinedWeights,
+        angle_idx: usize,
+        params: ScanParams,
+    ) -> CorrMatchResult<Vec<Peak>> {
+        Self::scan_roi(
+            image,
+            zncc,
+            ssd,
+            weights,
+            angle_idx,
+            (0, 0, usize::MAX, usize::MAX),
+            params,
+        )
+    }
+
+    /// Scans an inclusive ROI `(x0, y0, x1, y1)` of placement coordinates and
+    /// returns top-K peaks.
+    pub(crate) fn scan_roi(
+        image: ImageView<'_, u8>,
+        zncc: &TemplatePlan,
+        ssd: &SsdTemplatePlan,
+        weights: CombinedWeights,
+        angle_idx: usize,
+        (x0, y0, mut x1, mut y1): (usize, usize, usize, usize),
+        params: ScanParams,
+    ) -> CorrMatchResult<Vec<Peak>> {
+        if params.topk == 0 {
+            return Ok(Vec::new());
+        }
+
+        let img_width = image.width();
+        let img_height = image.height();
+        let tpl_width = zncc.width();
+        let tpl_height = zncc.height();
+        debug_assert_eq!((ssd.width(), ssd.height()), (tpl_width, tpl_height));
+
+        if img_width < tpl_width || img_height < tpl_height {
+            return Err(CorrMatchError::RoiOutOfBounds {
+                x: 0,
+                y: 0,
+                width: tpl_width,
+                height: tpl_height,
+                img_width,
+                img_height,
+            });
+        }
+
+        let max_x = img_width - tpl_width;
+        let max_y = img_height - tpl_height;
+        if x0 > max_x || y0 > max_y {
+            return Ok(Vec::new());
+        }
+        x1 = x1.min(max_x);
+        y1 = y1.min(max_y);
+        if x0 > x1 || y0 > y1 {
+            return Ok(Vec::new());
+        }
+
+        let var_t = zncc.var_t();
+        if var_t <= 1e-8 {
+            return Ok(Vec::new());
+        }
+        let n = tpl_width * tpl_height;
+
+        let region = image.roi(x0, y0, x1 - x0 + tpl_width, y1 - y0 + tpl_height)?;
+        let integral = IntegralImage::new(region);
+
+        let mut topk_buf = params.topk_buffer(max_x, max_y);
+        for y in y0..=y1 {
+            for x in x0..=x1 {
+                let (sum_i, sum_i2) = integral.window_sums(x - x0, y - y0, tpl_width, tpl_height);
+                let var_i = window_variance(sum_i, sum_i2, n);
+                if var_i <= params.min_var_i {
+                    continue;
+                }
+
+                let (dot, sse) =
+                    unmasked_combined_dot_sse(image, zncc, ssd, x, y, params.compensated);
+                let score = weights.blend(dot / (var_t * var_i).sqrt(), sse, n as f32);
+                if params.accepts(score) {
+                    topk_buf.push(Peak {
+                        x,
+                        y,
+                        score,
+                        angle_idx,
+                    });
+                }
+            }
+        }
+
+        Ok(topk_buf.into_sorted_desc())
+    }
+}
+
+#[cfg(feature = "std")]
+impl ZnccRgbScalar {
+    /// Computes the mean channel score at a single placement.
+    ///
+    /// Flat template channels are skipped. If any remaining channel scores
+    /// `NEG_INFINITY` (out of bounds or a flat image window), so does the
+    /// placement.
+    pub fn score_at(
+        image: [ImageView<'_, u8>; 3],
+        tpl: &RgbTemplatePlan,
+        x: usize,
+        y: usize,
+        min_var_i: f32,
+    ) -> f32 {
+        let mut sum = 0.0f32;
+        let mut count = 0usize;
+        for (index, view) in image.into_iter().enumerate() {
+            let Some(plan) = tpl.channel(index) else {
+                continue;
+            };
+            let score = <ZnccUnmaskedScalar as Kernel>::score_at(view, plan, x, y, min_var_i);
+            if !score.is_finite() {
+                return f32::NEG_INFINITY;
+            }
+            sum += score;
+            count += 1;
+        }
+        if count == 0 {
+            return f32::NEG_INFINITY;
+        }
+        sum / count as f32
+    }
+
+    /// Scans the full valid placement range and returns top-K peaks.
+    pub fn scan_full(
+        image: [ImageView<'_, u8>; 3],
+        tpl: &RgbTemplatePlan,
+        params: ScanParams,
+    ) -> CorrMatchResult<Vec<Peak>> {
+        Self::scan_roi(image, tpl, 0, 0, usize::MAX, usize::MAX, params)
+    }
+
+    /// Scans an inclusive ROI of placement coordinates and returns top-K peaks.
+    #[allow(clippy::too_many_arguments)]
+    pub fn scan_roi(
+        image: [ImageView<'_, u8>; 3],
+        tpl: &RgbTemplatePlan,
+        x0: usize,
+        y0: usize,
+        mut x1: usize,
+        mut y1: usize,
+        params: ScanParams,
+    ) -> CorrMatchResult<Vec<Peak>> {
+        if params.topk == 0 {
+            return Ok(Vec::new());
+        }
+
+        let img_width = image[0].width();
+        let img_height = image[0].height();
+        if image
+            .iter()
+            .any(|view| view.width() != img_width || view.height() != img_height)
+        {
+            return Err(CorrMatchError::InvalidDimensions {
+                width: img_width,
+                height: img_height,
+            });
+        }
+        let tpl_width = tpl.width();
+        let tpl_height = tpl.height();
+        if img_width < tpl_width || img_height < tpl_height {
+            return Err(CorrMatchError::RoiOutOfBounds {
+                x: 0,
+                y: 0,
+                width: tpl_width,
+                height: tpl_height,
+                img_width,
+                img_height,
+            });
+        }
+
+        let max_x = img_width - tpl_width;
+        let max_y = img_height - tpl_height;
+        if x0 > max_x || y0 > max_y {
+            return Ok(Vec::new());
+        }
+        x1 = x1.min(max_x);
+        y1 = y1.min(max_y);
+        if x0 > x1 || y0 > y1 {
+            return Ok(Vec::new());
+        }
+
+        let mut topk_buf = params.topk_buffer(max_x, max_y);
+        for y in y0..=y1 {
+            for x in x0..=x1 {
+                let score = Self::score_at(image, tpl, x, y, params.min_var_i);
+                if params.accepts(score) {
+                    topk_buf.push(Peak {
+                        x,
+                        y,
+                        score,
+                        angle_idx: 0,
+                    });
+                }
+            }
+        }
+
+        Ok(topk_buf.into_sorted_desc())
+    }
+}
+
+#[cfg(feature = "std")]
+impl ZnccImageMaskedScalar {
+    /// Scores one placement over the pixels valid in both masks.
+    ///
+    /// `image_mask` has the image's dimensions; nonzero marks a valid pixel.
+    /// Returns `NEG_INFINITY` out of bounds, when too little of the template
+    /// lands on valid pixels, or when either side of the overlap is flat.
+    pub(crate) fn score_at(
+        image: ImageView<'_, u8>,
+        image_mask: ImageView<'_, u8>,
+        tpl: &ZnccTerms<'_>,
+        x: usize,
+        y: usize,
+        min_var_i: f32,
+    ) -> f32 {
+        let img_width = image.width();
+        let img_height = image.height();
+        if img_width < tpl.width || img_height < tpl.height {
+            return f32::NEG_INFINITY;
+        }
+        if x > img_width - tpl.width || y > img_height - tpl.height {
+            return f32::NEG_INFINITY;
+        }
+
+        // Template values enter shifted by the full-template mean; ZNCC is
+        // invariant to that shift, so t' stands in for t on any subset.
+        let mut sum_w = 0.0f32;
+        let mut sum_t = 0.0f32;
+        let mut sum_t2 = 0.0f32;
+        let mut sum_i = 0.0f32;
+        let mut sum_i2 = 0.0f32;
+        let mut dot = 0.0f32;
+        for ty in 0..tpl.height {
+            let img_row = image.row(y + ty).expect("row within bounds for scan");
+            let mask_row = image_mask.row(y + ty).expect("row within bounds for scan");
+            let base = ty * tpl.width;
+            for tx in 0..tpl.width {
+                let idx = base + tx;
+                if mask_row[x + tx] == 0 || tpl.excludes(idx) {
+                    continue;
+                }
+                let w = tpl.weights.map_or(1.0, |weights| weights[idx]);
+                if w <= 0.0 {
+                    continue;
+                }
+                let t = tpl.t_prime(idx);
+                let value = img_row[x + tx] as f32;
+                sum_w += w;
+                sum_t += t;
+                sum_t2 += t * t / w;
+                sum_i += w * value;
+                sum_i2 += w * value * value;
+                dot += t * value;
+            }
+        }
+
+        if sum_w < MIN_VALID_WEIGHT_FRACTION * tpl.sum_w {
+            return f32::NEG_INFINITY;
+        }
+        let var_t = sum_t2 - sum_t * sum_t / sum_w;
+        let var_i = sum_i2 - sum_i * sum_i / sum_w;
+        if var_t <= 1e-8 || var_i <= min_var_i {
+            return f32::NEG_INFINITY;
+        }
+        let score = (dot - sum_t * sum_i / sum_w) / (var_t * var_i).sqrt();
+        if score.is_finite() {
+            score
+        } else {
+            f32::NEG_INFINITY
+        }
+    }
+
+    /// Scans an inclusive ROI of placement coordinates and returns top-K peaks.
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn scan_roi(
+        image: ImageView<'_, u8>,
+        image_mask: ImageView<'_, u8>,
+        tpl: &ZnccTerms<'_>,
+        angle_idx: usize,
+        x0: usize,
+        y0: usize,
+        mut x1: usize,
+        mut y1: usize,
+        params: ScanParams,
+    ) -> CorrMatchResult<Vec<Peak>> {
+        if params.topk == 0 {
+            return Ok(Vec::new());
+        }
+
+        let img_width = image.width();
+        let img_height = image.height();
+        if image_mask.width() != img_width || image_mask.height() != img_height {
+            return Err(CorrMatchError::InvalidDimensions {
+                width: image_mask.width(),
+                height: image_mask.height(),
+            });
+        }
+        if img_width < tpl.width || img_height < tpl.height {
+            return Err(CorrMatchError::RoiOutOfBounds {
+                x: 0,
+                y: 0,
+                width: tpl.width,
+                height: tpl.height,
+                img_width,
+                img_height,
+            });
+        }
+
+        let max_x = img_width - tpl.width;
+        let max_y = img_height - tpl.height;
+        if x0 > max_x || y0 > max_y {
+            return Ok(Vec::new());
+        }
+        x1 = x1.min(max_x);
+        y1 = y1.min(max_y);
+        if x0 > x1 || y0 > y1 {
+            return Ok(Vec::new());
+        }
+
+        let mut topk_buf = params.topk_buffer(max_x, max_y);
+        for y in y0..=y1 {
+            for x in x0..=x1 {
+                let score = Self::score_at(image, image_mask, tpl, x, y, params.min_var_i);
+                if params.accepts(score) {
+                    topk_buf.push(Peak {
+                        x,
+                        y,
+                        score,
+                        angle_idx,
+                    });
+                }
+            }
+        }
+
+        Ok(topk_buf.into_sorted_desc())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        Accumulator, CombinedMaskedScalar, CombinedUnmaskedScalar, CombinedWeights, Kahan, Kernel,
+        SsdMaskedScalar, SsdUnmaskedScalar, ZnccMaskedScalar, ZnccUnmaskedScalar,
+    };
+    use crate::candidate::topk::TieBreak;
+    use crate::kernel::ScanParams;
+    use crate::template::{
+        MaskedSsdTemplatePlan, MaskedTemplatePlan, SsdTemplatePlan, TemplatePlan,
+    };
+    use crate::ImageView;
+
+    #[test]
+    fn unmasked_zncc_scan_matches_bruteforce() {
+        let img_width = 6;
+        let img_height = 5;
+        let mut image = Vec::with_capacity(img_width * img_height);
+        for y in 0..img_height {
+            for x in 0..img_width {
+                image.push(((x * 17 + y * 9 + x * y) & 0xFF) as u8);
+            }
+        }
+        let tpl_width = 3;
+        let tpl_height = 2;
+        let mut tpl = Vec::with_capacity(tpl_width * tpl_height);
+        for y in 0..tpl_height {
+            for x in 0..tpl_width {
+                tpl.push(((x * 5 + y * 11 + x * y) & 0xFF) as u8);
+            }
+        }
+
+        let image_view = ImageView::from_slice(&image, img_width, img_height).unwrap();
+        let tpl_view = ImageView::from_slice(&tpl, tpl_width, tpl_height).unwrap();
+        let plan = TemplatePlan::from_view(tpl_view).unwrap();
+
+        let params = ScanParams {
+            topk: 1,
+            min_var_i: 1e-8,
+            min_score: f32::NEG_INFINITY,
+            rank_by_magnitude: false,
+            tie_break: TieBreak::TopLeftFirst,
+            compensated: false,
+            max_var_ratio: None,
         };
         let best = <ZnccUnmaskedScalar as Kernel>::scan_full(image_view, &plan, 0, params)
             .unwrap()
@@ -808,6 +1884,41 @@ mod tests {
         assert!((best.score - best_score as f32).abs() < 1e-5);
     }
 
+    #[test]
+    fn unmasked_zncc_roi_scan_agrees_with_score_at() {
+        let (img_width, img_height) = (40, 30);
+        let image: Vec<u8> = (0..img_width * img_height)
+            .map(|i| ((i * 37 + (i / img_width) * 11) % 251) as u8)
+            .collect();
+        let (tpl_width, tpl_height) = (12, 9);
+        let tpl: Vec<u8> = (0..tpl_width * tpl_height)
+            .map(|i| ((i * 13) ^ (i / tpl_width * 7)) as u8)
+            .collect();
+        let image_view = ImageView::from_slice(&image, img_width, img_height).unwrap();
+        let tpl_view = ImageView::from_slice(&tpl, tpl_width, tpl_height).unwrap();
+        let plan = TemplatePlan::from_view(tpl_view).unwrap();
+
+        let params = ScanParams {
+            topk: 1000,
+            min_var_i: 1e-8,
+            min_score: f32::NEG_INFINITY,
+            rank_by_magnitude: false,
+            tie_break: TieBreak::TopLeftFirst,
+            compensated: false,
+            max_var_ratio: None,
+        };
+        // The ROI is offset from the origin and clipped at the right edge.
+        let peaks =
+            <ZnccUnmaskedScalar as Kernel>::scan_roi(image_view, &plan, 0, 5, 3, 40, 12, params)
+                .unwrap();
+        assert_eq!(peaks.len(), (28 - 5 + 1) * (12 - 3 + 1));
+        for peak in peaks {
+            let score =
+                <ZnccUnmaskedScalar as Kernel>::score_at(image_view, &plan, peak.x, peak.y, 1e-8);
+            assert_eq!(peak.score, score, "placement ({}, {})", peak.x, peak.y);
+        }
+    }
+
     #[test]
     fn unmasked_ssd_scan_matches_bruteforce() {
         let img_width = 5;
@@ -835,6 +1946,10 @@ mod tests {
             topk: 1,
             min_var_i: 0.0,
             min_score: f32::NEG_INFINITY,
+            rank_by_magnitude: false,
+            tie_break: TieBreak::TopLeftFirst,
+            compensated: false,
+            max_var_ratio: None,
         };
         let best = <SsdUnmaskedScalar as Kernel>::scan_full(image_view, &plan, 0, params)
             .unwrap()
@@ -902,4 +2017,215 @@ mod tests {
         let expected = -(diff * diff);
         assert!((score - expected).abs() < 1e-6);
     }
+
+    #[test]
+    fn combined_scores_blend_zncc_and_normalized_ssd() {
+        let (tpl_width, tpl_height) = (5, 4);
+        let tpl: Vec<u8> = (0..tpl_width * tpl_height)
+            .map(|i| ((i * 37 + (i / tpl_width) * 11) % 251) as u8)
+            .collect();
+        let mask: Vec<u8> = (0..tpl_width * tpl_height)
+            .map(|i| u8::from(i % 7 != 3))
+            .collect();
+        let (img_width, img_height) = (12, 9);
+        let image: Vec<u8> = (0..img_width * img_height)
+            .map(|i| ((i * 29 + (i / img_width) * 7) % 241) as u8)
+            .collect();
+        let tpl_view = ImageView::from_slice(&tpl, tpl_width, tpl_height).unwrap();
+        let image_view = ImageView::from_slice(&image, img_width, img_height).unwrap();
+        let weights = CombinedWeights {
+            zncc: 0.7,
+            ssd: 0.3,
+        };
+        let blend = |zncc: f32, neg_sse: f32, n: usize| {
+            0.7 * zncc + 0.3 * (1.0 + neg_sse / (n as f32 * 255.0 * 255.0))
+        };
+
+        let zncc = MaskedTemplatePlan::from_rotated_u8(tpl_view, mask.clone(), 0.0).unwrap();
+        let ssd = MaskedSsdTemplatePlan::from_rotated_u8(tpl_view, mask.clone(), 0.0).unwrap();
+        let valid = mask.iter().filter(|&&m| m != 0).count();
+        let unmasked_zncc = TemplatePlan::from_view(tpl_view).unwrap();
+        let unmasked_ssd = SsdTemplatePlan::from_view(tpl_view).unwrap();
+        for (x, y) in [(0, 0), (3, 2), (7, 5)] {
+            let masked =
+                CombinedMaskedScalar::score_at(image_view, &zncc, &ssd, weights, x, y, 1e-8, false);
+            let expected = blend(
+                <ZnccMaskedScalar as Kernel>::score_at(image_view, &zncc, x, y, 1e-8),
+                <SsdMaskedScalar as Kernel>::score_at(image_view, &ssd, x, y, 0.0),
+                valid,
+            );
+            assert!((masked - expected).abs() < 1e-5, "{masked} vs {expected}");
+
+            let unmasked = CombinedUnmaskedScalar::score_at(
+                image_view,
+                &unmasked_zncc,
+                &unmasked_ssd,
+                weights,
+                x,
+                y,
+                1e-8,
+                false,
+            );
+            let expected = blend(
+                <ZnccUnmaskedScalar as Kernel>::score_at(image_view, &unmasked_zncc, x, y, 1e-8),
+                <SsdUnmaskedScalar as Kernel>::score_at(image_view, &unmasked_ssd, x, y, 0.0),
+                tpl_width * tpl_height,
+            );
+            assert!(
+                (unmasked - expected).abs() < 1e-5,
+                "{unmasked} vs {expected}"
+            );
+        }
+
+        let params = ScanParams {
+            topk: 1,
+            min_var_i: 1e-8,
+            min_score: f32::NEG_INFINITY,
+            rank_by_magnitude: false,
+            tie_break: TieBreak::TopLeftFirst,
+            compensated: false,
+            max_var_ratio: None,
+        };
+        let best = CombinedMaskedScalar::scan_full(image_view, &zncc, &ssd, weights, 0, params)
+            .unwrap()
+            .pop()
+            .unwrap();
+        let at = CombinedMaskedScalar::score_at(
+            image_view, &zncc, &ssd, weights, best.x, best.y, 1e-8, false,
+        );
+        assert_eq!(best.score, at);
+    }
+
+    #[test]
+    fn kahan_sum_tracks_exact_sum() {
+        let values: Vec<f32> = (0..20_000).map(|i| 0.1 + (i % 7) as f32 * 1e-3).collect();
+        let exact: f64 = values.iter().map(|&v| f64::from(v)).sum();
+        let mut plain = 0.0f32;
+        let mut kahan = Kahan::default();
+        for &v in &values {
+            plain.accumulate(v);
+            kahan.accumulate(v);
+        }
+        let plain_err = (f64::from(plain.total()) - exact).abs();
+        let kahan_err = (f64::from(kahan.total()) - exact).abs();
+        assert!(kahan_err <= f64::from(f32::EPSILON) * exact, "{kahan_err}");
+        assert!(kahan_err < plain_err, "{kahan_err} vs {plain_err}");
+    }
+
+    #[test]
+    fn compensated_scores_agree_between_scan_and_score_at() {
+        let (tpl_width, tpl_height) = (6, 5);
+        let tpl: Vec<u8> = (0..tpl_width * tpl_height)
+            .map(|i| ((i * 53 + (i / tpl_width) * 19) % 251) as u8)
+            .collect();
+        let mask: Vec<u8> = (0..tpl_width * tpl_height)
+            .map(|i| u8::from(i % 5 != 2))
+            .collect();
+        let (img_width, img_height) = (16, 12);
+        let image: Vec<u8> = (0..img_width * img_height)
+            .map(|i| ((i * 31 + (i / img_width) * 13) % 239) as u8)
+            .collect();
+        let tpl_view = ImageView::from_slice(&tpl, tpl_width, tpl_height).unwrap();
+        let image_view = ImageView::from_slice(&image, img_width, img_height).unwrap();
+        let masked = MaskedTemplatePlan::from_rotated_u8(tpl_view, mask.clone(), 0.0).unwrap();
+        let masked_ssd = MaskedSsdTemplatePlan::from_rotated_u8(tpl_view, mask, 0.0).unwrap();
+        let unmasked = TemplatePlan::from_view(tpl_view).unwrap();
+        let unmasked_ssd = SsdTemplatePlan::from_view(tpl_view).unwrap();
+        let params = ScanParams {
+            topk: 1,
+            min_var_i: 1e-8,
+            min_score: f32::NEG_INFINITY,
+            rank_by_magnitude: false,
+            tie_break: TieBreak::TopLeftFirst,
+            compensated: true,
+            max_var_ratio: None,
+        };
+
+        macro_rules! check {
+            ($kernel:ty, $plan:expr) => {{
+                let best = <$kernel as Kernel>::scan_full(image_view, $plan, 0, params)
+                    .unwrap()
+                    .pop()
+                    .unwrap();
+                let compensated = <$kernel as Kernel>::score_at_with(
+                    image_view, $plan, best.x, best.y, 1e-8, true,
+                );
+                assert_eq!(best.score, compensated);
+                let plain = <$kernel as Kernel>::score_at(image_view, $plan, best.x, best.y, 1e-8);
+                assert!(
+                    (plain - compensated).abs() < 1e-4,
+                    "{plain} vs {compensated}"
+                );
+            }};
+        }
+        check!(ZnccMaskedScalar, &masked);
+        check!(ZnccUnmaskedScalar, &unmasked);
+        check!(SsdMaskedScalar, &masked_ssd);
+        check!(SsdUnmaskedScalar, &unmasked_ssd);
+    }
+
+    #[test]
+    fn variance_ratio_gate_skips_mismatched_windows() {
+        let (tpl_width, tpl_height) = (4, 4);
+        let tpl: Vec<u8> = (0..tpl_width * tpl_height)
+            .map(|i| ((i * 67 + (i / tpl_width) * 23) % 251) as u8)
+            .collect();
+        let (img_width, img_height) = (14, 8);
+        // Textured left half, near-flat right half.
+        let image: Vec<u8> = (0..img_width * img_height)
+            .map(|i| {
+                if i % img_width < img_width / 2 {
+                    ((i * 41 + (i / img_width) * 17) % 247) as u8
+                } else {
+                    120 + (i % 3) as u8
+                }
+            })
+            .collect();
+        let tpl_view = ImageView::from_slice(&tpl, tpl_width, tpl_height).unwrap();
+        let image_view = ImageView::from_slice(&image, img_width, img_height).unwrap();
+        let unmasked = TemplatePlan::from_view(tpl_view).unwrap();
+        let mask = vec![1u8; tpl_width * tpl_height];
+        let masked = MaskedTemplatePlan::from_rotated_u8(tpl_view, mask, 0.0).unwrap();
+        let params = ScanParams {
+            topk: img_width * img_height,
+            min_var_i: 1e-8,
+            min_score: f32::NEG_INFINITY,
+            rank_by_magnitude: false,
+            tie_break: TieBreak::TopLeftFirst,
+            compensated: false,
+            max_var_ratio: None,
+        };
+        let gated = ScanParams {
+            max_var_ratio: Some(3.0),
+            ..params
+        };
+        let var_t = unmasked.var_t();
+        let window_var = |x: usize, y: usize| {
+            let values: Vec<f32> = (0..tpl_height)
+                .flat_map(|ty| (0..tpl_width).map(move |tx| (tx, ty)))
+                .map(|(tx, ty)| image[(y + ty) * img_width + x + tx] as f32)
+                .collect();
+            let mean = values.iter().sum::<f32>() / values.len() as f32;
+            values.iter().map(|v| (v - mean) * (v - mean)).sum::<f32>()
+        };
+
+        let all =
+            <ZnccUnmaskedScalar as Kernel>::scan_full(image_view, &unmasked, 0, params).unwrap();
+        let kept =
+            <ZnccUnmaskedScalar as Kernel>::scan_full(image_view, &unmasked, 0, gated).unwrap();
+        let masked_kept =
+            <ZnccMaskedScalar as Kernel>::scan_full(image_view, &masked, 0, gated).unwrap();
+        assert!(!kept.is_empty() && kept.len() < all.len());
+        assert_eq!(kept.len(), masked_kept.len());
+        for peak in &all {
+            let var_i = window_var(peak.x, peak.y);
+            let in_range = var_i <= var_t * 3.0 && var_i * 3.0 >= var_t;
+            let found = kept.iter().find(|p| (p.x, p.y) == (peak.x, peak.y));
+            if in_range {
+                assert_eq!(found.map(|p| p.score), Some(peak.score));
+            } else {
+                assert!(found.is_none(), "({}, {}) kept", peak.x, peak.y);
+            }
+        }
+    }
 }
@@ -38,33 +38,71 @@
 //! Matching is deterministic; enabling `rayon` via `MatchConfig.parallel` keeps results stable.
 //!
 //! # Feature flags
+//! - `std` (default): the matcher, compiled templates, and everything else
+//!   that needs the standard library.
 //! - `rayon`: parallel search execution.
 //! - `simd`: SIMD-accelerated kernels (planned).
 //! - `image-io`: file I/O helpers via the `image` crate.
+//! - `half`: half-precision ZNCC template plans (`CompileConfig.compact_plans`);
+//!   implies `std`.
+//! - `testutil`: deterministic synthetic cases with ground truth (`testutil`).
 //!
 //! # Low-level API
 //! Advanced building blocks are available under `corrmatch::lowlevel`.
 //!
 //! # CLI
 //! A JSON-driven CLI lives in the `corrmatch-cli` workspace crate.
+//!
+//! # `no_std`
+//! Without the `std` feature (`--no-default-features`), the crate builds as
+//! `no_std` + `alloc` and keeps `ImageView`/`OwnedImage`, the template plans,
+//! the scalar kernels, `lowlevel::score_masked_zncc_at`, and the
+//! allocation-free `lowlevel::FixedTopK`. The pyramid search itself stays
+//! `std`-only.
+
+#![cfg_attr(not(feature = "std"), no_std)]
 
+extern crate alloc;
+
+#[cfg(feature = "std")]
 mod bank;
 mod candidate;
 mod image;
 mod kernel;
 pub mod lowlevel;
+#[cfg(feature = "std")]
 mod refine;
+#[cfg(feature = "std")]
 mod search;
+/// Without `std`, only the single-placement and scan helpers are built.
+#[cfg(not(feature = "std"))]
+mod search {
+    pub(crate) mod scan;
+}
 mod template;
+#[cfg(feature = "testutil")]
+pub mod testutil;
 mod util;
 
-pub use bank::{CompileConfig, CompileConfigNoRot, CompiledTemplate};
-pub use image::pyramid::ImagePyramid;
+#[cfg(feature = "std")]
+pub use bank::{
+    AngleSchedule, CompileConfig, CompileConfigNoRot, CompileOptions, CompiledTemplate,
+    CompiledTemplate3,
+};
+pub use candidate::topk::TieBreak;
+#[cfg(feature = "std")]
+pub use image::pyramid::{Downsampler, ImagePyramid, PyramidFilter};
 pub use image::{ImageView, OwnedImage};
-pub use template::Template;
-pub use util::{CorrMatchError, CorrMatchResult};
+#[cfg(feature = "std")]
+pub use template::rotate::FillStrategy;
+pub use template::{Template, Template3};
+pub use util::{CorrMatchError, CorrMatchResult, TemplateVariance};
 
-pub use search::{Match, MatchConfig, Matcher, Metric, RotationMode};
+#[cfg(feature = "std")]
+pub use search::{
+    AngleRefine, Detection, Ensemble, EnsembleMatch, Match, MatchConfig, MatchDetails,
+    MatchTimings, Matcher, Metric, RgbMatcher, RotationMode, ScoreAgg, ScoreClamp, TemplateBatch,
+};
 
 /// Image I/O helpers available when the `image-io` feature is enabled.
 #[cfg(feature = "image-io")]
@@ -4,15 +4,19 @@
 //! advanced use cases beyond the high-level `Matcher` API. Most users should
 //! prefer the top-level `Template`, `CompiledTemplate`, and `Matcher` types.
 
-pub use crate::bank::AngleGrid;
-pub use crate::candidate::nms::nms_2d;
-pub use crate::candidate::topk::{Peak, TopK};
+#[cfg(feature = "std")]
+pub use crate::bank::{AngleGrid, PlanRef};
+pub use crate::candidate::nms::{nms_2d, nms_2d_keep_ratio};
+pub use crate::candidate::topk::{FixedTopK, Peak, TieBreak, TopK};
 pub use crate::kernel::{Kernel, ScanParams};
 pub use crate::search::scan::{
     scan_masked_zncc_scalar, scan_masked_zncc_scalar_full, scan_masked_zncc_scalar_roi,
-    score_masked_zncc_at,
+    scan_zncc_scalar_full, score_masked_zncc_at, zncc_score,
+};
+#[cfg(feature = "std")]
+pub use crate::template::rotate::{
+    rotate_u8_bilinear, rotate_u8_bilinear_masked, rotate_u8_bilinear_masked_packed,
 };
-pub use crate::template::rotate::{rotate_u8_bilinear, rotate_u8_bilinear_masked};
 pub use crate::template::{
-    MaskedSsdTemplatePlan, MaskedTemplatePlan, SsdTemplatePlan, TemplatePlan,
+    MaskedSsdTemplatePlan, MaskedTemplatePlan, RgbTemplatePlan, SsdTemplatePlan, TemplatePlan,
 };
@@ -0,0 +1,74 @@
+//! Golden-section search for bounded 1D maximization.
+
+/// Inverse golden ratio, `(sqrt(5) - 1) / 2`.
+const INV_PHI: f32 = 0.618_034;
+
+/// Maximizes `f` over `[lo, hi]` with `iters` golden-section steps.
+///
+/// Each step shrinks the bracket by the golden ratio and costs one
+/// evaluation after the first two. The best evaluated `(x, f(x))` is
+/// returned, including `seed` (a known sample such as the bracket center),
+/// so the result never scores below it even when `f` is not unimodal.
+/// Non-finite values count as worse than any finite one.
+pub(crate) fn golden_section_max<E>(
+    mut f: impl FnMut(f32) -> Result<f32, E>,
+    lo: f32,
+    hi: f32,
+    iters: usize,
+    seed: (f32, f32),
+) -> Result<(f32, f32), E> {
+    let rank = |v: f32| if v.is_nan() { f32::NEG_INFINITY } else { v };
+    let mut best = seed;
+    let mut keep = |x: f32, v: f32| {
+        if rank(v) > rank(best.1) {
+            best = (x, v);
+        }
+    };
+
+    let (mut a, mut b) = (lo, hi);
+    let mut c = b - INV_PHI * (b - a);
+    let mut d = a + INV_PHI * (b - a);
+    let mut fc = f(c)?;
+    let mut fd = f(d)?;
+    keep(c, fc);
+    keep(d, fd);
+    for _ in 1..iters {
+        if rank(fc) >= rank(fd) {
+            b = d;
+            d = c;
+            fd = fc;
+            c = b - INV_PHI * (b - a);
+            fc = f(c)?;
+            keep(c, fc);
+        } else {
+            a = c;
+            c = d;
+            fc = fd;
+            d = a + INV_PHI * (b - a);
+            fd = f(d)?;
+            keep(d, fd);
+        }
+    }
+    Ok(best)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::golden_section_max;
+    use std::convert::Infallible;
+
+    #[test]
+    fn golden_section_finds_off_center_peak() {
+        let f = |x: f32| Ok::<_, Infallible>(1.0 - (x - 0.3).powi(2));
+        let (x, v) = golden_section_max(f, -1.0, 1.0, 20, (0.0, 0.91)).unwrap();
+        assert!((x - 0.3).abs() < 1e-3, "x {x}");
+        assert!((v - 1.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn golden_section_keeps_seed_when_it_is_best() {
+        let f = |x: f32| Ok::<_, Infallible>(if x == 0.0 { 1.0 } else { 0.5 - x.abs() });
+        let (x, v) = golden_section_max(f, -1.0, 1.0, 8, (0.0, 1.0)).unwrap();
+        assert_eq!((x, v), (0.0, 1.0));
+    }
+}
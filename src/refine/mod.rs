@@ -1,4 +1,6 @@
 //! Local refinement models for subpixel and subangle estimation.
 
+pub(crate) mod golden;
 pub(crate) mod quad1d;
 pub(crate) mod quad2d;
+pub(crate) mod resample;
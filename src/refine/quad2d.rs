@@ -15,9 +15,44 @@ pub fn refine_subpixel_2d(center_x: usize, center_y: usize, s: [[f32; 3]; 3]) ->
     (center_x as f32 + dx, center_y as f32 + dy)
 }
 
+/// Returns the center of a 3-sample fit window for a peak at `peak`.
+///
+/// The window `center - 1..=center + 1` is slid inward so it stays inside
+/// `[0, max]`; with fewer than three positions the peak itself is returned.
+pub(crate) fn fit_window_center(peak: usize, max: usize) -> usize {
+    if max < 2 {
+        peak
+    } else {
+        peak.clamp(1, max - 1)
+    }
+}
+
+/// Refines a subpixel peak from a 3x3 window that may not be centered on it.
+///
+/// `s` is centered at `window`, as chosen by [`fit_window_center`] for the
+/// integer `peak`. On a border the window lies on the interior side, which
+/// gives a one-sided quadratic fit instead of one through a missing sample.
+/// Each coordinate is clamped to `[0, max]` and falls back to the integer
+/// peak if its fit is ill-conditioned.
+pub(crate) fn refine_subpixel_2d_windowed(
+    peak: (usize, usize),
+    window: (usize, usize),
+    max: (usize, usize),
+    s: [[f32; 3]; 3],
+) -> (f32, f32) {
+    let axis = |fm: f32, f0: f32, fp: f32, peak: usize, window: usize, max: usize| {
+        quad_peak_offset_1d(fm, f0, fp)
+            .map_or(peak as f32, |d| (window as f32 + d).clamp(0.0, max as f32))
+    };
+    (
+        axis(s[1][0], s[1][1], s[1][2], peak.0, window.0, max.0),
+        axis(s[0][1], s[1][1], s[2][1], peak.1, window.1, max.1),
+    )
+}
+
 #[cfg(test)]
 mod tests {
-    use super::refine_subpixel_2d;
+    use super::{fit_window_center, refine_subpixel_2d, refine_subpixel_2d_windowed};
 
     #[test]
     fn refine_subpixel_separable_paraboloid() {
@@ -33,4 +68,25 @@ mod tests {
         assert!((x_ref - 0.3).abs() < 1e-3);
         assert!((y_ref + 0.2).abs() < 1e-3);
     }
+
+    #[test]
+    fn windowed_fit_is_one_sided_on_the_border() {
+        // Peak at 9.6 on a 0..=10 axis; the integer peak 10 sits on the edge.
+        let f = |x: f32| 1.0 - (x - 9.6).powi(2);
+        let window = fit_window_center(10, 10);
+        assert_eq!(window, 9);
+
+        let w = window as f32;
+        let row = [f(w - 1.0), f(w), f(w + 1.0)];
+        let s = [[f(w); 3], row, [f(w); 3]];
+        let (x, _) = refine_subpixel_2d_windowed((10, 5), (window, 5), (10, 10), s);
+        assert!((x - 9.6).abs() < 1e-3, "x = {x}");
+
+        // A peak beyond the edge clamps to the last valid position.
+        let g = |x: f32| 1.0 - (x - 10.4).powi(2);
+        let row = [g(w - 1.0), g(w), g(w + 1.0)];
+        let s = [[g(w); 3], row, [g(w); 3]];
+        let (x, _) = refine_subpixel_2d_windowed((10, 5), (window, 5), (10, 10), s);
+        assert_eq!(x, 10.0);
+    }
 }
@@ -0,0 +1,146 @@
+//! ZNCC scoring on bilinearly resampled image patches.
+//!
+//! The integer-grid fit in [`crate::refine::quad2d`] only sees scores at whole
+//! pixel offsets. Resampling the image at the fractional estimate lets a
+//! second fit run on scores centered on the peak.
+
+use crate::refine::quad1d::quad_peak_offset_1d;
+use crate::template::ZnccTerms;
+use crate::ImageView;
+
+/// Spacing of the resampled scores, in pixels.
+///
+/// A whole-pixel step keeps every sample at the same fractional phase, so the
+/// smoothing introduced by bilinear interpolation is identical on both sides
+/// of the center and does not bias the fit.
+const RESAMPLE_STEP: f32 = 1.0;
+
+/// Bilinearly samples `image` at `(x, y)`; the caller keeps `x`, `y` in range.
+fn sample_bilinear(image: ImageView<'_, u8>, x: f32, y: f32) -> f32 {
+    let x0 = x.floor() as usize;
+    let y0 = y.floor() as usize;
+    let x1 = (x0 + 1).min(image.width() - 1);
+    let y1 = (y0 + 1).min(image.height() - 1);
+    let fx = x - x0 as f32;
+    let fy = y - y0 as f32;
+
+    let row0 = image.row(y0).expect("row within bounds for sample");
+    let row1 = image.row(y1).expect("row within bounds for sample");
+    let top = row0[x0] as f32 * (1.0 - fx) + row0[x1] as f32 * fx;
+    let bottom = row1[x0] as f32 * (1.0 - fx) + row1[x1] as f32 * fx;
+    top * (1.0 - fy) + bottom * fy
+}
+
+/// Scores ZNCC with the template placed at the fractional offset `(x, y)`.
+///
+/// Returns `NEG_INFINITY` when the placement leaves the image or the sampled
+/// patch is too flat, matching the integer-grid kernels.
+pub(crate) fn zncc_at_subpixel(
+    image: ImageView<'_, u8>,
+    tpl: &ZnccTerms<'_>,
+    x: f32,
+    y: f32,
+    min_var_i: f32,
+) -> f32 {
+    if !x.is_finite() || !y.is_finite() || x < 0.0 || y < 0.0 {
+        return f32::NEG_INFINITY;
+    }
+    if x + (tpl.width - 1) as f32 > (image.width() - 1) as f32
+        || y + (tpl.height - 1) as f32 > (image.height() - 1) as f32
+    {
+        return f32::NEG_INFINITY;
+    }
+    if tpl.var_t <= 1e-8 {
+        return f32::NEG_INFINITY;
+    }
+
+    let mut dot = 0.0f32;
+    let mut sum_i = 0.0f32;
+    let mut sum_i2 = 0.0f32;
+    for ty in 0..tpl.height {
+        let base = ty * tpl.width;
+        for tx in 0..tpl.width {
+            let idx = base + tx;
+            if tpl.excludes(idx) {
+                continue;
+            }
+            let w = tpl.weights.map_or(1.0, |weights| weights[idx]);
+            let value = sample_bilinear(image, x + tx as f32, y + ty as f32);
+            dot += tpl.t_prime(idx) * value;
+            sum_i += w * value;
+            sum_i2 += w * value * value;
+        }
+    }
+
+    let var_i = sum_i2 - (sum_i * sum_i) / tpl.sum_w;
+    if var_i <= min_var_i {
+        return f32::NEG_INFINITY;
+    }
+    let score = dot / (tpl.var_t * var_i).sqrt();
+    if score.is_finite() {
+        score
+    } else {
+        f32::NEG_INFINITY
+    }
+}
+
+/// Re-fits a subpixel position on ZNCC scores centered at `(x, y)`.
+///
+/// Scores are sampled one pixel either side of the estimate along its row and
+/// column and fitted with the same parabola as the integer grid. Centering the
+/// fit on the estimate removes most of the bias a parabola has on peaks that
+/// are not quadratic. Each axis keeps its input
+/// coordinate when its fit is ill-conditioned.
+pub(crate) fn refine_subpixel_resampled(
+    image: ImageView<'_, u8>,
+    tpl: ZnccTerms<'_>,
+    x: f32,
+    y: f32,
+    min_var_i: f32,
+) -> (f32, f32) {
+    let score = |dx: f32, dy: f32| zncc_at_subpixel(image, &tpl, x + dx, y + dy, min_var_i);
+    let center = score(0.0, 0.0);
+    let dx = quad_peak_offset_1d(
+        score(-RESAMPLE_STEP, 0.0),
+        center,
+        score(RESAMPLE_STEP, 0.0),
+    )
+    .unwrap_or(0.0);
+    let dy = quad_peak_offset_1d(
+        score(0.0, -RESAMPLE_STEP),
+        center,
+        score(0.0, RESAMPLE_STEP),
+    )
+    .unwrap_or(0.0);
+
+    (x + dx * RESAMPLE_STEP, y + dy * RESAMPLE_STEP)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::zncc_at_subpixel;
+    use crate::template::{TemplatePlan, ZnccTerms};
+    use crate::ImageView;
+
+    #[test]
+    fn integer_offsets_match_plain_zncc() {
+        let width = 12;
+        let height = 10;
+        let image: Vec<u8> = (0..width * height)
+            .map(|i| ((i * 37 + (i / width) * 11) % 251) as u8)
+            .collect();
+        let view = ImageView::from_slice(&image, width, height).unwrap();
+        let tpl_data: Vec<u8> = (0..4)
+            .flat_map(|y| image[(3 + y) * width + 2..(3 + y) * width + 7].to_vec())
+            .collect();
+        let plan =
+            TemplatePlan::from_view(ImageView::from_slice(&tpl_data, 5, 4).unwrap()).unwrap();
+
+        let exact = zncc_at_subpixel(view, &ZnccTerms::from(&plan), 2.0, 3.0, 1e-8);
+        assert!((exact - 1.0).abs() < 1e-4, "exact placement scored {exact}");
+        let shifted = zncc_at_subpixel(view, &ZnccTerms::from(&plan), 2.5, 3.0, 1e-8);
+        assert!(shifted < exact);
+        let outside = zncc_at_subpixel(view, &ZnccTerms::from(&plan), 7.5, 3.0, 1e-8);
+        assert_eq!(outside, f32::NEG_INFINITY);
+    }
+}
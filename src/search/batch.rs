@@ -0,0 +1,142 @@
+//! Matching one image against many small templates.
+//!
+//! For tiny templates (fiducials, glyphs) the scan itself is cheap and the
+//! per-call setup dominates: validating the configuration, building the
+//! image pyramid and, under `rayon`, splitting a small scan across threads.
+//! A batch compiles every template up front, validates once, builds a single
+//! pyramid no deeper than the deepest template needs, and parallelizes over
+//! templates instead of within each one.
+
+use crate::bank::{CompileConfig, CompileConfigNoRot, CompiledTemplate};
+use crate::search::{Match, MatchConfig, Matcher};
+use crate::util::{CorrMatchError, CorrMatchResult};
+use crate::{ImageView, Template};
+#[cfg(feature = "rayon")]
+use rayon::prelude::*;
+
+/// A set of compiled templates matched independently against each image.
+///
+/// Unlike [`crate::Ensemble`], which reports the single best template, a
+/// batch returns one result per template. Every template shares one
+/// [`MatchConfig`]; with `parallel` set, templates are matched concurrently
+/// and each individual search runs serially.
+#[derive(Clone)]
+pub struct TemplateBatch {
+    matchers: Vec<Matcher>,
+    max_levels: usize,
+    parallel: bool,
+}
+
+impl TemplateBatch {
+    /// Compiles every template without rotation support.
+    pub fn compile_unrotated(
+        templates: &[Template],
+        compile: CompileConfigNoRot,
+        cfg: MatchConfig,
+    ) -> CorrMatchResult<Self> {
+        Self::compile_with(templates, cfg, |tpl| {
+            CompiledTemplate::compile_unrotated(tpl, compile.clone())
+        })
+    }
+
+    /// Compiles every template with rotation support.
+    pub fn compile_rotated(
+        templates: &[Template],
+        compile: CompileConfig,
+        cfg: MatchConfig,
+    ) -> CorrMatchResult<Self> {
+        Self::compile_with(templates, cfg, |tpl| {
+            CompiledTemplate::compile_rotated(tpl, compile.clone())
+        })
+    }
+
+    fn compile_with(
+        templates: &[Template],
+        cfg: MatchConfig,
+        compile: impl Fn(&Template) -> CorrMatchResult<CompiledTemplate> + Sync,
+    ) -> CorrMatchResult<Self> {
+        cfg.validate()?;
+        if cfg.allow_partial {
+            return Err(CorrMatchError::InvalidConfig {
+                reason: "template batches do not support allow_partial",
+            });
+        }
+        if templates.is_empty() {
+            return Err(CorrMatchError::InvalidConfig {
+                reason: "template batch must contain at least one template",
+            });
+        }
+
+        #[cfg(feature = "rayon")]
+        let compiled: Vec<CorrMatchResult<CompiledTemplate>> = if cfg.use_parallel() {
+            templates.par_iter().map(&compile).collect()
+        } else {
+            templates.iter().map(&compile).collect()
+        };
+        #[cfg(not(feature = "rayon"))]
+        let compiled: Vec<CorrMatchResult<CompiledTemplate>> =
+            templates.iter().map(&compile).collect();
+
+        let parallel = cfg.parallel;
+        let template_cfg = MatchConfig {
+            parallel: false,
+            ..cfg
+        };
+        let matchers = compiled
+            .into_iter()
+            .map(|compiled| Ok(Matcher::new(compiled?).with_config(template_cfg.clone())))
+            .collect::<CorrMatchResult<Vec<_>>>()?;
+        for matcher in &matchers {
+            matcher.check_rotation()?;
+        }
+        let max_levels = matchers
+            .iter()
+            .map(|matcher| matcher.compiled.num_levels())
+            .max()
+            .unwrap_or(1)
+            .min(template_cfg.max_image_levels);
+
+        Ok(Self {
+            matchers,
+            max_levels,
+            parallel,
+        })
+    }
+
+    /// Returns the number of templates in the batch.
+    pub fn len(&self) -> usize {
+        self.matchers.len()
+    }
+
+    /// Returns `true` if the batch holds no templates (never, once built).
+    pub fn is_empty(&self) -> bool {
+        self.matchers.is_empty()
+    }
+
+    /// Returns the per-template matchers, in template order.
+    pub fn matchers(&self) -> &[Matcher] {
+        &self.matchers
+    }
+
+    /// Matches every template and returns one result per template.
+    ///
+    /// Entry `i` is `None` when template `i` yields no candidates; other
+    /// errors abort the call. Each result equals what `match_image` returns
+    /// for that template alone.
+    pub fn match_image(&self, image: ImageView<'_, u8>) -> CorrMatchResult<Vec<Option<Match>>> {
+        let pyramid = self.matchers[0].build_pyramid(image, self.max_levels)?;
+
+        let run = |matcher: &Matcher| match matcher.match_validated_in_pyramid(&pyramid, image) {
+            Ok(best) => Ok(Some(best)),
+            Err(CorrMatchError::NoCandidates { .. }) => Ok(None),
+            Err(err) => Err(err),
+        };
+        #[cfg(feature = "rayon")]
+        if self.parallel {
+            return self.matchers.par_iter().map(run).collect();
+        }
+        #[cfg(not(feature = "rayon"))]
+        debug_assert!(!self.parallel, "validate rejects parallel without rayon");
+        self.matchers.iter().map(run).collect()
+    }
+}
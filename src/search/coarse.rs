@@ -3,24 +3,161 @@
 //! Coarse search evaluates the full translation range at the coarsest level
 //! for each discrete rotation angle, then merges and prunes candidates.
 
-use crate::bank::CompiledTemplate;
-use crate::candidate::nms::nms_2d;
+use crate::bank::AngleGrid;
+use crate::bank::{CompiledTemplate, RotatedTemplate};
+use crate::candidate::topk::{Peak, PeakOrder};
 use crate::kernel::scalar::{
-    SsdMaskedScalar, SsdUnmaskedScalar, ZnccMaskedScalar, ZnccUnmaskedScalar,
+    CombinedMaskedScalar, CombinedUnmaskedScalar, CombinedWeights, SsdMaskedScalar,
+    SsdUnmaskedScalar, ZnccMaskedScalar, ZnccUnmaskedScalar,
 };
 use crate::kernel::{Kernel, ScanParams};
 use crate::search::refine::Candidate;
-use crate::search::{MatchConfig, Metric};
+use crate::search::{MatchConfig, Metric, RotationMode};
 use crate::util::{CorrMatchError, CorrMatchResult};
 use crate::ImageView;
 #[cfg(feature = "rayon")]
 use rayon::prelude::*;
 
+/// Per-call restriction of the coarse angle scan to a window around a prior.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct AngleWindow {
+    pub(crate) center_deg: f32,
+    pub(crate) half_range_deg: f32,
+}
+
+/// Returns the angle indices the coarse scan should evaluate.
+///
+/// A window narrower than the coarse step may contain no grid angle; the
+/// angle nearest the prior is scanned instead.
+fn coarse_angle_indices(grid: &AngleGrid, window: Option<AngleWindow>) -> Vec<usize> {
+    match window {
+        None => (0..grid.len()).collect(),
+        Some(window) => {
+            let indices = grid.indices_within(window.center_deg, window.half_range_deg);
+            if indices.is_empty() {
+                vec![grid.nearest_index(window.center_deg)]
+            } else {
+                indices
+            }
+        }
+    }
+}
+
+/// Scans one rotated slot, using the unmasked kernel when its mask is full.
+fn scan_rotated_slot(
+    image: ImageView<'_, u8>,
+    compiled: &CompiledTemplate,
+    level: usize,
+    angle_idx: usize,
+    cfg: &MatchConfig,
+    params: ScanParams,
+) -> CorrMatchResult<Vec<Peak>> {
+    let rotated = compiled.rotated(level, angle_idx)?;
+    match cfg.metric {
+        Metric::Zncc => {
+            let plan = rotated.zncc_plan();
+            let params = ScanParams {
+                min_var_i: cfg.min_var_i_for(plan.sum_w()),
+                ..params
+            };
+            match rotated.full_zncc_plan() {
+                Some(full) => {
+                    <ZnccUnmaskedScalar as Kernel>::scan_full(image, full, angle_idx, params)
+                }
+                None => <ZnccMaskedScalar as Kernel>::scan_full(image, plan, angle_idx, params),
+            }
+        }
+        Metric::Ssd => match rotated.full_ssd_plan() {
+            Some(plan) => <SsdUnmaskedScalar as Kernel>::scan_full(image, plan, angle_idx, params),
+            None => {
+                <SsdMaskedScalar as Kernel>::scan_full(image, rotated.ssd_plan(), angle_idx, params)
+            }
+        },
+        Metric::Combined {
+            zncc_weight,
+            ssd_weight,
+        } => {
+            let weights = CombinedWeights::new(zncc_weight, ssd_weight);
+            let plan = rotated.zncc_plan();
+            let params = ScanParams {
+                min_var_i: cfg.min_var_i_for(plan.sum_w()),
+                ..params
+            };
+            match (rotated.full_zncc_plan(), rotated.full_ssd_plan()) {
+                (Some(zncc), Some(ssd)) => {
+                    CombinedUnmaskedScalar::scan_full(image, zncc, ssd, weights, angle_idx, params)
+                }
+                _ => CombinedMaskedScalar::scan_full(
+                    image,
+                    plan,
+                    rotated.ssd_plan(),
+                    weights,
+                    angle_idx,
+                    params,
+                ),
+            }
+        }
+    }
+}
+
+/// Scores one placement of a rotated slot with the combined metric, using the
+/// unmasked kernel when the slot's mask is full.
+pub(crate) fn combined_slot_score(
+    image: ImageView<'_, u8>,
+    rotated: &RotatedTemplate,
+    x: usize,
+    y: usize,
+    weights: CombinedWeights,
+    cfg: &MatchConfig,
+) -> f32 {
+    let plan = rotated.zncc_plan();
+    let min_var_i = cfg.min_var_i_for(plan.sum_w());
+    match (rotated.full_zncc_plan(), rotated.full_ssd_plan()) {
+        (Some(zncc), Some(ssd)) => CombinedUnmaskedScalar::score_at(
+            image,
+            zncc,
+            ssd,
+            weights,
+            x,
+            y,
+            min_var_i,
+            cfg.deterministic_accumulation,
+        ),
+        _ => CombinedMaskedScalar::score_at(
+            image,
+            plan,
+            rotated.ssd_plan(),
+            weights,
+            x,
+            y,
+            min_var_i,
+            cfg.deterministic_accumulation,
+        ),
+    }
+}
+
+/// Returns the peak order for a full-range scan of `level` over `grid`.
+fn full_range_order(
+    image: ImageView<'_, u8>,
+    compiled: &CompiledTemplate,
+    level: usize,
+    cfg: &MatchConfig,
+    grid: Option<&AngleGrid>,
+) -> PeakOrder {
+    let (tpl_width, tpl_height) = compiled.level_size(level).unwrap_or((0, 0));
+    cfg.peak_order(
+        image.width().saturating_sub(tpl_width),
+        image.height().saturating_sub(tpl_height),
+        grid,
+    )
+}
+
 pub(crate) fn coarse_search_level(
     image: ImageView<'_, u8>,
     compiled: &CompiledTemplate,
     level: usize,
     cfg: &MatchConfig,
+    window: Option<AngleWindow>,
 ) -> CorrMatchResult<Vec<Candidate>> {
     let grid = compiled
         .angle_grid(level)
@@ -31,22 +168,17 @@ pub(crate) fn coarse_search_level(
         })?;
 
     let params = ScanParams {
-        topk: cfg.per_angle_topk,
+        topk: cfg.coarse_topk(),
         min_var_i: cfg.min_var_i,
         min_score: cfg.min_score,
+        rank_by_magnitude: cfg.rank_by_magnitude(),
+        tie_break: cfg.tie_break,
+        compensated: cfg.deterministic_accumulation,
+        max_var_ratio: cfg.coarse_variance_ratio,
     };
     let mut all_candidates = Vec::new();
-    for angle_idx in 0..grid.len() {
-        let peaks = match cfg.metric {
-            Metric::Zncc => {
-                let plan = compiled.rotated_zncc_plan(level, angle_idx)?;
-                <ZnccMaskedScalar as Kernel>::scan_full(image, plan, angle_idx, params)?
-            }
-            Metric::Ssd => {
-                let plan = compiled.rotated_ssd_plan(level, angle_idx)?;
-                <SsdMaskedScalar as Kernel>::scan_full(image, plan, angle_idx, params)?
-            }
-        };
+    for angle_idx in coarse_angle_indices(grid, window) {
+        let peaks = scan_rotated_slot(image, compiled, level, angle_idx, cfg, params)?;
         for peak in peaks {
             let angle_deg = grid.angle_at(peak.angle_idx);
             all_candidates.push(Candidate::from_peak(level, angle_deg, peak));
@@ -62,10 +194,8 @@ pub(crate) fn coarse_search_level(
         .copied()
         .map(Candidate::to_peak)
         .collect();
-    let mut kept = nms_2d(&mut peaks, cfg.nms_radius);
-    if kept.len() > cfg.beam_width {
-        kept.truncate(cfg.beam_width);
-    }
+    let order = full_range_order(image, compiled, level, cfg, Some(grid));
+    let mut kept = cfg.select_beam(cfg.nms(&mut peaks, level, order), level);
 
     let mut out = Vec::with_capacity(kept.len());
     for peak in kept.drain(..) {
@@ -76,6 +206,24 @@ pub(crate) fn coarse_search_level(
     Ok(out)
 }
 
+/// Scans the unrotated plans of `level` with the combined metric.
+fn scan_unmasked_combined(
+    image: ImageView<'_, u8>,
+    compiled: &CompiledTemplate,
+    level: usize,
+    weights: CombinedWeights,
+    cfg: &MatchConfig,
+    params: ScanParams,
+) -> CorrMatchResult<Vec<Peak>> {
+    let zncc = compiled.unmasked_zncc_plan(level)?;
+    let ssd = compiled.unmasked_ssd_plan(level)?;
+    let params = ScanParams {
+        min_var_i: cfg.min_var_i_for((zncc.width() * zncc.height()) as f32),
+        ..params
+    };
+    CombinedUnmaskedScalar::scan_full(image, zncc, ssd, weights, 0, params)
+}
+
 /// Coarse search without rotation using an unmasked kernel.
 pub(crate) fn coarse_search_level_unmasked(
     image: ImageView<'_, u8>,
@@ -84,28 +232,41 @@ pub(crate) fn coarse_search_level_unmasked(
     cfg: &MatchConfig,
 ) -> CorrMatchResult<Vec<Candidate>> {
     let params = ScanParams {
-        topk: cfg.per_angle_topk,
+        topk: cfg.coarse_topk(),
         min_var_i: cfg.min_var_i,
         min_score: cfg.min_score,
+        rank_by_magnitude: cfg.rank_by_magnitude(),
+        tie_break: cfg.tie_break,
+        compensated: cfg.deterministic_accumulation,
+        max_var_ratio: cfg.coarse_variance_ratio,
     };
     let mut peaks = match cfg.metric {
         Metric::Zncc => {
             let plan = compiled.unmasked_zncc_plan(level)?;
+            let params = ScanParams {
+                min_var_i: cfg.min_var_i_for((plan.width() * plan.height()) as f32),
+                ..params
+            };
             <ZnccUnmaskedScalar as Kernel>::scan_full(image, plan, 0, params)?
         }
         Metric::Ssd => {
             let plan = compiled.unmasked_ssd_plan(level)?;
             <SsdUnmaskedScalar as Kernel>::scan_full(image, plan, 0, params)?
         }
+        Metric::Combined {
+            zncc_weight,
+            ssd_weight,
+        } => {
+            let weights = CombinedWeights::new(zncc_weight, ssd_weight);
+            scan_unmasked_combined(image, compiled, level, weights, cfg, params)?
+        }
     };
     if peaks.is_empty() {
         return Ok(Vec::new());
     }
 
-    let mut kept = nms_2d(&mut peaks, cfg.nms_radius);
-    if kept.len() > cfg.beam_width {
-        kept.truncate(cfg.beam_width);
-    }
+    let order = full_range_order(image, compiled, level, cfg, None);
+    let mut kept = cfg.select_beam(cfg.nms(&mut peaks, level, order), level);
 
     let mut out = Vec::with_capacity(kept.len());
     for peak in kept.drain(..) {
@@ -116,12 +277,17 @@ pub(crate) fn coarse_search_level_unmasked(
 }
 
 /// Coarse search over angles in parallel (rayon).
+///
+/// Each angle is scanned by the same kernel as the sequential path, for both
+/// metrics, and results are merged in angle order, so the output matches
+/// [`coarse_search_level`] exactly.
 #[cfg(feature = "rayon")]
 pub(crate) fn coarse_search_level_par(
     image: ImageView<'_, u8>,
     compiled: &CompiledTemplate,
     level: usize,
     cfg: &MatchConfig,
+    window: Option<AngleWindow>,
 ) -> CorrMatchResult<Vec<Candidate>> {
     let grid = compiled
         .angle_grid(level)
@@ -132,22 +298,17 @@ pub(crate) fn coarse_search_level_par(
         })?;
 
     let params = ScanParams {
-        topk: cfg.per_angle_topk,
+        topk: cfg.coarse_topk(),
         min_var_i: cfg.min_var_i,
         min_score: cfg.min_score,
+        rank_by_magnitude: cfg.rank_by_magnitude(),
+        tie_break: cfg.tie_break,
+        compensated: cfg.deterministic_accumulation,
+        max_var_ratio: cfg.coarse_variance_ratio,
     };
-    let results: Vec<_> = (0..grid.len())
+    let results: Vec<_> = coarse_angle_indices(grid, window)
         .into_par_iter()
-        .map(|angle_idx| match cfg.metric {
-            Metric::Zncc => {
-                let plan = compiled.rotated_zncc_plan(level, angle_idx)?;
-                <ZnccMaskedScalar as Kernel>::scan_full(image, plan, angle_idx, params)
-            }
-            Metric::Ssd => {
-                let plan = compiled.rotated_ssd_plan(level, angle_idx)?;
-                <SsdMaskedScalar as Kernel>::scan_full(image, plan, angle_idx, params)
-            }
-        })
+        .map(|angle_idx| scan_rotated_slot(image, compiled, level, angle_idx, cfg, params))
         .collect();
 
     let mut peaks = Vec::new();
@@ -158,10 +319,8 @@ pub(crate) fn coarse_search_level_par(
         return Ok(Vec::new());
     }
 
-    let mut kept = nms_2d(&mut peaks, cfg.nms_radius);
-    if kept.len() > cfg.beam_width {
-        kept.truncate(cfg.beam_width);
-    }
+    let order = full_range_order(image, compiled, level, cfg, Some(grid));
+    let mut kept = cfg.select_beam(cfg.nms(&mut peaks, level, order), level);
 
     let mut out = Vec::with_capacity(kept.len());
     for peak in kept.drain(..) {
@@ -181,28 +340,41 @@ pub(crate) fn coarse_search_level_unmasked_par(
     cfg: &MatchConfig,
 ) -> CorrMatchResult<Vec<Candidate>> {
     let params = ScanParams {
-        topk: cfg.per_angle_topk,
+        topk: cfg.coarse_topk(),
         min_var_i: cfg.min_var_i,
         min_score: cfg.min_score,
+        rank_by_magnitude: cfg.rank_by_magnitude(),
+        tie_break: cfg.tie_break,
+        compensated: cfg.deterministic_accumulation,
+        max_var_ratio: cfg.coarse_variance_ratio,
     };
     let mut peaks = match cfg.metric {
         Metric::Zncc => {
             let plan = compiled.unmasked_zncc_plan(level)?;
+            let params = ScanParams {
+                min_var_i: cfg.min_var_i_for((plan.width() * plan.height()) as f32),
+                ..params
+            };
             <ZnccUnmaskedScalar as Kernel>::scan_full(image, plan, 0, params)?
         }
         Metric::Ssd => {
             let plan = compiled.unmasked_ssd_plan(level)?;
             <SsdUnmaskedScalar as Kernel>::scan_full(image, plan, 0, params)?
         }
+        Metric::Combined {
+            zncc_weight,
+            ssd_weight,
+        } => {
+            let weights = CombinedWeights::new(zncc_weight, ssd_weight);
+            scan_unmasked_combined(image, compiled, level, weights, cfg, params)?
+        }
     };
     if peaks.is_empty() {
         return Ok(Vec::new());
     }
 
-    let mut kept = nms_2d(&mut peaks, cfg.nms_radius);
-    if kept.len() > cfg.beam_width {
-        kept.truncate(cfg.beam_width);
-    }
+    let order = full_range_order(image, compiled, level, cfg, None);
+    let mut kept = cfg.select_beam(cfg.nms(&mut peaks, level, order), level);
 
     let mut out = Vec::with_capacity(kept.len());
     for peak in kept.drain(..) {
@@ -211,3 +383,337 @@ pub(crate) fn coarse_search_level_unmasked_par(
 
     Ok(out)
 }
+
+/// Scores a template-sized image at its single placement for every level-0 angle.
+///
+/// Without rotation only the unrotated plan is scored. Candidates are sorted
+/// by descending score and limited to the beam; angles scoring below
+/// `min_score` are dropped.
+pub(crate) fn score_exact_fit(
+    image: ImageView<'_, u8>,
+    compiled: &CompiledTemplate,
+    cfg: &MatchConfig,
+) -> CorrMatchResult<Vec<Candidate>> {
+    let angles = exact_fit_angles(compiled, cfg)?;
+    let mut peaks = Vec::with_capacity(angles.len());
+    for &(angle_idx, _) in &angles {
+        peaks.extend(score_exact_fit_slot(image, compiled, angle_idx, cfg)?);
+    }
+    Ok(exact_fit_beam(peaks, &angles, cfg))
+}
+
+/// Scores a template-sized image at every level-0 angle in parallel (rayon).
+#[cfg(feature = "rayon")]
+pub(crate) fn score_exact_fit_par(
+    image: ImageView<'_, u8>,
+    compiled: &CompiledTemplate,
+    cfg: &MatchConfig,
+) -> CorrMatchResult<Vec<Candidate>> {
+    let angles = exact_fit_angles(compiled, cfg)?;
+    let results: Vec<_> = angles
+        .par_iter()
+        .map(|&(angle_idx, _)| score_exact_fit_slot(image, compiled, angle_idx, cfg))
+        .collect();
+
+    let mut peaks = Vec::with_capacity(angles.len());
+    for result in results {
+        peaks.extend(result?);
+    }
+    Ok(exact_fit_beam(peaks, &angles, cfg))
+}
+
+/// Returns `(angle_idx, angle_deg)` for every level-0 angle to score.
+fn exact_fit_angles(
+    compiled: &CompiledTemplate,
+    cfg: &MatchConfig,
+) -> CorrMatchResult<Vec<(usize, f32)>> {
+    match compiled.search_rotation(cfg.rotation) {
+        RotationMode::Enabled => {
+            let grid = compiled
+                .angle_grid(0)
+                .ok_or(CorrMatchError::IndexOutOfBounds {
+                    index: 0,
+                    len: compiled.num_levels(),
+                    context: "level",
+                })?;
+            Ok((0..grid.len())
+                .map(|idx| (idx, grid.angle_at(idx)))
+                .collect())
+        }
+        RotationMode::Disabled => Ok(vec![(0, 0.0)]),
+    }
+}
+
+/// Scores the only placement of one level-0 slot.
+fn score_exact_fit_slot(
+    image: ImageView<'_, u8>,
+    compiled: &CompiledTemplate,
+    angle_idx: usize,
+    cfg: &MatchConfig,
+) -> CorrMatchResult<Vec<Peak>> {
+    let params = ScanParams {
+        topk: 1,
+        min_var_i: cfg.min_var_i,
+        min_score: cfg.min_score,
+        rank_by_magnitude: cfg.rank_by_magnitude(),
+        tie_break: cfg.tie_break,
+        compensated: cfg.deterministic_accumulation,
+        max_var_ratio: None,
+    };
+    match compiled.search_rotation(cfg.rotation) {
+        RotationMode::Enabled => scan_rotated_slot(image, compiled, 0, angle_idx, cfg, params),
+        RotationMode::Disabled => match cfg.metric {
+            Metric::Zncc => {
+                let plan = compiled.unmasked_zncc_plan(0)?;
+                let params = ScanParams {
+                    min_var_i: cfg.min_var_i_for((plan.width() * plan.height()) as f32),
+                    ..params
+                };
+                <ZnccUnmaskedScalar as Kernel>::scan_full(image, plan, 0, params)
+            }
+            Metric::Ssd => {
+                let plan = compiled.unmasked_ssd_plan(0)?;
+                <SsdUnmaskedScalar as Kernel>::scan_full(image, plan, 0, params)
+            }
+            Metric::Combined {
+                zncc_weight,
+                ssd_weight,
+            } => {
+                let weights = CombinedWeights::new(zncc_weight, ssd_weight);
+                scan_unmasked_combined(image, compiled, 0, weights, cfg, params)
+            }
+        },
+    }
+}
+
+/// Scores one level-0 placement of a slot without thresholding.
+///
+/// Only placements fully inside the image are scored: a partial placement
+/// (`allow_partial`) is out of bounds. Returns `f32::NEG_INFINITY` when the
+/// placement is out of bounds or the image window is too flat for ZNCC.
+pub(crate) fn score_level0_pose(
+    image: ImageView<'_, u8>,
+    compiled: &CompiledTemplate,
+    angle_idx: usize,
+    x: usize,
+    y: usize,
+    cfg: &MatchConfig,
+) -> CorrMatchResult<f32> {
+    let score = match compiled.search_rotation(cfg.rotation) {
+        RotationMode::Enabled => {
+            let rotated = compiled.rotated(0, angle_idx)?;
+            match cfg.metric {
+                Metric::Zncc => {
+                    let plan = rotated.zncc_plan();
+                    let min_var_i = cfg.min_var_i_for(plan.sum_w());
+                    match rotated.full_zncc_plan() {
+                        Some(full) => <ZnccUnmaskedScalar as Kernel>::score_at_with(
+                            image,
+                            full,
+                            x,
+                            y,
+                            min_var_i,
+                            cfg.deterministic_accumulation,
+                        ),
+                        None => <ZnccMaskedScalar as Kernel>::score_at_with(
+                            image,
+                            plan,
+                            x,
+                            y,
+                            min_var_i,
+                            cfg.deterministic_accumulation,
+                        ),
+                    }
+                }
+                Metric::Ssd => match rotated.full_ssd_plan() {
+                    Some(plan) => <SsdUnmaskedScalar as Kernel>::score_at_with(
+                        image,
+                        plan,
+                        x,
+                        y,
+                        cfg.min_var_i,
+                        cfg.deterministic_accumulation,
+                    ),
+                    None => <SsdMaskedScalar as Kernel>::score_at_with(
+                        image,
+                        rotated.ssd_plan(),
+                        x,
+                        y,
+                        cfg.min_var_i,
+                        cfg.deterministic_accumulation,
+                    ),
+                },
+                Metric::Combined {
+                    zncc_weight,
+                    ssd_weight,
+                } => {
+                    let weights = CombinedWeights::new(zncc_weight, ssd_weight);
+                    combined_slot_score(image, &rotated, x, y, weights, cfg)
+                }
+            }
+        }
+        RotationMode::Disabled => match cfg.metric {
+            Metric::Zncc => {
+                let plan = compiled.unmasked_zncc_plan(0)?;
+                let min_var_i = cfg.min_var_i_for((plan.width() * plan.height()) as f32);
+                <ZnccUnmaskedScalar as Kernel>::score_at_with(
+                    image,
+                    plan,
+                    x,
+                    y,
+                    min_var_i,
+                    cfg.deterministic_accumulation,
+                )
+            }
+            Metric::Ssd => {
+                let plan = compiled.unmasked_ssd_plan(0)?;
+                <SsdUnmaskedScalar as Kernel>::score_at_with(
+                    image,
+                    plan,
+                    x,
+                    y,
+                    cfg.min_var_i,
+                    cfg.deterministic_accumulation,
+                )
+            }
+            Metric::Combined {
+                zncc_weight,
+                ssd_weight,
+            } => {
+                let weights = CombinedWeights::new(zncc_weight, ssd_weight);
+                let zncc = compiled.unmasked_zncc_plan(0)?;
+                let ssd = compiled.unmasked_ssd_plan(0)?;
+                let min_var_i = cfg.min_var_i_for((zncc.width() * zncc.height()) as f32);
+                CombinedUnmaskedScalar::score_at(
+                    image,
+                    zncc,
+                    ssd,
+                    weights,
+                    x,
+                    y,
+                    min_var_i,
+                    cfg.deterministic_accumulation,
+                )
+            }
+        },
+    };
+    Ok(score)
+}
+
+/// Orders exact-fit peaks by score (ties prefer the angle nearest 0°, then
+/// the lower angle index) and limits them to the beam.
+fn exact_fit_beam(
+    mut peaks: Vec<Peak>,
+    angles: &[(usize, f32)],
+    cfg: &MatchConfig,
+) -> Vec<Candidate> {
+    let angle_deg = |peak: &Peak| {
+        angles
+            .iter()
+            .find(|&&(idx, _)| idx == peak.angle_idx)
+            .map_or(0.0, |&(_, deg)| deg)
+    };
+    peaks.sort_by(|a, b| {
+        cfg.rank_key(b.score)
+            .total_cmp(&cfg.rank_key(a.score))
+            .then(angle_deg(a).abs().total_cmp(&angle_deg(b).abs()))
+            .then(a.angle_idx.cmp(&b.angle_idx))
+    });
+    peaks.truncate(cfg.beam_width);
+    peaks
+        .into_iter()
+        .map(|peak| Candidate::from_peak(0, angle_deg(&peak), peak))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bank::CompileConfig;
+    use crate::candidate::topk::TieBreak;
+    use crate::Template;
+
+    #[test]
+    fn full_mask_slot_scores_match_masked_kernel() {
+        let (tw, th) = (12usize, 10usize);
+        let tpl_data: Vec<u8> = (0..tw * th)
+            .map(|i| ((i * 37 + (i / tw) * 11) % 251) as u8)
+            .collect();
+        let template = Template::new(tpl_data, tw, th).unwrap();
+        let compiled = CompiledTemplate::compile_rotated(
+            &template,
+            CompileConfig {
+                max_levels: 1,
+                coarse_step_deg: 45.0,
+                min_step_deg: 45.0,
+                ..CompileConfig::default()
+            },
+        )
+        .unwrap();
+
+        let (w, h) = (40usize, 32usize);
+        let image: Vec<u8> = (0..w * h)
+            .map(|i| ((i * 29 + (i / w) * 7) % 241) as u8)
+            .collect();
+        let view = ImageView::from_slice(&image, w, h).unwrap();
+        let params = ScanParams {
+            topk: 5,
+            min_var_i: 1e-8,
+            min_score: f32::NEG_INFINITY,
+            rank_by_magnitude: false,
+            tie_break: TieBreak::TopLeftFirst,
+            compensated: false,
+            max_var_ratio: None,
+        };
+
+        let grid = compiled.angle_grid(0).unwrap();
+        let zero = grid.nearest_index(0.0);
+        assert!(compiled.rotated_mask_is_full(0, zero).unwrap());
+        assert!(!compiled
+            .rotated_mask_is_full(0, grid.nearest_index(45.0))
+            .unwrap());
+
+        let combined = Metric::Combined {
+            zncc_weight: 0.7,
+            ssd_weight: 0.3,
+        };
+        for metric in [Metric::Zncc, Metric::Ssd, combined] {
+            let cfg = MatchConfig {
+                metric,
+                ..MatchConfig::default()
+            };
+            let fast = scan_rotated_slot(view, &compiled, 0, zero, &cfg, params).unwrap();
+            let masked = match metric {
+                Metric::Zncc => <ZnccMaskedScalar as Kernel>::scan_full(
+                    view,
+                    compiled.rotated_zncc_plan(0, zero).unwrap(),
+                    zero,
+                    params,
+                ),
+                Metric::Ssd => <SsdMaskedScalar as Kernel>::scan_full(
+                    view,
+                    compiled.rotated_ssd_plan(0, zero).unwrap(),
+                    zero,
+                    params,
+                ),
+                Metric::Combined {
+                    zncc_weight,
+                    ssd_weight,
+                } => CombinedMaskedScalar::scan_full(
+                    view,
+                    compiled.rotated_zncc_plan(0, zero).unwrap(),
+                    compiled.rotated_ssd_plan(0, zero).unwrap(),
+                    CombinedWeights::new(zncc_weight, ssd_weight),
+                    zero,
+                    params,
+                ),
+            }
+            .unwrap();
+            assert_eq!(fast.len(), masked.len());
+            for (a, b) in fast.iter().zip(masked.iter()) {
+                assert_eq!((a.x, a.y, a.angle_idx), (b.x, b.y, b.angle_idx));
+                assert!((a.score - b.score).abs() <= 1e-4 * b.score.abs().max(1.0));
+            }
+        }
+    }
+}
@@ -0,0 +1,130 @@
+//! Matching one image against a set of templates.
+//!
+//! An ensemble holds several matchers (for example, the same object captured
+//! under different lighting) and reports the best match across all of them.
+//! The image pyramid is built once per call and shared by every matcher.
+
+use crate::search::{Match, Matcher};
+use crate::util::{CorrMatchError, CorrMatchResult};
+use crate::ImageView;
+#[cfg(feature = "rayon")]
+use rayon::prelude::*;
+use std::sync::Arc;
+
+/// Best match across an ensemble, with the index of the winning template.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct EnsembleMatch {
+    /// Index of the winning matcher in the ensemble.
+    pub template_index: usize,
+    /// Refined match for the winning template.
+    pub best: Match,
+}
+
+/// A set of matchers evaluated against the same image.
+///
+/// Scores are compared directly, so all matchers should use the same metric.
+/// SSD scores are only comparable between templates of the same size. The
+/// shared image pyramid requires every matcher to use the same
+/// `pyramid_filter` and the same custom downsampler, if any (the same `Arc`).
+#[derive(Clone)]
+pub struct Ensemble {
+    matchers: Vec<Matcher>,
+    parallel: bool,
+}
+
+impl Ensemble {
+    /// Creates an ensemble from one or more matchers.
+    pub fn new(matchers: Vec<Matcher>) -> CorrMatchResult<Self> {
+        if matchers.is_empty() {
+            return Err(CorrMatchError::InvalidConfig {
+                reason: "ensemble must contain at least one matcher",
+            });
+        }
+        let filter = matchers[0].cfg.pyramid_filter;
+        if matchers.iter().any(|m| m.cfg.pyramid_filter != filter) {
+            return Err(CorrMatchError::InvalidConfig {
+                reason: "ensemble matchers must share the same pyramid_filter",
+            });
+        }
+        let downsampler = matchers[0].compiled.downsampler();
+        let same_downsampler = |m: &Matcher| match (m.compiled.downsampler(), downsampler) {
+            (Some(a), Some(b)) => Arc::ptr_eq(a, b),
+            (a, b) => a.is_none() && b.is_none(),
+        };
+        if !matchers.iter().all(same_downsampler) {
+            return Err(CorrMatchError::InvalidConfig {
+                reason: "ensemble matchers must share the same downsampler",
+            });
+        }
+        Ok(Self {
+            matchers,
+            parallel: false,
+        })
+    }
+
+    /// Evaluates templates in parallel when the `rayon` feature is available.
+    pub fn with_parallel(mut self, parallel: bool) -> Self {
+        self.parallel = parallel;
+        self
+    }
+
+    /// Returns the matchers in this ensemble.
+    pub fn matchers(&self) -> &[Matcher] {
+        &self.matchers
+    }
+
+    /// Matches every template and returns the highest-scoring result.
+    ///
+    /// Templates that yield no candidates are skipped; other errors abort the
+    /// call. Ties keep the lowest template index, so results are identical
+    /// with and without parallel evaluation.
+    pub fn match_image(&self, image: ImageView<'_, u8>) -> CorrMatchResult<EnsembleMatch> {
+        #[cfg(not(feature = "rayon"))]
+        if self.parallel {
+            return Err(CorrMatchError::ParallelUnavailable);
+        }
+
+        let max_levels = self
+            .matchers
+            .iter()
+            .map(|matcher| matcher.cfg.max_image_levels)
+            .max()
+            .unwrap_or(1);
+        let pyramid = self.matchers[0].build_pyramid(image, max_levels)?;
+
+        let run = |matcher: &Matcher| matcher.match_image_in_pyramid(&pyramid, image);
+        #[cfg(feature = "rayon")]
+        let results: Vec<CorrMatchResult<Match>> = if self.parallel {
+            self.matchers.par_iter().map(run).collect()
+        } else {
+            self.matchers.iter().map(run).collect()
+        };
+        #[cfg(not(feature = "rayon"))]
+        let results: Vec<CorrMatchResult<Match>> = self.matchers.iter().map(run).collect();
+
+        let mut winner: Option<(f32, EnsembleMatch)> = None;
+        for (template_index, result) in results.into_iter().enumerate() {
+            let best = match result {
+                Ok(best) => best,
+                Err(CorrMatchError::NoCandidates { .. }) => continue,
+                Err(err) => return Err(err),
+            };
+            let key = self.matchers[template_index].cfg.rank_key(best.score);
+            if winner.as_ref().is_none_or(|(current, _)| key > *current) {
+                winner = Some((
+                    key,
+                    EnsembleMatch {
+                        template_index,
+                        best,
+                    },
+                ));
+            }
+        }
+
+        winner
+            .map(|(_, best)| best)
+            .ok_or(CorrMatchError::NoCandidates {
+                reason: "no template in the ensemble produced a candidate",
+            })
+    }
+}
@@ -0,0 +1,471 @@
+//! Matching that excludes invalid image pixels.
+//!
+//! Dead pixels, reflections, or occluders can be left out of scoring by
+//! marking them in an image-space mask. Every placement is scored with
+//! [`ZnccImageMaskedScalar`] over the pixels valid in both the template and
+//! the image, following the same coarse-to-fine schedule as the grayscale
+//! matcher. The mask is carried down the pyramid with
+//! [`ImagePyramid::mask_levels`].
+
+use crate::bank::{AngleGrid, PlanRef};
+use crate::candidate::topk::{Peak, PeakOrder};
+use crate::image::pyramid::level_factor;
+use crate::kernel::scalar::ZnccImageMaskedScalar;
+use crate::kernel::ScanParams;
+use crate::refine::quad1d::quad_peak_offset_1d;
+use crate::refine::quad2d::{fit_window_center, refine_subpixel_2d_windowed};
+use crate::search::refine::{
+    fit_polarity, inherit_level_scores, roi_bounds, upscale_pos, Candidate,
+};
+use crate::search::{level_scale, AngleRefine, Match, Matcher, Metric, RotationMode};
+use crate::template::{MaskedTemplatePlan, TemplatePlan, ZnccTerms};
+use crate::util::math::wrap_deg;
+use crate::util::{CorrMatchError, CorrMatchResult};
+use crate::ImageView;
+
+#[cfg(feature = "rayon")]
+use rayon::prelude::*;
+
+/// ZNCC plan of one slot, rotated or not.
+enum SlotPlan<'a> {
+    Rotated(PlanRef<'a, MaskedTemplatePlan>),
+    Unrotated(&'a TemplatePlan),
+}
+
+impl SlotPlan<'_> {
+    fn terms(&self) -> ZnccTerms<'_> {
+        match self {
+            Self::Rotated(plan) => (&**plan).into(),
+            Self::Unrotated(plan) => (*plan).into(),
+        }
+    }
+}
+
+/// Image and validity mask at one pyramid level.
+#[derive(Clone, Copy)]
+struct MaskedLevel<'a> {
+    image: ImageView<'a, u8>,
+    mask: ImageView<'a, u8>,
+}
+
+impl Matcher {
+    /// Matches the template while ignoring invalid image pixels.
+    ///
+    /// `image_mask` holds one byte per image pixel in row-major order
+    /// (`width * height` bytes, no row padding); nonzero marks a valid
+    /// pixel. It combines with the template's rotation mask, and placements
+    /// where less than half of the template lands on valid pixels are not
+    /// scored. Requires the ZNCC metric; `subpixel_resample`,
+    /// `retry_on_empty`, `verify_full_res`, `compute_psr`,
+    /// `coarse_variance_ratio` and golden-section angle refinement are not
+    /// supported. With `allow_partial`, placements may also extend past the
+    /// image border.
+    pub fn match_image_masked(
+        &self,
+        image: ImageView<'_, u8>,
+        image_mask: &[u8],
+    ) -> CorrMatchResult<Match> {
+        self.validate_image_masked()?;
+        let needed = image.width() * image.height();
+        if image_mask.len() < needed {
+            return Err(CorrMatchError::BufferTooSmall {
+                needed,
+                got: image_mask.len(),
+            });
+        }
+        if image_mask.len() > needed {
+            return Err(CorrMatchError::InvalidDimensions {
+                width: image.width(),
+                height: image.height(),
+            });
+        }
+        let mask = ImageView::from_slice(image_mask, image.width(), image.height())?;
+        if self.cfg.allow_partial {
+            return self.match_partial(image, Some(mask));
+        }
+        self.match_masked_views(image, mask)
+    }
+
+    /// Matches with placements that may extend past the image border (see
+    /// [`MatchConfig::allow_partial`](crate::MatchConfig::allow_partial)).
+    ///
+    /// The image and its validity mask are copied into buffers padded on
+    /// every side with invalid pixels, so the masked search scores only the
+    /// overlapping pixels, and the result is shifted back into image
+    /// coordinates. Each pad covers
+    /// half the template, the most the masked kernel's overlap rule allows
+    /// off-image, rounded up to a multiple of the coarsest decimation factor
+    /// so coarse pixels do not straddle the image border.
+    pub(crate) fn match_partial(
+        &self,
+        image: ImageView<'_, u8>,
+        mask: Option<ImageView<'_, u8>>,
+    ) -> CorrMatchResult<Match> {
+        self.validate_image_masked()?;
+        let (tpl_width, tpl_height) = self.masked_level_size(0)?;
+        let levels = self
+            .cfg
+            .max_image_levels
+            .min(self.compiled.num_levels())
+            .max(1);
+        let align = level_factor(levels - 1);
+        let pad = |len: usize| len.div_ceil(2).next_multiple_of(align);
+        let (pad_x, pad_y) = (pad(tpl_width), pad(tpl_height));
+        let width = image.width() + 2 * pad_x;
+        let height = image.height() + 2 * pad_y;
+
+        let mut data = vec![0u8; width * height];
+        let mut valid = vec![0u8; width * height];
+        for y in 0..image.height() {
+            let row = image.row(y).expect("row within bounds for image");
+            let start = (y + pad_y) * width + pad_x;
+            data[start..start + row.len()].copy_from_slice(row);
+            let valid_row = &mut valid[start..start + row.len()];
+            match mask {
+                Some(mask) => {
+                    let mask_row = mask.row(y).expect("row within bounds for mask");
+                    for (dst, &m) in valid_row.iter_mut().zip(mask_row) {
+                        *dst = u8::from(m != 0);
+                    }
+                }
+                None => valid_row.fill(1),
+            }
+        }
+        let padded = ImageView::from_slice(&data, width, height)?;
+        let valid = ImageView::from_slice(&valid, width, height)?;
+        let found = self.match_masked_views(padded, valid)?;
+        Ok(Match {
+            x: found.x - pad_x as f32,
+            y: found.y - pad_y as f32,
+            ..found
+        })
+    }
+
+    /// Runs the masked coarse-to-fine search on an image and its validity mask.
+    fn match_masked_views(
+        &self,
+        image: ImageView<'_, u8>,
+        mask: ImageView<'_, u8>,
+    ) -> CorrMatchResult<Match> {
+        self.check_image_size(image)?;
+        let pyramid = self.build_pyramid(image, self.cfg.max_image_levels)?;
+        let masks = pyramid.mask_levels(mask, self.cfg.pyramid_filter)?;
+        let level_at = |level: usize| MaskedLevel {
+            image: pyramid.levels()[level].view(),
+            mask: masks[level].view(),
+        };
+
+        let num_levels = self
+            .cfg
+            .searched_levels(pyramid.levels().len(), self.compiled.num_levels())?;
+        let finest = self.cfg.finest_level();
+        if finest >= num_levels {
+            return Err(CorrMatchError::InvalidConfig {
+                reason: "initial_downscale exceeds the searchable pyramid levels",
+            });
+        }
+
+        let coarsest = num_levels - 1;
+        let mut beam = self.coarse_masked(level_at(coarsest), coarsest)?;
+        if beam.is_empty() {
+            return Err(CorrMatchError::NoCandidates {
+                reason: "no coarse candidates",
+            });
+        }
+        let mut from_level = coarsest;
+        for level in self.cfg.refinement_levels(coarsest, finest) {
+            if beam.is_empty() {
+                break;
+            }
+            let mut refined = self.refine_masked(level_at(level), level, from_level, &beam)?;
+            inherit_level_scores(&self.cfg, &beam, &mut refined);
+            beam = refined;
+            from_level = level;
+        }
+
+        for cand in beam {
+            let refined = if self.cfg.final_refinement {
+                self.refine_final_masked(level_at(finest), cand)?
+            } else {
+                self.masked_match(cand, cand.x as f32, cand.y as f32, cand.angle_deg)
+            };
+            if let Some(found) = self.finish_match(self.aggregate_levels(cand, refined)) {
+                return Ok(found);
+            }
+        }
+        Err(CorrMatchError::NoCandidates {
+            reason: "all candidates refined below min_score",
+        })
+    }
+
+    fn validate_image_masked(&self) -> CorrMatchResult<()> {
+        self.cfg.validate()?;
+        self.check_rotation()?;
+        if self.cfg.metric != Metric::Zncc {
+            return Err(CorrMatchError::InvalidConfig {
+                reason: "image masks require the ZNCC metric",
+            });
+        }
+        if self.cfg.subpixel_resample {
+            return Err(CorrMatchError::InvalidConfig {
+                reason: "image masks do not support subpixel_resample",
+            });
+        }
+        if self.compiled.downsampler().is_some() {
+            return Err(CorrMatchError::InvalidConfig {
+                reason: "image masks do not support custom downsamplers",
+            });
+        }
+        if self.cfg.retry_on_empty
+            || self.cfg.verify_full_res
+            || self.cfg.compute_psr
+            || self.cfg.coarse_variance_ratio.is_some()
+            || matches!(self.cfg.angle_refine, AngleRefine::GoldenSection { .. })
+        {
+            return Err(CorrMatchError::InvalidConfig {
+                reason: "image masks do not support retry_on_empty, verify_full_res, compute_psr, coarse_variance_ratio or golden-section angle refinement",
+            });
+        }
+        Ok(())
+    }
+
+    /// Returns the angle grid searched at `level`, or `None` without rotation.
+    fn masked_grid(&self, level: usize) -> CorrMatchResult<Option<&AngleGrid>> {
+        if self.compiled.search_rotation(self.cfg.rotation) == RotationMode::Disabled {
+            return Ok(None);
+        }
+        self.compiled
+            .angle_grid(level)
+            .map(Some)
+            .ok_or(CorrMatchError::IndexOutOfBounds {
+                index: level,
+                len: self.compiled.num_levels(),
+                context: "level",
+            })
+    }
+
+    fn masked_level_size(&self, level: usize) -> CorrMatchResult<(usize, usize)> {
+        self.compiled
+            .level_size(level)
+            .ok_or(CorrMatchError::IndexOutOfBounds {
+                index: level,
+                len: self.compiled.num_levels(),
+                context: "level",
+            })
+    }
+
+    fn masked_plan(&self, level: usize, angle_idx: usize) -> CorrMatchResult<SlotPlan<'_>> {
+        Ok(match self.compiled.search_rotation(self.cfg.rotation) {
+            RotationMode::Enabled => {
+                SlotPlan::Rotated(self.compiled.cached_zncc_plan(level, angle_idx)?)
+            }
+            RotationMode::Disabled => SlotPlan::Unrotated(self.compiled.unmasked_zncc_plan(level)?),
+        })
+    }
+
+    fn masked_scan_params(&self, terms: &ZnccTerms<'_>, topk: usize) -> ScanParams {
+        ScanParams {
+            topk,
+            min_var_i: self.cfg.min_var_i_for(terms.sum_w),
+            min_score: self.cfg.min_score,
+            rank_by_magnitude: self.cfg.rank_by_magnitude(),
+            tie_break: self.cfg.tie_break,
+            compensated: self.cfg.deterministic_accumulation,
+            max_var_ratio: None,
+        }
+    }
+
+    /// Scans every angle over the full placement range at the coarsest level.
+    fn coarse_masked(&self, at: MaskedLevel<'_>, level: usize) -> CorrMatchResult<Vec<Candidate>> {
+        let grid = self.masked_grid(level)?;
+        let angles: Vec<usize> = (0..grid.map_or(1, AngleGrid::len)).collect();
+        let scan = |&angle_idx: &usize| {
+            let plan = self.masked_plan(level, angle_idx)?;
+            let terms = plan.terms();
+            let params = self.masked_scan_params(&terms, self.cfg.coarse_topk());
+            ZnccImageMaskedScalar::scan_roi(
+                at.image,
+                at.mask,
+                &terms,
+                angle_idx,
+                0,
+                0,
+                usize::MAX,
+                usize::MAX,
+                params,
+            )
+        };
+        let peaks = self.collect_masked_peaks(&angles, scan)?;
+        let (tpl_width, tpl_height) = self.masked_level_size(level)?;
+        let order = self.cfg.peak_order(
+            at.image.width().saturating_sub(tpl_width),
+            at.image.height().saturating_sub(tpl_height),
+            grid,
+        );
+        Ok(self.masked_beam(peaks, level, grid, order))
+    }
+
+    /// Rescans an ROI and angle window around each candidate upscaled from
+    /// `from_level`.
+    fn refine_masked(
+        &self,
+        at: MaskedLevel<'_>,
+        level: usize,
+        from_level: usize,
+        prev: &[Candidate],
+    ) -> CorrMatchResult<Vec<Candidate>> {
+        let grid = self.masked_grid(level)?;
+        let (tpl_width, tpl_height) = self.masked_level_size(level)?;
+        let (Some(max_x), Some(max_y)) = (
+            at.image.width().checked_sub(tpl_width),
+            at.image.height().checked_sub(tpl_height),
+        ) else {
+            return Ok(Vec::new());
+        };
+        let radius = self.cfg.roi_radius_from(level, from_level);
+
+        let scan = |cand: &Candidate| {
+            let (x, y) = upscale_pos(cand.x, cand.y, from_level - level);
+            let Some((x0, y0, x1, y1)) = roi_bounds(x, y, radius, max_x, max_y) else {
+                return Ok(Vec::new());
+            };
+            let angle_indices = match grid {
+                Some(grid) => grid.indices_within(
+                    cand.angle_deg,
+                    self.cfg
+                        .angle_half_range_from(grid.step_deg(), level, from_level),
+                ),
+                None => vec![0],
+            };
+            let mut peaks = Vec::new();
+            for angle_idx in angle_indices {
+                let plan = self.masked_plan(level, angle_idx)?;
+                let terms = plan.terms();
+                let params = self.masked_scan_params(&terms, self.cfg.refine_topk());
+                peaks.extend(ZnccImageMaskedScalar::scan_roi(
+                    at.image, at.mask, &terms, angle_idx, x0, y0, x1, y1, params,
+                )?);
+            }
+            Ok(peaks)
+        };
+        let peaks = self.collect_masked_peaks(prev, scan)?;
+        let order = self.cfg.peak_order(max_x, max_y, grid);
+        Ok(self.masked_beam(peaks, level, grid, order))
+    }
+
+    /// Runs `scan` over `items`, in parallel when enabled, and concatenates
+    /// the peaks.
+    fn collect_masked_peaks<T: Sync>(
+        &self,
+        items: &[T],
+        scan: impl Fn(&T) -> CorrMatchResult<Vec<Peak>> + Send + Sync,
+    ) -> CorrMatchResult<Vec<Peak>> {
+        #[cfg(feature = "rayon")]
+        let results: Vec<CorrMatchResult<Vec<Peak>>> = if self.cfg.use_parallel() {
+            items.par_iter().map(scan).collect()
+        } else {
+            items.iter().map(scan).collect()
+        };
+        #[cfg(not(feature = "rayon"))]
+        let results: Vec<CorrMatchResult<Vec<Peak>>> = items.iter().map(scan).collect();
+
+        let mut peaks = Vec::new();
+        for result in results {
+            peaks.extend(result?);
+        }
+        Ok(peaks)
+    }
+
+    fn masked_beam(
+        &self,
+        mut peaks: Vec<Peak>,
+        level: usize,
+        grid: Option<&AngleGrid>,
+        order: PeakOrder,
+    ) -> Vec<Candidate> {
+        self.cfg
+            .select_beam(self.cfg.nms(&mut peaks, level, order), level)
+            .into_iter()
+            .map(|peak| {
+                let angle_deg = grid.map_or(0.0, |grid| grid.angle_at(peak.angle_idx));
+                Candidate::from_peak(level, angle_deg, peak)
+            })
+            .collect()
+    }
+
+    /// Fits subpixel position and subangle rotation around the best candidate.
+    fn refine_final_masked(&self, at: MaskedLevel<'_>, cand: Candidate) -> CorrMatchResult<Match> {
+        let score_at = |angle_idx: usize, x: usize, y: usize| {
+            let plan = self.masked_plan(cand.level, angle_idx)?;
+            let terms = plan.terms();
+            let min_var_i = self.cfg.min_var_i_for(terms.sum_w);
+            Ok(ZnccImageMaskedScalar::score_at(
+                at.image, at.mask, &terms, x, y, min_var_i,
+            ))
+        };
+
+        let (tpl_width, tpl_height) = self.masked_level_size(cand.level)?;
+        let max_x = at.image.width().saturating_sub(tpl_width);
+        let max_y = at.image.height().saturating_sub(tpl_height);
+        // On a border the 3x3 window slides inward so every sample is in bounds.
+        let (wx, wy) = (
+            fit_window_center(cand.x, max_x),
+            fit_window_center(cand.y, max_y),
+        );
+        let (cx, cy) = (cand.x + 1 - wx, cand.y + 1 - wy);
+        let mut s = [[f32::NEG_INFINITY; 3]; 3];
+        for (iy, row) in s.iter_mut().enumerate() {
+            let Some(y) = (wy + iy).checked_sub(1) else {
+                continue;
+            };
+            for (ix, score) in row.iter_mut().enumerate() {
+                let Some(x) = (wx + ix).checked_sub(1) else {
+                    continue;
+                };
+                *score = score_at(cand.angle_idx, x, y)?;
+            }
+        }
+        let center_score = if s[cy][cx].is_finite() {
+            s[cy][cx]
+        } else {
+            cand.score
+        };
+        let polarity = fit_polarity(&self.cfg, center_score);
+        let (x, y) = refine_subpixel_2d_windowed(
+            (cand.x, cand.y),
+            (wx, wy),
+            (max_x, max_y),
+            s.map(|row| row.map(|v| polarity * v)),
+        );
+
+        let angle_deg = match self.masked_grid(cand.level)? {
+            Some(grid) if grid.len() > 1 => {
+                let len = grid.len();
+                let sm = score_at((cand.angle_idx + len - 1) % len, cand.x, cand.y)?;
+                let sp = score_at((cand.angle_idx + 1) % len, cand.x, cand.y)?;
+                let offset =
+                    quad_peak_offset_1d(polarity * sm, polarity * center_score, polarity * sp)
+                        .unwrap_or(0.0);
+                wrap_deg(cand.angle_deg + offset * grid.step_deg())
+            }
+            _ => cand.angle_deg,
+        };
+
+        Ok(Match {
+            score: center_score,
+            ..self.masked_match(cand, x, y, angle_deg)
+        })
+    }
+
+    /// Scales a pose at the candidate's level to level-0 coordinates.
+    fn masked_match(&self, cand: Candidate, x: f32, y: f32, angle_deg: f32) -> Match {
+        let scale = level_scale(cand.level);
+        Match {
+            x: x * scale,
+            y: y * scale,
+            angle_deg,
+            score: cand.score,
+            peak_sharpness: None,
+        }
+    }
+}
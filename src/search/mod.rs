@@ -2,40 +2,142 @@
 //!
 //! The scan module provides baseline scalar ZNCC evaluation helpers.
 
+mod batch;
 mod coarse;
+mod ensemble;
+mod image_mask;
 mod refine;
+mod rgb;
 pub(crate) mod scan;
 
-use crate::bank::CompiledTemplate;
-use crate::image::pyramid::ImagePyramid;
-use crate::search::coarse::{coarse_search_level, coarse_search_level_unmasked};
+use crate::bank::{AngleGrid, CompiledTemplate};
+use crate::candidate::nms::nms_2d_ranked;
+use crate::candidate::topk::{rank_key, Peak, PeakOrder, TieBreak};
+use crate::image::pyramid::{level_factor, ImagePyramid, PyramidFilter};
+use crate::search::coarse::{
+    coarse_search_level, coarse_search_level_unmasked, score_exact_fit, score_level0_pose,
+    AngleWindow,
+};
 #[cfg(feature = "rayon")]
-use crate::search::coarse::{coarse_search_level_par, coarse_search_level_unmasked_par};
+use crate::search::coarse::{
+    coarse_search_level_par, coarse_search_level_unmasked_par, score_exact_fit_par,
+};
 use crate::search::refine::{
-    refine_final_match, refine_final_match_unmasked, refine_to_finer_level,
+    inherit_level_scores, refine_final_match, refine_final_match_unmasked, refine_to_finer_level,
     refine_to_finer_level_unmasked, Candidate,
 };
 #[cfg(feature = "rayon")]
 use crate::search::refine::{refine_to_finer_level_par, refine_to_finer_level_unmasked_par};
+use crate::template::rotate::rotate_u8_bilinear;
+use crate::util::math::{sin_cos_deg, wrap_deg};
 use crate::util::{CorrMatchError, CorrMatchResult};
-use crate::ImageView;
+use crate::{ImageView, OwnedImage};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+pub use crate::template::rotate::RotationMode;
+pub use batch::TemplateBatch;
+pub use ensemble::{Ensemble, EnsembleMatch};
+pub use rgb::RgbMatcher;
 
 /// Matching metric selector.
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[derive(Clone, Copy, Debug)]
 pub enum Metric {
     /// Zero-mean normalized cross-correlation (higher is better, roughly [-1, 1]).
     Zncc,
     /// Sum of squared differences (reported as negative SSE, higher is better).
     Ssd,
+    /// Weighted sum of ZNCC and normalized SSD (higher is better).
+    ///
+    /// Each placement scores
+    /// `zncc_weight * zncc + ssd_weight * (1 - sse / (n * 255²))` over its `n`
+    /// valid template pixels, so scores lie in
+    /// `[-zncc_weight, zncc_weight + ssd_weight]` and stay comparable across
+    /// template sizes. Both terms are accumulated in one pass over the image
+    /// window. Placements too flat for ZNCC are rejected as with
+    /// [`Metric::Zncc`]. Weights must be finite, non-negative, and not both
+    /// zero.
+    Combined { zncc_weight: f32, ssd_weight: f32 },
+}
+
+// Weights compare by bit pattern, so equality stays reflexive and `Eq` holds.
+impl PartialEq for Metric {
+    fn eq(&self, other: &Self) -> bool {
+        match (*self, *other) {
+            (Self::Zncc, Self::Zncc) | (Self::Ssd, Self::Ssd) => true,
+            (
+                Self::Combined {
+                    zncc_weight: za,
+                    ssd_weight: sa,
+                },
+                Self::Combined {
+                    zncc_weight: zb,
+                    ssd_weight: sb,
+                },
+            ) => za.to_bits() == zb.to_bits() && sa.to_bits() == sb.to_bits(),
+            _ => false,
+        }
+    }
 }
 
-/// Controls whether rotation is searched.
+impl Eq for Metric {}
+
+/// Post-processing applied to the final `Match::score`.
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
-pub enum RotationMode {
-    /// Skip rotation and use the unmasked fast path.
-    Disabled,
-    /// Enable rotation search using masked kernels.
-    Enabled,
+pub enum ScoreClamp {
+    /// Report the raw metric score.
+    None,
+    /// Clamp ZNCC scores to `[0, 1]`, mapping anti-correlation to zero.
+    ///
+    /// SSD and combined scores are left unchanged.
+    ZeroToOne,
+}
+
+impl ScoreClamp {
+    fn apply(self, metric: Metric, score: f32) -> f32 {
+        match (self, metric) {
+            (Self::ZeroToOne, Metric::Zncc) => score.clamp(0.0, 1.0),
+            _ => score,
+        }
+    }
+}
+
+/// How the reported score combines a match's scores across pyramid levels.
+///
+/// Each refined candidate remembers the score it had at every coarser
+/// searched level; the final (finest) score is combined with that history.
+/// A match that only looks good at one scale, for example because of
+/// aliasing at coarse levels, then scores lower under `MinAcrossLevels` or
+/// `MeanAcrossLevels`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ScoreAgg {
+    /// Report the score at the finest level only.
+    #[default]
+    Finest,
+    /// Report the weakest score across all searched levels.
+    MinAcrossLevels,
+    /// Report the mean score across all searched levels.
+    MeanAcrossLevels,
+}
+
+/// Sub-step angle estimation used by the final refinement.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum AngleRefine {
+    /// Fit a parabola through the scores at the best grid angle and its two
+    /// neighbors.
+    #[default]
+    Quadratic,
+    /// Golden-section search within one grid step of the best grid angle,
+    /// scoring templates rotated on the fly.
+    ///
+    /// Each iteration rotates and scores the template once, so this is
+    /// slower than the quadratic fit but is not limited by how well a
+    /// parabola describes the score profile. Ends no worse than the grid
+    /// angle.
+    GoldenSection {
+        /// Number of bracket reductions; each shrinks the bracket by 0.618.
+        iters: usize,
+    },
 }
 
 /// Configuration for the coarse-to-fine matcher pipeline.
@@ -48,45 +150,301 @@ pub struct MatchConfig {
     /// Enables parallel search when the `rayon` feature is available.
     ///
     /// When the feature is disabled, this flag is ignored and execution stays sequential.
+    /// With it, the image pyramid is also built with
+    /// `ImagePyramid::build_u8_filtered_par`.
     pub parallel: bool,
     /// Maximum pyramid levels to build for the image.
+    ///
+    /// The search starts at the coarsest level both pyramids share: image
+    /// level `l` is always scored with template level `l`, because a plan
+    /// from another level would be off by a power-of-two scale. To start
+    /// coarser on large images, compile the template with more levels
+    /// (a higher `max_levels` or a lower `min_template_dim`).
     pub max_image_levels: usize,
+    /// Fails with [`CorrMatchError::InsufficientPyramidLevels`] instead of
+    /// starting finer when the image pyramid has fewer levels than the
+    /// compiled template.
+    ///
+    /// The image side is capped by `max_image_levels` (or cut short by a
+    /// custom downsampler); starting the search below the template's
+    /// coarsest level weakens coarse rejection, which this flag surfaces.
+    pub require_full_pyramid: bool,
+    /// Downsampling filter for the image pyramid.
+    ///
+    /// Template levels always use the box filter; the slightly different
+    /// smoothing of [`PyramidFilter::OpenCvCompatible`] only lowers coarse
+    /// ZNCC scores a little.
+    pub pyramid_filter: PyramidFilter,
+    /// Factor by which the image is downsampled before the search finishes.
+    ///
+    /// Must be a power of two: the downscaled image is matched against the
+    /// template pyramid, whose levels only exist at power-of-two scales, so
+    /// any other factor would need a separately resampled template. The
+    /// search stops at pyramid level `log2(initial_downscale)` instead of
+    /// level 0, and the final subpixel/subangle refinement runs there;
+    /// reported coordinates are scaled back to full resolution. Skipping the finest levels saves time
+    /// on very large images at the cost of precision, which degrades roughly
+    /// in proportion to the factor. The skipped levels still count toward
+    /// `max_image_levels`. The default of 1 refines at full resolution.
+    pub initial_downscale: usize,
     /// Beam width kept per level after merge and NMS.
     pub beam_width: usize,
-    /// Top-M peaks per angle at the coarsest level.
+    /// Minimum number of spatially distinct instances kept per level,
+    /// overriding `beam_width` when the beam holds fewer.
     ///
-    /// Ignored when rotation is disabled.
+    /// Set this to the expected object count when counting instances with
+    /// `match_image_topk`: peaks past `beam_width` that lie outside the
+    /// refinement ROI radius of every kept peak are retained until this many
+    /// separate positions are in the beam, and the coarse scan keeps at least
+    /// this many peaks per angle. The default of 0 leaves the beam capped at
+    /// `beam_width`.
+    pub min_instances: usize,
+    /// Deprecated alias that sets both `coarse_per_angle_topk` and
+    /// `refine_per_angle_topk`.
+    ///
+    /// A nonzero value overrides both; 0 (the default) leaves them in effect.
+    #[deprecated(note = "set `coarse_per_angle_topk` and `refine_per_angle_topk` instead")]
     pub per_angle_topk: usize,
+    /// Top-M peaks per angle kept by the full scan of the coarsest level.
+    ///
+    /// Raising this keeps more weak instances alive into the beam; without
+    /// rotation there is a single "angle".
+    pub coarse_per_angle_topk: usize,
+    /// Top-M peaks per angle kept from each refinement ROI at finer levels.
+    ///
+    /// Each ROI surrounds one upscaled candidate, so 1 is often enough.
+    pub refine_per_angle_topk: usize,
     /// Spatial NMS radius in pixels for the current level.
     pub nms_radius: usize,
+    /// Keeps a peak inside `nms_radius` when its score is within this ratio
+    /// of the suppressing peak and it is a local maximum.
+    ///
+    /// Lets two overlapping instances of similar strength both survive NMS.
+    /// Must lie in `[0, 1]`; 0 keeps plain distance-based suppression. See
+    /// [`crate::lowlevel::nms_2d_keep_ratio`].
+    pub nms_keep_ratio: f32,
+    /// Minimum separation in level-0 pixels between the matches returned by
+    /// `match_image_topk`.
+    ///
+    /// Applied only to the final refined list, so the search can keep a
+    /// coarse `nms_radius` while instances are separated precisely. `None`
+    /// falls back to `nms_radius` at level 0.
+    pub result_min_separation: Option<usize>,
+    /// Minimum angle difference in degrees at which nearby peaks count as
+    /// distinct instances.
+    ///
+    /// When set, search NMS and the final `match_image_topk` dedup only
+    /// suppress a peak near a better one if their angles also differ by at
+    /// most this much, so a part with N-fold symmetry is reported at each of
+    /// its equivalent orientations. `None` suppresses by position alone.
+    pub nms_angle_separation_deg: Option<f32>,
+    /// Which of several equally scored peaks ranks first, in per-angle Top-K
+    /// selection and in NMS.
+    ///
+    /// The default keeps the historical top-left-first order.
+    pub tie_break: TieBreak,
     /// Refinement ROI radius in pixels for the current level.
     pub roi_radius: usize,
+    /// Number of pyramid levels descended per refinement step.
+    ///
+    /// The default of 1 refines at every level between the coarsest and the
+    /// finest. Larger strides skip levels: each candidate is upscaled by
+    /// `2^stride` and its ROI radius and angle half-range are widened by
+    /// `2^(stride - 1)` to cover the larger uncertainty. The finest searched
+    /// level is always visited. Skipping levels saves time on deep pyramids
+    /// but trades away robustness, since a wrong coarse candidate is no
+    /// longer corrected gradually. Must be at least 1.
+    pub level_stride: usize,
+    /// Interprets `nms_radius` and `roi_radius` in level-0 pixels.
+    ///
+    /// When true, both radii are divided by the decimation factor of the
+    /// level being searched (rounded up, so a non-zero radius stays non-zero).
+    /// When false, the same radius is applied at every level's own resolution.
+    pub radius_in_level0_pixels: bool,
+    /// Retains the best peak of every spatially separate cluster past `beam_width`.
+    ///
+    /// After NMS, peaks beyond the beam are normally dropped. When enabled, a
+    /// dropped peak is kept anyway if it lies outside the refinement ROI
+    /// radius (Chebyshev distance) of every peak already in the beam, so a
+    /// distant true match is not lost to a marginally stronger distractor.
+    /// The beam can then grow past `beam_width`.
+    pub keep_runner_up_positions: bool,
     /// Angle neighborhood half-range in multiples of the grid step.
     ///
     /// Ignored when rotation is disabled.
     pub angle_half_range_steps: usize,
     /// Minimum variance for image patches.
     ///
-    /// Ignored for SSD.
+    /// Compared against the weighted sum of squared deviations of the image
+    /// window, so its useful range depends on the template size. Ignored for
+    /// SSD and when `min_contrast` is set.
     pub min_var_i: f32,
+    /// Minimum image-window standard deviation, in intensity units.
+    ///
+    /// Windows flatter than this are skipped. It is converted per template to
+    /// `min_contrast² * sum_w`, where `sum_w` is the template's (mask) weight
+    /// sum, and takes precedence over `min_var_i` when set. Ignored for SSD.
+    pub min_contrast: Option<f32>,
+    /// Skips coarse-scan placements whose window variance differs from the
+    /// template's by more than this factor in either direction.
+    ///
+    /// Unmasked scans take the window sums from the integral image, so a
+    /// rejected placement costs no dot product; on flat or uniform
+    /// backgrounds this prunes most of the dense coarse scan. Masked
+    /// templates accumulate the sums and the dot product in one pass, so the
+    /// gate only filters their candidates. Only the coarsest searched level
+    /// is gated; ROI refinement, the level-0 score, and RGB or image-masked
+    /// searches are unaffected. Must be at least 1 and requires the ZNCC metric
+    /// (default: `None`, no gate).
+    pub coarse_variance_ratio: Option<f32>,
     /// Minimum score threshold (discard below this value).
+    ///
+    /// The threshold is applied to raw kernel scores during search and again
+    /// to the refined level-0 score, both before `score_clamp`; a negative
+    /// threshold therefore still filters on the unclamped ZNCC value. It must
+    /// not exceed the metric's best score, 1 for ZNCC, 0 for SSD, and the sum
+    /// of the weights for [`Metric::Combined`], since no match could pass.
     pub min_score: f32,
+    /// Re-runs a search level that produced no candidates with relaxed limits
+    /// instead of failing with [`CorrMatchError::NoCandidates`].
+    ///
+    /// The retry scans the same level once more with `min_score` and
+    /// `coarse_variance_ratio` disabled and both per-angle Top-K limits
+    /// doubled; its candidates then continue to
+    /// the finer levels as usual. Only the level that came up empty is
+    /// relaxed, and the refined level-0 score is still compared against
+    /// `min_score`, so a weak retried candidate can still end the match with
+    /// `NoCandidates`. A level that stays empty after the retry fails as
+    /// before.
+    pub retry_on_empty: bool,
+    /// Also considers placements where the template extends past the image
+    /// border, e.g. for objects entering the frame.
+    ///
+    /// Each placement is scored with ZNCC over the pixels where the template
+    /// overlaps the image, as in [`Matcher::match_image_masked`], so at least
+    /// half of the template must lie inside. Reported positions can then be
+    /// negative or exceed the image size minus the template size. Applies to
+    /// `match_image`, `match_image_detailed` and `match_image_masked`;
+    /// `match_image_topk` and [`TemplateBatch`] reject it, and other entry
+    /// points keep fully contained placements.
+    ///
+    /// Each call pads a copy of the image with invalid pixels and runs the
+    /// masked search on it, so an image of exactly the template size is
+    /// searched like any other rather than scored at its single full fit.
+    /// Requires [`Metric::Zncc`] without `subpixel_resample`, and shares the
+    /// other restrictions of [`Matcher::match_image_masked`].
+    pub allow_partial: bool,
+    /// Combines the finest-level score with the candidate's coarser-level
+    /// scores before `min_score` and `score_clamp` are applied.
+    ///
+    /// Only [`ScoreAgg::Finest`] is supported with [`Metric::Ssd`], whose
+    /// scores are not comparable across template sizes.
+    pub score_aggregation: ScoreAgg,
+    /// Post-processing applied to the reported score of each final match.
+    pub score_clamp: ScoreClamp,
+    /// Runs the subpixel/subangle fit on each final candidate.
+    ///
+    /// When false, matches report the integer position and grid angle found by
+    /// the search, which saves the extra score evaluations of the quadratic
+    /// fit. `min_score` and `score_clamp` still apply. `Matcher::refine`
+    /// always refines.
+    pub final_refinement: bool,
+    /// Re-fits the subpixel position on bilinearly resampled ZNCC scores.
+    ///
+    /// After the integer-grid fit, the image is resampled around the
+    /// fractional estimate and the parabola is fitted again on those scores. This
+    /// costs four extra full-template evaluations per match but localizes
+    /// smooth, gradient-dominated patterns more accurately. Only valid with
+    /// [`Metric::Zncc`]; the reported score stays the integer-grid score.
+    pub subpixel_resample: bool,
+    /// Rescores each final match against the full-resolution image.
+    ///
+    /// After refinement, the reported score is replaced by the level-0 score
+    /// at the rounded position and the nearest level-0 grid angle, so it no
+    /// longer depends on the level the search stopped at (see
+    /// `initial_downscale`). `min_score` then applies to that score. Costs one
+    /// full-template evaluation per match; ignored by image-mask matching.
+    pub verify_full_res: bool,
+    /// How the final refinement estimates the angle between grid steps.
+    ///
+    /// Only used with rotation enabled and `final_refinement` set; image-mask
+    /// matching always uses the quadratic fit.
+    pub angle_refine: AngleRefine,
+    /// Also detects polarity-inverted instances (dark-on-bright versus
+    /// bright-on-dark).
+    ///
+    /// Candidates are ranked, suppressed, and compared against `min_score` by
+    /// `|score|`, while reported scores keep their sign, so an inverted match
+    /// shows up with a score near -1. Inverted matches skip the
+    /// `subpixel_resample` re-fit. Only valid with [`Metric::Zncc`] and
+    /// without [`ScoreClamp::ZeroToOne`], which would hide the sign.
+    pub allow_inverted: bool,
+    /// Reports [`Match::peak_sharpness`] from `match_image` and its prior
+    /// and timing variants.
+    ///
+    /// The ratio compares the refined seed with the strongest finest-level
+    /// beam entry outside its NMS radius, using raw kernel scores ranked as
+    /// for `min_score`: `best / runner_up` for ZNCC and the inverse SSE
+    /// ratio for SSD. It is infinite when no such runner-up exists, so it
+    /// needs a `beam_width` of at least 2 to be informative.
+    pub compute_psr: bool,
+    /// Accumulates the grayscale kernels' `dot`, `sum_i`, `sum_i2`, and SSE
+    /// sums with Kahan compensation (RGB and image-masked searches keep plain
+    /// sums).
+    ///
+    /// Compensated sums stay within a few ulps of the exact value, so scores
+    /// no longer carry the rounding error compounded over the window and
+    /// near-ties rank the same however the sums were ordered. Rust never
+    /// contracts `a * b + c` into FMA on its own, so the scalar kernels do not
+    /// depend on FMA support. Rotated templates are built with the
+    /// platform's `sin`/`cos`, which may differ in the last bit; golden tests
+    /// across platforms are most robust without rotation or at grid angles
+    /// that are multiples of 90°. Adds a few float operations per pixel to
+    /// every window, so scans run noticeably slower.
+    pub deterministic_accumulation: bool,
 }
 
 impl Default for MatchConfig {
+    #[allow(deprecated)]
     fn default() -> Self {
         Self {
             metric: Metric::Zncc,
             rotation: RotationMode::Disabled,
             parallel: false,
             max_image_levels: 6,
+            require_full_pyramid: false,
+            pyramid_filter: PyramidFilter::Box2x2,
+            initial_downscale: 1,
             beam_width: 8,
-            per_angle_topk: 3,
+            min_instances: 0,
+            per_angle_topk: 0,
+            coarse_per_angle_topk: 3,
+            refine_per_angle_topk: 3,
             nms_radius: 6,
+            nms_keep_ratio: 0.0,
+            result_min_separation: None,
+            nms_angle_separation_deg: None,
+            tie_break: TieBreak::TopLeftFirst,
             roi_radius: 8,
+            level_stride: 1,
+            radius_in_level0_pixels: false,
+            keep_runner_up_positions: false,
             angle_half_range_steps: 1,
             min_var_i: 1e-8,
+            min_contrast: None,
+            coarse_variance_ratio: None,
             min_score: f32::NEG_INFINITY,
+            retry_on_empty: false,
+            allow_partial: false,
+            score_aggregation: ScoreAgg::Finest,
+            score_clamp: ScoreClamp::None,
+            final_refinement: true,
+            subpixel_resample: false,
+            verify_full_res: false,
+            angle_refine: AngleRefine::Quadratic,
+            allow_inverted: false,
+            compute_psr: false,
+            deterministic_accumulation: false,
         }
     }
 }
@@ -94,14 +452,20 @@ impl Default for MatchConfig {
 impl MatchConfig {
     /// Validates the configuration, returning an error if any parameter is invalid.
     pub fn validate(&self) -> CorrMatchResult<()> {
+        if self.level_stride == 0 {
+            return Err(CorrMatchError::InvalidConfig {
+                reason: "level_stride must be at least 1",
+            });
+        }
         if self.beam_width == 0 {
             return Err(CorrMatchError::InvalidConfig {
                 reason: "beam_width must be at least 1",
             });
         }
-        if self.per_angle_topk == 0 {
+        let coarse_topk = self.per_angle_alias().unwrap_or(self.coarse_per_angle_topk);
+        if coarse_topk == 0 || self.refine_topk() == 0 {
             return Err(CorrMatchError::InvalidConfig {
-                reason: "per_angle_topk must be at least 1",
+                reason: "per_angle_topk values must be at least 1",
             });
         }
         if self.max_image_levels == 0 {
@@ -109,16 +473,129 @@ impl MatchConfig {
                 reason: "max_image_levels must be at least 1",
             });
         }
+        if !self.initial_downscale.is_power_of_two() {
+            return Err(CorrMatchError::InvalidConfig {
+                reason: "initial_downscale must be a power of two (at least 1)",
+            });
+        }
+        if self.finest_level() >= self.max_image_levels {
+            return Err(CorrMatchError::InvalidConfig {
+                reason: "initial_downscale must leave at least one image level to search",
+            });
+        }
+        if !(0.0..=1.0).contains(&self.nms_keep_ratio) {
+            return Err(CorrMatchError::InvalidConfig {
+                reason: "nms_keep_ratio must lie in [0, 1]",
+            });
+        }
+        if self
+            .nms_angle_separation_deg
+            .is_some_and(|sep| !sep.is_finite() || sep < 0.0)
+        {
+            return Err(CorrMatchError::InvalidConfig {
+                reason: "nms_angle_separation_deg must be a non-negative finite value",
+            });
+        }
         if !self.min_var_i.is_finite() || self.min_var_i < 0.0 {
             return Err(CorrMatchError::InvalidConfig {
                 reason: "min_var_i must be a non-negative finite value",
             });
         }
+        if self
+            .min_contrast
+            .is_some_and(|contrast| !contrast.is_finite() || contrast < 0.0)
+        {
+            return Err(CorrMatchError::InvalidConfig {
+                reason: "min_contrast must be a non-negative finite value",
+            });
+        }
+        if self
+            .coarse_variance_ratio
+            .is_some_and(|ratio| !ratio.is_finite() || ratio < 1.0)
+        {
+            return Err(CorrMatchError::InvalidConfig {
+                reason: "coarse_variance_ratio must be a finite value of at least 1",
+            });
+        }
+        if self.coarse_variance_ratio.is_some() && self.metric != Metric::Zncc {
+            return Err(CorrMatchError::InvalidConfig {
+                reason: "coarse_variance_ratio requires the ZNCC metric",
+            });
+        }
         if !self.min_score.is_finite() && self.min_score != f32::NEG_INFINITY {
             return Err(CorrMatchError::InvalidConfig {
                 reason: "min_score must be finite or NEG_INFINITY",
             });
         }
+        if let Metric::Combined {
+            zncc_weight,
+            ssd_weight,
+        } = self.metric
+        {
+            let valid = |w: f32| w.is_finite() && w >= 0.0;
+            if !valid(zncc_weight) || !valid(ssd_weight) || zncc_weight + ssd_weight <= 0.0 {
+                return Err(CorrMatchError::InvalidConfig {
+                    reason:
+                        "combined metric weights must be finite, non-negative, and not both zero",
+                });
+            }
+        }
+        match self.metric {
+            Metric::Zncc if self.min_score > 1.0 => {
+                return Err(CorrMatchError::InvalidConfig {
+                    reason: "min_score above 1 can never pass: ZNCC scores lie in [-1, 1]",
+                });
+            }
+            Metric::Ssd if self.min_score > 0.0 => {
+                return Err(CorrMatchError::InvalidConfig {
+                    reason: "min_score above 0 can never pass: SSD scores are negative SSE",
+                });
+            }
+            Metric::Combined {
+                zncc_weight,
+                ssd_weight,
+            } if self.min_score > zncc_weight + ssd_weight => {
+                return Err(CorrMatchError::InvalidConfig {
+                    reason: "min_score above the sum of the combined weights can never pass",
+                });
+            }
+            _ => {}
+        }
+        if self.subpixel_resample && self.metric != Metric::Zncc {
+            return Err(CorrMatchError::InvalidConfig {
+                reason: "subpixel_resample requires the ZNCC metric",
+            });
+        }
+        if self.angle_refine == (AngleRefine::GoldenSection { iters: 0 }) {
+            return Err(CorrMatchError::InvalidConfig {
+                reason: "angle_refine golden-section iters must be at least 1",
+            });
+        }
+        if self.allow_inverted && self.metric != Metric::Zncc {
+            return Err(CorrMatchError::InvalidConfig {
+                reason: "allow_inverted requires the ZNCC metric",
+            });
+        }
+        if self.allow_inverted && self.score_clamp == ScoreClamp::ZeroToOne {
+            return Err(CorrMatchError::InvalidConfig {
+                reason: "allow_inverted cannot be combined with score_clamp zero_to_one",
+            });
+        }
+        if self.metric == Metric::Ssd && self.score_aggregation != ScoreAgg::Finest {
+            return Err(CorrMatchError::InvalidConfig {
+                reason: "score_aggregation across levels requires the ZNCC metric",
+            });
+        }
+        if self.allow_partial && (self.metric != Metric::Zncc || self.subpixel_resample) {
+            return Err(CorrMatchError::InvalidConfig {
+                reason: "allow_partial requires the ZNCC metric without subpixel_resample",
+            });
+        }
+        if self.compute_psr && self.beam_width < 2 {
+            return Err(CorrMatchError::InvalidConfig {
+                reason: "compute_psr needs a beam_width of at least 2",
+            });
+        }
         #[cfg(not(feature = "rayon"))]
         if self.parallel {
             return Err(CorrMatchError::ParallelUnavailable);
@@ -126,9 +603,238 @@ impl MatchConfig {
         Ok(())
     }
 
+    /// Sets both `coarse_per_angle_topk` and `refine_per_angle_topk`.
+    #[deprecated(note = "set `coarse_per_angle_topk` and `refine_per_angle_topk` instead")]
+    #[allow(deprecated)]
+    pub fn with_per_angle_topk(mut self, topk: usize) -> Self {
+        self.per_angle_topk = 0;
+        self.coarse_per_angle_topk = topk;
+        self.refine_per_angle_topk = topk;
+        self
+    }
+
+    /// Variance threshold for a template whose weights sum to `sum_w`.
+    pub(crate) fn min_var_i_for(&self, sum_w: f32) -> f32 {
+        match self.min_contrast {
+            Some(contrast) => contrast * contrast * sum_w,
+            None => self.min_var_i,
+        }
+    }
+
     pub(crate) fn use_parallel(&self) -> bool {
         self.parallel && cfg!(feature = "rayon")
     }
+
+    /// Returns how many levels to search for an image pyramid of
+    /// `image_levels` and a template compiled with `template_levels`.
+    pub(crate) fn searched_levels(
+        &self,
+        image_levels: usize,
+        template_levels: usize,
+    ) -> CorrMatchResult<usize> {
+        let available = image_levels.min(self.max_image_levels);
+        if self.require_full_pyramid && available < template_levels {
+            return Err(CorrMatchError::InsufficientPyramidLevels {
+                available,
+                required: template_levels,
+            });
+        }
+        Ok(available.min(template_levels))
+    }
+
+    /// Returns the limits used to retry a level that came up empty (see
+    /// `retry_on_empty`).
+    #[allow(deprecated)]
+    pub(crate) fn relaxed(&self) -> Self {
+        Self {
+            min_score: f32::NEG_INFINITY,
+            coarse_variance_ratio: None,
+            per_angle_topk: self.per_angle_topk.saturating_mul(2),
+            coarse_per_angle_topk: self.coarse_per_angle_topk.saturating_mul(2),
+            refine_per_angle_topk: self.refine_per_angle_topk.saturating_mul(2),
+            ..self.clone()
+        }
+    }
+
+    /// Builds the image pyramid with `pyramid_filter`, downsampling in
+    /// parallel when `use_parallel` holds.
+    pub(crate) fn build_pyramid(
+        &self,
+        image: ImageView<'_, u8>,
+        max_levels: usize,
+    ) -> CorrMatchResult<ImagePyramid> {
+        #[cfg(feature = "rayon")]
+        if self.use_parallel() {
+            return ImagePyramid::build_u8_filtered_par(image, max_levels, self.pyramid_filter);
+        }
+        ImagePyramid::build_u8_filtered(image, max_levels, self.pyramid_filter)
+    }
+
+    /// Returns the pyramid level at which the search finishes.
+    pub(crate) fn finest_level(&self) -> usize {
+        self.initial_downscale.trailing_zeros() as usize
+    }
+
+    /// Returns the NMS radius to apply at a pyramid level.
+    pub(crate) fn nms_radius_at(&self, level: usize) -> usize {
+        self.radius_at(self.nms_radius, level)
+    }
+
+    /// Returns whether candidates are ranked by `|score|` (see `allow_inverted`).
+    pub(crate) fn rank_by_magnitude(&self) -> bool {
+        self.allow_inverted && self.metric == Metric::Zncc
+    }
+
+    /// Returns the value a score is ranked and thresholded by.
+    pub(crate) fn rank_key(&self, score: f32) -> f32 {
+        rank_key(score, self.rank_by_magnitude())
+    }
+
+    /// Returns the peak order for placements in `0..=max_x` x `0..=max_y`
+    /// searched over `grid` (`None` for translation-only searches).
+    pub(crate) fn peak_order(
+        &self,
+        max_x: usize,
+        max_y: usize,
+        grid: Option<&AngleGrid>,
+    ) -> PeakOrder {
+        let order = PeakOrder::new(self.rank_by_magnitude()).with_tie_break(
+            self.tie_break,
+            max_x,
+            max_y,
+            grid.map_or(0, |grid| grid.nearest_index(0.0)),
+        );
+        match (grid, self.nms_angle_separation_deg) {
+            (Some(grid), Some(min_sep_deg)) => {
+                order.with_angle_separation(grid.step_deg(), min_sep_deg)
+            }
+            _ => order,
+        }
+    }
+
+    /// Applies spatial NMS with this configuration's radius and keep ratio.
+    pub(crate) fn nms(&self, peaks: &mut [Peak], level: usize, order: PeakOrder) -> Vec<Peak> {
+        nms_2d_ranked(peaks, self.nms_radius_at(level), self.nms_keep_ratio, order)
+    }
+
+    /// Returns the refinement ROI radius to apply at a pyramid level.
+    pub(crate) fn roi_radius_at(&self, level: usize) -> usize {
+        self.radius_at(self.roi_radius, level)
+    }
+
+    /// Returns the ROI radius for candidates refined from `from_level` down
+    /// to `level`, widened when the step skips levels.
+    pub(crate) fn roi_radius_from(&self, level: usize, from_level: usize) -> usize {
+        self.roi_radius_at(level)
+            .saturating_mul(skip_factor(level, from_level))
+    }
+
+    /// Returns the angle half-range in degrees for candidates refined from
+    /// `from_level` down to `level`, whose grid step is `step_deg`.
+    pub(crate) fn angle_half_range_from(
+        &self,
+        step_deg: f32,
+        level: usize,
+        from_level: usize,
+    ) -> f32 {
+        self.angle_half_range_steps as f32 * step_deg * skip_factor(level, from_level) as f32
+    }
+
+    /// Returns the levels refined after the coarsest, in search order.
+    ///
+    /// Steps down by `level_stride` and always ends at `finest`.
+    pub(crate) fn refinement_levels(
+        &self,
+        coarsest: usize,
+        finest: usize,
+    ) -> impl Iterator<Item = usize> {
+        let stride = self.level_stride.max(1);
+        std::iter::successors(Some(coarsest), move |&level| {
+            (level > finest).then(|| level.saturating_sub(stride).max(finest))
+        })
+        .skip(1)
+    }
+
+    /// Returns the per-angle Top-K of the coarsest-level scan, raised to
+    /// `min_instances`.
+    pub(crate) fn coarse_topk(&self) -> usize {
+        self.per_angle_alias()
+            .unwrap_or(self.coarse_per_angle_topk)
+            .max(self.min_instances)
+    }
+
+    /// Returns the per-angle Top-K kept from each refinement ROI.
+    pub(crate) fn refine_topk(&self) -> usize {
+        self.per_angle_alias().unwrap_or(self.refine_per_angle_topk)
+    }
+
+    /// Returns the deprecated `per_angle_topk` when it is set.
+    #[allow(deprecated)]
+    fn per_angle_alias(&self) -> Option<usize> {
+        (self.per_angle_topk != 0).then_some(self.per_angle_topk)
+    }
+
+    /// Limits NMS survivors (sorted by descending score) to the beam for a
+    /// level, keeping separated runner-ups per `keep_runner_up_positions`
+    /// and `min_instances`.
+    pub(crate) fn select_beam(&self, mut kept: Vec<Peak>, level: usize) -> Vec<Peak> {
+        if kept.len() <= self.beam_width {
+            return kept;
+        }
+        let rest = kept.split_off(self.beam_width);
+        if !self.keep_runner_up_positions && self.min_instances == 0 {
+            return kept;
+        }
+        let radius = self.roi_radius_at(level);
+        let separated = |peak: &Peak, kept: &[Peak]| {
+            kept.iter().all(|k| {
+                let dx = peak.x.abs_diff(k.x);
+                let dy = peak.y.abs_diff(k.y);
+                dx.max(dy) > radius
+            })
+        };
+        let mut instances = (0..kept.len())
+            .filter(|&i| separated(&kept[i], &kept[..i]))
+            .count();
+        for peak in rest {
+            let wanted = self.keep_runner_up_positions || instances < self.min_instances;
+            if wanted && separated(&peak, &kept) {
+                kept.push(peak);
+                instances += 1;
+            }
+        }
+        kept
+    }
+
+    fn radius_at(&self, radius: usize, level: usize) -> usize {
+        if !self.radius_in_level0_pixels || level == 0 {
+            return radius;
+        }
+        radius.div_ceil(level_factor(level))
+    }
+}
+
+/// Wall-clock time spent in each stage of one match call.
+///
+/// Returned by [`Matcher::match_image_with_timings`]; plain `match_image`
+/// calls take no measurements.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct MatchTimings {
+    /// Building the image pyramid.
+    pub pyramid: Duration,
+    /// Full scan of the coarsest level.
+    pub coarse: Duration,
+    /// ROI refinement through the finer pyramid levels.
+    pub refinement: Duration,
+    /// Subpixel/subangle fit of the final candidates.
+    pub final_refinement: Duration,
+}
+
+impl MatchTimings {
+    /// Returns the sum of all stages.
+    pub fn total(&self) -> Duration {
+        self.pyramid + self.coarse + self.refinement + self.final_refinement
+    }
 }
 
 /// Match result for the finest pyramid level.
@@ -142,23 +848,139 @@ pub struct Match {
     pub angle_deg: f32,
     /// Score for the chosen metric (ZNCC in [-1, 1], SSD as negative SSE).
     pub score: f32,
+    /// Peak-to-sidelobe ratio of the final beam when
+    /// [`MatchConfig::compute_psr`] is set; `None` otherwise.
+    ///
+    /// Values near 1 mean a spatially distinct runner-up scored almost as
+    /// well, i.e. an ambiguous match; larger values mean a sharper peak.
+    pub peak_sharpness: Option<f32>,
+}
+
+impl Match {
+    /// Returns the axis-aligned bounding box of the rotated template footprint.
+    ///
+    /// The box is `(min_x, min_y, max_x, max_y)` in level-0 pixel-edge
+    /// coordinates, for a `tpl_width` x `tpl_height` template placed at this
+    /// match. The rotation is about the template center, as in
+    /// `rotate_u8_bilinear`, so at 0° the box is `(x, y, x + w, y + h)`.
+    pub fn aabb(&self, tpl_width: usize, tpl_height: usize) -> (f32, f32, f32, f32) {
+        let half_w = tpl_width as f32 * 0.5;
+        let half_h = tpl_height as f32 * 0.5;
+        let cx = self.x + half_w;
+        let cy = self.y + half_h;
+        let (sin_a, cos_a) = sin_cos_deg(self.angle_deg);
+        let extent_x = cos_a.abs() * half_w + sin_a.abs() * half_h;
+        let extent_y = sin_a.abs() * half_w + cos_a.abs() * half_h;
+        (cx - extent_x, cy - extent_y, cx + extent_x, cy + extent_y)
+    }
+
+    /// Returns the four corners of the rotated template footprint.
+    ///
+    /// Corners are in level-0 pixel-edge coordinates, ordered top-left,
+    /// top-right, bottom-right, bottom-left in template space, so at 0° they
+    /// trace `(x, y)` to `(x + w, y + h)` clockwise. [`Match::aabb`] bounds
+    /// the same polygon.
+    pub fn corners(&self, tpl_width: usize, tpl_height: usize) -> [[f32; 2]; 4] {
+        let half_w = tpl_width as f32 * 0.5;
+        let half_h = tpl_height as f32 * 0.5;
+        let cx = self.x + half_w;
+        let cy = self.y + half_h;
+        let (sin_a, cos_a) = sin_cos_deg(self.angle_deg);
+        [
+            (-half_w, -half_h),
+            (half_w, -half_h),
+            (half_w, half_h),
+            (-half_w, half_h),
+        ]
+        .map(|(dx, dy)| [cx + cos_a * dx - sin_a * dy, cy + sin_a * dx + cos_a * dy])
+    }
+
+    /// Returns the intersection-over-union of two matches' bounding boxes.
+    ///
+    /// Both matches are assumed to come from the same template size; boxes
+    /// come from [`Match::aabb`]. Returns 0.0 when the boxes do not overlap.
+    pub fn iou(&self, other: &Match, tpl_width: usize, tpl_height: usize) -> f32 {
+        let a = self.aabb(tpl_width, tpl_height);
+        let b = other.aabb(tpl_width, tpl_height);
+        let inter_w = (a.2.min(b.2) - a.0.max(b.0)).max(0.0);
+        let inter_h = (a.3.min(b.3) - a.1.max(b.1)).max(0.0);
+        let inter = inter_w * inter_h;
+        let area_a = (a.2 - a.0) * (a.3 - a.1);
+        let area_b = (b.2 - b.0) * (b.3 - b.1);
+        let union = area_a + area_b - inter;
+        if union > 0.0 {
+            inter / union
+        } else {
+            0.0
+        }
+    }
+}
+
+/// Presence decision returned by [`Matcher::detect`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Detection {
+    /// Whether the best match reaches the detection threshold.
+    pub present: bool,
+    /// Best match found, kept even when it falls below the threshold;
+    /// `None` when the search yielded no candidates at all.
+    pub best: Option<Match>,
+}
+
+/// Best match plus metadata, returned by [`Matcher::match_image_detailed`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MatchDetails {
+    /// Refined best match, as returned by `match_image`.
+    pub best: Match,
+    /// Pyramid level at which the final refinement ran and the reported
+    /// score was computed (0 is full resolution).
+    ///
+    /// This is the finest searched level: `initial_downscale` stops the
+    /// search above level 0, and `verify_full_res` rescores at level 0.
+    /// Positions are always reported in level-0 pixels, but their precision
+    /// follows this level.
+    pub score_level: usize,
 }
 
 /// Matcher that runs coarse-to-fine search using a compiled template.
+///
+/// Compiled assets are held behind an `Arc`, so cloning a matcher or building
+/// several matchers from one `Arc<CompiledTemplate>` shares the template
+/// pyramid and rotation cache instead of recompiling.
+///
+/// `Matcher` is `Send + Sync`: matching takes `&self`, and the only interior
+/// mutability is the write-once rotation cache, so one matcher can be shared
+/// across threads (for example in an `Arc`) and every thread gets the same
+/// results as a sequential caller.
+#[derive(Clone)]
 pub struct Matcher {
-    compiled: CompiledTemplate,
+    compiled: Arc<CompiledTemplate>,
     cfg: MatchConfig,
 }
 
 impl Matcher {
     /// Creates a matcher with default configuration.
     pub fn new(compiled: CompiledTemplate) -> Self {
+        Self::from_shared(Arc::new(compiled))
+    }
+
+    /// Creates a matcher with default configuration from shared compiled assets.
+    pub fn from_shared(compiled: Arc<CompiledTemplate>) -> Self {
         Self {
             compiled,
             cfg: MatchConfig::default(),
         }
     }
 
+    /// Returns the compiled template assets used by this matcher.
+    pub fn compiled(&self) -> &CompiledTemplate {
+        &self.compiled
+    }
+
+    /// Returns the matcher configuration.
+    pub fn config(&self) -> &MatchConfig {
+        &self.cfg
+    }
+
     /// Replaces the matcher configuration.
     ///
     /// Use `try_with_config` for validation of the configuration.
@@ -178,80 +1000,868 @@ impl Matcher {
 
     /// Matches a template against an image and returns the best candidate.
     ///
-    /// When rotation is disabled, angle-related settings are ignored.
+    /// When rotation is disabled, angle-related settings are ignored. With
+    /// rotation enabled, a template compiled without angle banks fails with
+    /// [`CorrMatchError::RotationNotCompiled`].
     pub fn match_image(&self, image: ImageView<'_, u8>) -> CorrMatchResult<Match> {
         self.cfg.validate()?;
-        let seeds = self.match_candidates(image)?;
-        let best = seeds[0];
-        let refined = match self.cfg.rotation {
-            RotationMode::Enabled => refine_final_match(image, &self.compiled, 0, best, &self.cfg),
-            RotationMode::Disabled => {
-                refine_final_match_unmasked(image, &self.compiled, 0, best, &self.cfg)
-            }
+        if self.cfg.allow_partial {
+            return self.match_partial(image, None);
+        }
+        let (pyramid, seeds) = self.match_candidates(image)?;
+        self.refine_best(&pyramid, seeds)
+    }
+
+    /// Like `match_image`, and also reports where the final score came from.
+    ///
+    /// See [`MatchDetails::score_level`]: a level above 0 means the search
+    /// never reached full resolution, e.g. with `initial_downscale`.
+    pub fn match_image_detailed(&self, image: ImageView<'_, u8>) -> CorrMatchResult<MatchDetails> {
+        self.cfg.validate()?;
+        if self.cfg.allow_partial {
+            let best = self.match_partial(image, None)?;
+            return Ok(MatchDetails {
+                best,
+                score_level: self.cfg.finest_level(),
+            });
+        }
+        let (pyramid, seeds) = self.match_candidates(image)?;
+        let (best, score_level) = self.refine_best_with_level(&pyramid, seeds)?;
+        Ok(MatchDetails { best, score_level })
+    }
+
+    /// Matches and decides whether the object is present.
+    ///
+    /// The best match from `match_image` is `present` when its score (after
+    /// `score_clamp`, compared by magnitude with `allow_inverted`) is at least
+    /// `threshold`. A sub-threshold best match is still returned for logging,
+    /// and a search without candidates reports `present = false` with no
+    /// match instead of failing. Matches below `min_score` are never seen, so
+    /// keep it at or below `threshold`.
+    pub fn detect(&self, image: ImageView<'_, u8>, threshold: f32) -> CorrMatchResult<Detection> {
+        match self.match_image(image) {
+            Ok(best) => Ok(Detection {
+                present: self.cfg.rank_key(best.score) >= threshold,
+                best: Some(best),
+            }),
+            Err(CorrMatchError::NoCandidates { .. }) => Ok(Detection {
+                present: false,
+                best: None,
+            }),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Matches with the coarse angle scan limited to a window around a prior.
+    ///
+    /// Only angles within `angle_window_deg` of `angle_prior_deg` (circular
+    /// distance) are scanned at the coarsest level; finer levels search around
+    /// those seeds as usual. If the window holds no coarse grid angle, the
+    /// angle nearest the prior is scanned. The prior is ignored when rotation
+    /// is disabled.
+    pub fn match_image_with_prior(
+        &self,
+        image: ImageView<'_, u8>,
+        angle_prior_deg: f32,
+        angle_window_deg: f32,
+    ) -> CorrMatchResult<Match> {
+        self.cfg.validate()?;
+        if !angle_prior_deg.is_finite() || !angle_window_deg.is_finite() || angle_window_deg < 0.0 {
+            return Err(CorrMatchError::InvalidConfig {
+                reason: "angle prior must be finite and angle window non-negative",
+            });
+        }
+        self.check_rotation()?;
+        let pyramid = self.build_pyramid(image, self.cfg.max_image_levels)?;
+        let window = AngleWindow {
+            center_deg: angle_prior_deg,
+            half_range_deg: angle_window_deg,
+        };
+        let seeds = self.search_pyramid(&pyramid, image, Some(window), |_| {})?;
+        self.refine_best(&pyramid, seeds)
+    }
+
+    /// Like `match_image`, and also reports how long each stage took.
+    ///
+    /// Stage boundaries are taken from the same per-level hook that
+    /// `debug_levels` uses, so the search itself runs unchanged.
+    pub fn match_image_with_timings(
+        &self,
+        image: ImageView<'_, u8>,
+    ) -> CorrMatchResult<(Match, MatchTimings)> {
+        self.cfg.validate()?;
+        self.check_rotation()?;
+        let start = Instant::now();
+        let pyramid = self.build_pyramid(image, self.cfg.max_image_levels)?;
+        let pyramid_done = Instant::now();
+
+        let mut coarse_done = None;
+        let seeds = self.search_pyramid(&pyramid, image, None, |_| {
+            coarse_done.get_or_insert_with(Instant::now);
+        })?;
+        let search_done = Instant::now();
+        let coarse_done = coarse_done.unwrap_or(search_done);
+
+        let best = self.refine_best(&pyramid, seeds)?;
+        let timings = MatchTimings {
+            pyramid: pyramid_done - start,
+            coarse: coarse_done - pyramid_done,
+            refinement: search_done - coarse_done,
+            final_refinement: search_done.elapsed(),
         };
-        Ok(refined.unwrap_or(Match {
-            x: best.x as f32,
-            y: best.y as f32,
-            angle_deg: best.angle_deg,
-            score: best.score,
-        }))
+        Ok((best, timings))
     }
 
     /// Matches a template against an image and returns up to `k` best candidates.
     ///
     /// Results are returned in descending score order and include the same
-    /// refinement steps as `match_image`.
+    /// refinement steps as `match_image`. Every seed is refined before the
+    /// final selection, and refined matches that converged onto a better one
+    /// are dropped by a last NMS pass over their rounded positions (see
+    /// `dedupe_matches`).
     pub fn match_image_topk(
         &self,
         image: ImageView<'_, u8>,
         k: usize,
     ) -> CorrMatchResult<Vec<Match>> {
+        let mut out = Vec::new();
+        self.match_image_topk_into(image, k, &mut out)?;
+        Ok(out)
+    }
+
+    /// Like [`Matcher::match_image_topk`], but writes the results into `out`.
+    ///
+    /// `out` is cleared first and then filled in descending score order, so
+    /// a buffer reused across calls keeps its capacity and the results need
+    /// no new allocation once it is large enough. The search itself (image
+    /// pyramid and candidate lists) still allocates per call. On error `out`
+    /// is left empty.
+    pub fn match_image_topk_into(
+        &self,
+        image: ImageView<'_, u8>,
+        k: usize,
+        out: &mut Vec<Match>,
+    ) -> CorrMatchResult<()> {
+        out.clear();
         self.cfg.validate()?;
-        if k == 0 {
-            return Ok(Vec::new());
+        if self.cfg.allow_partial {
+            return Err(CorrMatchError::InvalidConfig {
+                reason: "match_image_topk does not support allow_partial",
+            });
         }
-
-        let seeds = self.match_candidates(image)?;
-        let limit = k.min(seeds.len());
-        let mut out = Vec::with_capacity(limit);
-        for cand in seeds.into_iter().take(limit) {
-            let refined = match self.cfg.rotation {
-                RotationMode::Enabled => {
-                    refine_final_match(image, &self.compiled, 0, cand, &self.cfg)
-                }
-                RotationMode::Disabled => {
-                    refine_final_match_unmasked(image, &self.compiled, 0, cand, &self.cfg)
-                }
-            };
-            out.push(refined.unwrap_or(Match {
-                x: cand.x as f32,
-                y: cand.y as f32,
-                angle_deg: cand.angle_deg,
-                score: cand.score,
-            }));
+        if k == 0 {
+            return Ok(());
         }
 
-        Ok(out)
+        let (pyramid, seeds) = self.match_candidates(image)?;
+        let refined = seeds
+            .into_iter()
+            .filter_map(|cand| self.refine_candidate(&pyramid, cand))
+            .collect();
+        self.dedupe_matches(refined, out);
+        out.truncate(k);
+        Ok(())
     }
 
-    fn match_candidates(&self, image: ImageView<'_, u8>) -> CorrMatchResult<Vec<Candidate>> {
-        if matches!(self.compiled, CompiledTemplate::Unrotated(_))
-            && self.cfg.rotation == RotationMode::Enabled
-        {
-            return Err(CorrMatchError::RotationUnavailable {
-                reason: "rotation enabled but template compiled without angle banks",
+    /// Sorts refined matches by rank and applies NMS at level 0, appending
+    /// the kept matches to `kept`.
+    ///
+    /// A match within `result_min_separation` (Chebyshev distance between
+    /// rounded positions) of a better-ranked kept match is dropped unless it
+    /// scores within `nms_keep_ratio` of every such neighbor, as in the
+    /// search NMS. Matches that round to the same pixel are always dropped,
+    /// even with a separation of 0. With `nms_angle_separation_deg`, only
+    /// kept matches at a similar angle count as neighbors.
+    fn dedupe_matches(&self, mut matches: Vec<Match>, kept: &mut Vec<Match>) {
+        matches.sort_by(|a, b| {
+            self.cfg
+                .rank_key(b.score)
+                .total_cmp(&self.cfg.rank_key(a.score))
+        });
+        let radius = self
+            .cfg
+            .result_min_separation
+            .unwrap_or_else(|| self.cfg.nms_radius_at(0));
+        let pixel = |m: &Match| (m.x.round() as i64, m.y.round() as i64);
+        let same_angle = |a: &Match, b: &Match| {
+            self.cfg
+                .nms_angle_separation_deg
+                .is_none_or(|sep| wrap_deg(a.angle_deg - b.angle_deg).abs() <= sep)
+        };
+        kept.reserve(matches.len());
+        for m in matches {
+            let (x, y) = pixel(&m);
+            let mut near = kept
+                .iter()
+                .map(|k| (k, pixel(k)))
+                .filter(|(k, (kx, ky))| {
+                    x.abs_diff(*kx).max(y.abs_diff(*ky)) <= radius as u64 && same_angle(&m, k)
+                })
+                .peekable();
+            if near.peek().is_none() {
+                kept.push(m);
+                continue;
+            }
+            if self.cfg.nms_keep_ratio <= 0.0 {
+                continue;
+            }
+            let key = self.cfg.rank_key(m.score);
+            let spared = near.all(|(k, k_pixel)| {
+                let k_key = self.cfg.rank_key(k.score);
+                k_pixel != (x, y) && key >= k_key - (1.0 - self.cfg.nms_keep_ratio) * k_key.abs()
             });
+            if spared {
+                kept.push(m);
+            }
+        }
+    }
+
+    /// Evaluates only the given top-left positions instead of scanning the image.
+    ///
+    /// Each position (level-0 pixels) is scored at every level-0 angle, and
+    /// the best angle goes through the same subpixel/subangle refinement as
+    /// `match_image`. Positions with no angle scoring at least `min_score`
+    /// are skipped; the rest are returned in descending score order. A
+    /// position whose template footprint leaves the image is an error.
+    pub fn match_at_positions(
+        &self,
+        image: ImageView<'_, u8>,
+        positions: &[(usize, usize)],
+    ) -> CorrMatchResult<Vec<Match>> {
+        self.cfg.validate()?;
+        self.check_rotation()?;
+        self.check_image_size(image)?;
+        let (width, height) =
+            self.compiled
+                .level_size(0)
+                .ok_or(CorrMatchError::IndexOutOfBounds {
+                    index: 0,
+                    len: self.compiled.num_levels(),
+                    context: "level",
+                })?;
+
+        let mut out = Vec::with_capacity(positions.len());
+        for &(x, y) in positions {
+            let window = image.roi(x, y, width, height)?;
+            let Some(best) = self.score_exact_fit(&self.cfg, window)?.into_iter().next() else {
+                continue;
+            };
+            let cand = Candidate { x, y, ..best };
+            let refined = if self.cfg.final_refinement {
+                self.refine_final(image, cand)
+                    .unwrap_or_else(|_| level0_match(cand))
+            } else {
+                level0_match(cand)
+            };
+            out.extend(self.finish_match(refined));
+        }
+        out.sort_by(|a, b| {
+            self.cfg
+                .rank_key(b.score)
+                .total_cmp(&self.cfg.rank_key(a.score))
+        });
+        Ok(out)
+    }
+
+    /// Returns up to `k_angles` angle hypotheses at the best-matching position.
+    ///
+    /// Runs `match_image`, then scores every level-0 grid angle at the
+    /// rounded best position and keeps the local maxima along the angle axis
+    /// (wrapping around for full-circle grids). The first entry is the
+    /// `match_image` result; each further maximum more than one grid step
+    /// from it gets the same subpixel/subangle refinement. Meant for parts
+    /// with ambiguous orientation, where the runner-up angle matters more
+    /// than the runner-up position. Results are in descending score order
+    /// and drop hypotheses below `min_score`; without rotation, or when the
+    /// best match extends past the image border (`allow_partial`), only the
+    /// best match is returned.
+    pub fn match_angles_at_best(
+        &self,
+        image: ImageView<'_, u8>,
+        k_angles: usize,
+    ) -> CorrMatchResult<Vec<Match>> {
+        self.cfg.validate()?;
+        if k_angles == 0 {
+            return Ok(Vec::new());
+        }
+        let best = self.match_image(image)?;
+        let grid = match self.compiled.search_rotation(self.cfg.rotation) {
+            RotationMode::Enabled => {
+                self.compiled
+                    .angle_grid(0)
+                    .ok_or(CorrMatchError::IndexOutOfBounds {
+                        index: 0,
+                        len: self.compiled.num_levels(),
+                        context: "level",
+                    })?
+            }
+            RotationMode::Disabled => return Ok(vec![best]),
+        };
+        let (width, height) =
+            self.compiled
+                .level_size(0)
+                .ok_or(CorrMatchError::IndexOutOfBounds {
+                    index: 0,
+                    len: self.compiled.num_levels(),
+                    context: "level",
+                })?;
+        // Subpixel offsets may round just past the last placement; a pose
+        // farther out (`allow_partial`) has no full-fit placement to rescore.
+        let place = |pos: f32, tpl: usize, img: usize| {
+            let max = img.checked_sub(tpl)? as f32;
+            (pos > -1.0 && pos < max + 1.0).then(|| pos.round().clamp(0.0, max) as usize)
+        };
+        let (Some(x), Some(y)) = (
+            place(best.x, width, image.width()),
+            place(best.y, height, image.height()),
+        ) else {
+            return Ok(vec![best]);
+        };
+
+        let scores = (0..grid.len())
+            .map(|angle_idx| score_level0_pose(image, &self.compiled, angle_idx, x, y, &self.cfg))
+            .collect::<CorrMatchResult<Vec<f32>>>()?;
+        let mut maxima: Vec<usize> = angle_maxima(&scores, grid, |score| self.cfg.rank_key(score))
+            .into_iter()
+            .filter(|&idx| wrap_deg(grid.angle_at(idx) - best.angle_deg).abs() > grid.step_deg())
+            .collect();
+        maxima.sort_by(|&a, &b| {
+            self.cfg
+                .rank_key(scores[b])
+                .total_cmp(&self.cfg.rank_key(scores[a]))
+        });
+        maxima.truncate(k_angles - 1);
+
+        let mut out = vec![best];
+        out.extend(maxima.into_iter().filter_map(|angle_idx| {
+            let peak = Peak {
+                x,
+                y,
+                score: scores[angle_idx],
+                angle_idx,
+            };
+            let cand = Candidate::from_peak(0, grid.angle_at(angle_idx), peak);
+            let refined = if self.cfg.final_refinement {
+                self.refine_final(image, cand)
+                    .unwrap_or_else(|_| level0_match(cand))
+            } else {
+                level0_match(cand)
+            };
+            self.finish_match(refined)
+        }));
+        out.sort_by(|a, b| {
+            self.cfg
+                .rank_key(b.score)
+                .total_cmp(&self.cfg.rank_key(a.score))
+        });
+        Ok(out)
+    }
+
+    /// Scores a single level-0 pose without searching.
+    ///
+    /// `(x, y)` is the template's top-left placement in level-0 pixels. With
+    /// rotation enabled, `angle_deg` is snapped to the nearest level-0 grid
+    /// angle; with rotation disabled it is ignored and the unmasked template is
+    /// used. The raw score is post-processed by `score_clamp` but not compared
+    /// against `min_score`; a ZNCC window too flat to score yields
+    /// `f32::NEG_INFINITY`. A placement whose template footprint leaves the
+    /// image is an error.
+    pub fn score_pose(
+        &self,
+        image: ImageView<'_, u8>,
+        x: usize,
+        y: usize,
+        angle_deg: f32,
+    ) -> CorrMatchResult<f32> {
+        self.cfg.validate()?;
+        self.check_rotation()?;
+        self.check_image_size(image)?;
+        let (width, height) =
+            self.compiled
+                .level_size(0)
+                .ok_or(CorrMatchError::IndexOutOfBounds {
+                    index: 0,
+                    len: self.compiled.num_levels(),
+                    context: "level",
+                })?;
+        image.roi(x, y, width, height)?;
+        let angle_idx = match self.compiled.search_rotation(self.cfg.rotation) {
+            RotationMode::Enabled => self
+                .compiled
+                .angle_grid(0)
+                .ok_or(CorrMatchError::IndexOutOfBounds {
+                    index: 0,
+                    len: self.compiled.num_levels(),
+                    context: "level",
+                })?
+                .nearest_index(angle_deg),
+            RotationMode::Disabled => 0,
+        };
+        let score = score_level0_pose(image, &self.compiled, angle_idx, x, y, &self.cfg)?;
+        if !score.is_finite() {
+            return Ok(score);
+        }
+        Ok(self.cfg.score_clamp.apply(self.cfg.metric, score))
+    }
+
+    /// Matches a template against an image and yields refined candidates lazily.
+    ///
+    /// The coarse-to-fine search runs once when this method is called; each
+    /// call to `next()` then applies the final subpixel/subangle refinement to
+    /// the next seed in descending score order. Stopping early skips the
+    /// refinement of the remaining seeds. If the search fails, the iterator
+    /// yields the error once and then ends.
+    pub fn match_iter<'a>(
+        &'a self,
+        image: ImageView<'a, u8>,
+    ) -> impl Iterator<Item = CorrMatchResult<Match>> + 'a {
+        let (pyramid, seeds, err) = match self
+            .cfg
+            .validate()
+            .and_then(|()| self.match_candidates(image))
+        {
+            Ok((pyramid, seeds)) => (Some(pyramid), seeds, None),
+            Err(err) => (None, Vec::new(), Some(err)),
+        };
+        err.map(Err).into_iter().chain(
+            seeds
+                .into_iter()
+                .filter_map(move |cand| self.refine_candidate(pyramid.as_ref()?, cand).map(Ok)),
+        )
+    }
+
+    /// Returns per-pixel contributions to the ZNCC dot product at a match.
+    ///
+    /// The match position is rounded to the nearest integer placement and,
+    /// with rotation enabled, its angle is snapped to the nearest level-0 grid
+    /// angle. Entry `i` (row-major over the template) is `t_prime[i] * img[i]`,
+    /// where `t_prime` is the zero-mean (and masked) template; the entries sum
+    /// to the ZNCC numerator. Masked-out pixels contribute zero. ZNCC plans are
+    /// used regardless of the configured metric. A placement that does not fit
+    /// in the image, such as an `allow_partial` match past the border, fails
+    /// with [`CorrMatchError::RoiOutOfBounds`].
+    pub fn explain(&self, image: ImageView<'_, u8>, m: &Match) -> CorrMatchResult<Vec<f32>> {
+        self.check_rotation()?;
+        self.check_image_size(image)?;
+        let rotated;
+        let (t_prime, width, height) = match self.compiled.search_rotation(self.cfg.rotation) {
+            RotationMode::Enabled => {
+                let grid = self
+                    .compiled
+                    .angle_grid(0)
+                    .ok_or(CorrMatchError::IndexOutOfBounds {
+                        index: 0,
+                        len: self.compiled.num_levels(),
+                        context: "level",
+                    })?;
+                rotated = self
+                    .compiled
+                    .cached_zncc_plan(0, grid.nearest_index(m.angle_deg))?;
+                (rotated.values().to_f32(), rotated.width(), rotated.height())
+            }
+            RotationMode::Disabled => {
+                let plan = self.compiled.unmasked_zncc_plan(0)?;
+                (plan.values().to_f32(), plan.width(), plan.height())
+            }
+        };
+
+        let patch = match_region(image, m, (width, height))?;
+        let mut out = Vec::with_capacity(t_prime.len());
+        for ty in 0..height {
+            let row = patch.row(ty).expect("row in bounds");
+            let base = ty * width;
+            for (tx, &value) in row.iter().enumerate() {
+                out.push(t_prime[base + tx] * value as f32);
+            }
         }
+        Ok(out)
+    }
 
-        let use_parallel = self.cfg.use_parallel();
-        let pyramid = ImagePyramid::build_u8(image, self.cfg.max_image_levels)?;
-        let num_levels = pyramid.levels().len().min(self.compiled.num_levels());
+    /// Copies the image pixels under a match into the template frame.
+    ///
+    /// The match position is rounded to the nearest integer placement and the
+    /// template-sized region there is copied. A nonzero `angle_deg` is then
+    /// undone by rotating the patch by `-angle_deg` about its center, so
+    /// corners that come from outside the region are filled with 0. The
+    /// result has the level-0 template size. A placement that does not fit in
+    /// the image, such as an `allow_partial` match past the border, fails with
+    /// [`CorrMatchError::RoiOutOfBounds`].
+    pub fn extract_match_patch(
+        &self,
+        image: ImageView<'_, u8>,
+        m: &Match,
+    ) -> CorrMatchResult<OwnedImage> {
+        let (width, height) =
+            self.compiled
+                .level_size(0)
+                .ok_or(CorrMatchError::IndexOutOfBounds {
+                    index: 0,
+                    len: self.compiled.num_levels(),
+                    context: "level",
+                })?;
+        let patch = match_region(image, m, (width, height))?;
+        if m.angle_deg == 0.0 {
+            OwnedImage::from_view(patch)
+        } else {
+            Ok(rotate_u8_bilinear(patch, -m.angle_deg, 0))
+        }
+    }
+
+    /// Refines an externally provided pose without running the pyramid search.
+    ///
+    /// The seed position is rounded to the nearest pixel and, with rotation
+    /// enabled, its angle is snapped to the nearest level-0 grid angle. The
+    /// seed then goes through the same subpixel/subangle refinement as
+    /// `match_image`, so it should already be within a pixel or so of the
+    /// true peak. Unlike the search path, a seed whose template footprint
+    /// leaves the image is reported as an error instead of being passed through.
+    pub fn refine(&self, image: ImageView<'_, u8>, seed: Match) -> CorrMatchResult<Match> {
+        self.cfg.validate()?;
+        self.check_rotation()?;
+        self.check_image_size(image)?;
+        let (angle_idx, angle_deg) = match self.compiled.search_rotation(self.cfg.rotation) {
+            RotationMode::Enabled => {
+                let grid = self
+                    .compiled
+                    .angle_grid(0)
+                    .ok_or(CorrMatchError::IndexOutOfBounds {
+                        index: 0,
+                        len: self.compiled.num_levels(),
+                        context: "level",
+                    })?;
+                let idx = grid.nearest_index(seed.angle_deg);
+                (idx, grid.angle_at(idx))
+            }
+            RotationMode::Disabled => (0, 0.0),
+        };
+        let peak = Peak {
+            x: seed.x.round().max(0.0) as usize,
+            y: seed.y.round().max(0.0) as usize,
+            score: seed.score,
+            angle_idx,
+        };
+        let mut out = self.refine_final(image, Candidate::from_peak(0, angle_deg, peak))?;
+        out.score = self.cfg.score_clamp.apply(self.cfg.metric, out.score);
+        Ok(out)
+    }
+
+    /// Runs subpixel/subangle refinement at the candidate's own level.
+    ///
+    /// `image` must be the pyramid level matching `cand.level`; the result is
+    /// in that level's coordinates.
+    fn refine_final(&self, image: ImageView<'_, u8>, cand: Candidate) -> CorrMatchResult<Match> {
+        match self.compiled.search_rotation(self.cfg.rotation) {
+            RotationMode::Enabled => {
+                refine_final_match(image, &self.compiled, cand.level, cand, &self.cfg)
+            }
+            RotationMode::Disabled => {
+                refine_final_match_unmasked(image, &self.compiled, cand.level, cand, &self.cfg)
+            }
+        }
+    }
+
+    /// Applies final refinement at the finest searched level, falling back to
+    /// the integer seed, then scales the result to level-0 coordinates,
+    /// rescores it there if `verify_full_res` is set, combines the score
+    /// across levels per `score_aggregation`, and post-processes it
+    /// according to `score_clamp`.
+    ///
+    /// Returns `None` when the refined score falls below `min_score`.
+    fn refine_candidate(&self, pyramid: &ImagePyramid, cand: Candidate) -> Option<Match> {
+        if !self.cfg.final_refinement {
+            let out = self.verify_match(pyramid, level0_match(cand));
+            return self.finish_match(self.aggregate_levels(cand, out));
+        }
+        let refined = pyramid
+            .level(cand.level)
+            .ok_or(CorrMatchError::IndexOutOfBounds {
+                index: cand.level,
+                len: pyramid.levels().len(),
+                context: "image level",
+            })
+            .and_then(|view| self.refine_final(view, cand));
+        let out = match refined {
+            Ok(m) => {
+                let scale = level_scale(cand.level);
+                Match {
+                    x: m.x * scale,
+                    y: m.y * scale,
+                    ..m
+                }
+            }
+            Err(_) => level0_match(cand),
+        };
+        let out = self.verify_match(pyramid, out);
+        self.finish_match(self.aggregate_levels(cand, out))
+    }
+
+    /// Combines the final score of `m` with the scores `cand` had at coarser
+    /// levels according to `score_aggregation`.
+    fn aggregate_levels(&self, cand: Candidate, m: Match) -> Match {
+        Match {
+            score: cand.coarser.aggregate(&self.cfg, m.score),
+            ..m
+        }
+    }
+
+    /// Replaces the score of a level-0 match with the full-resolution score
+    /// at its rounded pose when `verify_full_res` is set.
+    ///
+    /// A pose that cannot be rescored gets `f32::NEG_INFINITY`, so it is
+    /// dropped rather than reported with an unverified score.
+    fn verify_match(&self, pyramid: &ImagePyramid, m: Match) -> Match {
+        if !self.cfg.verify_full_res {
+            return m;
+        }
+        let score = self
+            .full_res_score(pyramid, &m)
+            .unwrap_or(f32::NEG_INFINITY);
+        Match { score, ..m }
+    }
+
+    fn full_res_score(&self, pyramid: &ImagePyramid, m: &Match) -> CorrMatchResult<f32> {
+        let image = pyramid.level(0).ok_or(CorrMatchError::IndexOutOfBounds {
+            index: 0,
+            len: pyramid.levels().len(),
+            context: "image level",
+        })?;
+        let (width, height) =
+            self.compiled
+                .level_size(0)
+                .ok_or(CorrMatchError::IndexOutOfBounds {
+                    index: 0,
+                    len: self.compiled.num_levels(),
+                    context: "level",
+                })?;
+        // Scaled coarse coordinates can overshoot the last valid placement.
+        let max_x = image.width().saturating_sub(width);
+        let max_y = image.height().saturating_sub(height);
+        let x = (m.x.round().max(0.0) as usize).min(max_x);
+        let y = (m.y.round().max(0.0) as usize).min(max_y);
+        let angle_idx = match self.compiled.search_rotation(self.cfg.rotation) {
+            RotationMode::Enabled => self
+                .compiled
+                .angle_grid(0)
+                .ok_or(CorrMatchError::IndexOutOfBounds {
+                    index: 0,
+                    len: self.compiled.num_levels(),
+                    context: "level",
+                })?
+                .nearest_index(m.angle_deg),
+            RotationMode::Disabled => 0,
+        };
+        score_level0_pose(image, &self.compiled, angle_idx, x, y, &self.cfg)
+    }
+
+    /// Drops matches below `min_score` and applies `score_clamp`.
+    fn finish_match(&self, mut out: Match) -> Option<Match> {
+        if !out.score.is_finite() || self.cfg.rank_key(out.score) < self.cfg.min_score {
+            return None;
+        }
+        out.score = self.cfg.score_clamp.apply(self.cfg.metric, out.score);
+        Some(out)
+    }
+
+    /// Refines seeds in score order and returns the first that passes `min_score`.
+    fn refine_best(&self, pyramid: &ImagePyramid, seeds: Vec<Candidate>) -> CorrMatchResult<Match> {
+        self.refine_best_with_level(pyramid, seeds)
+            .map(|(best, _)| best)
+    }
+
+    /// Like `refine_best`, and also returns the pyramid level the reported
+    /// score was computed at: the winning seed's level, or 0 when
+    /// `verify_full_res` rescored it there.
+    fn refine_best_with_level(
+        &self,
+        pyramid: &ImagePyramid,
+        seeds: Vec<Candidate>,
+    ) -> CorrMatchResult<(Match, usize)> {
+        seeds
+            .iter()
+            .find_map(|&cand| {
+                let found = self.refine_candidate(pyramid, cand)?;
+                let best = Match {
+                    peak_sharpness: self
+                        .cfg
+                        .compute_psr
+                        .then(|| self.peak_sharpness(cand, &seeds)),
+                    ..found
+                };
+                let level = if self.cfg.verify_full_res {
+                    0
+                } else {
+                    cand.level
+                };
+                Some((best, level))
+            })
+            .ok_or(CorrMatchError::NoCandidates {
+                reason: "all candidates refined below min_score",
+            })
+    }
+
+    /// Returns the peak-to-sidelobe ratio of `best` against the strongest
+    /// seed outside its NMS radius (see [`MatchConfig::compute_psr`]).
+    fn peak_sharpness(&self, best: Candidate, seeds: &[Candidate]) -> f32 {
+        let radius = self.cfg.nms_radius_at(best.level);
+        let runner_up = seeds
+            .iter()
+            .filter(|cand| cand.x.abs_diff(best.x).max(cand.y.abs_diff(best.y)) > radius)
+            .map(|cand| self.cfg.rank_key(cand.score))
+            .fold(f32::NEG_INFINITY, f32::max);
+        let best = self.cfg.rank_key(best.score);
+        match self.cfg.metric {
+            Metric::Zncc | Metric::Combined { .. } if runner_up > 0.0 => best / runner_up,
+            Metric::Ssd if best < 0.0 => runner_up / best,
+            _ => f32::INFINITY,
+        }
+    }
+
+    /// Returns the surviving candidate beam at every searched pyramid level.
+    ///
+    /// Levels are ordered from coarsest to finest and skip the levels
+    /// stepped over by `level_stride`; the last entry holds the
+    /// level-0 seeds before final subpixel refinement. Positions are scaled
+    /// to level-0 pixels and scores are the raw per-level kernel scores. This
+    /// is intended for visualizing the search and does not change
+    /// `match_image`.
+    pub fn debug_levels(&self, image: ImageView<'_, u8>) -> CorrMatchResult<Vec<Vec<Match>>> {
+        self.cfg.validate()?;
+        let mut levels = Vec::new();
+        self.match_candidates_with(image, |beam| {
+            levels.push(beam.iter().map(|cand| level0_match(*cand)).collect());
+        })?;
+        Ok(levels)
+    }
+
+    fn match_candidates(
+        &self,
+        image: ImageView<'_, u8>,
+    ) -> CorrMatchResult<(ImagePyramid, Vec<Candidate>)> {
+        self.match_candidates_with(image, |_| {})
+    }
+
+    /// Runs coarse-to-fine search, passing the beam at each level to `record`.
+    ///
+    /// An image exactly the size of the level-0 template has a single
+    /// placement, so it is scored directly at `(0, 0)` for every level-0
+    /// angle instead of going through the pyramid.
+    fn match_candidates_with(
+        &self,
+        image: ImageView<'_, u8>,
+        mut record: impl FnMut(&[Candidate]),
+    ) -> CorrMatchResult<(ImagePyramid, Vec<Candidate>)> {
+        self.check_rotation()?;
+        if self.cfg.finest_level() == 0
+            && self.compiled.level_size(0) == Some((image.width(), image.height()))
+        {
+            let pyramid = ImagePyramid::build_u8(image, 1)?;
+            let mut seeds = self.score_exact_fit(&self.cfg, image)?;
+            if seeds.is_empty() && self.cfg.retry_on_empty {
+                seeds = self.score_exact_fit(&self.cfg.relaxed(), image)?;
+            }
+            if seeds.is_empty() {
+                return Err(CorrMatchError::NoCandidates {
+                    reason: "no angle scored above min_score",
+                });
+            }
+            record(&seeds);
+            return Ok((pyramid, seeds));
+        }
+        let pyramid = self.build_pyramid(image, self.cfg.max_image_levels)?;
+        let seeds = self.search_pyramid(&pyramid, image, None, record)?;
+        Ok((pyramid, seeds))
+    }
+
+    fn score_exact_fit(
+        &self,
+        cfg: &MatchConfig,
+        image: ImageView<'_, u8>,
+    ) -> CorrMatchResult<Vec<Candidate>> {
+        #[cfg(feature = "rayon")]
+        if cfg.use_parallel() {
+            return score_exact_fit_par(image, &self.compiled, cfg);
+        }
+        score_exact_fit(image, &self.compiled, cfg)
+    }
+
+    /// Builds the image pyramid with the template's custom downsampler, or
+    /// with `pyramid_filter` when it has none.
+    pub(crate) fn build_pyramid(
+        &self,
+        image: ImageView<'_, u8>,
+        max_levels: usize,
+    ) -> CorrMatchResult<ImagePyramid> {
+        match self.compiled.downsampler() {
+            Some(downsampler) => ImagePyramid::build_with(image, max_levels, downsampler.as_ref()),
+            None => self.cfg.build_pyramid(image, max_levels),
+        }
+    }
+
+    /// Rejects images that the level-0 template does not fit into.
+    pub(crate) fn check_image_size(&self, image: ImageView<'_, u8>) -> CorrMatchResult<()> {
+        match self.compiled.level_size(0) {
+            Some(size) => check_image_fits(image, size),
+            None => Ok(()),
+        }
+    }
+
+    fn check_rotation(&self) -> CorrMatchResult<()> {
+        if matches!(*self.compiled, CompiledTemplate::Unrotated(_))
+            && self.cfg.rotation == RotationMode::Enabled
+        {
+            return Err(CorrMatchError::RotationNotCompiled);
+        }
+        Ok(())
+    }
+
+    /// Matches against a pre-built image pyramid whose level 0 is `image`.
+    ///
+    /// The pyramid may be deeper than `max_image_levels`; extra levels are
+    /// ignored so the result matches `match_image`.
+    pub(crate) fn match_image_in_pyramid(
+        &self,
+        pyramid: &ImagePyramid,
+        image: ImageView<'_, u8>,
+    ) -> CorrMatchResult<Match> {
+        self.cfg.validate()?;
+        self.check_rotation()?;
+        self.match_validated_in_pyramid(pyramid, image)
+    }
+
+    /// Like `match_image_in_pyramid`, for callers that already validated the
+    /// configuration and rotation mode.
+    pub(crate) fn match_validated_in_pyramid(
+        &self,
+        pyramid: &ImagePyramid,
+        image: ImageView<'_, u8>,
+    ) -> CorrMatchResult<Match> {
+        let seeds = self.search_pyramid(pyramid, image, None, |_| {})?;
+        self.refine_best(pyramid, seeds)
+    }
+
+    fn search_pyramid(
+        &self,
+        pyramid: &ImagePyramid,
+        image: ImageView<'_, u8>,
+        window: Option<AngleWindow>,
+        mut record: impl FnMut(&[Candidate]),
+    ) -> CorrMatchResult<Vec<Candidate>> {
+        self.check_image_size(image)?;
+        let num_levels = self
+            .cfg
+            .searched_levels(pyramid.levels().len(), self.compiled.num_levels())?;
         if num_levels == 0 {
             return Err(CorrMatchError::InvalidDimensions {
                 width: image.width(),
                 height: image.height(),
             });
         }
+        let finest = self.cfg.finest_level();
+        if finest >= num_levels {
+            return Err(CorrMatchError::InvalidConfig {
+                reason: "initial_downscale exceeds the searchable pyramid levels",
+            });
+        }
 
         let coarsest = num_levels - 1;
         let coarse_view = pyramid
@@ -261,127 +1871,394 @@ impl Matcher {
                 len: pyramid.levels().len(),
                 context: "image level",
             })?;
-        let mut seeds = match self.cfg.rotation {
+        let mut seeds = self.coarse_seeds(&self.cfg, coarse_view, coarsest, window)?;
+        if seeds.is_empty() && self.cfg.retry_on_empty {
+            seeds = self.coarse_seeds(&self.cfg.relaxed(), coarse_view, coarsest, window)?;
+        }
+        if seeds.is_empty() {
+            return Err(CorrMatchError::NoCandidates {
+                reason: "no coarse candidates",
+            });
+        }
+        record(&seeds);
+
+        for level in self.cfg.refinement_levels(coarsest, finest) {
+            let level_view = pyramid
+                .level(level)
+                .ok_or(CorrMatchError::IndexOutOfBounds {
+                    index: level,
+                    len: pyramid.levels().len(),
+                    context: "image level",
+                })?;
+            let mut refined = self.refine_seeds(&self.cfg, level_view, level, &seeds)?;
+            if refined.is_empty() && self.cfg.retry_on_empty {
+                refined = self.refine_seeds(&self.cfg.relaxed(), level_view, level, &seeds)?;
+            }
+            if refined.is_empty() {
+                return Err(CorrMatchError::NoCandidates {
+                    reason: "no candidates after refinement",
+                });
+            }
+            seeds = refined;
+            record(&seeds);
+        }
+
+        Ok(seeds)
+    }
+
+    /// Scans the coarsest searched level with `cfg`.
+    fn coarse_seeds(
+        &self,
+        cfg: &MatchConfig,
+        coarse_view: ImageView<'_, u8>,
+        coarsest: usize,
+        window: Option<AngleWindow>,
+    ) -> CorrMatchResult<Vec<Candidate>> {
+        match self.compiled.search_rotation(cfg.rotation) {
             RotationMode::Enabled => {
-                if use_parallel {
+                if cfg.use_parallel() {
                     #[cfg(feature = "rayon")]
                     {
-                        coarse_search_level_par(coarse_view, &self.compiled, coarsest, &self.cfg)?
+                        coarse_search_level_par(coarse_view, &self.compiled, coarsest, cfg, window)
                     }
                     #[cfg(not(feature = "rayon"))]
                     {
-                        coarse_search_level(coarse_view, &self.compiled, coarsest, &self.cfg)?
+                        coarse_search_level(coarse_view, &self.compiled, coarsest, cfg, window)
                     }
                 } else {
-                    coarse_search_level(coarse_view, &self.compiled, coarsest, &self.cfg)?
+                    coarse_search_level(coarse_view, &self.compiled, coarsest, cfg, window)
                 }
             }
             RotationMode::Disabled => {
-                if use_parallel {
+                if cfg.use_parallel() {
                     #[cfg(feature = "rayon")]
                     {
-                        coarse_search_level_unmasked_par(
-                            coarse_view,
-                            &self.compiled,
-                            coarsest,
-                            &self.cfg,
-                        )?
+                        coarse_search_level_unmasked_par(coarse_view, &self.compiled, coarsest, cfg)
                     }
                     #[cfg(not(feature = "rayon"))]
                     {
-                        coarse_search_level_unmasked(
-                            coarse_view,
-                            &self.compiled,
-                            coarsest,
-                            &self.cfg,
-                        )?
+                        coarse_search_level_unmasked(coarse_view, &self.compiled, coarsest, cfg)
                     }
                 } else {
-                    coarse_search_level_unmasked(coarse_view, &self.compiled, coarsest, &self.cfg)?
+                    coarse_search_level_unmasked(coarse_view, &self.compiled, coarsest, cfg)
                 }
             }
-        };
-        if seeds.is_empty() {
-            return Err(CorrMatchError::NoCandidates {
-                reason: "no coarse candidates",
-            });
         }
+    }
 
-        for level in (0..coarsest).rev() {
-            let level_view = pyramid
-                .level(level)
-                .ok_or(CorrMatchError::IndexOutOfBounds {
-                    index: level,
-                    len: pyramid.levels().len(),
-                    context: "image level",
-                })?;
-            seeds = match self.cfg.rotation {
-                RotationMode::Enabled => {
-                    if use_parallel {
-                        #[cfg(feature = "rayon")]
-                        {
-                            refine_to_finer_level_par(
-                                level_view,
-                                &self.compiled,
-                                level,
-                                &seeds,
-                                &self.cfg,
-                            )?
-                        }
-                        #[cfg(not(feature = "rayon"))]
-                        {
-                            refine_to_finer_level(
-                                level_view,
-                                &self.compiled,
-                                level,
-                                &seeds,
-                                &self.cfg,
-                            )?
-                        }
-                    } else {
-                        refine_to_finer_level(level_view, &self.compiled, level, &seeds, &self.cfg)?
+    /// Refines `seeds` from the previous level within ROIs at `level` with `cfg`.
+    fn refine_seeds(
+        &self,
+        cfg: &MatchConfig,
+        level_view: ImageView<'_, u8>,
+        level: usize,
+        seeds: &[Candidate],
+    ) -> CorrMatchResult<Vec<Candidate>> {
+        let mut refined = match self.compiled.search_rotation(cfg.rotation) {
+            RotationMode::Enabled => {
+                if cfg.use_parallel() {
+                    #[cfg(feature = "rayon")]
+                    {
+                        refine_to_finer_level_par(level_view, &self.compiled, level, seeds, cfg)
+                    }
+                    #[cfg(not(feature = "rayon"))]
+                    {
+                        refine_to_finer_level(level_view, &self.compiled, level, seeds, cfg)
                     }
+                } else {
+                    refine_to_finer_level(level_view, &self.compiled, level, seeds, cfg)
                 }
-                RotationMode::Disabled => {
-                    if use_parallel {
-                        #[cfg(feature = "rayon")]
-                        {
-                            refine_to_finer_level_unmasked_par(
-                                level_view,
-                                &self.compiled,
-                                level,
-                                &seeds,
-                                &self.cfg,
-                            )?
-                        }
-                        #[cfg(not(feature = "rayon"))]
-                        {
-                            refine_to_finer_level_unmasked(
-                                level_view,
-                                &self.compiled,
-                                level,
-                                &seeds,
-                                &self.cfg,
-                            )?
-                        }
-                    } else {
+            }
+            RotationMode::Disabled => {
+                if cfg.use_parallel() {
+                    #[cfg(feature = "rayon")]
+                    {
+                        refine_to_finer_level_unmasked_par(
+                            level_view,
+                            &self.compiled,
+                            level,
+                            seeds,
+                            cfg,
+                        )
+                    }
+                    #[cfg(not(feature = "rayon"))]
+                    {
                         refine_to_finer_level_unmasked(
                             level_view,
                             &self.compiled,
                             level,
-                            &seeds,
-                            &self.cfg,
-                        )?
+                            seeds,
+                            cfg,
+                        )
                     }
+                } else {
+                    refine_to_finer_level_unmasked(level_view, &self.compiled, level, seeds, cfg)
                 }
-            };
-            if seeds.is_empty() {
-                return Err(CorrMatchError::NoCandidates {
-                    reason: "no candidates after refinement",
-                });
             }
+        }?;
+        inherit_level_scores(cfg, seeds, &mut refined);
+        Ok(refined)
+    }
+}
+
+/// Returns the indices of local maxima of `scores` (ranked by `key`) along
+/// `grid`, whose ends are neighbors when it covers the full circle.
+///
+/// A plateau reports only its first index; non-finite scores are skipped.
+fn angle_maxima(scores: &[f32], grid: &AngleGrid, key: impl Fn(f32) -> f32) -> Vec<usize> {
+    let len = scores.len();
+    let wraps = len as f32 * grid.step_deg() >= 360.0 - 0.5 * grid.step_deg();
+    let neighbor = |idx: usize, forward: bool| match (forward, idx) {
+        (false, 0) => (wraps && len > 1).then_some(len - 1),
+        (false, idx) => Some(idx - 1),
+        (true, idx) if idx + 1 == len => (wraps && len > 1).then_some(0),
+        (true, idx) => Some(idx + 1),
+    };
+    (0..len)
+        .filter(|&idx| {
+            let here = key(scores[idx]);
+            scores[idx].is_finite()
+                && neighbor(idx, false).is_none_or(|prev| here > key(scores[prev]))
+                && neighbor(idx, true).is_none_or(|next| here >= key(scores[next]))
+        })
+        .collect()
+}
+
+/// Returns [`CorrMatchError::ImageSmallerThanTemplate`] when a template of
+/// `tpl_width` x `tpl_height` cannot be placed anywhere in `image`.
+pub(crate) fn check_image_fits(
+    image: ImageView<'_, u8>,
+    (tpl_width, tpl_height): (usize, usize),
+) -> CorrMatchResult<()> {
+    let (img_width, img_height) = (image.width(), image.height());
+    if img_width < tpl_width || img_height < tpl_height {
+        return Err(CorrMatchError::ImageSmallerThanTemplate {
+            img_width,
+            img_height,
+            tpl_width,
+            tpl_height,
+        });
+    }
+    Ok(())
+}
+
+/// Returns the template-sized region of `image` at the rounded position of
+/// `m`, or [`CorrMatchError::RoiOutOfBounds`] when it does not fit, including
+/// the negative positions an `allow_partial` match can report.
+fn match_region<'a>(
+    image: ImageView<'a, u8>,
+    m: &Match,
+    (width, height): (usize, usize),
+) -> CorrMatchResult<ImageView<'a, u8>> {
+    let (x, y) = (m.x.round(), m.y.round());
+    if x < 0.0 || y < 0.0 {
+        return Err(CorrMatchError::RoiOutOfBounds {
+            x: x.max(0.0) as usize,
+            y: y.max(0.0) as usize,
+            width,
+            height,
+            img_width: image.width(),
+            img_height: image.height(),
+        });
+    }
+    image.roi(x as usize, y as usize, width, height)
+}
+
+/// Returns the decimation factor of a pyramid level relative to level 0.
+fn level_scale(level: usize) -> f32 {
+    level_factor(level) as f32
+}
+
+/// Returns `2^(from_level - level - 1)`: how much a refinement step that
+/// skips levels widens its search window (1 for adjacent levels).
+fn skip_factor(level: usize, from_level: usize) -> usize {
+    let skipped = from_level.saturating_sub(level).saturating_sub(1);
+    level_factor(skipped)
+}
+
+/// Converts a candidate at its own pyramid level into level-0 coordinates.
+fn level0_match(cand: Candidate) -> Match {
+    let scale = level_scale(cand.level);
+    Match {
+        x: cand.x as f32 * scale,
+        y: cand.y as f32 * scale,
+        angle_deg: cand.angle_deg,
+        score: cand.score,
+        peak_sharpness: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Candidate, MatchConfig, Matcher, Metric, Peak};
+    use crate::{CompileConfigNoRot, CompiledTemplate, ImagePyramid, ImageView, Template};
+
+    #[test]
+    fn radii_are_unscaled_by_default() {
+        let cfg = MatchConfig::default();
+        for level in 0..4 {
+            assert_eq!(cfg.nms_radius_at(level), cfg.nms_radius);
+            assert_eq!(cfg.roi_radius_at(level), cfg.roi_radius);
         }
+    }
 
-        Ok(seeds)
+    #[test]
+    fn radii_scale_with_level_when_in_level0_pixels() {
+        let cfg = MatchConfig {
+            nms_radius: 6,
+            roi_radius: 8,
+            radius_in_level0_pixels: true,
+            ..MatchConfig::default()
+        };
+        assert_eq!(cfg.nms_radius_at(0), 6);
+        assert_eq!(cfg.nms_radius_at(1), 3);
+        assert_eq!(cfg.nms_radius_at(2), 2);
+        assert_eq!(cfg.nms_radius_at(3), 1);
+        assert_eq!(cfg.roi_radius_at(2), 2);
+        assert_eq!(cfg.roi_radius_at(70), 1);
+
+        let zero = MatchConfig {
+            nms_radius: 0,
+            radius_in_level0_pixels: true,
+            ..MatchConfig::default()
+        };
+        assert_eq!(zero.nms_radius_at(3), 0);
+    }
+
+    #[test]
+    fn level_stride_skips_levels_and_widens_windows() {
+        let cfg = MatchConfig::default();
+        assert_eq!(
+            cfg.refinement_levels(4, 0).collect::<Vec<_>>(),
+            [3, 2, 1, 0]
+        );
+        assert_eq!(cfg.roi_radius_from(2, 3), cfg.roi_radius);
+
+        let cfg = MatchConfig {
+            level_stride: 2,
+            roi_radius: 4,
+            angle_half_range_steps: 1,
+            ..MatchConfig::default()
+        };
+        assert_eq!(cfg.refinement_levels(5, 0).collect::<Vec<_>>(), [3, 1, 0]);
+        assert_eq!(cfg.refinement_levels(4, 1).collect::<Vec<_>>(), [2, 1]);
+        assert_eq!(cfg.refinement_levels(2, 2).count(), 0);
+        assert_eq!(cfg.roi_radius_from(1, 3), 8);
+        assert_eq!(cfg.roi_radius_from(0, 1), 4);
+        assert_eq!(cfg.angle_half_range_from(5.0, 1, 3), 10.0);
+
+        let zero = MatchConfig {
+            level_stride: 0,
+            ..MatchConfig::default()
+        };
+        assert!(zero.validate().is_err());
+    }
+
+    #[test]
+    #[allow(deprecated)]
+    fn per_angle_topk_settings_are_validated_independently() {
+        let cfg = MatchConfig::default().with_per_angle_topk(5);
+        assert_eq!(cfg.coarse_per_angle_topk, 5);
+        assert_eq!(cfg.refine_per_angle_topk, 5);
+
+        // The deprecated field overrides both settings while it is nonzero.
+        let cfg = MatchConfig {
+            per_angle_topk: 2,
+            coarse_per_angle_topk: 4,
+            ..MatchConfig::default()
+        };
+        assert_eq!((cfg.coarse_topk(), cfg.refine_topk()), (2, 2));
+        assert_eq!(cfg.relaxed().refine_topk(), 4);
+        assert_eq!(MatchConfig::default().coarse_topk(), 3);
+
+        let cfg = MatchConfig {
+            coarse_per_angle_topk: 4,
+            refine_per_angle_topk: 1,
+            ..MatchConfig::default()
+        };
+        assert!(cfg.validate().is_ok());
+        let cfg = MatchConfig {
+            refine_per_angle_topk: 0,
+            ..cfg
+        };
+        assert!(cfg.validate().is_err());
+    }
+
+    #[test]
+    fn min_score_beyond_the_metric_range_is_rejected() {
+        let zncc = |min_score| MatchConfig {
+            min_score,
+            ..MatchConfig::default()
+        };
+        assert!(zncc(1.0).validate().is_ok());
+        assert!(zncc(50.0).validate().is_err());
+
+        let ssd = |min_score| MatchConfig {
+            metric: Metric::Ssd,
+            ..zncc(min_score)
+        };
+        assert!(ssd(0.0).validate().is_ok());
+        assert!(ssd(-500.0).validate().is_ok());
+        assert!(ssd(0.5).validate().is_err());
+    }
+
+    fn peak(x: usize, y: usize, score: f32) -> Peak {
+        Peak {
+            x,
+            y,
+            score,
+            angle_idx: 0,
+        }
+    }
+
+    #[test]
+    fn select_beam_keeps_separated_runner_ups_when_enabled() {
+        let peaks = vec![peak(10, 10, 0.9), peak(14, 10, 0.8), peak(60, 40, 0.7)];
+        let cfg = MatchConfig {
+            beam_width: 1,
+            roi_radius: 8,
+            ..MatchConfig::default()
+        };
+        assert_eq!(cfg.select_beam(peaks.clone(), 0).len(), 1);
+
+        let cfg = MatchConfig {
+            keep_runner_up_positions: true,
+            ..cfg
+        };
+        let kept = cfg.select_beam(peaks, 0);
+        assert_eq!(kept.len(), 2);
+        assert_eq!((kept[1].x, kept[1].y), (60, 40));
+    }
+
+    #[test]
+    fn refined_candidates_below_min_score_are_dropped() {
+        let width = 12;
+        let height = 10;
+        let tpl: Vec<u8> = (0..width * height)
+            .map(|i| ((i * 29) % 241) as u8)
+            .collect();
+        let template = Template::new(tpl.clone(), width, height).unwrap();
+        let compiled =
+            CompiledTemplate::compile_unrotated(&template, CompileConfigNoRot { max_levels: 1 })
+                .unwrap();
+        let matcher = Matcher::new(compiled).with_config(MatchConfig {
+            max_image_levels: 1,
+            min_score: 0.5,
+            ..MatchConfig::default()
+        });
+
+        // The image holds a vertically mirrored copy, so the seed's claimed
+        // score is far above what level-0 refinement measures.
+        let flipped: Vec<u8> = tpl.chunks(width).rev().flatten().copied().collect();
+        let image = ImageView::from_slice(&flipped, width, height).unwrap();
+        let pyramid = ImagePyramid::build_u8(image, 1).unwrap();
+        let seed = Candidate::from_peak(0, 0.0, peak(0, 0, 0.9));
+        assert!(matcher.refine_candidate(&pyramid, seed).is_none());
+        assert!(matcher.refine_best(&pyramid, vec![seed]).is_err());
+
+        let image = ImageView::from_slice(&tpl, width, height).unwrap();
+        let pyramid = ImagePyramid::build_u8(image, 1).unwrap();
+        let refined = matcher.refine_candidate(&pyramid, seed).unwrap();
+        assert!(refined.score > 0.99);
     }
 }
@@ -4,15 +4,19 @@
 //! ROI and angle neighborhood to improve position and angle estimates.
 
 use crate::bank::CompiledTemplate;
-use crate::candidate::nms::nms_2d;
 use crate::candidate::topk::Peak;
+use crate::image::pyramid::level_factor;
 use crate::kernel::scalar::{
-    SsdMaskedScalar, SsdUnmaskedScalar, ZnccMaskedScalar, ZnccUnmaskedScalar,
+    CombinedMaskedScalar, CombinedUnmaskedScalar, CombinedWeights, SsdMaskedScalar,
+    SsdUnmaskedScalar, ZnccMaskedScalar, ZnccUnmaskedScalar,
 };
 use crate::kernel::{Kernel, ScanParams};
+use crate::refine::golden::golden_section_max;
 use crate::refine::quad1d::quad_peak_offset_1d;
-use crate::refine::quad2d::refine_subpixel_2d;
-use crate::search::{Match, MatchConfig, Metric};
+use crate::refine::quad2d::{fit_window_center, refine_subpixel_2d_windowed};
+use crate::refine::resample::refine_subpixel_resampled;
+use crate::search::coarse::combined_slot_score;
+use crate::search::{AngleRefine, Match, MatchConfig, Metric, ScoreAgg};
 use crate::util::math::wrap_deg;
 use crate::util::{CorrMatchError, CorrMatchResult};
 use crate::ImageView;
@@ -27,6 +31,43 @@ pub(crate) struct Candidate {
     pub(crate) angle_idx: usize,
     pub(crate) angle_deg: f32,
     pub(crate) score: f32,
+    /// Scores of this candidate's ancestors at coarser levels.
+    pub(crate) coarser: LevelScores,
+}
+
+/// Running summary of a candidate's scores at the coarser levels it was
+/// refined from (see [`MatchConfig::score_aggregation`]).
+#[derive(Clone, Copy, Debug, Default)]
+pub(crate) struct LevelScores {
+    worst: f32,
+    sum: f32,
+    count: u32,
+}
+
+impl LevelScores {
+    /// Adds one level's score.
+    pub(crate) fn push(self, cfg: &MatchConfig, score: f32) -> Self {
+        let worst = if self.count == 0 || cfg.rank_key(score) < cfg.rank_key(self.worst) {
+            score
+        } else {
+            self.worst
+        };
+        Self {
+            worst,
+            sum: self.sum + score,
+            count: self.count + 1,
+        }
+    }
+
+    /// Combines the finest-level score with the recorded coarser scores.
+    pub(crate) fn aggregate(self, cfg: &MatchConfig, finest: f32) -> f32 {
+        let all = self.push(cfg, finest);
+        match cfg.score_aggregation {
+            ScoreAgg::Finest => finest,
+            ScoreAgg::MinAcrossLevels => all.worst,
+            ScoreAgg::MeanAcrossLevels => all.sum / all.count as f32,
+        }
+    }
 }
 
 impl Candidate {
@@ -47,15 +88,48 @@ impl Candidate {
             angle_idx: peak.angle_idx,
             angle_deg,
             score: peak.score,
+            coarser: LevelScores::default(),
         }
     }
 }
 
-fn upscale_pos(x: usize, y: usize) -> (usize, usize) {
-    (x.saturating_mul(2), y.saturating_mul(2))
+/// Gives each refined candidate the level history of the parent it most
+/// likely came from: the nearest parent after upscaling, preferring the
+/// higher-ranked parent on ties since `parents` is in rank order.
+///
+/// Refinement pools the peaks of all parents before NMS, so lineage is
+/// recovered by position. Skipped when `score_aggregation` is `Finest`.
+pub(crate) fn inherit_level_scores(
+    cfg: &MatchConfig,
+    parents: &[Candidate],
+    children: &mut [Candidate],
+) {
+    if cfg.score_aggregation == ScoreAgg::Finest {
+        return;
+    }
+    for child in children.iter_mut() {
+        let nearest = parents
+            .iter()
+            .filter(|parent| parent.level > child.level)
+            .min_by_key(|parent| {
+                let (x, y) = upscale_pos(parent.x, parent.y, parent.level - child.level);
+                let dx = x.abs_diff(child.x);
+                let dy = y.abs_diff(child.y);
+                dx.saturating_mul(dx).saturating_add(dy.saturating_mul(dy))
+            });
+        if let Some(parent) = nearest {
+            child.coarser = parent.coarser.push(cfg, parent.score);
+        }
+    }
+}
+
+/// Maps a position `levels` pyramid levels down, scaling by `2^levels`.
+pub(super) fn upscale_pos(x: usize, y: usize, levels: usize) -> (usize, usize) {
+    let factor = level_factor(levels);
+    (x.saturating_mul(factor), y.saturating_mul(factor))
 }
 
-fn roi_bounds(
+pub(super) fn roi_bounds(
     x: usize,
     y: usize,
     radius: usize,
@@ -126,34 +200,69 @@ pub(crate) fn refine_to_finer_level(
     let max_x = img_width - tpl_width;
     let max_y = img_height - tpl_height;
     let params = ScanParams {
-        topk: cfg.per_angle_topk,
+        topk: cfg.refine_topk(),
         min_var_i: cfg.min_var_i,
         min_score: cfg.min_score,
+        rank_by_magnitude: cfg.rank_by_magnitude(),
+        tie_break: cfg.tie_break,
+        compensated: cfg.deterministic_accumulation,
+        max_var_ratio: None,
     };
     let mut all_peaks = Vec::new();
 
     for cand in prev.iter().copied() {
         debug_assert!(cand.level > finer_level);
-        let (x_up, y_up) = upscale_pos(cand.x, cand.y);
-        let roi = match roi_bounds(x_up, y_up, cfg.roi_radius, max_x, max_y) {
+        let (x_up, y_up) = upscale_pos(cand.x, cand.y, cand.level - finer_level);
+        let roi = match roi_bounds(
+            x_up,
+            y_up,
+            cfg.roi_radius_from(finer_level, cand.level),
+            max_x,
+            max_y,
+        ) {
             Some(bounds) => bounds,
             None => continue,
         };
 
-        let half_range = cfg.angle_half_range_steps as f32 * grid.step_deg();
+        let half_range = cfg.angle_half_range_from(grid.step_deg(), finer_level, cand.level);
         let angle_indices = grid.indices_within(cand.angle_deg, half_range);
         for angle_idx in angle_indices {
             let peaks = match cfg.metric {
                 Metric::Zncc => {
-                    let plan = compiled.rotated_zncc_plan(finer_level, angle_idx)?;
+                    let plan = compiled.cached_zncc_plan(finer_level, angle_idx)?;
+                    let params = ScanParams {
+                        min_var_i: cfg.min_var_i_for(plan.sum_w()),
+                        ..params
+                    };
                     <ZnccMaskedScalar as Kernel>::scan_roi(
-                        image, plan, angle_idx, roi.0, roi.1, roi.2, roi.3, params,
+                        image, &plan, angle_idx, roi.0, roi.1, roi.2, roi.3, params,
                     )?
                 }
                 Metric::Ssd => {
-                    let plan = compiled.rotated_ssd_plan(finer_level, angle_idx)?;
+                    let plan = compiled.cached_ssd_plan(finer_level, angle_idx)?;
                     <SsdMaskedScalar as Kernel>::scan_roi(
-                        image, plan, angle_idx, roi.0, roi.1, roi.2, roi.3, params,
+                        image, &plan, angle_idx, roi.0, roi.1, roi.2, roi.3, params,
+                    )?
+                }
+                Metric::Combined {
+                    zncc_weight,
+                    ssd_weight,
+                } => {
+                    let weights = CombinedWeights::new(zncc_weight, ssd_weight);
+                    let rotated = compiled.rotated(finer_level, angle_idx)?;
+                    let plan = rotated.zncc_plan();
+                    let params = ScanParams {
+                        min_var_i: cfg.min_var_i_for(plan.sum_w()),
+                        ..params
+                    };
+                    CombinedMaskedScalar::scan_roi(
+                        image,
+                        plan,
+                        rotated.ssd_plan(),
+                        weights,
+                        angle_idx,
+                        roi,
+                        params,
                     )?
                 }
             };
@@ -165,10 +274,8 @@ pub(crate) fn refine_to_finer_level(
         return Ok(Vec::new());
     }
 
-    let mut kept = nms_2d(&mut all_peaks, cfg.nms_radius);
-    if kept.len() > cfg.beam_width {
-        kept.truncate(cfg.beam_width);
-    }
+    let order = cfg.peak_order(max_x, max_y, Some(grid));
+    let mut kept = cfg.select_beam(cfg.nms(&mut all_peaks, finer_level, order), finer_level);
 
     let mut out = Vec::with_capacity(kept.len());
     for peak in kept.drain(..) {
@@ -216,19 +323,33 @@ pub(crate) fn refine_to_finer_level_unmasked(
     let max_x = img_width - tpl_width;
     let max_y = img_height - tpl_height;
     let params = ScanParams {
-        topk: cfg.per_angle_topk,
+        topk: cfg.refine_topk(),
         min_var_i: cfg.min_var_i,
         min_score: cfg.min_score,
+        rank_by_magnitude: cfg.rank_by_magnitude(),
+        tie_break: cfg.tie_break,
+        compensated: cfg.deterministic_accumulation,
+        max_var_ratio: None,
     };
     let mut all_peaks = Vec::new();
 
     match cfg.metric {
         Metric::Zncc => {
             let plan = compiled.unmasked_zncc_plan(finer_level)?;
+            let params = ScanParams {
+                min_var_i: cfg.min_var_i_for((plan.width() * plan.height()) as f32),
+                ..params
+            };
             for cand in prev.iter().copied() {
                 debug_assert!(cand.level > finer_level);
-                let (x_up, y_up) = upscale_pos(cand.x, cand.y);
-                let roi = match roi_bounds(x_up, y_up, cfg.roi_radius, max_x, max_y) {
+                let (x_up, y_up) = upscale_pos(cand.x, cand.y, cand.level - finer_level);
+                let roi = match roi_bounds(
+                    x_up,
+                    y_up,
+                    cfg.roi_radius_from(finer_level, cand.level),
+                    max_x,
+                    max_y,
+                ) {
                     Some(bounds) => bounds,
                     None => continue,
                 };
@@ -242,8 +363,14 @@ pub(crate) fn refine_to_finer_level_unmasked(
             let plan = compiled.unmasked_ssd_plan(finer_level)?;
             for cand in prev.iter().copied() {
                 debug_assert!(cand.level > finer_level);
-                let (x_up, y_up) = upscale_pos(cand.x, cand.y);
-                let roi = match roi_bounds(x_up, y_up, cfg.roi_radius, max_x, max_y) {
+                let (x_up, y_up) = upscale_pos(cand.x, cand.y, cand.level - finer_level);
+                let roi = match roi_bounds(
+                    x_up,
+                    y_up,
+                    cfg.roi_radius_from(finer_level, cand.level),
+                    max_x,
+                    max_y,
+                ) {
                     Some(bounds) => bounds,
                     None => continue,
                 };
@@ -253,16 +380,43 @@ pub(crate) fn refine_to_finer_level_unmasked(
                 all_peaks.extend(peaks);
             }
         }
+        Metric::Combined {
+            zncc_weight,
+            ssd_weight,
+        } => {
+            let weights = CombinedWeights::new(zncc_weight, ssd_weight);
+            let zncc = compiled.unmasked_zncc_plan(finer_level)?;
+            let ssd = compiled.unmasked_ssd_plan(finer_level)?;
+            let params = ScanParams {
+                min_var_i: cfg.min_var_i_for((zncc.width() * zncc.height()) as f32),
+                ..params
+            };
+            for cand in prev.iter().copied() {
+                debug_assert!(cand.level > finer_level);
+                let (x_up, y_up) = upscale_pos(cand.x, cand.y, cand.level - finer_level);
+                let roi = match roi_bounds(
+                    x_up,
+                    y_up,
+                    cfg.roi_radius_from(finer_level, cand.level),
+                    max_x,
+                    max_y,
+                ) {
+                    Some(bounds) => bounds,
+                    None => continue,
+                };
+                let peaks =
+                    CombinedUnmaskedScalar::scan_roi(image, zncc, ssd, weights, 0, roi, params)?;
+                all_peaks.extend(peaks);
+            }
+        }
     }
 
     if all_peaks.is_empty() {
         return Ok(Vec::new());
     }
 
-    let mut kept = nms_2d(&mut all_peaks, cfg.nms_radius);
-    if kept.len() > cfg.beam_width {
-        kept.truncate(cfg.beam_width);
-    }
+    let order = cfg.peak_order(max_x, max_y, None);
+    let mut kept = cfg.select_beam(cfg.nms(&mut all_peaks, finer_level, order), finer_level);
 
     let mut out = Vec::with_capacity(kept.len());
     for peak in kept.drain(..) {
@@ -322,32 +476,67 @@ pub(crate) fn refine_to_finer_level_par(
         .copied()
         .map(|cand| {
             debug_assert!(cand.level > finer_level);
-            let (x_up, y_up) = upscale_pos(cand.x, cand.y);
-            let roi = match roi_bounds(x_up, y_up, cfg.roi_radius, max_x, max_y) {
+            let (x_up, y_up) = upscale_pos(cand.x, cand.y, cand.level - finer_level);
+            let roi = match roi_bounds(
+                x_up,
+                y_up,
+                cfg.roi_radius_from(finer_level, cand.level),
+                max_x,
+                max_y,
+            ) {
                 Some(bounds) => bounds,
                 None => return Ok(Vec::new()),
             };
 
-            let half_range = cfg.angle_half_range_steps as f32 * grid.step_deg();
+            let half_range = cfg.angle_half_range_from(grid.step_deg(), finer_level, cand.level);
             let angle_indices = grid.indices_within(cand.angle_deg, half_range);
             let mut local_peaks = Vec::new();
             let params = ScanParams {
-                topk: cfg.per_angle_topk,
+                topk: cfg.refine_topk(),
                 min_var_i: cfg.min_var_i,
                 min_score: cfg.min_score,
+                rank_by_magnitude: cfg.rank_by_magnitude(),
+                tie_break: cfg.tie_break,
+                compensated: cfg.deterministic_accumulation,
+                max_var_ratio: None,
             };
             for angle_idx in angle_indices {
                 let peaks = match cfg.metric {
                     Metric::Zncc => {
-                        let plan = compiled.rotated_zncc_plan(finer_level, angle_idx)?;
+                        let plan = compiled.cached_zncc_plan(finer_level, angle_idx)?;
+                        let params = ScanParams {
+                            min_var_i: cfg.min_var_i_for(plan.sum_w()),
+                            ..params
+                        };
                         <ZnccMaskedScalar as Kernel>::scan_roi(
-                            image, plan, angle_idx, roi.0, roi.1, roi.2, roi.3, params,
+                            image, &plan, angle_idx, roi.0, roi.1, roi.2, roi.3, params,
                         )?
                     }
                     Metric::Ssd => {
-                        let plan = compiled.rotated_ssd_plan(finer_level, angle_idx)?;
+                        let plan = compiled.cached_ssd_plan(finer_level, angle_idx)?;
                         <SsdMaskedScalar as Kernel>::scan_roi(
-                            image, plan, angle_idx, roi.0, roi.1, roi.2, roi.3, params,
+                            image, &plan, angle_idx, roi.0, roi.1, roi.2, roi.3, params,
+                        )?
+                    }
+                    Metric::Combined {
+                        zncc_weight,
+                        ssd_weight,
+                    } => {
+                        let weights = CombinedWeights::new(zncc_weight, ssd_weight);
+                        let rotated = compiled.rotated(finer_level, angle_idx)?;
+                        let plan = rotated.zncc_plan();
+                        let params = ScanParams {
+                            min_var_i: cfg.min_var_i_for(plan.sum_w()),
+                            ..params
+                        };
+                        CombinedMaskedScalar::scan_roi(
+                            image,
+                            plan,
+                            rotated.ssd_plan(),
+                            weights,
+                            angle_idx,
+                            roi,
+                            params,
                         )?
                     }
                 };
@@ -366,10 +555,8 @@ pub(crate) fn refine_to_finer_level_par(
         return Ok(Vec::new());
     }
 
-    let mut kept = nms_2d(&mut all_peaks, cfg.nms_radius);
-    if kept.len() > cfg.beam_width {
-        kept.truncate(cfg.beam_width);
-    }
+    let order = cfg.peak_order(max_x, max_y, Some(grid));
+    let mut kept = cfg.select_beam(cfg.nms(&mut all_peaks, finer_level, order), finer_level);
 
     let mut out = Vec::with_capacity(kept.len());
     for peak in kept.drain(..) {
@@ -418,20 +605,34 @@ pub(crate) fn refine_to_finer_level_unmasked_par(
     let max_x = img_width - tpl_width;
     let max_y = img_height - tpl_height;
     let params = ScanParams {
-        topk: cfg.per_angle_topk,
+        topk: cfg.refine_topk(),
         min_var_i: cfg.min_var_i,
         min_score: cfg.min_score,
+        rank_by_magnitude: cfg.rank_by_magnitude(),
+        tie_break: cfg.tie_break,
+        compensated: cfg.deterministic_accumulation,
+        max_var_ratio: None,
     };
 
     let results: Vec<_> = match cfg.metric {
         Metric::Zncc => {
             let plan = compiled.unmasked_zncc_plan(finer_level)?;
+            let params = ScanParams {
+                min_var_i: cfg.min_var_i_for((plan.width() * plan.height()) as f32),
+                ..params
+            };
             prev.par_iter()
                 .copied()
                 .map(|cand| {
                     debug_assert!(cand.level > finer_level);
-                    let (x_up, y_up) = upscale_pos(cand.x, cand.y);
-                    let roi = match roi_bounds(x_up, y_up, cfg.roi_radius, max_x, max_y) {
+                    let (x_up, y_up) = upscale_pos(cand.x, cand.y, cand.level - finer_level);
+                    let roi = match roi_bounds(
+                        x_up,
+                        y_up,
+                        cfg.roi_radius_from(finer_level, cand.level),
+                        max_x,
+                        max_y,
+                    ) {
                         Some(bounds) => bounds,
                         None => return Ok(Vec::new()),
                     };
@@ -447,8 +648,14 @@ pub(crate) fn refine_to_finer_level_unmasked_par(
                 .copied()
                 .map(|cand| {
                     debug_assert!(cand.level > finer_level);
-                    let (x_up, y_up) = upscale_pos(cand.x, cand.y);
-                    let roi = match roi_bounds(x_up, y_up, cfg.roi_radius, max_x, max_y) {
+                    let (x_up, y_up) = upscale_pos(cand.x, cand.y, cand.level - finer_level);
+                    let roi = match roi_bounds(
+                        x_up,
+                        y_up,
+                        cfg.roi_radius_from(finer_level, cand.level),
+                        max_x,
+                        max_y,
+                    ) {
                         Some(bounds) => bounds,
                         None => return Ok(Vec::new()),
                     };
@@ -458,6 +665,36 @@ pub(crate) fn refine_to_finer_level_unmasked_par(
                 })
                 .collect()
         }
+        Metric::Combined {
+            zncc_weight,
+            ssd_weight,
+        } => {
+            let weights = CombinedWeights::new(zncc_weight, ssd_weight);
+            let zncc = compiled.unmasked_zncc_plan(finer_level)?;
+            let ssd = compiled.unmasked_ssd_plan(finer_level)?;
+            let params = ScanParams {
+                min_var_i: cfg.min_var_i_for((zncc.width() * zncc.height()) as f32),
+                ..params
+            };
+            prev.par_iter()
+                .copied()
+                .map(|cand| {
+                    debug_assert!(cand.level > finer_level);
+                    let (x_up, y_up) = upscale_pos(cand.x, cand.y, cand.level - finer_level);
+                    let roi = match roi_bounds(
+                        x_up,
+                        y_up,
+                        cfg.roi_radius_from(finer_level, cand.level),
+                        max_x,
+                        max_y,
+                    ) {
+                        Some(bounds) => bounds,
+                        None => return Ok(Vec::new()),
+                    };
+                    CombinedUnmaskedScalar::scan_roi(image, zncc, ssd, weights, 0, roi, params)
+                })
+                .collect()
+        }
     };
 
     let mut all_peaks = Vec::new();
@@ -468,10 +705,8 @@ pub(crate) fn refine_to_finer_level_unmasked_par(
         return Ok(Vec::new());
     }
 
-    let mut kept = nms_2d(&mut all_peaks, cfg.nms_radius);
-    if kept.len() > cfg.beam_width {
-        kept.truncate(cfg.beam_width);
-    }
+    let order = cfg.peak_order(max_x, max_y, None);
+    let mut kept = cfg.select_beam(cfg.nms(&mut all_peaks, finer_level, order), finer_level);
 
     let mut out = Vec::with_capacity(kept.len());
     for peak in kept.drain(..) {
@@ -481,6 +716,16 @@ pub(crate) fn refine_to_finer_level_unmasked_par(
     Ok(out)
 }
 
+/// Returns -1 for an inverted match, whose peak is a minimum of the score
+/// surface, so the quadratic fits can work on the negated scores.
+pub(super) fn fit_polarity(cfg: &MatchConfig, center_score: f32) -> f32 {
+    if cfg.rank_by_magnitude() && center_score < 0.0 {
+        -1.0
+    } else {
+        1.0
+    }
+}
+
 /// Refines the best candidate at the finest level with subpixel and subangle fits.
 pub(crate) fn refine_final_match(
     image: ImageView<'_, u8>,
@@ -530,27 +775,34 @@ pub(crate) fn refine_final_match(
         });
     }
 
+    // On a border the 3x3 window slides inward so every sample is valid.
+    let (wx, wy) = (
+        fit_window_center(best.x, max_x),
+        fit_window_center(best.y, max_y),
+    );
+    let (cx, cy) = (best.x + 1 - wx, best.y + 1 - wy);
     let mut s = [[f32::NEG_INFINITY; 3]; 3];
     let offsets = [-1isize, 0, 1];
     let (center_score, sm, sp) = match cfg.metric {
         Metric::Zncc => {
-            let plan = compiled.rotated_zncc_plan(level, best.angle_idx)?;
+            let plan = compiled.cached_zncc_plan(level, best.angle_idx)?;
             for (iy, &dy) in offsets.iter().enumerate() {
-                let y = best.y as isize + dy;
+                let y = wy as isize + dy;
                 if y < 0 || y > max_y as isize {
                     continue;
                 }
                 for (ix, &dx) in offsets.iter().enumerate() {
-                    let x = best.x as isize + dx;
+                    let x = wx as isize + dx;
                     if x < 0 || x > max_x as isize {
                         continue;
                     }
-                    s[iy][ix] = <ZnccMaskedScalar as Kernel>::score_at(
+                    s[iy][ix] = <ZnccMaskedScalar as Kernel>::score_at_with(
                         image,
-                        plan,
+                        &plan,
                         x as usize,
                         y as usize,
-                        cfg.min_var_i,
+                        cfg.min_var_i_for(plan.sum_w()),
+                        cfg.deterministic_accumulation,
                     );
                 }
             }
@@ -558,40 +810,45 @@ pub(crate) fn refine_final_match(
             let len = grid.len();
             let im = (best.angle_idx + len - 1) % len;
             let ip = (best.angle_idx + 1) % len;
-            let sm = <ZnccMaskedScalar as Kernel>::score_at(
+            let plan_m = compiled.cached_zncc_plan(level, im)?;
+            let sm = <ZnccMaskedScalar as Kernel>::score_at_with(
                 image,
-                compiled.rotated_zncc_plan(level, im)?,
+                &plan_m,
                 best.x,
                 best.y,
-                cfg.min_var_i,
+                cfg.min_var_i_for(plan_m.sum_w()),
+                cfg.deterministic_accumulation,
             );
-            let sp = <ZnccMaskedScalar as Kernel>::score_at(
+            let plan_p = compiled.cached_zncc_plan(level, ip)?;
+            let sp = <ZnccMaskedScalar as Kernel>::score_at_with(
                 image,
-                compiled.rotated_zncc_plan(level, ip)?,
+                &plan_p,
                 best.x,
                 best.y,
-                cfg.min_var_i,
+                cfg.min_var_i_for(plan_p.sum_w()),
+                cfg.deterministic_accumulation,
             );
-            (s[1][1], sm, sp)
+            (s[cy][cx], sm, sp)
         }
         Metric::Ssd => {
-            let plan = compiled.rotated_ssd_plan(level, best.angle_idx)?;
+            let plan = compiled.cached_ssd_plan(level, best.angle_idx)?;
             for (iy, &dy) in offsets.iter().enumerate() {
-                let y = best.y as isize + dy;
+                let y = wy as isize + dy;
                 if y < 0 || y > max_y as isize {
                     continue;
                 }
                 for (ix, &dx) in offsets.iter().enumerate() {
-                    let x = best.x as isize + dx;
+                    let x = wx as isize + dx;
                     if x < 0 || x > max_x as isize {
                         continue;
                     }
-                    s[iy][ix] = <SsdMaskedScalar as Kernel>::score_at(
+                    s[iy][ix] = <SsdMaskedScalar as Kernel>::score_at_with(
                         image,
-                        plan,
+                        &plan,
                         x as usize,
                         y as usize,
                         cfg.min_var_i,
+                        cfg.deterministic_accumulation,
                     );
                 }
             }
@@ -599,21 +856,55 @@ pub(crate) fn refine_final_match(
             let len = grid.len();
             let im = (best.angle_idx + len - 1) % len;
             let ip = (best.angle_idx + 1) % len;
-            let sm = <SsdMaskedScalar as Kernel>::score_at(
+            let plan_m = compiled.cached_ssd_plan(level, im)?;
+            let sm = <SsdMaskedScalar as Kernel>::score_at_with(
                 image,
-                compiled.rotated_ssd_plan(level, im)?,
+                &plan_m,
                 best.x,
                 best.y,
                 cfg.min_var_i,
+                cfg.deterministic_accumulation,
             );
-            let sp = <SsdMaskedScalar as Kernel>::score_at(
+            let plan_p = compiled.cached_ssd_plan(level, ip)?;
+            let sp = <SsdMaskedScalar as Kernel>::score_at_with(
                 image,
-                compiled.rotated_ssd_plan(level, ip)?,
+                &plan_p,
                 best.x,
                 best.y,
                 cfg.min_var_i,
+                cfg.deterministic_accumulation,
             );
-            (s[1][1], sm, sp)
+            (s[cy][cx], sm, sp)
+        }
+        Metric::Combined {
+            zncc_weight,
+            ssd_weight,
+        } => {
+            let weights = CombinedWeights::new(zncc_weight, ssd_weight);
+            let rotated = compiled.rotated(level, best.angle_idx)?;
+            for (iy, &dy) in offsets.iter().enumerate() {
+                let y = wy as isize + dy;
+                if y < 0 || y > max_y as isize {
+                    continue;
+                }
+                for (ix, &dx) in offsets.iter().enumerate() {
+                    let x = wx as isize + dx;
+                    if x < 0 || x > max_x as isize {
+                        continue;
+                    }
+                    s[iy][ix] =
+                        combined_slot_score(image, &rotated, x as usize, y as usize, weights, cfg);
+                }
+            }
+
+            let len = grid.len();
+            let im = (best.angle_idx + len - 1) % len;
+            let ip = (best.angle_idx + 1) % len;
+            let rotated_m = compiled.rotated(level, im)?;
+            let sm = combined_slot_score(image, &rotated_m, best.x, best.y, weights, cfg);
+            let rotated_p = compiled.rotated(level, ip)?;
+            let sp = combined_slot_score(image, &rotated_p, best.x, best.y, weights, cfg);
+            (s[cy][cx], sm, sp)
         }
     };
 
@@ -622,13 +913,63 @@ pub(crate) fn refine_final_match(
     } else {
         best.score
     };
-    let (x_ref, y_ref) = refine_subpixel_2d(best.x, best.y, s);
+    let polarity = fit_polarity(cfg, center_score);
+    let (mut x_ref, mut y_ref) = refine_subpixel_2d_windowed(
+        (best.x, best.y),
+        (wx, wy),
+        (max_x, max_y),
+        s.map(|row| row.map(|v| polarity * v)),
+    );
+    if cfg.subpixel_resample && cfg.metric == Metric::Zncc && polarity > 0.0 {
+        let plan = compiled.cached_zncc_plan(level, best.angle_idx)?;
+        let min_var_i = cfg.min_var_i_for(plan.sum_w());
+        (x_ref, y_ref) = refine_subpixel_resampled(image, (&*plan).into(), x_ref, y_ref, min_var_i);
+    }
 
     let len = grid.len();
     debug_assert!(len > 0);
     let center_angle = grid.angle_at(best.angle_idx);
     let step = grid.step_deg();
-    let angle_offset = quad_peak_offset_1d(sm, center_score, sp).unwrap_or(0.0);
+    let angle_offset = match cfg.angle_refine {
+        AngleRefine::GoldenSection { iters } if len > 1 => {
+            let score_at = |offset: f32| -> CorrMatchResult<f32> {
+                let rotated = compiled.rotated_at(level, center_angle + offset * step)?;
+                let score = match cfg.metric {
+                    Metric::Zncc => {
+                        let plan = rotated.zncc_plan();
+                        <ZnccMaskedScalar as Kernel>::score_at_with(
+                            image,
+                            plan,
+                            best.x,
+                            best.y,
+                            cfg.min_var_i_for(plan.sum_w()),
+                            cfg.deterministic_accumulation,
+                        )
+                    }
+                    Metric::Ssd => <SsdMaskedScalar as Kernel>::score_at_with(
+                        image,
+                        rotated.ssd_plan(),
+                        best.x,
+                        best.y,
+                        cfg.min_var_i,
+                        cfg.deterministic_accumulation,
+                    ),
+                    Metric::Combined {
+                        zncc_weight,
+                        ssd_weight,
+                    } => {
+                        let weights = CombinedWeights::new(zncc_weight, ssd_weight);
+                        combined_slot_score(image, &rotated, best.x, best.y, weights, cfg)
+                    }
+                };
+                Ok(polarity * score)
+            };
+            let seed = (0.0, polarity * center_score);
+            golden_section_max(score_at, -1.0, 1.0, iters, seed)?.0
+        }
+        _ => quad_peak_offset_1d(polarity * sm, polarity * center_score, polarity * sp)
+            .unwrap_or(0.0),
+    };
     let angle_deg = wrap_deg(center_angle + angle_offset * step);
 
     Ok(Match {
@@ -636,6 +977,7 @@ pub(crate) fn refine_final_match(
         y: y_ref,
         angle_deg,
         score: center_score,
+        peak_sharpness: None,
     })
 }
 
@@ -681,27 +1023,35 @@ pub(crate) fn refine_final_match_unmasked(
         });
     }
 
+    // On a border the 3x3 window slides inward so every sample is valid.
+    let (wx, wy) = (
+        fit_window_center(best.x, max_x),
+        fit_window_center(best.y, max_y),
+    );
+    let (cx, cy) = (best.x + 1 - wx, best.y + 1 - wy);
     let mut s = [[f32::NEG_INFINITY; 3]; 3];
     let offsets = [-1isize, 0, 1];
     match cfg.metric {
         Metric::Zncc => {
             let plan = compiled.unmasked_zncc_plan(level)?;
+            let min_var_i = cfg.min_var_i_for((plan.width() * plan.height()) as f32);
             for (iy, &dy) in offsets.iter().enumerate() {
-                let y = best.y as isize + dy;
+                let y = wy as isize + dy;
                 if y < 0 || y > max_y as isize {
                     continue;
                 }
                 for (ix, &dx) in offsets.iter().enumerate() {
-                    let x = best.x as isize + dx;
+                    let x = wx as isize + dx;
                     if x < 0 || x > max_x as isize {
                         continue;
                     }
-                    s[iy][ix] = <ZnccUnmaskedScalar as Kernel>::score_at(
+                    s[iy][ix] = <ZnccUnmaskedScalar as Kernel>::score_at_with(
                         image,
                         plan,
                         x as usize,
                         y as usize,
-                        cfg.min_var_i,
+                        min_var_i,
+                        cfg.deterministic_accumulation,
                     );
                 }
             }
@@ -709,38 +1059,82 @@ pub(crate) fn refine_final_match_unmasked(
         Metric::Ssd => {
             let plan = compiled.unmasked_ssd_plan(level)?;
             for (iy, &dy) in offsets.iter().enumerate() {
-                let y = best.y as isize + dy;
+                let y = wy as isize + dy;
                 if y < 0 || y > max_y as isize {
                     continue;
                 }
                 for (ix, &dx) in offsets.iter().enumerate() {
-                    let x = best.x as isize + dx;
+                    let x = wx as isize + dx;
                     if x < 0 || x > max_x as isize {
                         continue;
                     }
-                    s[iy][ix] = <SsdUnmaskedScalar as Kernel>::score_at(
+                    s[iy][ix] = <SsdUnmaskedScalar as Kernel>::score_at_with(
                         image,
                         plan,
                         x as usize,
                         y as usize,
                         cfg.min_var_i,
+                        cfg.deterministic_accumulation,
+                    );
+                }
+            }
+        }
+        Metric::Combined {
+            zncc_weight,
+            ssd_weight,
+        } => {
+            let weights = CombinedWeights::new(zncc_weight, ssd_weight);
+            let zncc = compiled.unmasked_zncc_plan(level)?;
+            let ssd = compiled.unmasked_ssd_plan(level)?;
+            let min_var_i = cfg.min_var_i_for((zncc.width() * zncc.height()) as f32);
+            for (iy, &dy) in offsets.iter().enumerate() {
+                let y = wy as isize + dy;
+                if y < 0 || y > max_y as isize {
+                    continue;
+                }
+                for (ix, &dx) in offsets.iter().enumerate() {
+                    let x = wx as isize + dx;
+                    if x < 0 || x > max_x as isize {
+                        continue;
+                    }
+                    s[iy][ix] = CombinedUnmaskedScalar::score_at(
+                        image,
+                        zncc,
+                        ssd,
+                        weights,
+                        x as usize,
+                        y as usize,
+                        min_var_i,
+                        cfg.deterministic_accumulation,
                     );
                 }
             }
         }
     }
 
-    let center_score = if s[1][1].is_finite() {
-        s[1][1]
+    let center_score = if s[cy][cx].is_finite() {
+        s[cy][cx]
     } else {
         best.score
     };
-    let (x_ref, y_ref) = refine_subpixel_2d(best.x, best.y, s);
+    let polarity = fit_polarity(cfg, center_score);
+    let (mut x_ref, mut y_ref) = refine_subpixel_2d_windowed(
+        (best.x, best.y),
+        (wx, wy),
+        (max_x, max_y),
+        s.map(|row| row.map(|v| polarity * v)),
+    );
+    if cfg.subpixel_resample && cfg.metric == Metric::Zncc && polarity > 0.0 {
+        let plan = compiled.unmasked_zncc_plan(level)?;
+        let min_var_i = cfg.min_var_i_for((plan.width() * plan.height()) as f32);
+        (x_ref, y_ref) = refine_subpixel_resampled(image, plan.into(), x_ref, y_ref, min_var_i);
+    }
 
     Ok(Match {
         x: x_ref,
         y: y_ref,
         angle_deg: 0.0,
         score: center_score,
+        peak_sharpness: None,
     })
 }
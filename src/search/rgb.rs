@@ -0,0 +1,273 @@
+//! Translation-only matching of RGB templates.
+//!
+//! Grayscale conversion maps distinct colors with equal luma to the same
+//! value. The RGB path keeps the channels separate and scores each placement
+//! with the mean of the per-channel ZNCC scores, using the same coarse-to-fine
+//! schedule as the grayscale matcher.
+
+use crate::bank::CompiledTemplate3;
+use crate::candidate::topk::Peak;
+use crate::image::pyramid::ImagePyramid;
+use crate::image::split_rgb;
+use crate::kernel::scalar::ZnccRgbScalar;
+use crate::kernel::ScanParams;
+use crate::refine::quad2d::refine_subpixel_2d;
+use crate::search::refine::{roi_bounds, upscale_pos};
+use crate::search::{check_image_fits, level_scale, Match, MatchConfig, Metric, RotationMode};
+use crate::template::RgbTemplatePlan;
+use crate::util::{CorrMatchError, CorrMatchResult};
+use crate::ImageView;
+use std::sync::Arc;
+
+#[cfg(feature = "rayon")]
+use rayon::prelude::*;
+
+/// Matcher for RGB templates compiled with [`crate::Template3::compile`].
+///
+/// Uses [`MatchConfig`] with the ZNCC metric and rotation disabled;
+/// `subpixel_resample` is not supported. Like [`crate::Matcher`], it is
+/// `Send + Sync` and can be shared across threads.
+#[derive(Clone)]
+pub struct RgbMatcher {
+    compiled: Arc<CompiledTemplate3>,
+    cfg: MatchConfig,
+}
+
+impl RgbMatcher {
+    /// Creates a matcher with default configuration.
+    pub fn new(compiled: CompiledTemplate3) -> Self {
+        Self {
+            compiled: Arc::new(compiled),
+            cfg: MatchConfig::default(),
+        }
+    }
+
+    /// Returns the compiled template assets used by this matcher.
+    pub fn compiled(&self) -> &CompiledTemplate3 {
+        &self.compiled
+    }
+
+    /// Replaces the matcher configuration.
+    pub fn with_config(mut self, cfg: MatchConfig) -> Self {
+        self.cfg = cfg;
+        self
+    }
+
+    /// Matches against an interleaved RGB image (`width * height * 3` bytes).
+    pub fn match_image(&self, data: &[u8], width: usize, height: usize) -> CorrMatchResult<Match> {
+        self.validate()?;
+        let [r, g, b] = split_rgb(data, width, height)?;
+        if let Some(size) = self.compiled.level_size(0) {
+            check_image_fits(r.view(), size)?;
+        }
+        let pyramids = [
+            self.cfg
+                .build_pyramid(r.view(), self.cfg.max_image_levels)?,
+            self.cfg
+                .build_pyramid(g.view(), self.cfg.max_image_levels)?,
+            self.cfg
+                .build_pyramid(b.view(), self.cfg.max_image_levels)?,
+        ];
+
+        let num_levels = self
+            .cfg
+            .searched_levels(pyramids[0].levels().len(), self.compiled.num_levels())?;
+        let finest = self.cfg.finest_level();
+        if finest >= num_levels {
+            return Err(CorrMatchError::InvalidConfig {
+                reason: "initial_downscale exceeds the searchable pyramid levels",
+            });
+        }
+
+        let coarsest = num_levels - 1;
+        let plan = self.compiled.plan(coarsest)?;
+        let coarse_views = level_views(&pyramids, coarsest)?;
+        let mut peaks = ZnccRgbScalar::scan_full(
+            coarse_views,
+            plan,
+            self.scan_params(plan, self.cfg.coarse_topk()),
+        )?;
+        let (max_x, max_y) = max_placement(coarse_views[0], plan.width(), plan.height())?;
+        let order = self.cfg.peak_order(max_x, max_y, None);
+        let mut beam = self
+            .cfg
+            .select_beam(self.cfg.nms(&mut peaks, coarsest, order), coarsest);
+
+        let mut from_level = coarsest;
+        for level in self.cfg.refinement_levels(coarsest, finest) {
+            if beam.is_empty() {
+                break;
+            }
+            beam = self.refine_level(level_views(&pyramids, level)?, level, from_level, &beam)?;
+            from_level = level;
+        }
+
+        let best = beam.first().copied().ok_or(CorrMatchError::NoCandidates {
+            reason: "no RGB candidates",
+        })?;
+        let (x, y) = if self.cfg.final_refinement {
+            self.refine_subpixel(level_views(&pyramids, finest)?, finest, best)?
+        } else {
+            (best.x as f32, best.y as f32)
+        };
+
+        let scale = level_scale(finest);
+        let score = best.score;
+        if !score.is_finite() || self.cfg.rank_key(score) < self.cfg.min_score {
+            return Err(CorrMatchError::NoCandidates {
+                reason: "all candidates refined below min_score",
+            });
+        }
+        Ok(Match {
+            x: x * scale,
+            y: y * scale,
+            angle_deg: 0.0,
+            score: self.cfg.score_clamp.apply(Metric::Zncc, score),
+            peak_sharpness: None,
+        })
+    }
+
+    fn validate(&self) -> CorrMatchResult<()> {
+        self.cfg.validate()?;
+        if self.cfg.metric != Metric::Zncc {
+            return Err(CorrMatchError::InvalidConfig {
+                reason: "RGB matching requires the ZNCC metric",
+            });
+        }
+        if self.cfg.rotation != RotationMode::Disabled {
+            return Err(CorrMatchError::InvalidConfig {
+                reason: "RGB matching does not support rotation",
+            });
+        }
+        if self.cfg.subpixel_resample {
+            return Err(CorrMatchError::InvalidConfig {
+                reason: "RGB matching does not support subpixel_resample",
+            });
+        }
+        Ok(())
+    }
+
+    /// Per-channel variance threshold for the template at one level.
+    fn min_var_i(&self, plan: &RgbTemplatePlan) -> f32 {
+        self.cfg
+            .min_var_i_for((plan.width() * plan.height()) as f32)
+    }
+
+    fn scan_params(&self, plan: &RgbTemplatePlan, topk: usize) -> ScanParams {
+        ScanParams {
+            topk,
+            min_var_i: self.min_var_i(plan),
+            min_score: self.cfg.min_score,
+            rank_by_magnitude: self.cfg.rank_by_magnitude(),
+            tie_break: self.cfg.tie_break,
+            compensated: self.cfg.deterministic_accumulation,
+            max_var_ratio: None,
+        }
+    }
+
+    /// Rescans an ROI around each peak upscaled from `from_level` and keeps the beam.
+    fn refine_level(
+        &self,
+        image: [ImageView<'_, u8>; 3],
+        level: usize,
+        from_level: usize,
+        prev: &[Peak],
+    ) -> CorrMatchResult<Vec<Peak>> {
+        let plan = self.compiled.plan(level)?;
+        let (max_x, max_y) = max_placement(image[0], plan.width(), plan.height())?;
+        let params = self.scan_params(plan, self.cfg.refine_topk());
+        let radius = self.cfg.roi_radius_from(level, from_level);
+
+        let scan = |peak: &Peak| {
+            let (x, y) = upscale_pos(peak.x, peak.y, from_level - level);
+            match roi_bounds(x, y, radius, max_x, max_y) {
+                Some((x0, y0, x1, y1)) => {
+                    ZnccRgbScalar::scan_roi(image, plan, x0, y0, x1, y1, params)
+                }
+                None => Ok(Vec::new()),
+            }
+        };
+        #[cfg(feature = "rayon")]
+        let results: Vec<CorrMatchResult<Vec<Peak>>> = if self.cfg.use_parallel() {
+            prev.par_iter().map(scan).collect()
+        } else {
+            prev.iter().map(scan).collect()
+        };
+        #[cfg(not(feature = "rayon"))]
+        let results: Vec<CorrMatchResult<Vec<Peak>>> = prev.iter().map(scan).collect();
+
+        let mut all_peaks = Vec::new();
+        for result in results {
+            all_peaks.extend(result?);
+        }
+        let order = self.cfg.peak_order(max_x, max_y, None);
+        Ok(self
+            .cfg
+            .select_beam(self.cfg.nms(&mut all_peaks, level, order), level))
+    }
+
+    /// Fits a subpixel position on the 3x3 neighborhood of mean scores.
+    fn refine_subpixel(
+        &self,
+        image: [ImageView<'_, u8>; 3],
+        level: usize,
+        best: Peak,
+    ) -> CorrMatchResult<(f32, f32)> {
+        let plan = self.compiled.plan(level)?;
+        let min_var_i = self.min_var_i(plan);
+        let mut s = [[f32::NEG_INFINITY; 3]; 3];
+        for (iy, row) in s.iter_mut().enumerate() {
+            let Some(y) = (best.y + iy).checked_sub(1) else {
+                continue;
+            };
+            for (ix, score) in row.iter_mut().enumerate() {
+                let Some(x) = (best.x + ix).checked_sub(1) else {
+                    continue;
+                };
+                *score = ZnccRgbScalar::score_at(image, plan, x, y, min_var_i);
+            }
+        }
+        Ok(refine_subpixel_2d(best.x, best.y, s))
+    }
+}
+
+fn level_views(
+    pyramids: &[ImagePyramid; 3],
+    level: usize,
+) -> CorrMatchResult<[ImageView<'_, u8>; 3]> {
+    Ok([
+        level_view(&pyramids[0], level)?,
+        level_view(&pyramids[1], level)?,
+        level_view(&pyramids[2], level)?,
+    ])
+}
+
+fn level_view(pyramid: &ImagePyramid, level: usize) -> CorrMatchResult<ImageView<'_, u8>> {
+    pyramid
+        .level(level)
+        .ok_or(CorrMatchError::IndexOutOfBounds {
+            index: level,
+            len: pyramid.levels().len(),
+            context: "image level",
+        })
+}
+
+fn max_placement(
+    image: ImageView<'_, u8>,
+    tpl_width: usize,
+    tpl_height: usize,
+) -> CorrMatchResult<(usize, usize)> {
+    let img_width = image.width();
+    let img_height = image.height();
+    if img_width < tpl_width || img_height < tpl_height {
+        return Err(CorrMatchError::RoiOutOfBounds {
+            x: 0,
+            y: 0,
+            width: tpl_width,
+            height: tpl_height,
+            img_width,
+            img_height,
+        });
+    }
+    Ok((img_width - tpl_width, img_height - tpl_height))
+}
@@ -1,11 +1,12 @@
 //! Dense scan over search regions.
 
-use crate::candidate::topk::Peak;
-use crate::kernel::scalar::ZnccMaskedScalar;
+use crate::candidate::topk::{Peak, TieBreak};
+use crate::kernel::scalar::{ZnccMaskedScalar, ZnccUnmaskedScalar};
 use crate::kernel::{Kernel, ScanParams};
-use crate::template::MaskedTemplatePlan;
-use crate::util::CorrMatchResult;
+use crate::template::{MaskedTemplatePlan, TemplatePlan};
+use crate::util::{CorrMatchError, CorrMatchResult};
 use crate::ImageView;
+use alloc::vec::Vec;
 
 /// Computes the masked ZNCC score for a single placement.
 ///
@@ -22,6 +23,35 @@ pub fn score_masked_zncc_at(
     <ZnccMaskedScalar as Kernel>::score_at(image, tpl, x, y, min_var_i)
 }
 
+/// Computes plain ZNCC between two contiguous `width` x `height` patches.
+///
+/// The score is symmetric in `a` and `b` and lies in `[-1, 1]`. A flat
+/// patch has no defined correlation and scores `f32::NEG_INFINITY`, as in
+/// the scan kernels. Returns an error unless both buffers hold exactly
+/// `width * height` pixels.
+pub fn zncc_score(a: &[u8], b: &[u8], width: usize, height: usize) -> CorrMatchResult<f32> {
+    let needed = width
+        .checked_mul(height)
+        .ok_or(CorrMatchError::InvalidDimensions { width, height })?;
+    for len in [a.len(), b.len()] {
+        if len < needed {
+            return Err(CorrMatchError::BufferTooSmall { needed, got: len });
+        }
+        if len > needed {
+            return Err(CorrMatchError::InvalidDimensions { width, height });
+        }
+    }
+    let plan = match TemplatePlan::from_view(ImageView::from_slice(a, width, height)?) {
+        Ok(plan) => plan,
+        Err(CorrMatchError::DegenerateTemplate { .. }) => return Ok(f32::NEG_INFINITY),
+        Err(err) => return Err(err),
+    };
+    let b = ImageView::from_slice(b, width, height)?;
+    Ok(<ZnccUnmaskedScalar as Kernel>::score_at(
+        b, &plan, 0, 0, 1e-8,
+    ))
+}
+
 /// Scans an image with a masked ZNCC template and returns the top-K peaks.
 ///
 /// The score is expected to lie in approximately `[-1, 1]` for normalized data.
@@ -48,10 +78,37 @@ pub fn scan_masked_zncc_scalar_full(
         topk,
         min_var_i,
         min_score,
+        rank_by_magnitude: false,
+        tie_break: TieBreak::TopLeftFirst,
+        compensated: false,
+        max_var_ratio: None,
     };
     <ZnccMaskedScalar as Kernel>::scan_full(image, tpl, angle_idx, params)
 }
 
+/// Scans the full valid placement range for an unmasked ZNCC template.
+///
+/// This is the kernel used for rotated slots whose mask covers every pixel.
+pub fn scan_zncc_scalar_full(
+    image: ImageView<'_, u8>,
+    tpl: &TemplatePlan,
+    angle_idx: usize,
+    topk: usize,
+    min_var_i: f32,
+    min_score: f32,
+) -> CorrMatchResult<Vec<Peak>> {
+    let params = ScanParams {
+        topk,
+        min_var_i,
+        min_score,
+        rank_by_magnitude: false,
+        tie_break: TieBreak::TopLeftFirst,
+        compensated: false,
+        max_var_ratio: None,
+    };
+    <ZnccUnmaskedScalar as Kernel>::scan_full(image, tpl, angle_idx, params)
+}
+
 /// Scans an ROI of placement coordinates for a masked ZNCC template.
 #[allow(clippy::too_many_arguments)]
 pub fn scan_masked_zncc_scalar_roi(
@@ -70,6 +127,10 @@ pub fn scan_masked_zncc_scalar_roi(
         topk,
         min_var_i,
         min_score,
+        rank_by_magnitude: false,
+        tie_break: TieBreak::TopLeftFirst,
+        compensated: false,
+        max_var_ratio: None,
     };
     <ZnccMaskedScalar as Kernel>::scan_roi(image, tpl, angle_idx, x0, y0, x1, y1, params)
 }
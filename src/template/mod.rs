@@ -1,13 +1,21 @@
 //! Template storage and planning utilities.
 
-use crate::bank::{CompileConfig, CompiledTemplate};
-use crate::image::{ImageView, OwnedImage};
+#[cfg(feature = "std")]
+use crate::bank::{CompileConfig, CompileConfigNoRot, CompiledTemplate, CompiledTemplate3};
+use crate::image::{split_rgb, ImageView, OwnedImage};
 use crate::util::CorrMatchResult;
+use alloc::vec::Vec;
 
 mod plan;
+#[cfg(feature = "std")]
 pub mod rotate;
 
-pub use plan::{MaskedSsdTemplatePlan, MaskedTemplatePlan, SsdTemplatePlan, TemplatePlan};
+pub(crate) use plan::{MaskLookup, PlanMask, PlanValue, PlanValues};
+pub use plan::{
+    MaskedSsdTemplatePlan, MaskedTemplatePlan, RgbTemplatePlan, SsdTemplatePlan, TemplatePlan,
+};
+#[cfg(feature = "std")]
+pub(crate) use plan::{PackedMask, ZnccTerms};
 
 /// Owned template image in contiguous grayscale format.
 ///
@@ -18,11 +26,33 @@ pub struct Template {
 
 impl Template {
     /// Creates a template from a contiguous grayscale buffer.
+    ///
+    /// Any non-zero size is accepted here, down to 1×1. Compiling requires
+    /// non-zero variance, so a 1×1 (or flat) template fails with
+    /// `DegenerateTemplate`; 1×N strips compile without rotation, while
+    /// rotation needs both sides at least 3 pixels.
+    /// Images searched must be at least as large as the template, or matching
+    /// fails with `ImageSmallerThanTemplate`.
     pub fn new(data: Vec<u8>, width: usize, height: usize) -> CorrMatchResult<Self> {
         let img = OwnedImage::new(data, width, height)?;
         Ok(Self { img })
     }
 
+    /// Creates a template by copying the `width x height` region of `image`
+    /// whose top-left corner is at `(x, y)`.
+    ///
+    /// Fails with `RoiOutOfBounds` if the region does not fit in `image`.
+    pub fn from_region(
+        image: ImageView<'_, u8>,
+        x: usize,
+        y: usize,
+        width: usize,
+        height: usize,
+    ) -> CorrMatchResult<Self> {
+        let img = OwnedImage::from_view(image.roi(x, y, width, height)?)?;
+        Ok(Self { img })
+    }
+
     /// Returns the template width.
     pub fn width(&self) -> usize {
         self.img.width()
@@ -41,7 +71,53 @@ impl Template {
     /// Compiles template assets for matching with rotation support.
     ///
     /// For translation-only matching, use `CompiledTemplate::compile_unrotated`.
+    #[cfg(feature = "std")]
     pub fn compile(&self, cfg: CompileConfig) -> CorrMatchResult<CompiledTemplate> {
         CompiledTemplate::compile_rotated(self, cfg)
     }
 }
+
+/// Owned RGB template stored as three planar channels.
+///
+/// Use `Template3::compile` to build assets for `RgbMatcher`.
+pub struct Template3 {
+    channels: [OwnedImage; 3],
+}
+
+impl Template3 {
+    /// Creates a template from an interleaved RGB buffer (`width * height * 3` bytes).
+    pub fn new(data: Vec<u8>, width: usize, height: usize) -> CorrMatchResult<Self> {
+        let channels = split_rgb(&data, width, height)?;
+        Ok(Self { channels })
+    }
+
+    /// Returns the template width.
+    pub fn width(&self) -> usize {
+        self.channels[0].width()
+    }
+
+    /// Returns the template height.
+    pub fn height(&self) -> usize {
+        self.channels[0].height()
+    }
+
+    /// Returns a borrowed view of one channel (0 = red, 1 = green, 2 = blue).
+    pub fn channel(&self, index: usize) -> Option<ImageView<'_, u8>> {
+        self.channels.get(index).map(OwnedImage::view)
+    }
+
+    /// Compiles translation-only assets for RGB matching.
+    #[cfg(feature = "std")]
+    pub fn compile(&self, cfg: CompileConfigNoRot) -> CorrMatchResult<CompiledTemplate3> {
+        CompiledTemplate3::compile(self, cfg)
+    }
+
+    #[cfg(feature = "std")]
+    pub(crate) fn views(&self) -> [ImageView<'_, u8>; 3] {
+        [
+            self.channels[0].view(),
+            self.channels[1].view(),
+            self.channels[2].view(),
+        ]
+    }
+}
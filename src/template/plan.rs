@@ -1,8 +1,215 @@
 //! Template plan precomputation for ZNCC and SSD metrics.
 
 use crate::image::ImageView;
+#[cfg(not(feature = "std"))]
+use crate::util::float::FloatExt;
 use crate::util::{CorrMatchError, CorrMatchResult};
-use std::sync::Arc;
+#[cfg(feature = "std")]
+use alloc::borrow::Cow;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+
+/// Zero-mean template values, stored in full or half precision.
+///
+/// Half-precision storage (feature `half`) halves the memory of a ZNCC plan's
+/// largest buffer. Values are widened to `f32` as the kernels read them, so
+/// accumulation still happens in `f32`. The `f32` form of half-precision
+/// values is only built when a caller asks for it through the public
+/// `t_prime()` accessors.
+pub(crate) enum PlanValues {
+    F32(Vec<f32>),
+    #[cfg(feature = "half")]
+    F16 {
+        values: Vec<half::f16>,
+        decoded: std::sync::OnceLock<Vec<f32>>,
+    },
+}
+
+impl PlanValues {
+    /// Returns the values in `f32`, decoding half precision on first use.
+    fn as_f32(&self) -> &[f32] {
+        match self {
+            Self::F32(values) => values,
+            #[cfg(feature = "half")]
+            Self::F16 { values, decoded } => decoded.get_or_init(|| widen_all(values)),
+        }
+    }
+
+    /// Returns the values in `f32` without keeping a decoded copy.
+    #[cfg(feature = "std")]
+    pub(crate) fn to_f32(&self) -> Cow<'_, [f32]> {
+        match self {
+            Self::F32(values) => Cow::Borrowed(values),
+            #[cfg(feature = "half")]
+            Self::F16 { values, decoded } => match decoded.get() {
+                Some(decoded) => Cow::Borrowed(decoded),
+                None => Cow::Owned(widen_all(values)),
+            },
+        }
+    }
+
+    /// Returns value `idx` widened to `f32`.
+    #[cfg(feature = "std")]
+    #[inline]
+    pub(crate) fn get(&self, idx: usize) -> f32 {
+        match self {
+            Self::F32(values) => values[idx],
+            #[cfg(feature = "half")]
+            Self::F16 { values, .. } => values[idx].widen(),
+        }
+    }
+
+    fn is_compact(&self) -> bool {
+        !matches!(self, Self::F32(_))
+    }
+
+    #[cfg(feature = "half")]
+    fn into_compact(self) -> Self {
+        match self {
+            Self::F32(values) => Self::F16 {
+                values: values.into_iter().map(half::f16::from_f32).collect(),
+                decoded: std::sync::OnceLock::new(),
+            },
+            compact => compact,
+        }
+    }
+}
+
+#[cfg(feature = "half")]
+fn widen_all(values: &[half::f16]) -> Vec<f32> {
+    values.iter().map(|v| v.to_f32()).collect()
+}
+
+/// Per-pixel template validity, stored as bytes or packed bits.
+///
+/// Packed storage (`CompileConfig::compact_mask`) keeps 8 pixels per byte in
+/// the layout of [`pack_mask_bits`], cutting mask memory by 8x; kernels then
+/// extract each bit as they read it. The byte form of a packed mask is only
+/// built when a caller asks for it through the public `mask()` accessors.
+#[derive(Clone)]
+pub(crate) enum PlanMask {
+    Bytes(Arc<[u8]>),
+    // Only the std-only rotation cache packs masks.
+    #[cfg(feature = "std")]
+    Bits {
+        bits: Arc<[u8]>,
+        bytes: std::sync::OnceLock<Arc<[u8]>>,
+    },
+}
+
+impl PlanMask {
+    /// Packs a byte mask (nonzero = valid) into bits.
+    #[cfg(feature = "std")]
+    pub(crate) fn packed(mask: &[u8]) -> Self {
+        Self::Bits {
+            bits: Arc::from(pack_mask_bits(mask)),
+            bytes: std::sync::OnceLock::new(),
+        }
+    }
+
+    /// Returns the mask as bytes, unpacking bits on first use.
+    fn bytes(&self, len: usize) -> &[u8] {
+        match self {
+            Self::Bytes(mask) => &mask[..len],
+            #[cfg(feature = "std")]
+            Self::Bits { bits, bytes } => bytes.get_or_init(|| unpack_mask_bits(bits, len)),
+        }
+    }
+
+    /// Returns the mask as bytes without keeping an unpacked copy.
+    #[cfg(feature = "std")]
+    pub(crate) fn to_bytes(&self, len: usize) -> Cow<'_, [u8]> {
+        match self {
+            Self::Bytes(mask) => Cow::Borrowed(&mask[..len]),
+            Self::Bits { bits, bytes } => match bytes.get() {
+                Some(bytes) => Cow::Borrowed(bytes),
+                None => Cow::Owned(unpack_mask_bits(bits, len).to_vec()),
+            },
+        }
+    }
+
+    fn bits(&self) -> Option<&[u8]> {
+        match self {
+            Self::Bytes(_) => None,
+            #[cfg(feature = "std")]
+            Self::Bits { bits, .. } => Some(bits),
+        }
+    }
+
+    /// Returns true when pixel `idx` is valid.
+    #[cfg(feature = "std")]
+    #[inline]
+    pub(crate) fn valid(&self, idx: usize) -> bool {
+        match self {
+            Self::Bytes(mask) => mask[..].valid(idx),
+            Self::Bits { bits, .. } => PackedMask(bits).valid(idx),
+        }
+    }
+}
+
+/// Packs a byte mask (nonzero = valid) into bits, 8 pixels per byte with the
+/// first pixel in the least significant bit.
+#[cfg(feature = "std")]
+pub(crate) fn pack_mask_bits(mask: &[u8]) -> Vec<u8> {
+    let mut bits = alloc::vec![0u8; mask.len().div_ceil(8)];
+    for (idx, _) in mask.iter().enumerate().filter(|&(_, &m)| m != 0) {
+        bits[idx / 8] |= 1 << (idx % 8);
+    }
+    bits
+}
+
+/// Unpacks the first `len` pixels of a mask packed by [`pack_mask_bits`].
+#[cfg(feature = "std")]
+fn unpack_mask_bits(bits: &[u8], len: usize) -> Arc<[u8]> {
+    (0..len)
+        .map(|idx| u8::from(PackedMask(bits).valid(idx)))
+        .collect()
+}
+
+/// A template mask that kernels read pixel by pixel.
+pub(crate) trait MaskLookup: Copy {
+    fn valid(self, idx: usize) -> bool;
+}
+
+impl MaskLookup for &[u8] {
+    #[inline]
+    fn valid(self, idx: usize) -> bool {
+        self[idx] != 0
+    }
+}
+
+/// A mask packed by [`pack_mask_bits`].
+#[cfg(feature = "std")]
+#[derive(Clone, Copy)]
+pub(crate) struct PackedMask<'a>(pub(crate) &'a [u8]);
+
+#[cfg(feature = "std")]
+impl MaskLookup for PackedMask<'_> {
+    #[inline]
+    fn valid(self, idx: usize) -> bool {
+        (self.0[idx / 8] >> (idx % 8)) & 1 != 0
+    }
+}
+
+/// A stored template value that kernels widen to `f32`.
+pub(crate) trait PlanValue: Copy {
+    fn widen(self) -> f32;
+}
+
+impl PlanValue for f32 {
+    #[inline]
+    fn widen(self) -> f32 {
+        self
+    }
+}
+
+#[cfg(feature = "half")]
+impl PlanValue for half::f16 {
+    #[inline]
+    fn widen(self) -> f32 {
+        self.to_f32()
+    }
+}
 
 /// Precomputed statistics and zero-mean buffer for unmasked ZNCC matching.
 pub struct TemplatePlan {
@@ -11,7 +218,7 @@ pub struct TemplatePlan {
     mean: f32,
     inv_std: f32,
     var_t: f32,
-    zero_mean: Vec<f32>,
+    zero_mean: PlanValues,
 }
 
 impl TemplatePlan {
@@ -78,7 +285,7 @@ impl TemplatePlan {
             mean,
             inv_std,
             var_t,
-            zero_mean,
+            zero_mean: PlanValues::F32(zero_mean),
         })
     }
 
@@ -108,16 +315,103 @@ impl TemplatePlan {
     }
 
     /// Returns the zero-mean template buffer in row-major order.
+    ///
+    /// Compact plans decode their half-precision values on the first call and
+    /// keep the decoded buffer alongside them.
     pub fn t_prime(&self) -> &[f32] {
-        &self.zero_mean
+        self.zero_mean.as_f32()
     }
 
     /// Returns the zero-mean template buffer in row-major order.
+    ///
+    /// Same buffer as [`Self::t_prime`].
     pub fn zero_mean(&self) -> &[f32] {
+        self.zero_mean.as_f32()
+    }
+
+    /// Returns true when the zero-mean buffer is stored in half precision.
+    pub fn is_compact(&self) -> bool {
+        self.zero_mean.is_compact()
+    }
+
+    /// Stores the zero-mean buffer in half precision.
+    ///
+    /// `var_t` keeps its full-precision value, so scores drift by roughly
+    /// 1e-4 from the full-precision plan.
+    #[cfg(feature = "half")]
+    pub fn into_compact(self) -> Self {
+        Self {
+            zero_mean: self.zero_mean.into_compact(),
+            ..self
+        }
+    }
+
+    pub(crate) fn values(&self) -> &PlanValues {
         &self.zero_mean
     }
 }
 
+/// Per-channel ZNCC plans for an RGB template.
+///
+/// Channels with zero variance (for example the blue channel of a pure red
+/// part) carry no shape information and are left out of the score.
+pub struct RgbTemplatePlan {
+    width: usize,
+    height: usize,
+    channels: [Option<TemplatePlan>; 3],
+}
+
+impl RgbTemplatePlan {
+    /// Builds a plan from three planar channel views of equal size.
+    ///
+    /// Fails with `DegenerateTemplate` when every channel is flat.
+    pub fn from_views(channels: [ImageView<'_, u8>; 3]) -> CorrMatchResult<Self> {
+        let width = channels[0].width();
+        let height = channels[0].height();
+        if channels
+            .iter()
+            .any(|view| view.width() != width || view.height() != height)
+        {
+            return Err(CorrMatchError::InvalidDimensions { width, height });
+        }
+
+        let mut plans = [None, None, None];
+        for (slot, view) in plans.iter_mut().zip(channels) {
+            *slot = match TemplatePlan::from_view(view) {
+                Ok(plan) => Some(plan),
+                Err(CorrMatchError::DegenerateTemplate { .. }) => None,
+                Err(err) => return Err(err),
+            };
+        }
+        if plans.iter().all(Option::is_none) {
+            return Err(CorrMatchError::DegenerateTemplate {
+                reason: "zero variance in every channel",
+            });
+        }
+
+        Ok(Self {
+            width,
+            height,
+            channels: plans,
+        })
+    }
+
+    /// Returns the template width in pixels.
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    /// Returns the template height in pixels.
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// Returns the plan for a channel, or `None` if that channel is flat.
+    pub fn channel(&self, index: usize) -> Option<&TemplatePlan> {
+        self.channels.get(index).and_then(Option::as_ref)
+    }
+}
+
 /// Precomputed template buffer for SSD matching.
 pub struct SsdTemplatePlan {
     width: usize,
@@ -175,13 +469,18 @@ impl SsdTemplatePlan {
 }
 
 /// Precomputed masked statistics for ZNCC-style matching on rotated templates.
+///
+/// Plans built from a binary mask weight every valid pixel equally. Plans built
+/// with `from_rotated_weights` carry continuous per-pixel weights, and the
+/// statistics (`sum_w`, mean, `var_t`) and image accumulation are weighted.
 pub struct MaskedTemplatePlan {
     width: usize,
     height: usize,
     sum_w: f32,
     var_t: f32,
-    t_prime: Vec<f32>,
-    mask: Arc<[u8]>,
+    t_prime: PlanValues,
+    mask: PlanMask,
+    weights: Option<Vec<f32>>,
     angle_deg: f32,
 }
 
@@ -277,8 +576,107 @@ impl MaskedTemplatePlan {
             height,
             sum_w,
             var_t,
-            t_prime,
-            mask,
+            t_prime: PlanValues::F32(t_prime),
+            mask: PlanMask::Bytes(mask),
+            weights: None,
+            angle_deg,
+        })
+    }
+
+    /// Builds a masked plan from a rotated template view and soft weights.
+    ///
+    /// Weights must be finite and non-negative; a weight of zero excludes the
+    /// pixel. Weighted ZNCC uses `sum_w = Σw`, `mu_t = Σ(w·t) / sum_w`,
+    /// `t' = w·(t - mu_t)`, and `var_t = Σ w·(t - mu_t)²`, with the image
+    /// window statistics accumulated using the same weights.
+    pub fn from_rotated_weights(
+        rot: ImageView<'_, u8>,
+        weights: Vec<f32>,
+        angle_deg: f32,
+    ) -> CorrMatchResult<Self> {
+        let width = rot.width();
+        let height = rot.height();
+        let needed = width
+            .checked_mul(height)
+            .ok_or(CorrMatchError::InvalidDimensions { width, height })?;
+        if weights.len() < needed {
+            return Err(CorrMatchError::BufferTooSmall {
+                needed,
+                got: weights.len(),
+            });
+        }
+        if weights.len() > needed {
+            return Err(CorrMatchError::InvalidDimensions { width, height });
+        }
+        if weights.iter().any(|w| !w.is_finite() || *w < 0.0) {
+            return Err(CorrMatchError::DegenerateTemplate {
+                reason: "mask weights must be finite and non-negative",
+            });
+        }
+
+        let mut sum_w = 0.0f32;
+        let mut sum_wt = 0.0f32;
+        for y in 0..height {
+            let row = rot.row(y).ok_or_else(|| {
+                let needed = (y + 1)
+                    .checked_mul(rot.stride())
+                    .and_then(|v| v.checked_add(rot.width()))
+                    .unwrap_or(usize::MAX);
+                CorrMatchError::BufferTooSmall {
+                    needed,
+                    got: rot.as_slice().len(),
+                }
+            })?;
+            for (x, &value) in row.iter().enumerate() {
+                let w = weights[y * width + x];
+                sum_w += w;
+                sum_wt += w * value as f32;
+            }
+        }
+
+        if sum_w <= 1e-8 {
+            return Err(CorrMatchError::DegenerateTemplate {
+                reason: "mask has no valid pixels",
+            });
+        }
+
+        let mu_t = sum_wt / sum_w;
+        let mut t_prime = Vec::with_capacity(needed);
+        let mut var_t = 0.0f32;
+        for y in 0..height {
+            let row = rot.row(y).ok_or_else(|| {
+                let needed = (y + 1)
+                    .checked_mul(rot.stride())
+                    .and_then(|v| v.checked_add(rot.width()))
+                    .unwrap_or(usize::MAX);
+                CorrMatchError::BufferTooSmall {
+                    needed,
+                    got: rot.as_slice().len(),
+                }
+            })?;
+            for (x, &value) in row.iter().enumerate() {
+                let w = weights[y * width + x];
+                let centered = value as f32 - mu_t;
+                t_prime.push(w * centered);
+                var_t += w * centered * centered;
+            }
+        }
+
+        if var_t <= 1e-8 {
+            return Err(CorrMatchError::DegenerateTemplate {
+                reason: "template variance too small",
+            });
+        }
+
+        let mask: Arc<[u8]> = weights.iter().map(|&w| u8::from(w > 0.0)).collect();
+        Ok(Self {
+            width,
+            height,
+            sum_w,
+            var_t,
+            t_prime: PlanValues::F32(t_prime),
+            mask: PlanMask::Bytes(mask),
+            weights: Some(weights),
             angle_deg,
         })
     }
@@ -293,7 +691,7 @@ impl MaskedTemplatePlan {
         self.height
     }
 
-    /// Returns the sum of mask weights (count of valid pixels).
+    /// Returns the sum of mask weights (count of valid pixels for binary masks).
     pub fn sum_w(&self) -> f32 {
         self.sum_w
     }
@@ -304,13 +702,67 @@ impl MaskedTemplatePlan {
     }
 
     /// Returns the masked zero-mean template buffer.
+    ///
+    /// Compact plans decode their half-precision values on the first call and
+    /// keep the decoded buffer alongside them.
     pub fn t_prime(&self) -> &[f32] {
+        self.t_prime.as_f32()
+    }
+
+    /// Returns true when the zero-mean buffer is stored in half precision.
+    pub fn is_compact(&self) -> bool {
+        self.t_prime.is_compact()
+    }
+
+    /// Stores the zero-mean buffer in half precision.
+    ///
+    /// See [`TemplatePlan::into_compact`] for the accuracy trade-off.
+    #[cfg(feature = "half")]
+    pub fn into_compact(self) -> Self {
+        Self {
+            t_prime: self.t_prime.into_compact(),
+            ..self
+        }
+    }
+
+    pub(crate) fn values(&self) -> &PlanValues {
         &self.t_prime
     }
 
     /// Returns the binary mask buffer (0 or 1 per pixel).
+    ///
+    /// For weighted plans, pixels with a non-zero weight are marked with 1.
+    /// A bitpacked mask is unpacked on the first call and the bytes are kept
+    /// alongside the bits; see [`Self::packed_mask`].
     pub fn mask(&self) -> &[u8] {
-        self.mask.as_ref()
+        self.mask.bytes(self.width * self.height)
+    }
+
+    /// Returns the bitpacked mask, 8 pixels per byte with the first pixel in
+    /// the least significant bit, or `None` for byte masks.
+    pub fn packed_mask(&self) -> Option<&[u8]> {
+        self.mask.bits()
+    }
+
+    /// Returns true when the mask is stored with one bit per pixel.
+    pub fn is_mask_packed(&self) -> bool {
+        self.packed_mask().is_some()
+    }
+
+    pub(crate) fn plan_mask(&self) -> &PlanMask {
+        &self.mask
+    }
+
+    #[cfg(feature = "std")]
+    /// Replaces the mask with `mask`, which must mark the same pixels.
+    pub(crate) fn with_mask(self, mask: PlanMask) -> Self {
+        debug_assert!(self.weights.is_none(), "weighted plans keep byte masks");
+        Self { mask, ..self }
+    }
+
+    /// Returns the per-pixel weights for plans built with soft weights.
+    pub fn weights(&self) -> Option<&[f32]> {
+        self.weights.as_deref()
     }
 
     /// Returns the rotation angle in degrees.
@@ -324,7 +776,7 @@ pub struct MaskedSsdTemplatePlan {
     width: usize,
     height: usize,
     data: Vec<f32>,
-    mask: Arc<[u8]>,
+    mask: PlanMask,
     angle_deg: f32,
 }
 
@@ -390,7 +842,7 @@ impl MaskedSsdTemplatePlan {
             width,
             height,
             data,
-            mask,
+            mask: PlanMask::Bytes(mask),
             angle_deg,
         })
     }
@@ -411,8 +863,33 @@ impl MaskedSsdTemplatePlan {
     }
 
     /// Returns the binary mask buffer (0 or 1 per pixel).
+    ///
+    /// A bitpacked mask is unpacked on the first call and the bytes are kept
+    /// alongside the bits; see [`Self::packed_mask`].
     pub fn mask(&self) -> &[u8] {
-        self.mask.as_ref()
+        self.mask.bytes(self.width * self.height)
+    }
+
+    /// Returns the bitpacked mask, 8 pixels per byte with the first pixel in
+    /// the least significant bit, or `None` for byte masks.
+    pub fn packed_mask(&self) -> Option<&[u8]> {
+        self.mask.bits()
+    }
+
+    /// Returns true when the mask is stored with one bit per pixel.
+    pub fn is_mask_packed(&self) -> bool {
+        self.packed_mask().is_some()
+    }
+
+    #[cfg(feature = "std")]
+    pub(crate) fn plan_mask(&self) -> &PlanMask {
+        &self.mask
+    }
+
+    #[cfg(feature = "std")]
+    /// Replaces the mask with `mask`, which must mark the same pixels.
+    pub(crate) fn with_mask(self, mask: PlanMask) -> Self {
+        Self { mask, ..self }
     }
 
     /// Returns the rotation angle in degrees.
@@ -420,3 +897,63 @@ impl MaskedSsdTemplatePlan {
         self.angle_deg
     }
 }
+
+#[cfg(feature = "std")]
+/// Template terms needed to score ZNCC outside the plan-specific kernels.
+///
+/// Unifies masked and unmasked plans for subpixel resampling and for scoring
+/// against image-space masks.
+pub(crate) struct ZnccTerms<'a> {
+    pub(crate) width: usize,
+    pub(crate) height: usize,
+    pub(crate) t_prime: &'a PlanValues,
+    pub(crate) mask: Option<&'a PlanMask>,
+    pub(crate) weights: Option<&'a [f32]>,
+    pub(crate) sum_w: f32,
+    pub(crate) var_t: f32,
+}
+
+#[cfg(feature = "std")]
+impl ZnccTerms<'_> {
+    /// Returns zero-mean template value `idx`.
+    #[inline]
+    pub(crate) fn t_prime(&self, idx: usize) -> f32 {
+        self.t_prime.get(idx)
+    }
+
+    /// Returns true when template pixel `idx` is masked out.
+    #[inline]
+    pub(crate) fn excludes(&self, idx: usize) -> bool {
+        self.mask.is_some_and(|mask| !mask.valid(idx))
+    }
+}
+
+#[cfg(feature = "std")]
+impl<'a> From<&'a TemplatePlan> for ZnccTerms<'a> {
+    fn from(plan: &'a TemplatePlan) -> Self {
+        Self {
+            width: plan.width(),
+            height: plan.height(),
+            t_prime: plan.values(),
+            mask: None,
+            weights: None,
+            sum_w: (plan.width() * plan.height()) as f32,
+            var_t: plan.var_t(),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl<'a> From<&'a MaskedTemplatePlan> for ZnccTerms<'a> {
+    fn from(plan: &'a MaskedTemplatePlan) -> Self {
+        Self {
+            width: plan.width(),
+            height: plan.height(),
+            t_prime: plan.values(),
+            mask: Some(plan.plan_mask()),
+            weights: plan.weights(),
+            sum_w: plan.sum_w(),
+            var_t: plan.var_t(),
+        }
+    }
+}
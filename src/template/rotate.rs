@@ -1,8 +1,89 @@
 //! Rotation handling and angle normalization utilities.
 
+use super::plan::pack_mask_bits;
 use crate::image::{ImageView, OwnedImage};
 use crate::util::math::sin_cos_deg;
 
+/// How rotation fills pixels whose source lies outside the template.
+///
+/// The rotation mask always marks filled pixels invalid and ZNCC skips them.
+/// SSD skips constant-filled pixels too, but scores edge-clamped ones, which
+/// extends the compared footprint to the whole template rectangle.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FillStrategy {
+    /// Fill with a constant intensity.
+    Constant(u8),
+    /// Replicate the nearest template edge (bilinear sample at the clamped
+    /// source position).
+    EdgeClamp,
+}
+
+impl Default for FillStrategy {
+    fn default() -> Self {
+        Self::Constant(0)
+    }
+}
+
+/// Controls whether rotation is searched.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RotationMode {
+    /// Skip rotation and use the unmasked fast path.
+    Disabled,
+    /// Enable rotation search using masked kernels.
+    Enabled,
+}
+
+/// Returns the number of quarter turns when `angle_deg` is a multiple of 90°.
+fn quarter_turns(angle_deg: f32) -> Option<u8> {
+    let turns = angle_deg / 90.0;
+    let rounded = turns.round();
+    if !turns.is_finite() || (turns - rounded).abs() > 1e-6 {
+        return None;
+    }
+    Some(rounded.rem_euclid(4.0) as u8)
+}
+
+/// Rotates by whole quarter turns with exact integer sampling.
+///
+/// Uses the same center convention as the bilinear path, computed on doubled
+/// coordinates to stay in integers. Returns `None` when the source grid does
+/// not align with the destination grid (90°/270° with `width + height` odd),
+/// in which case interpolation is still required.
+fn rotate_quarter_turns(src: ImageView<'_, u8>, turns: u8, fill: u8) -> Option<(Vec<u8>, Vec<u8>)> {
+    let width = src.width();
+    let height = src.height();
+    if turns % 2 == 1 && (width + height) % 2 == 1 {
+        return None;
+    }
+
+    let mut out = vec![fill; width * height];
+    let mut mask = vec![0u8; width * height];
+    let w1 = width as isize - 1;
+    let h1 = height as isize - 1;
+    for y in 0..height {
+        for x in 0..width {
+            let dx2 = 2 * x as isize - w1;
+            let dy2 = 2 * y as isize - h1;
+            let (sx2, sy2) = match turns {
+                0 => (dx2, dy2),
+                1 => (dy2, -dx2),
+                2 => (-dx2, -dy2),
+                _ => (-dy2, dx2),
+            };
+            let src_x = (sx2 + w1) / 2;
+            let src_y = (sy2 + h1) / 2;
+            if src_x < 0 || src_y < 0 || src_x > w1 || src_y > h1 {
+                continue;
+            }
+            let row = src.row(src_y as usize).expect("row in bounds");
+            let idx = y * width + x;
+            out[idx] = row[src_x as usize];
+            mask[idx] = 1;
+        }
+    }
+    Some((out, mask))
+}
+
 /// Rotates a grayscale template using bilinear sampling.
 ///
 /// Rotation is performed about the image center with
@@ -12,9 +93,18 @@ use crate::util::math::sin_cos_deg;
 /// `fill`. Bilinear interpolation clamps indices to the valid image range.
 /// The output image has the same dimensions as the input and uses rounding
 /// to the nearest integer before clamping to `[0, 255]`.
+///
+/// Multiples of 90° whose sample positions land on the pixel grid (always
+/// for 0° and 180°, and for 90°/270° when `width + height` is even) are
+/// copied exactly without interpolation.
 pub fn rotate_u8_bilinear(src: ImageView<'_, u8>, angle_deg: f32, fill: u8) -> OwnedImage {
     let width = src.width();
     let height = src.height();
+    if let Some((out, _)) =
+        quarter_turns(angle_deg).and_then(|turns| rotate_quarter_turns(src, turns, fill))
+    {
+        return OwnedImage::new(out, width, height).expect("rotation output is contiguous");
+    }
     let mut out = vec![fill; width * height];
 
     let (sin_a, cos_a) = sin_cos_deg(angle_deg);
@@ -77,6 +167,68 @@ pub fn rotate_u8_bilinear(src: ImageView<'_, u8>, angle_deg: f32, fill: u8) -> O
     OwnedImage::new(out, width, height).expect("rotation output is contiguous")
 }
 
+/// Rotates with [`rotate_u8_bilinear_masked`] and fills masked-out pixels
+/// according to `fill`.
+pub(crate) fn rotate_u8_bilinear_masked_fill(
+    src: ImageView<'_, u8>,
+    angle_deg: f32,
+    fill: FillStrategy,
+) -> (OwnedImage, Vec<u8>) {
+    let FillStrategy::Constant(value) = fill else {
+        let (img, mask) = rotate_u8_bilinear_masked(src, angle_deg, 0);
+        let mut out = img.data().to_vec();
+        fill_from_edges(src, angle_deg, &mask, &mut out);
+        return (
+            OwnedImage::new(out, src.width(), src.height()).expect("rotation output is contiguous"),
+            mask,
+        );
+    };
+    rotate_u8_bilinear_masked(src, angle_deg, value)
+}
+
+/// Replaces masked-out pixels with a bilinear sample at the source position
+/// clamped to the template bounds.
+fn fill_from_edges(src: ImageView<'_, u8>, angle_deg: f32, mask: &[u8], out: &mut [u8]) {
+    let width = src.width();
+    let height = src.height();
+    let (sin_a, cos_a) = sin_cos_deg(angle_deg);
+    let cx = (width as f32 - 1.0) * 0.5;
+    let cy = (height as f32 - 1.0) * 0.5;
+    let max_x = width as f32 - 1.0;
+    let max_y = height as f32 - 1.0;
+
+    for y in 0..height {
+        for x in 0..width {
+            let idx = y * width + x;
+            if mask[idx] != 0 {
+                continue;
+            }
+            let dx = x as f32 - cx;
+            let dy = y as f32 - cy;
+            let src_x = cos_a * dx + sin_a * dy + cx;
+            let src_y = -sin_a * dx + cos_a * dy + cy;
+            if !src_x.is_finite() || !src_y.is_finite() {
+                continue;
+            }
+            let src_x = src_x.clamp(0.0, max_x);
+            let src_y = src_y.clamp(0.0, max_y);
+            let x0 = src_x.floor() as usize;
+            let y0 = src_y.floor() as usize;
+            let x1 = (x0 + 1).min(width - 1);
+            let y1 = (y0 + 1).min(height - 1);
+            let fx = src_x - x0 as f32;
+            let fy = src_y - y0 as f32;
+
+            let row0 = src.row(y0).expect("row in bounds");
+            let row1 = src.row(y1).expect("row in bounds");
+            let top = row0[x0] as f32 * (1.0 - fx) + row0[x1] as f32 * fx;
+            let bottom = row1[x0] as f32 * (1.0 - fx) + row1[x1] as f32 * fx;
+            let value = top * (1.0 - fy) + bottom * fy;
+            out[idx] = value.round().clamp(0.0, 255.0) as u8;
+        }
+    }
+}
+
 /// Rotates a grayscale template using bilinear sampling and returns a mask.
 ///
 /// Rotation is performed about the image center with
@@ -87,6 +239,10 @@ pub fn rotate_u8_bilinear(src: ImageView<'_, u8>, angle_deg: f32, fill: u8) -> O
 /// footprint for bilinear sampling is fully inside bounds. Valid pixels are
 /// marked with `mask = 1` and invalid pixels with `mask = 0`. Invalid pixels
 /// are filled with `fill` without clamping to the image edge.
+///
+/// Grid-aligned multiples of 90° use the exact integer path described in
+/// [`rotate_u8_bilinear`] and mark every pixel with a source as valid, so 0°
+/// and 180° always yield a full mask, as do 90° and 270° for square templates.
 pub fn rotate_u8_bilinear_masked(
     src: ImageView<'_, u8>,
     angle_deg: f32,
@@ -94,6 +250,14 @@ pub fn rotate_u8_bilinear_masked(
 ) -> (OwnedImage, Vec<u8>) {
     let width = src.width();
     let height = src.height();
+    if let Some((out, mask)) =
+        quarter_turns(angle_deg).and_then(|turns| rotate_quarter_turns(src, turns, fill))
+    {
+        return (
+            OwnedImage::new(out, width, height).expect("rotation output is contiguous"),
+            mask,
+        );
+    }
     let mut out = vec![fill; width * height];
     let mut mask = vec![0u8; width * height];
 
@@ -134,6 +298,7 @@ pub fn rotate_u8_bilinear_masked(
 
             let fx = src_x - x0 as f32;
             let fy = src_y - y0 as f32;
+
             let row0 = src.row(y0).expect("row in bounds");
             let row1 = src.row(y1).expect("row in bounds");
             let a = row0[x0] as f32;
@@ -158,3 +323,17 @@ pub fn rotate_u8_bilinear_masked(
         mask,
     )
 }
+
+/// Rotates like [`rotate_u8_bilinear_masked`] and returns the mask bitpacked.
+///
+/// Pixel `idx = y * width + x` is valid when
+/// `(mask[idx / 8] >> (idx % 8)) & 1` is set; the mask holds
+/// `(width * height).div_ceil(8)` bytes.
+pub fn rotate_u8_bilinear_masked_packed(
+    src: ImageView<'_, u8>,
+    angle_deg: f32,
+    fill: u8,
+) -> (OwnedImage, Vec<u8>) {
+    let (out, mask) = rotate_u8_bilinear_masked(src, angle_deg, fill);
+    (out, pack_mask_bits(&mask))
+}
@@ -0,0 +1,766 @@
+//! Deterministic synthetic matching cases with ground truth.
+//!
+//! This is an in-memory port of `tools/synth_cases/generate_cases.py`: the
+//! same case families (translation, rotation, noise, blur, illumination,
+//! occlusion, distractors, borders, negatives, deep pyramids) built from
+//! procedural textures and rotated with
+//! [`rotate_u8_bilinear_masked`].
+//! Cases are reproducible for a given seed but are not byte-identical to the
+//! Python output, whose random streams differ, and `smoke_rotation` and
+//! `pyramid_stress` use the asymmetric pattern so their ground truth is
+//! unambiguous.
+
+use crate::template::rotate::rotate_u8_bilinear_masked;
+use crate::{
+    CompileConfig, CompileConfigNoRot, CompiledTemplate, CorrMatchResult, FillStrategy, ImageView,
+    MatchConfig, OwnedImage, RotationMode, Template,
+};
+
+/// Named set of synthetic cases.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Suite {
+    /// Three small cases: translation, rotation, and a negative.
+    Smoke,
+    /// One case per family, including large pyramid stress cases.
+    Standard,
+}
+
+/// Ground-truth placement of one embedded template copy.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SynthInstance {
+    /// Top-left x coordinate at level 0.
+    pub x: usize,
+    /// Top-left y coordinate at level 0.
+    pub y: usize,
+    /// Rotation applied to the template, in degrees.
+    pub angle_deg: f32,
+    /// True for the extra copies of distractor cases; the target is first.
+    pub distractor: bool,
+}
+
+/// One generated case: inputs, matcher settings, and ground truth.
+pub struct SynthCase {
+    /// Stable case name, matching the Python generator.
+    pub case_id: &'static str,
+    /// Case family, such as `"rotation"` or `"noise"`.
+    pub family: &'static str,
+    /// Image to search.
+    pub image: OwnedImage,
+    /// Unrotated template.
+    pub template: OwnedImage,
+    /// Whether the target is embedded in the image.
+    pub present: bool,
+    /// Embedded copies, target first.
+    pub instances: Vec<SynthInstance>,
+    /// Template compilation settings; rotated cases use all of them,
+    /// unrotated cases only `max_levels`.
+    pub compile: CompileConfig,
+    /// Matcher settings.
+    pub match_cfg: MatchConfig,
+    /// Number of results the case expects from a top-k query.
+    pub topk: usize,
+}
+
+impl SynthCase {
+    /// Compiles the template as the case's rotation mode requires.
+    pub fn compile_template(&self) -> CorrMatchResult<CompiledTemplate> {
+        let template = Template::new(
+            self.template.data().to_vec(),
+            self.template.width(),
+            self.template.height(),
+        )?;
+        match self.match_cfg.rotation {
+            RotationMode::Enabled => {
+                CompiledTemplate::compile_rotated(&template, self.compile.clone())
+            }
+            RotationMode::Disabled => CompiledTemplate::compile_unrotated(
+                &template,
+                CompileConfigNoRot {
+                    max_levels: self.compile.max_levels,
+                },
+            ),
+        }
+    }
+}
+
+/// Generates every case of `suite` from `seed`.
+pub fn generate(suite: Suite, seed: u64) -> CorrMatchResult<Vec<SynthCase>> {
+    let specs = match suite {
+        Suite::Smoke => smoke_specs(),
+        Suite::Standard => standard_specs(),
+    };
+    specs
+        .iter()
+        .enumerate()
+        .map(|(index, spec)| generate_case(spec, seed, index))
+        .collect()
+}
+
+#[derive(Clone, Copy, Debug)]
+enum Pattern {
+    Xor,
+    Checker,
+    Rings,
+    Bars,
+    Asymmetric,
+}
+
+#[derive(Clone, Copy, Debug)]
+enum Background {
+    Flat,
+    Gradient,
+    Xor,
+    Noise,
+    Mixed,
+}
+
+#[derive(Clone, Copy, Debug)]
+struct CaseSpec {
+    case_id: &'static str,
+    family: &'static str,
+    image_size: (usize, usize),
+    template_size: (usize, usize),
+    pattern: Pattern,
+    background: Background,
+    rotation_deg: f32,
+    present: bool,
+    template_gain: f64,
+    template_bias: f64,
+    noise_sigma: f64,
+    blur_sigma: f64,
+    occlusion_frac: f64,
+    distractors: usize,
+    near_edge: bool,
+    compile_levels: usize,
+    image_levels: usize,
+    /// Coarse and finest rotation steps in degrees.
+    steps_deg: (f32, f32),
+    per_angle_topk: usize,
+    topk: Option<usize>,
+}
+
+const BASE: CaseSpec = CaseSpec {
+    case_id: "",
+    family: "",
+    image_size: (320, 240),
+    template_size: (80, 60),
+    pattern: Pattern::Xor,
+    background: Background::Flat,
+    rotation_deg: 0.0,
+    present: true,
+    template_gain: 1.0,
+    template_bias: 0.0,
+    noise_sigma: 0.0,
+    blur_sigma: 0.0,
+    occlusion_frac: 0.0,
+    distractors: 0,
+    near_edge: false,
+    compile_levels: 4,
+    image_levels: 4,
+    steps_deg: (30.0, 7.5),
+    per_angle_topk: 3,
+    topk: None,
+};
+
+fn standard_specs() -> Vec<CaseSpec> {
+    vec![
+        CaseSpec {
+            case_id: "clean_translation",
+            family: "translation",
+            image_size: (256, 192),
+            template_size: (64, 48),
+            compile_levels: 3,
+            image_levels: 3,
+            ..BASE
+        },
+        CaseSpec {
+            case_id: "clean_translation_large",
+            family: "translation",
+            image_size: (1024, 768),
+            template_size: (192, 160),
+            background: Background::Gradient,
+            compile_levels: 5,
+            image_levels: 5,
+            ..BASE
+        },
+        CaseSpec {
+            case_id: "rotation_coarse_30deg",
+            family: "rotation",
+            template_size: (96, 72),
+            rotation_deg: 30.0,
+            compile_levels: 3,
+            image_levels: 3,
+            steps_deg: (30.0, 30.0),
+            ..BASE
+        },
+        CaseSpec {
+            case_id: "rotation_fine_22_5deg",
+            family: "rotation",
+            pattern: Pattern::Asymmetric,
+            background: Background::Gradient,
+            rotation_deg: 22.5,
+            ..BASE
+        },
+        CaseSpec {
+            case_id: "rotation_wrap_172_5deg",
+            family: "rotation",
+            image_size: (300, 220),
+            template_size: (72, 56),
+            pattern: Pattern::Bars,
+            rotation_deg: 172.5,
+            ..BASE
+        },
+        CaseSpec {
+            case_id: "noise_gaussian",
+            family: "noise",
+            template_size: (80, 64),
+            background: Background::Gradient,
+            noise_sigma: 12.0,
+            ..BASE
+        },
+        CaseSpec {
+            case_id: "blur_sigma_1_5",
+            family: "blur",
+            template_size: (88, 68),
+            pattern: Pattern::Checker,
+            background: Background::Mixed,
+            blur_sigma: 1.5,
+            ..BASE
+        },
+        CaseSpec {
+            case_id: "illumination_shift",
+            family: "illumination",
+            image_size: (300, 220),
+            template_size: (72, 52),
+            background: Background::Gradient,
+            template_gain: 1.25,
+            template_bias: 14.0,
+            image_levels: 3,
+            ..BASE
+        },
+        CaseSpec {
+            case_id: "occluded_25pct",
+            family: "occlusion",
+            template_size: (90, 70),
+            occlusion_frac: 0.25,
+            noise_sigma: 4.0,
+            ..BASE
+        },
+        CaseSpec {
+            case_id: "distractors_topk",
+            family: "distractors",
+            image_size: (420, 320),
+            pattern: Pattern::Rings,
+            background: Background::Mixed,
+            distractors: 3,
+            per_angle_topk: 4,
+            topk: Some(4),
+            ..BASE
+        },
+        CaseSpec {
+            case_id: "near_border",
+            family: "edge",
+            template_size: (88, 68),
+            pattern: Pattern::Bars,
+            background: Background::Gradient,
+            near_edge: true,
+            ..BASE
+        },
+        CaseSpec {
+            case_id: "negative_no_match",
+            family: "negative",
+            template_size: (80, 64),
+            background: Background::Noise,
+            present: false,
+            noise_sigma: 6.0,
+            ..BASE
+        },
+        CaseSpec {
+            case_id: "pyramid_stress",
+            family: "pyramid",
+            image_size: (1200, 900),
+            template_size: (160, 120),
+            // The Python case uses a checker, whose 15 px cells average out
+            // by level 5 and leave the coarse search a period-aliased peak.
+            pattern: Pattern::Asymmetric,
+            background: Background::Xor,
+            noise_sigma: 3.0,
+            compile_levels: 6,
+            image_levels: 6,
+            ..BASE
+        },
+        CaseSpec {
+            case_id: "pyramid_stress_single_level",
+            family: "pyramid",
+            image_size: (1200, 900),
+            template_size: (160, 120),
+            pattern: Pattern::Checker,
+            background: Background::Xor,
+            noise_sigma: 3.0,
+            compile_levels: 1,
+            image_levels: 1,
+            ..BASE
+        },
+        CaseSpec {
+            case_id: "rotation_fine_single_level",
+            family: "rotation",
+            pattern: Pattern::Asymmetric,
+            background: Background::Gradient,
+            rotation_deg: 22.5,
+            compile_levels: 1,
+            image_levels: 1,
+            steps_deg: (5.0, 1.0),
+            ..BASE
+        },
+    ]
+}
+
+fn smoke_specs() -> Vec<CaseSpec> {
+    vec![
+        CaseSpec {
+            case_id: "smoke_translation",
+            family: "translation",
+            image_size: (192, 144),
+            template_size: (56, 40),
+            compile_levels: 3,
+            image_levels: 3,
+            ..BASE
+        },
+        CaseSpec {
+            case_id: "smoke_rotation",
+            family: "rotation",
+            image_size: (256, 192),
+            template_size: (64, 48),
+            // The Python case uses rings, which are close to rotation
+            // invariant and do not pin the angle to the 7.5 degree step.
+            pattern: Pattern::Asymmetric,
+            background: Background::Gradient,
+            rotation_deg: 22.5,
+            compile_levels: 3,
+            image_levels: 3,
+            ..BASE
+        },
+        CaseSpec {
+            case_id: "smoke_negative",
+            family: "negative",
+            image_size: (192, 144),
+            template_size: (56, 40),
+            pattern: Pattern::Bars,
+            background: Background::Noise,
+            present: false,
+            image_levels: 3,
+            ..BASE
+        },
+    ]
+}
+
+/// SplitMix64 generator; small, seedable, and stable across platforms.
+struct Rng(u64);
+
+impl Rng {
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// Uniform value in `[0, 1)`.
+    fn unit(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    fn uniform(&mut self, lo: f64, hi: f64) -> f64 {
+        lo + (hi - lo) * self.unit()
+    }
+
+    /// Uniform integer in `lo..=hi`.
+    fn int(&mut self, lo: i64, hi: i64) -> i64 {
+        debug_assert!(lo <= hi);
+        lo + (self.next_u64() % ((hi - lo) as u64 + 1)) as i64
+    }
+
+    /// Normal sample with zero mean (Box-Muller).
+    fn gauss(&mut self, sigma: f64) -> f64 {
+        let u1 = 1.0 - self.unit();
+        let u2 = self.unit();
+        sigma * (-2.0 * u1.ln()).sqrt() * (std::f64::consts::TAU * u2).cos()
+    }
+}
+
+/// FNV-1a hash of the case id mixed with the index and base seed, as in the
+/// Python generator.
+fn stable_seed(base_seed: u64, case_id: &str, index: usize) -> u64 {
+    let mut h: u32 = 2_166_136_261;
+    for byte in case_id.bytes() {
+        h ^= u32::from(byte);
+        h = h.wrapping_mul(16_777_619);
+    }
+    h ^= ((index as u32) + 1).wrapping_mul(0x9E37_79B1);
+    h ^= base_seed as u32;
+    u64::from(h)
+}
+
+fn clamp_u8(value: f64) -> u8 {
+    value.round().clamp(0.0, 255.0) as u8
+}
+
+fn make_pattern(pattern: Pattern, width: usize, height: usize, rng: &mut Rng) -> Vec<u8> {
+    let pixels = (0..height).flat_map(|y| (0..width).map(move |x| (x, y)));
+    match pattern {
+        Pattern::Xor => pixels
+            .map(|(x, y)| (((x * 13) ^ (y * 7) ^ (x * y)) & 0xFF) as u8)
+            .collect(),
+        Pattern::Checker => {
+            let cell = (width.min(height) / 8).max(2);
+            pixels
+                .map(|(x, y)| {
+                    if (x / cell + y / cell) % 2 == 0 {
+                        32
+                    } else {
+                        224
+                    }
+                })
+                .collect()
+        }
+        Pattern::Rings => rings(width, height, 0.35),
+        Pattern::Bars => {
+            let cell = (width / 12).max(2);
+            pixels
+                .map(|(x, _)| if (x / cell) % 2 == 0 { 40 } else { 210 })
+                .collect()
+        }
+        Pattern::Asymmetric => asymmetric(width, height, rng),
+    }
+}
+
+fn rings(width: usize, height: usize, freq: f64) -> Vec<u8> {
+    let cx = (width as f64 - 1.0) * 0.5;
+    let cy = (height as f64 - 1.0) * 0.5;
+    (0..height)
+        .flat_map(|y| (0..width).map(move |x| (x, y)))
+        .map(|(x, y)| {
+            let r = (x as f64 - cx).hypot(y as f64 - cy);
+            clamp_u8(128.0 + 110.0 * (r * freq).sin())
+        })
+        .collect()
+}
+
+/// L-shaped bars plus a gradient in one quadrant, so no rotation maps the
+/// pattern onto itself.
+fn asymmetric(width: usize, height: usize, rng: &mut Rng) -> Vec<u8> {
+    let mut out = vec![80u8; width * height];
+    for y in 0..height {
+        for x in 0..width {
+            let value = if y < height / 4 || x < width / 4 {
+                200
+            } else if y >= height / 2 && x >= width / 2 {
+                let t = (x - width / 2) as f64 / (width / 2).max(1) as f64;
+                (40.0 + 180.0 * t) as u8
+            } else {
+                80
+            };
+            out[y * width + x] = value;
+        }
+    }
+    for value in &mut out {
+        *value = clamp_u8(f64::from(*value) + rng.int(-15, 15) as f64);
+    }
+    out
+}
+
+fn make_background(background: Background, width: usize, height: usize, rng: &mut Rng) -> Vec<u8> {
+    match background {
+        Background::Flat => vec![rng.int(20, 200) as u8; width * height],
+        Background::Gradient => {
+            let base = rng.int(40, 140) as f64;
+            let ax = rng.uniform(-0.4, 0.4);
+            let ay = rng.uniform(-0.4, 0.4);
+            (0..height)
+                .flat_map(|y| (0..width).map(move |x| (x, y)))
+                .map(|(x, y)| clamp_u8(base + ax * x as f64 + ay * y as f64))
+                .collect()
+        }
+        Background::Xor => (0..height)
+            .flat_map(|y| (0..width).map(move |x| (x, y)))
+            .map(|(x, y)| ((x * 9 + y * 5 + x * y) & 0xFF) as u8)
+            .collect(),
+        Background::Noise => (0..width * height).map(|_| rng.int(0, 255) as u8).collect(),
+        Background::Mixed => make_background(Background::Gradient, width, height, rng)
+            .into_iter()
+            .map(|value| clamp_u8(f64::from(value) + rng.int(-20, 20) as f64))
+            .collect(),
+    }
+}
+
+/// Separable Gaussian blur with edge clamping.
+fn gaussian_blur(data: &[u8], width: usize, height: usize, sigma: f64) -> Vec<u8> {
+    if sigma <= 0.0 {
+        return data.to_vec();
+    }
+    let radius = ((3.0 * sigma).ceil() as usize).max(1);
+    let kernel: Vec<f64> = (0..=2 * radius)
+        .map(|k| {
+            let d = k as f64 - radius as f64;
+            (-(d * d) / (2.0 * sigma * sigma)).exp()
+        })
+        .collect();
+    let norm: f64 = kernel.iter().sum();
+    let tap = |k: usize, pos: usize, len: usize| (pos + k).saturating_sub(radius).min(len - 1);
+
+    let mut temp = vec![0.0f64; width * height];
+    for y in 0..height {
+        for x in 0..width {
+            temp[y * width + x] = kernel
+                .iter()
+                .enumerate()
+                .map(|(k, w)| f64::from(data[y * width + tap(k, x, width)]) * w)
+                .sum::<f64>()
+                / norm;
+        }
+    }
+    let mut out = vec![0u8; width * height];
+    for y in 0..height {
+        for x in 0..width {
+            let acc: f64 = kernel
+                .iter()
+                .enumerate()
+                .map(|(k, w)| temp[tap(k, y, height) * width + x] * w)
+                .sum();
+            out[y * width + x] = clamp_u8(acc / norm);
+        }
+    }
+    out
+}
+
+fn overlaps(a: (usize, usize), b: (usize, usize), size: (usize, usize)) -> bool {
+    a.0 < b.0 + size.0 && b.0 < a.0 + size.0 && a.1 < b.1 + size.1 && b.1 < a.1 + size.1
+}
+
+/// Picks a top-left position that avoids earlier placements when possible.
+fn choose_position(
+    rng: &mut Rng,
+    image_size: (usize, usize),
+    template_size: (usize, usize),
+    near_edge: bool,
+    avoid: &[(usize, usize)],
+) -> (usize, usize) {
+    const MARGIN: i64 = 2;
+    let max_x = image_size.0.saturating_sub(template_size.0) as i64;
+    let max_y = image_size.1.saturating_sub(template_size.1) as i64;
+    let mut pick = || {
+        let (x, y) = if near_edge {
+            match rng.int(0, 3) {
+                0 => (rng.int(0, MARGIN.min(max_x)), rng.int(0, max_y)),
+                1 => (rng.int((max_x - MARGIN).max(0), max_x), rng.int(0, max_y)),
+                2 => (rng.int(0, max_x), rng.int(0, MARGIN.min(max_y))),
+                _ => (rng.int(0, max_x), rng.int((max_y - MARGIN).max(0), max_y)),
+            }
+        } else {
+            let x = if max_x > 2 * MARGIN {
+                rng.int(MARGIN, max_x - MARGIN)
+            } else {
+                0
+            };
+            let y = if max_y > 2 * MARGIN {
+                rng.int(MARGIN, max_y - MARGIN)
+            } else {
+                0
+            };
+            (x, y)
+        };
+        (x as usize, y as usize)
+    };
+    for _ in 0..80 {
+        let pos = pick();
+        if !avoid
+            .iter()
+            .any(|&other| overlaps(pos, other, template_size))
+        {
+            return pos;
+        }
+    }
+    pick()
+}
+
+/// Copies the valid template pixels into the image with a gain and bias.
+#[allow(clippy::too_many_arguments)]
+fn embed(
+    image: &mut [u8],
+    image_width: usize,
+    template: &[u8],
+    mask: &[u8],
+    template_size: (usize, usize),
+    pos: (usize, usize),
+    gain: f64,
+    bias: f64,
+) {
+    let (tpl_width, tpl_height) = template_size;
+    for y in 0..tpl_height {
+        for x in 0..tpl_width {
+            let idx = y * tpl_width + x;
+            if mask[idx] != 0 {
+                image[(pos.1 + y) * image_width + pos.0 + x] =
+                    clamp_u8(f64::from(template[idx]) * gain + bias);
+            }
+        }
+    }
+}
+
+/// Restores a random rectangle covering `frac` of the target to background.
+fn occlude(
+    image: &mut [u8],
+    background: &[u8],
+    image_width: usize,
+    pos: (usize, usize),
+    template_size: (usize, usize),
+    frac: f64,
+    rng: &mut Rng,
+) {
+    let (tpl_width, tpl_height) = template_size;
+    let area = ((tpl_width * tpl_height) as f64 * frac) as usize;
+    let area = area.clamp(1, tpl_width * tpl_height);
+    let ratio = rng.uniform(0.4, 2.5);
+    let occ_width = (((area as f64).sqrt() * ratio).round() as usize).clamp(1, tpl_width);
+    let occ_height = ((area as f64 / occ_width as f64).round() as usize).clamp(1, tpl_height);
+    let ox = rng.int(pos.0 as i64, (pos.0 + tpl_width - occ_width) as i64) as usize;
+    let oy = rng.int(pos.1 as i64, (pos.1 + tpl_height - occ_height) as i64) as usize;
+    for y in oy..oy + occ_height {
+        let row = y * image_width;
+        image[row + ox..row + ox + occ_width]
+            .copy_from_slice(&background[row + ox..row + ox + occ_width]);
+    }
+}
+
+fn generate_case(spec: &CaseSpec, base_seed: u64, index: usize) -> CorrMatchResult<SynthCase> {
+    let mut rng = Rng(stable_seed(base_seed, spec.case_id, index));
+    let (tpl_width, tpl_height) = spec.template_size;
+    let (img_width, img_height) = spec.image_size;
+    let template = make_pattern(spec.pattern, tpl_width, tpl_height, &mut rng);
+    let background = make_background(spec.background, img_width, img_height, &mut rng);
+    let mut image = background.clone();
+
+    let rotated = spec.rotation_deg != 0.0;
+    let tpl_view = ImageView::from_slice(&template, tpl_width, tpl_height)?;
+    let posed = |angle_deg: f32| {
+        if rotated {
+            let (img, mask) = rotate_u8_bilinear_masked(tpl_view, angle_deg, 0);
+            (img.data().to_vec(), mask)
+        } else {
+            (template.clone(), vec![1u8; template.len()])
+        }
+    };
+
+    let mut instances = Vec::new();
+    let mut avoid = Vec::new();
+    if spec.present {
+        let pos = choose_position(
+            &mut rng,
+            spec.image_size,
+            spec.template_size,
+            spec.near_edge,
+            &avoid,
+        );
+        let (pixels, mask) = posed(spec.rotation_deg);
+        embed(
+            &mut image,
+            img_width,
+            &pixels,
+            &mask,
+            spec.template_size,
+            pos,
+            spec.template_gain,
+            spec.template_bias,
+        );
+        avoid.push(pos);
+        instances.push(SynthInstance {
+            x: pos.0,
+            y: pos.1,
+            angle_deg: spec.rotation_deg,
+            distractor: false,
+        });
+        if spec.occlusion_frac > 0.0 {
+            occlude(
+                &mut image,
+                &background,
+                img_width,
+                pos,
+                spec.template_size,
+                spec.occlusion_frac,
+                &mut rng,
+            );
+        }
+    }
+    for _ in 0..spec.distractors {
+        let pos = choose_position(&mut rng, spec.image_size, spec.template_size, false, &avoid);
+        let (pixels, mask) = posed(spec.rotation_deg);
+        let gain = spec.template_gain * rng.uniform(0.9, 1.1);
+        let bias = spec.template_bias + rng.uniform(-5.0, 5.0);
+        embed(
+            &mut image,
+            img_width,
+            &pixels,
+            &mask,
+            spec.template_size,
+            pos,
+            gain,
+            bias,
+        );
+        avoid.push(pos);
+        instances.push(SynthInstance {
+            x: pos.0,
+            y: pos.1,
+            angle_deg: spec.rotation_deg,
+            distractor: true,
+        });
+    }
+
+    if spec.blur_sigma > 0.0 {
+        image = gaussian_blur(&image, img_width, img_height, spec.blur_sigma);
+    }
+    if spec.noise_sigma > 0.0 {
+        for value in &mut image {
+            *value = clamp_u8(f64::from(*value) + rng.gauss(spec.noise_sigma));
+        }
+    }
+
+    let compile = CompileConfig {
+        max_levels: spec.compile_levels,
+        coarse_step_deg: spec.steps_deg.0,
+        min_step_deg: spec.steps_deg.1,
+        fill_strategy: FillStrategy::Constant(0),
+        precompute_coarsest: true,
+        ..CompileConfig::default()
+    };
+    let match_cfg = MatchConfig {
+        rotation: if rotated {
+            RotationMode::Enabled
+        } else {
+            RotationMode::Disabled
+        },
+        max_image_levels: spec.image_levels,
+        beam_width: 6,
+        coarse_per_angle_topk: spec.per_angle_topk,
+        refine_per_angle_topk: spec.per_angle_topk,
+        nms_radius: 4,
+        roi_radius: 6,
+        angle_half_range_steps: 1,
+        ..MatchConfig::default()
+    };
+    let topk = spec
+        .topk
+        .unwrap_or_else(|| (usize::from(spec.present) + spec.distractors).max(1));
+
+    Ok(SynthCase {
+        case_id: spec.case_id,
+        family: spec.family,
+        image: OwnedImage::new(image, img_width, img_height)?,
+        template: OwnedImage::new(template, tpl_width, tpl_height)?,
+        present: spec.present,
+        instances,
+        compile,
+        match_cfg,
+        topk,
+    })
+}
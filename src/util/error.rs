@@ -1,9 +1,11 @@
 //! Error types for corrmatch.
 
+use alloc::string::String;
+use core::fmt;
 use thiserror::Error;
 
 /// Result alias for corrmatch operations.
-pub type CorrMatchResult<T> = std::result::Result<T, CorrMatchError>;
+pub type CorrMatchResult<T> = core::result::Result<T, CorrMatchError>;
 
 /// Errors that can occur when running corrmatch operations.
 #[derive(Error, Debug, Clone, PartialEq, Eq)]
@@ -29,9 +31,30 @@ pub enum CorrMatchError {
         img_width: usize,
         img_height: usize,
     },
+    /// The image is smaller than the template in at least one dimension, so
+    /// no placement fits.
+    #[error(
+        "image ({img_width}x{img_height}) is smaller than the template ({tpl_width}x{tpl_height})"
+    )]
+    ImageSmallerThanTemplate {
+        img_width: usize,
+        img_height: usize,
+        tpl_width: usize,
+        tpl_height: usize,
+    },
+    /// `MatchConfig::require_full_pyramid` is set and the image pyramid has
+    /// fewer levels than the compiled template.
+    #[error("image pyramid has {available} levels but the template needs {required}")]
+    InsufficientPyramidLevels { available: usize, required: usize },
     /// The template is degenerate and cannot be normalized.
     #[error("degenerate template: {reason}")]
     DegenerateTemplate { reason: &'static str },
+    /// The level-0 template variance is below `min_template_variance`.
+    #[error("template variance {variance} below min_template_variance={min}")]
+    LowTemplateVariance {
+        variance: TemplateVariance,
+        min: TemplateVariance,
+    },
     /// The requested angle grid parameters are invalid.
     #[error("invalid angle grid: {reason}")]
     InvalidAngleGrid { reason: &'static str },
@@ -51,6 +74,15 @@ pub enum CorrMatchError {
     /// Rotation assets are unavailable for the requested operation.
     #[error("rotation unavailable: {reason}")]
     RotationUnavailable { reason: &'static str },
+    /// `RotationMode::Enabled` was requested for a template compiled without
+    /// angle banks.
+    ///
+    /// Compile the template with `CompiledTemplate::compile_rotated` (or
+    /// `Template::compile`), or search with `RotationMode::Disabled`.
+    #[error(
+        "rotation enabled but the template was compiled without rotation support; compile it with rotation or disable rotation"
+    )]
+    RotationNotCompiled,
     /// Image I/O failed.
     #[error("image io error: {reason}")]
     ImageIo { reason: String },
@@ -61,3 +93,31 @@ pub enum CorrMatchError {
     #[error("invalid config: {reason}")]
     InvalidConfig { reason: &'static str },
 }
+
+/// Per-pixel intensity variance reported by
+/// [`CorrMatchError::LowTemplateVariance`].
+///
+/// Compares by bit pattern so the error type stays `Eq`.
+#[derive(Clone, Copy, Debug)]
+pub struct TemplateVariance(pub f32);
+
+impl TemplateVariance {
+    /// Returns the variance value.
+    pub fn get(self) -> f32 {
+        self.0
+    }
+}
+
+impl PartialEq for TemplateVariance {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.to_bits() == other.0.to_bits()
+    }
+}
+
+impl Eq for TemplateVariance {}
+
+impl fmt::Display for TemplateVariance {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&self.0, f)
+    }
+}
@@ -0,0 +1,21 @@
+//! Float methods that `core` lacks, for `no_std` builds.
+//!
+//! With `std` the inherent methods are used; importing [`FloatExt`] makes the
+//! same method calls resolve to `libm` instead.
+
+/// Square root backed by `libm`.
+pub(crate) trait FloatExt {
+    fn sqrt(self) -> Self;
+}
+
+impl FloatExt for f32 {
+    fn sqrt(self) -> Self {
+        libm::sqrtf(self)
+    }
+}
+
+impl FloatExt for f64 {
+    fn sqrt(self) -> Self {
+        libm::sqrt(self)
+    }
+}
@@ -1,6 +1,9 @@
 //! Shared utility helpers.
 
 pub mod error;
+#[cfg(not(feature = "std"))]
+pub(crate) mod float;
+#[cfg(feature = "std")]
 pub(crate) mod math;
 
-pub use error::{CorrMatchError, CorrMatchResult};
+pub use error::{CorrMatchError, CorrMatchResult, TemplateVariance};
@@ -1,5 +1,10 @@
-use corrmatch::lowlevel::{rotate_u8_bilinear, AngleGrid};
-use corrmatch::{CompileConfig, CompiledTemplate, ImageView, Template};
+use corrmatch::lowlevel::{
+    rotate_u8_bilinear, rotate_u8_bilinear_masked, rotate_u8_bilinear_masked_packed, AngleGrid,
+};
+use corrmatch::{
+    CompileConfig, CompileConfigNoRot, CompiledTemplate, ImageView, MatchConfig, Matcher, Metric,
+    RotationMode, Template,
+};
 
 #[test]
 fn angle_grid_full_range_and_nearest_index() {
@@ -39,9 +44,57 @@ fn rotate_u8_bilinear_identity_and_180() {
         }
     }
     assert_eq!(rotated_180.data(), expected.as_slice());
+}
+
+#[test]
+fn rotate_quarter_turns_are_exact() {
+    // Non-square with an even width + height so 90°/270° stay on the pixel grid.
+    let data: Vec<u8> = (0u8..24).collect();
+    let view = ImageView::from_slice(&data, 6, 4).unwrap();
+
+    // The canvas keeps its 6x4 size: a quarter turn moves the central 4x4
+    // block onto itself and leaves the outer columns without a source.
+    let quarter_cw = [
+        [0u8, 19, 13, 7, 1, 0],
+        [0, 20, 14, 8, 2, 0],
+        [0, 21, 15, 9, 3, 0],
+        [0, 22, 16, 10, 4, 0],
+    ];
+    let quarter_ccw = [
+        [0u8, 4, 10, 16, 22, 0],
+        [0, 3, 9, 15, 21, 0],
+        [0, 2, 8, 14, 20, 0],
+        [0, 1, 7, 13, 19, 0],
+    ];
+    let half = [
+        [23u8, 22, 21, 20, 19, 18],
+        [17, 16, 15, 14, 13, 12],
+        [11, 10, 9, 8, 7, 6],
+        [5, 4, 3, 2, 1, 0],
+    ];
+    let inner_mask = [[0u8, 1, 1, 1, 1, 0]; 4];
+
+    for (angle, expected, expected_mask) in [
+        (90.0f32, quarter_cw, inner_mask),
+        (180.0, half, [[1u8; 6]; 4]),
+        (270.0, quarter_ccw, inner_mask),
+        (-90.0, quarter_ccw, inner_mask),
+    ] {
+        let (rotated, mask) = rotate_u8_bilinear_masked(view, angle, 0);
+        assert_eq!(rotated.data(), expected.as_flattened(), "angle {angle}");
+        assert_eq!(mask, expected_mask.as_flattened(), "angle {angle}");
+        assert_eq!(
+            rotate_u8_bilinear(view, angle, 0).data(),
+            expected.as_flattened()
+        );
+    }
 
-    // We avoid 90-degree tests because bilinear sampling and center
-    // conventions make exact integer equality ambiguous.
+    // Square templates map onto themselves, so the mask is fully valid.
+    let square: Vec<u8> = (0u8..25).collect();
+    let square_view = ImageView::from_slice(&square, 5, 5).unwrap();
+    let (rotated, mask) = rotate_u8_bilinear_masked(square_view, 90.0, 0);
+    assert!(mask.iter().all(|&m| m == 1));
+    assert_eq!(rotated.data()[0], square[4 * 5]);
 }
 
 #[test]
@@ -56,6 +109,7 @@ fn rotate_constant_image_with_matching_fill() {
 }
 
 #[test]
+#[allow(deprecated)]
 fn compiled_template_caches_rotations() {
     let width = 8;
     let height = 6;
@@ -68,6 +122,7 @@ fn compiled_template_caches_rotations() {
         min_step_deg: 90.0,
         fill_value: 0,
         precompute_coarsest: true,
+        ..CompileConfig::default()
     };
     let compiled = CompiledTemplate::compile_rotated(&template, cfg).unwrap();
 
@@ -79,7 +134,7 @@ fn compiled_template_caches_rotations() {
     assert_eq!(first.width(), width);
     assert_eq!(first.height(), height);
     assert!(std::ptr::eq(first, second));
-    let expected_sum_w = (width - 1) as f32 * (height - 1) as f32;
+    let expected_sum_w = (width * height) as f32;
     assert!((first.sum_w() - expected_sum_w).abs() < 1e-6);
 
     let grid1 = compiled.angle_grid(1).unwrap();
@@ -88,3 +143,225 @@ fn compiled_template_caches_rotations() {
     assert_eq!(plan1.width(), width / 2);
     assert_eq!(plan1.height(), height / 2);
 }
+
+#[test]
+fn half_turn_slot_has_full_mask() {
+    let width = 8;
+    let height = 6;
+    let data: Vec<u8> = (0u8..(width * height) as u8).collect();
+    let template = Template::new(data.clone(), width, height).unwrap();
+
+    let cfg = CompileConfig {
+        max_levels: 1,
+        coarse_step_deg: 90.0,
+        min_step_deg: 90.0,
+        ..CompileConfig::default()
+    };
+    let compiled = CompiledTemplate::compile_rotated(&template, cfg).unwrap();
+    let grid = compiled.angle_grid(0).unwrap();
+
+    let half = grid.nearest_index(180.0);
+    assert!(compiled.rotated_mask_is_full(0, half).unwrap());
+    let plan = compiled.rotated_zncc_plan(0, half).unwrap();
+    assert!((plan.sum_w() - (width * height) as f32).abs() < 1e-6);
+    let (pixels, _) = compiled.rotated_template_image(0, half).unwrap();
+    let reversed: Vec<u8> = data.iter().rev().copied().collect();
+    assert_eq!(pixels, reversed);
+
+    let zero = grid.nearest_index(0.0);
+    assert!(compiled.rotated_mask_is_full(0, zero).unwrap());
+}
+
+#[test]
+fn compiled_template_lists_sampled_angles() {
+    let data: Vec<u8> = (0..32 * 32).map(|i| (i * 7 % 251) as u8).collect();
+    let template = Template::new(data, 32, 32).unwrap();
+    let cfg = CompileConfig {
+        max_levels: 2,
+        coarse_step_deg: 90.0,
+        min_step_deg: 45.0,
+        ..CompileConfig::default()
+    };
+    let compiled = CompiledTemplate::compile_rotated(&template, cfg).unwrap();
+
+    assert_eq!(compiled.angles(1).unwrap(), [-180.0, -90.0, 0.0, 90.0]);
+    let fine = compiled.angles(0).unwrap();
+    assert_eq!(fine.len(), 8);
+    assert_eq!(
+        fine,
+        compiled.angle_grid(0).unwrap().iter().collect::<Vec<_>>()
+    );
+    assert!(compiled.angles(2).is_none());
+
+    let unrotated =
+        CompiledTemplate::compile_unrotated(&template, CompileConfigNoRot::default()).unwrap();
+    assert!(unrotated.angles(0).is_none());
+}
+
+#[test]
+#[allow(deprecated)]
+fn rotated_template_image_matches_cached_rotation() {
+    let width = 16;
+    let height = 12;
+    let data: Vec<u8> = (0..width * height).map(|i| (i * 5 % 251) as u8).collect();
+    let template = Template::new(data.clone(), width, height).unwrap();
+
+    let cfg = CompileConfig {
+        max_levels: 1,
+        coarse_step_deg: 30.0,
+        min_step_deg: 30.0,
+        fill_value: 7,
+        precompute_coarsest: false,
+        ..CompileConfig::default()
+    };
+    let compiled = CompiledTemplate::compile_rotated(&template, cfg).unwrap();
+    let grid = compiled.angle_grid(0).unwrap();
+
+    let zero = grid.nearest_index(0.0);
+    let (pixels, mask) = compiled.rotated_template_image(0, zero).unwrap();
+    assert_eq!(pixels, data);
+    assert!(mask.iter().all(|&m| m == 1));
+
+    let idx = grid.nearest_index(30.0);
+    let (pixels, mask) = compiled.rotated_template_image(0, idx).unwrap();
+    let view = ImageView::from_slice(&data, width, height).unwrap();
+    let (expected, expected_mask) = rotate_u8_bilinear_masked(view, grid.angle_at(idx), 7);
+    assert_eq!(pixels, expected.view().as_slice());
+    assert_eq!(mask, expected_mask);
+    assert!(mask.contains(&0));
+
+    let plan = compiled.rotated_zncc_plan(0, idx).unwrap();
+    assert_eq!(plan.mask(), mask.as_slice());
+
+    assert!(compiled.rotated_template_image(0, grid.len()).is_err());
+}
+
+#[test]
+fn bounded_rotation_cache_matches_unbounded_search() {
+    let (tpl_width, tpl_height) = (24, 20);
+    let data: Vec<u8> = (0..tpl_width * tpl_height)
+        .map(|i| (((i % tpl_width) * 13) ^ ((i / tpl_width) * 7)) as u8)
+        .collect();
+    let template = Template::new(data.clone(), tpl_width, tpl_height).unwrap();
+    let view = ImageView::from_slice(&data, tpl_width, tpl_height).unwrap();
+    let (rotated, mask) = rotate_u8_bilinear_masked(view, 20.0, 0);
+
+    let (img_width, img_height) = (96, 80);
+    let mut image = vec![0u8; img_width * img_height];
+    for y in 0..tpl_height {
+        for x in 0..tpl_width {
+            let idx = y * tpl_width + x;
+            if mask[idx] == 1 {
+                image[(30 + y) * img_width + 35 + x] = rotated.data()[idx];
+            }
+        }
+    }
+    let image_view = ImageView::from_slice(&image, img_width, img_height).unwrap();
+
+    let cfg = CompileConfig {
+        max_levels: 2,
+        coarse_step_deg: 20.0,
+        min_step_deg: 5.0,
+        ..CompileConfig::default()
+    };
+    let match_cfg = MatchConfig {
+        rotation: RotationMode::Enabled,
+        max_image_levels: 2,
+        ..MatchConfig::default()
+    };
+    let unbounded =
+        Matcher::new(CompiledTemplate::compile_rotated(&template, cfg.clone()).unwrap())
+            .with_config(match_cfg.clone());
+    let bounded = Matcher::new(
+        CompiledTemplate::compile_rotated(
+            &template,
+            CompileConfig {
+                rotation_cache_capacity: Some(1),
+                ..cfg
+            },
+        )
+        .unwrap(),
+    )
+    .with_config(match_cfg);
+
+    let expected = unbounded.match_image(image_view).unwrap();
+    for _ in 0..2 {
+        assert_eq!(bounded.match_image(image_view).unwrap(), expected);
+    }
+}
+
+#[test]
+fn compact_mask_matches_byte_mask_search() {
+    let (tpl_width, tpl_height) = (24, 20);
+    let data: Vec<u8> = (0..tpl_width * tpl_height)
+        .map(|i| (((i % tpl_width) * 13) ^ ((i / tpl_width) * 7)) as u8)
+        .collect();
+    let template = Template::new(data.clone(), tpl_width, tpl_height).unwrap();
+    let view = ImageView::from_slice(&data, tpl_width, tpl_height).unwrap();
+    let (rotated, mask) = rotate_u8_bilinear_masked(view, 20.0, 0);
+
+    let (packed_img, bits) = rotate_u8_bilinear_masked_packed(view, 20.0, 0);
+    assert_eq!(packed_img.data(), rotated.data());
+    assert_eq!(bits.len(), (tpl_width * tpl_height).div_ceil(8));
+    for (idx, &m) in mask.iter().enumerate() {
+        assert_eq!((bits[idx / 8] >> (idx % 8)) & 1, m);
+    }
+
+    let (img_width, img_height) = (96, 80);
+    let mut image = vec![0u8; img_width * img_height];
+    for y in 0..tpl_height {
+        for x in 0..tpl_width {
+            let idx = y * tpl_width + x;
+            if mask[idx] == 1 {
+                image[(30 + y) * img_width + 35 + x] = rotated.data()[idx];
+            }
+        }
+    }
+    let image_view = ImageView::from_slice(&image, img_width, img_height).unwrap();
+
+    let cfg = CompileConfig {
+        max_levels: 2,
+        coarse_step_deg: 20.0,
+        min_step_deg: 5.0,
+        ..CompileConfig::default()
+    };
+    let bytes = CompiledTemplate::compile_rotated(&template, cfg.clone()).unwrap();
+    let packed = CompiledTemplate::compile_rotated(
+        &template,
+        CompileConfig {
+            compact_mask: true,
+            ..cfg
+        },
+    )
+    .unwrap();
+
+    let idx = packed.angle_grid(0).unwrap().nearest_index(20.0);
+    let zncc = packed.rotated_zncc_plan(0, idx).unwrap();
+    let ssd = packed.rotated_ssd_plan(0, idx).unwrap();
+    assert!(zncc.is_mask_packed() && ssd.is_mask_packed());
+    assert!(!bytes.rotated_zncc_plan(0, idx).unwrap().is_mask_packed());
+    assert_eq!(zncc.packed_mask(), Some(bits.as_slice()));
+    assert_eq!(ssd.packed_mask(), Some(bits.as_slice()));
+    assert_eq!(zncc.mask(), mask.as_slice());
+    assert_eq!(ssd.mask(), mask.as_slice());
+
+    let bytes = std::sync::Arc::new(bytes);
+    let packed = std::sync::Arc::new(packed);
+    for metric in [Metric::Zncc, Metric::Ssd] {
+        let match_cfg = MatchConfig {
+            metric,
+            rotation: RotationMode::Enabled,
+            max_image_levels: 2,
+            ..MatchConfig::default()
+        };
+        let expected = Matcher::from_shared(bytes.clone())
+            .with_config(match_cfg.clone())
+            .match_image(image_view)
+            .unwrap();
+        let actual = Matcher::from_shared(packed.clone())
+            .with_config(match_cfg)
+            .match_image(image_view)
+            .unwrap();
+        assert_eq!(actual, expected);
+    }
+}
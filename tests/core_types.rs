@@ -1,7 +1,8 @@
 use corrmatch::lowlevel::TemplatePlan;
 use corrmatch::{
-    CompileConfig, CompileConfigNoRot, CompiledTemplate, CorrMatchError, ImagePyramid, ImageView,
-    Template,
+    CompileConfig, CompileConfigNoRot, CompileOptions, CompiledTemplate, CorrMatchError,
+    CorrMatchResult, Downsampler, ImagePyramid, ImageView, Match, OwnedImage, PyramidFilter,
+    Template, Template3,
 };
 
 #[test]
@@ -64,6 +65,8 @@ fn image_view_roi_matches_expected_values() {
     assert_eq!(roi.row(1).unwrap(), &[9u8, 10u8]);
     assert_eq!(roi.get(0, 0).copied(), Some(5u8));
     assert!(roi.get(2, 0).is_none());
+    assert_eq!(*roi.at(1, 1), 10u8);
+    assert_eq!(roi.row_at(1), &[9u8, 10u8]);
 
     let err = view.roi(3, 3, 2, 2).err().unwrap();
     assert_eq!(
@@ -79,6 +82,40 @@ fn image_view_roi_matches_expected_values() {
     );
 }
 
+#[test]
+fn image_view_roi_clamped_clips_to_bounds() {
+    let data: Vec<u8> = (0u8..16).collect();
+    let view = ImageView::from_slice(&data, 4, 4).unwrap();
+
+    let roi = view.roi_clamped(2, 3, 5, 5).unwrap();
+    assert_eq!((roi.width(), roi.height()), (2, 1));
+    assert_eq!(roi.row(0).unwrap(), &[14u8, 15u8]);
+
+    let inside = view.roi_clamped(1, 1, 2, 2).unwrap();
+    assert_eq!(inside.row(1).unwrap(), &[9u8, 10u8]);
+    let whole = view.roi_clamped(0, 0, usize::MAX, usize::MAX).unwrap();
+    assert_eq!((whole.width(), whole.height()), (4, 4));
+
+    assert!(view.roi_clamped(4, 0, 2, 2).is_none());
+    assert!(view.roi_clamped(1, 1, 0, 2).is_none());
+}
+
+#[test]
+fn image_view_subsample_keeps_every_nth_pixel() {
+    let data: Vec<u8> = (0u8..35).collect();
+    let view = ImageView::new(&data, 5, 5, 7).unwrap();
+
+    let half = view.subsample(2).unwrap();
+    assert_eq!((half.width(), half.height()), (2, 2));
+    assert_eq!(half.data(), &[0u8, 2, 14, 16]);
+
+    let same = view.subsample(1).unwrap();
+    assert_eq!(same.data(), OwnedImage::from_view(view).unwrap().data());
+
+    assert!(view.subsample(0).is_err());
+    assert!(view.subsample(6).is_err());
+}
+
 #[test]
 fn image_pyramid_downsamples_by_two() {
     let data: Vec<u8> = (0u8..16).collect();
@@ -98,6 +135,160 @@ fn image_pyramid_downsamples_by_two() {
     assert_eq!(level2.height(), 1);
 }
 
+/// Keeps the top-left pixel of every 2x2 block.
+#[derive(Debug)]
+struct Decimate;
+
+impl Downsampler for Decimate {
+    fn downsample(&self, src: ImageView<'_, u8>) -> CorrMatchResult<OwnedImage> {
+        let (width, height) = (src.width() / 2, src.height() / 2);
+        let mut data = Vec::with_capacity(width * height);
+        for y in 0..height {
+            let row = src.row(2 * y).unwrap();
+            data.extend((0..width).map(|x| row[2 * x]));
+        }
+        OwnedImage::new(data, width, height)
+    }
+}
+
+/// Returns its input unchanged, which is not a valid pyramid step.
+#[derive(Debug)]
+struct Identity;
+
+impl Downsampler for Identity {
+    fn downsample(&self, src: ImageView<'_, u8>) -> CorrMatchResult<OwnedImage> {
+        OwnedImage::from_view(src)
+    }
+}
+
+#[test]
+fn image_pyramid_builds_with_a_custom_downsampler() {
+    let data: Vec<u8> = (0u8..64).collect();
+    let view = ImageView::from_slice(&data, 8, 8).unwrap();
+
+    let pyramid = ImagePyramid::build_with(view, 10, &Decimate).unwrap();
+    assert_eq!(pyramid.levels().len(), 4);
+    assert_eq!(
+        pyramid.level(1).unwrap().row(1).unwrap(),
+        &[16u8, 18, 20, 22]
+    );
+
+    let boxed = ImagePyramid::build_with(view, 10, &PyramidFilter::Box2x2).unwrap();
+    let reference = ImagePyramid::build_u8(view, 10).unwrap();
+    assert_eq!(boxed.levels().len(), reference.levels().len());
+    for (a, b) in boxed.levels().iter().zip(reference.levels()) {
+        assert_eq!(a.data(), b.data());
+    }
+
+    assert!(matches!(
+        ImagePyramid::build_with(view, 2, &Identity),
+        Err(CorrMatchError::InvalidConfig { .. })
+    ));
+}
+
+#[test]
+fn level_coordinates_map_to_base_by_the_decimation_factor() {
+    let data: Vec<u8> = (0..64u32).map(|i| (i * 37 % 251) as u8).collect();
+    let view = ImageView::from_slice(&data, 8, 8).unwrap();
+    let pyramid = ImagePyramid::build_u8(view, 3).unwrap();
+    assert_eq!(pyramid.scale_factor(0), 1.0);
+    assert_eq!(pyramid.scale_factor(2), 4.0);
+
+    let template = Template::new(data, 8, 8).unwrap();
+    let compiled =
+        CompiledTemplate::compile_unrotated(&template, CompileConfigNoRot { max_levels: 3 })
+            .unwrap();
+    assert_eq!(compiled.level_to_base(0, 3.5, 2.0), (3.5, 2.0));
+    assert_eq!(compiled.level_to_base(2, 3.5, 2.0), (14.0, 8.0));
+    let (width, height) = compiled.level_size(1).unwrap();
+    assert_eq!(
+        compiled.level_to_base(1, width as f32, height as f32),
+        (8.0, 8.0)
+    );
+}
+
+#[test]
+fn owned_image_from_view_copies_a_strided_roi() {
+    let data: Vec<u8> = (0u8..30).collect();
+    let view = ImageView::new(&data, 5, 5, 6).unwrap();
+    let roi = view.roi(1, 2, 3, 2).unwrap();
+
+    let owned = OwnedImage::from_view(roi).unwrap();
+    assert_eq!((owned.width(), owned.height(), owned.stride()), (3, 2, 3));
+    assert_eq!(owned.data(), &[13u8, 14, 15, 19, 20, 21]);
+}
+
+#[test]
+fn template_from_region_copies_the_crop() {
+    let data: Vec<u8> = (0u8..30).collect();
+    let view = ImageView::new(&data, 5, 5, 6).unwrap();
+
+    let template = Template::from_region(view, 1, 2, 3, 2).unwrap();
+    assert_eq!((template.width(), template.height()), (3, 2));
+    let tpl_view = template.view();
+    assert_eq!(tpl_view.row(0).unwrap(), &[13u8, 14, 15]);
+    assert_eq!(tpl_view.row(1).unwrap(), &[19u8, 20, 21]);
+
+    assert!(matches!(
+        Template::from_region(view, 3, 3, 3, 2),
+        Err(CorrMatchError::RoiOutOfBounds { .. })
+    ));
+}
+
+#[test]
+fn opencv_pyramid_matches_pyr_down_definition() {
+    let data = vec![0u8; 5 * 3];
+    let view = ImageView::from_slice(&data, 5, 3).unwrap();
+
+    // cv2.pyrDown halves odd sizes rounding up.
+    let pyramid =
+        ImagePyramid::build_u8_filtered(view, 10, PyramidFilter::OpenCvCompatible).unwrap();
+    let sizes: Vec<(usize, usize)> = pyramid
+        .levels()
+        .iter()
+        .map(|level| (level.width(), level.height()))
+        .collect();
+    assert_eq!(sizes, vec![(5, 3), (3, 2), (2, 1), (1, 1)]);
+
+    // cv2.pyrDown output for a single 255 pixel: the sampled [1 4 6 4 1]
+    // outer product scaled by 255 / 256, i.e. 36, 6 and 1. BORDER_REFLECT_101
+    // mirrors about the edge pixel, so an impulse in the bottom-right corner
+    // of an odd-sized image is not folded onto itself.
+    let impulse = |width: usize, height: usize, x: usize, y: usize| {
+        let mut data = vec![0u8; width * height];
+        data[y * width + x] = 255;
+        let view = ImageView::from_slice(&data, width, height).unwrap();
+        let pyramid =
+            ImagePyramid::build_u8_filtered(view, 2, PyramidFilter::OpenCvCompatible).unwrap();
+        let level1 = pyramid.level(1).unwrap();
+        (0..level1.height())
+            .map(|y| level1.row(y).unwrap().to_vec())
+            .collect::<Vec<_>>()
+    };
+    let interior = impulse(8, 8, 4, 4);
+    assert_eq!(interior[0], [0u8, 0, 0, 0]);
+    assert_eq!(interior[1], [0u8, 1, 6, 1]);
+    assert_eq!(interior[2], [0u8, 6, 36, 6]);
+    assert_eq!(interior[3], [0u8, 1, 6, 1]);
+    let corner = impulse(7, 5, 6, 4);
+    assert_eq!(corner.len(), 3);
+    assert_eq!(corner[0], [0u8, 0, 0, 0]);
+    assert_eq!(corner[1], [0u8, 0, 1, 6]);
+    assert_eq!(corner[2], [0u8, 0, 6, 36]);
+}
+
+#[test]
+fn opencv_pyramid_keeps_constant_images_constant() {
+    let data = vec![77u8; 9 * 7];
+    let view = ImageView::from_slice(&data, 9, 7).unwrap();
+
+    let pyramid =
+        ImagePyramid::build_u8_filtered(view, 3, PyramidFilter::OpenCvCompatible).unwrap();
+    let level2 = pyramid.level(2).unwrap();
+    assert_eq!((level2.width(), level2.height()), (3, 2));
+    assert!(level2.as_slice().iter().all(|&v| v == 77));
+}
+
 #[test]
 fn template_plan_matches_known_stats() {
     let tpl = Template::new(vec![0u8, 1, 2, 3], 2, 2).unwrap();
@@ -128,6 +319,42 @@ fn template_plan_rejects_degenerate_templates() {
     );
 }
 
+#[test]
+fn min_template_variance_rejects_low_contrast_templates() {
+    // Alternating 100/101 pixels: per-pixel variance 0.25.
+    let data: Vec<u8> = (0..64).map(|i| 100 + ((i + i / 8) % 2) as u8).collect();
+    let template = Template::new(data, 8, 8).unwrap();
+    assert!(CompiledTemplate::compile_unrotated(&template, CompileConfigNoRot::default()).is_ok());
+
+    let opts = CompileOptions::new().with_min_template_variance(1.0);
+    let err = CompiledTemplate::compile_unrotated_with(
+        &template,
+        CompileConfigNoRot::default(),
+        opts.clone(),
+    )
+    .err()
+    .unwrap();
+    match &err {
+        CorrMatchError::LowTemplateVariance { variance, min } => {
+            assert!((variance.get() - 0.25).abs() < 1e-4, "{variance}");
+            assert_eq!(min.get(), 1.0);
+        }
+        other => panic!("unexpected error: {other:?}"),
+    }
+    assert!(err.to_string().contains("variance 0.25"), "{err}");
+
+    let err = CompiledTemplate::compile_with(&template, CompileConfig::default(), opts)
+        .err()
+        .unwrap();
+    assert!(matches!(err, CorrMatchError::LowTemplateVariance { .. }));
+    assert!(matches!(
+        CompileOptions::new()
+            .with_min_template_variance(f32::NAN)
+            .validate(),
+        Err(CorrMatchError::InvalidConfig { .. })
+    ));
+}
+
 #[test]
 fn compiled_template_trims_degenerate_coarsest_levels() {
     let tpl_width = 4;
@@ -165,3 +392,204 @@ fn compiled_rotated_skips_too_small_levels() {
     let (w, h) = compiled.level_size(compiled.num_levels() - 1).unwrap();
     assert!(w >= 3 && h >= 3);
 }
+
+#[test]
+fn level_image_exposes_stored_template_levels() {
+    let (width, height) = (24, 16);
+    let data: Vec<u8> = (0..width * height).map(|i| (i * 7 % 251) as u8).collect();
+    let template = Template::new(data.clone(), width, height).unwrap();
+
+    let unrotated =
+        CompiledTemplate::compile_unrotated(&template, CompileConfigNoRot { max_levels: 2 })
+            .unwrap();
+    let rotated = CompiledTemplate::compile_rotated(
+        &template,
+        CompileConfig {
+            max_levels: 2,
+            min_template_dim: 4,
+            precompute_coarsest: false,
+            ..CompileConfig::default()
+        },
+    )
+    .unwrap();
+
+    for compiled in [&unrotated, &rotated] {
+        assert_eq!(compiled.num_levels(), 2);
+        let level0 = compiled.level_image(0).unwrap();
+        assert_eq!(level0.as_slice(), data.as_slice());
+        for level in 0..compiled.num_levels() {
+            let view = compiled.level_image(level).unwrap();
+            assert_eq!(
+                Some((view.width(), view.height())),
+                compiled.level_size(level)
+            );
+            let plan = TemplatePlan::from_view(view).unwrap();
+            let stored = compiled.unmasked_zncc_plan(level).unwrap();
+            assert_eq!(plan.t_prime(), stored.t_prime());
+        }
+        assert!(compiled.level_image(compiled.num_levels()).is_none());
+    }
+}
+
+#[test]
+fn image_pyramid_stops_at_min_dim() {
+    let data = vec![0u8; 40 * 24];
+    let view = ImageView::from_slice(&data, 40, 24).unwrap();
+
+    let pyramid = ImagePyramid::build_u8_min_dim(view, 10, 6).unwrap();
+    let sizes: Vec<(usize, usize)> = pyramid
+        .levels()
+        .iter()
+        .map(|level| (level.width(), level.height()))
+        .collect();
+    assert_eq!(sizes, vec![(40, 24), (20, 12), (10, 6)]);
+}
+
+#[test]
+fn compiled_rotated_level_count_follows_min_template_dim() {
+    let tpl_width = 64;
+    let tpl_height = 40;
+    let tpl_data: Vec<u8> = (0..tpl_width * tpl_height)
+        .map(|i| ((i * 37) % 251) as u8)
+        .collect();
+    let template = Template::new(tpl_data, tpl_width, tpl_height).unwrap();
+
+    // 64x40 -> 32x20 -> 16x10 -> 8x5 -> 4x2; the shorter side must stay >= 3.
+    for (min_template_dim, expected_levels) in [(8, 4), (4, 4), (16, 3), (32, 2), (40, 1)] {
+        let compiled = CompiledTemplate::compile_rotated(
+            &template,
+            CompileConfig {
+                max_levels: 10,
+                min_template_dim,
+                precompute_coarsest: false,
+                ..CompileConfig::default()
+            },
+        )
+        .unwrap();
+        assert_eq!(compiled.num_levels(), expected_levels);
+    }
+
+    let err = CompileConfig {
+        min_template_dim: 0,
+        ..CompileConfig::default()
+    }
+    .validate()
+    .unwrap_err();
+    assert!(matches!(err, CorrMatchError::InvalidConfig { .. }));
+}
+
+#[test]
+fn match_aabb_and_iou_follow_rotated_footprint() {
+    let base = Match {
+        x: 10.0,
+        y: 20.0,
+        angle_deg: 0.0,
+        score: 1.0,
+        peak_sharpness: None,
+    };
+    assert_eq!(base.aabb(40, 20), (10.0, 20.0, 50.0, 40.0));
+
+    let quarter = Match {
+        angle_deg: 90.0,
+        ..base
+    };
+    let (x0, y0, x1, y1) = quarter.aabb(40, 20);
+    let expected = (20.0, 10.0, 40.0, 50.0);
+    for (got, want) in [x0, y0, x1, y1]
+        .into_iter()
+        .zip([expected.0, expected.1, expected.2, expected.3])
+    {
+        assert!((got - want).abs() < 1e-4, "{got} vs {want}");
+    }
+
+    let diagonal = Match {
+        angle_deg: 45.0,
+        ..base
+    };
+    let (x0, _, x1, _) = diagonal.aabb(40, 20);
+    assert!((x1 - x0 - 60.0 * std::f32::consts::FRAC_1_SQRT_2).abs() < 1e-3);
+
+    assert!((base.iou(&base, 40, 20) - 1.0).abs() < 1e-6);
+    let shifted = Match { x: 30.0, ..base };
+    assert!((base.iou(&shifted, 40, 20) - 1.0 / 3.0).abs() < 1e-6);
+    let far = Match { x: 100.0, ..base };
+    assert_eq!(base.iou(&far, 40, 20), 0.0);
+}
+
+#[test]
+fn match_corners_trace_the_rotated_footprint() {
+    let base = Match {
+        x: 10.0,
+        y: 20.0,
+        angle_deg: 0.0,
+        score: 1.0,
+        peak_sharpness: None,
+    };
+    assert_eq!(
+        base.corners(40, 20),
+        [[10.0, 20.0], [50.0, 20.0], [50.0, 40.0], [10.0, 40.0]]
+    );
+
+    // A quarter turn about the center (30, 30) carries the template's
+    // top-left corner to the top-right of the rotated footprint.
+    let quarter = Match {
+        angle_deg: 90.0,
+        ..base
+    };
+    let expected = [[40.0, 10.0], [40.0, 50.0], [20.0, 50.0], [20.0, 10.0]];
+    for (got, want) in quarter.corners(40, 20).iter().zip(expected) {
+        assert!(
+            (got[0] - want[0]).abs() < 1e-4 && (got[1] - want[1]).abs() < 1e-4,
+            "{got:?} vs {want:?}"
+        );
+    }
+
+    let diagonal = Match {
+        angle_deg: 30.0,
+        ..base
+    };
+    let corners = diagonal.corners(40, 20);
+    let (x0, y0, x1, y1) = diagonal.aabb(40, 20);
+    let min_x = corners.iter().map(|c| c[0]).fold(f32::INFINITY, f32::min);
+    let max_y = corners
+        .iter()
+        .map(|c| c[1])
+        .fold(f32::NEG_INFINITY, f32::max);
+    assert!((min_x - x0).abs() < 1e-4 && (max_y - y1).abs() < 1e-4);
+    assert!(corners
+        .iter()
+        .all(|c| c[0] <= x1 + 1e-4 && c[1] >= y0 - 1e-4));
+}
+
+#[test]
+fn rgb_template_skips_flat_channels() {
+    let err = Template3::new(vec![0u8; 4 * 4 * 3 - 1], 4, 4)
+        .err()
+        .unwrap();
+    assert_eq!(
+        err,
+        CorrMatchError::BufferTooSmall {
+            needed: 48,
+            got: 47
+        }
+    );
+
+    // Only the red channel varies; green and blue are constant.
+    let data: Vec<u8> = (0..8 * 8)
+        .flat_map(|i| [((i * 37) % 251) as u8, 90, 0])
+        .collect();
+    let template = Template3::new(data, 8, 8).unwrap();
+    assert_eq!(template.channel(1).unwrap().get(3, 3), Some(&90));
+    let compiled = template
+        .compile(CompileConfigNoRot { max_levels: 1 })
+        .unwrap();
+    let plan = compiled.plan(0).unwrap();
+    assert!(plan.channel(0).is_some());
+    assert!(plan.channel(1).is_none() && plan.channel(2).is_none());
+
+    let flat = Template3::new(vec![7u8; 8 * 8 * 3], 8, 8).unwrap();
+    assert!(matches!(
+        flat.compile(CompileConfigNoRot::default()),
+        Err(CorrMatchError::DegenerateTemplate { .. })
+    ));
+}
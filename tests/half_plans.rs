@@ -0,0 +1,124 @@
+#![cfg(feature = "half")]
+
+use corrmatch::lowlevel::{scan_zncc_scalar_full, score_masked_zncc_at};
+use corrmatch::{
+    CompileConfig, CompiledTemplate, ImageView, MatchConfig, Matcher, RotationMode, Template,
+};
+
+fn make_template(width: usize, height: usize) -> Vec<u8> {
+    let mut data = Vec::with_capacity(width * height);
+    for y in 0..height {
+        for x in 0..width {
+            let value = ((x * 13) ^ (y * 7) ^ (x * y)) & 0xFF;
+            data.push(value as u8);
+        }
+    }
+    data
+}
+
+fn make_image(width: usize, height: usize) -> Vec<u8> {
+    (0..width * height)
+        .map(|i| ((i * 37 + (i / width) * 11) % 251) as u8)
+        .collect()
+}
+
+fn compile(template: &Template, compact_plans: bool) -> CompiledTemplate {
+    CompiledTemplate::compile_rotated(
+        template,
+        CompileConfig {
+            max_levels: 3,
+            coarse_step_deg: 30.0,
+            min_step_deg: 10.0,
+            compact_plans,
+            ..CompileConfig::default()
+        },
+    )
+    .unwrap()
+}
+
+#[test]
+fn compact_plans_score_within_half_precision_tolerance() {
+    let template = Template::new(make_template(40, 32), 40, 32).unwrap();
+    let full = compile(&template, false);
+    let compact = compile(&template, true);
+
+    let image_data = make_image(96, 80);
+    let image = ImageView::from_slice(&image_data, 96, 80).unwrap();
+    let unmasked_full = full.unmasked_zncc_plan(0).unwrap();
+    let unmasked_compact = compact.unmasked_zncc_plan(0).unwrap();
+    assert!(!unmasked_full.is_compact());
+    assert!(unmasked_compact.is_compact());
+
+    // Scores stay within 1e-3 of the f32 plans across placements and angles.
+    let mut max_err = 0.0f32;
+    let num_angles = full.angle_grid(0).unwrap().len();
+    for angle_idx in 0..num_angles {
+        let plan_full = full.rotated_zncc_plan(0, angle_idx).unwrap();
+        let plan_compact = compact.rotated_zncc_plan(0, angle_idx).unwrap();
+        assert!(plan_compact.is_compact());
+        for (x, y) in [(0, 0), (13, 7), (30, 25), (56, 48)] {
+            let a = score_masked_zncc_at(image, plan_full, x, y, 1e-8);
+            let b = score_masked_zncc_at(image, plan_compact, x, y, 1e-8);
+            max_err = max_err.max((a - b).abs());
+        }
+    }
+    let best_full = scan_zncc_scalar_full(image, unmasked_full, 0, 1, 1e-8, -1.0).unwrap();
+    let best_compact = scan_zncc_scalar_full(image, unmasked_compact, 0, 1, 1e-8, -1.0).unwrap();
+    max_err = max_err.max((best_full[0].score - best_compact[0].score).abs());
+    assert!(max_err < 1e-3, "max score error {max_err}");
+}
+
+#[test]
+fn compact_plans_find_the_same_match() {
+    let tpl_data = make_template(40, 32);
+    let template = Template::new(tpl_data.clone(), 40, 32).unwrap();
+    let mut image_data = make_image(128, 96);
+    for y in 0..32 {
+        let row = (30 + y) * 128 + 50;
+        image_data[row..row + 40].copy_from_slice(&tpl_data[y * 40..(y + 1) * 40]);
+    }
+    let image = ImageView::from_slice(&image_data, 128, 96).unwrap();
+    let cfg = MatchConfig {
+        rotation: RotationMode::Enabled,
+        ..MatchConfig::default()
+    };
+
+    let full = Matcher::new(compile(&template, false))
+        .with_config(cfg.clone())
+        .match_image(image)
+        .unwrap();
+    let compact = Matcher::new(compile(&template, true))
+        .with_config(cfg)
+        .match_image(image)
+        .unwrap();
+    assert!((full.x - compact.x).abs() < 0.05 && (full.y - compact.y).abs() < 0.05);
+    assert!((full.score - compact.score).abs() < 1e-3);
+}
+
+#[test]
+fn compact_plans_expose_decoded_zero_mean_buffers() {
+    let template = Template::new(make_template(40, 32), 40, 32).unwrap();
+    let full = compile(&template, false);
+    let compact = compile(&template, true);
+
+    let pairs = [
+        (
+            full.unmasked_zncc_plan(0).unwrap().t_prime(),
+            compact.unmasked_zncc_plan(0).unwrap().t_prime(),
+        ),
+        (
+            full.rotated_zncc_plan(0, 1).unwrap().t_prime(),
+            compact.rotated_zncc_plan(0, 1).unwrap().t_prime(),
+        ),
+    ];
+    for (expected, decoded) in pairs {
+        assert_eq!(decoded.len(), expected.len());
+        for (&a, &b) in expected.iter().zip(decoded) {
+            // Half precision keeps 11 significant bits.
+            assert!((a - b).abs() <= a.abs() / 1024.0 + 1e-6, "{a} vs {b}");
+        }
+    }
+    // The decoded buffer is kept, so repeated calls return the same slice.
+    let plan = compact.unmasked_zncc_plan(0).unwrap();
+    assert!(std::ptr::eq(plan.t_prime(), plan.t_prime()));
+}
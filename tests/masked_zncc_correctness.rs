@@ -1,5 +1,6 @@
 use corrmatch::lowlevel::{
-    nms_2d, rotate_u8_bilinear_masked, scan_masked_zncc_scalar, MaskedTemplatePlan, Peak,
+    nms_2d, rotate_u8_bilinear_masked, scan_masked_zncc_scalar, score_masked_zncc_at, zncc_score,
+    MaskedTemplatePlan, Peak,
 };
 use corrmatch::ImageView;
 use rand::rngs::StdRng;
@@ -213,3 +214,127 @@ fn nms_keeps_all_with_zero_radius() {
     assert_eq!(kept.len(), 2);
     assert!(kept[0].score >= kept[1].score);
 }
+
+#[test]
+fn weighted_zncc_matches_reference() {
+    let tpl_width = 5;
+    let tpl_height = 4;
+    let mut tpl_data = Vec::with_capacity(tpl_width * tpl_height);
+    let mut weights = Vec::with_capacity(tpl_width * tpl_height);
+    for y in 0..tpl_height {
+        for x in 0..tpl_width {
+            tpl_data.push(((x * 37 + y * 53 + x * y * 11) % 251) as u8);
+            weights.push(((x + 2 * y) % 4) as f32 / 3.0);
+        }
+    }
+
+    let width = 12;
+    let height = 9;
+    let mut image = vec![0u8; width * height];
+    for y in 0..height {
+        for x in 0..width {
+            image[y * width + x] = ((x * 29 + y * 17 + x * y * 7) % 241) as u8;
+        }
+    }
+
+    let tpl_view = ImageView::from_slice(&tpl_data, tpl_width, tpl_height).unwrap();
+    let plan = MaskedTemplatePlan::from_rotated_weights(tpl_view, weights.clone(), 0.0).unwrap();
+    let image_view = ImageView::from_slice(&image, width, height).unwrap();
+
+    let sum_w: f64 = weights.iter().map(|&w| w as f64).sum();
+    let mu_t = tpl_data
+        .iter()
+        .zip(weights.iter())
+        .map(|(&t, &w)| w as f64 * t as f64)
+        .sum::<f64>()
+        / sum_w;
+    assert!((plan.sum_w() as f64 - sum_w).abs() < 1e-4);
+
+    let (x, y) = (4usize, 3usize);
+    let mut sum_i = 0.0f64;
+    for ty in 0..tpl_height {
+        for tx in 0..tpl_width {
+            let w = weights[ty * tpl_width + tx] as f64;
+            sum_i += w * image[(y + ty) * width + (x + tx)] as f64;
+        }
+    }
+    let mu_i = sum_i / sum_w;
+    let mut cov = 0.0f64;
+    let mut var_t = 0.0f64;
+    let mut var_i = 0.0f64;
+    for ty in 0..tpl_height {
+        for tx in 0..tpl_width {
+            let idx = ty * tpl_width + tx;
+            let w = weights[idx] as f64;
+            let dt = tpl_data[idx] as f64 - mu_t;
+            let di = image[(y + ty) * width + (x + tx)] as f64 - mu_i;
+            cov += w * dt * di;
+            var_t += w * dt * dt;
+            var_i += w * di * di;
+        }
+    }
+    let expected = cov / (var_t * var_i).sqrt();
+
+    let score = score_masked_zncc_at(image_view, &plan, x, y, 1e-8);
+    assert!(
+        (score as f64 - expected).abs() < 1e-4,
+        "score {score} vs expected {expected}"
+    );
+
+    let mut embedded = image.clone();
+    for ty in 0..tpl_height {
+        for tx in 0..tpl_width {
+            embedded[(y + ty) * width + (x + tx)] = tpl_data[ty * tpl_width + tx];
+        }
+    }
+    let embedded_view = ImageView::from_slice(&embedded, width, height).unwrap();
+    let peaks = scan_masked_zncc_scalar(embedded_view, &plan, 0, 1).unwrap();
+    assert_eq!((peaks[0].x, peaks[0].y), (x, y));
+    assert!(peaks[0].score > 0.999);
+}
+
+#[test]
+fn weighted_plan_rejects_negative_weights() {
+    let tpl_data: Vec<u8> = (0u8..9).collect();
+    let tpl_view = ImageView::from_slice(&tpl_data, 3, 3).unwrap();
+    let mut weights = vec![1.0f32; 9];
+    weights[4] = -0.5;
+    assert!(MaskedTemplatePlan::from_rotated_weights(tpl_view, weights, 0.0).is_err());
+}
+
+#[test]
+fn zncc_score_matches_reference_for_patches() {
+    let mut rng = StdRng::seed_from_u64(0x2cc);
+    let (width, height) = (9, 7);
+    let a: Vec<u8> = (0..width * height).map(|_| rng.random()).collect();
+    let b: Vec<u8> = (0..width * height).map(|_| rng.random()).collect();
+
+    let n = (width * height) as f64;
+    let mean = |p: &[u8]| p.iter().map(|&v| v as f64).sum::<f64>() / n;
+    let (mean_a, mean_b) = (mean(&a), mean(&b));
+    let (mut dot, mut var_a, mut var_b) = (0.0f64, 0.0f64, 0.0f64);
+    for (&va, &vb) in a.iter().zip(&b) {
+        let (da, db) = (va as f64 - mean_a, vb as f64 - mean_b);
+        dot += da * db;
+        var_a += da * da;
+        var_b += db * db;
+    }
+    let expected = dot / (var_a * var_b).sqrt();
+
+    let score = zncc_score(&a, &b, width, height).unwrap();
+    assert!(
+        (score as f64 - expected).abs() < 1e-4,
+        "{score} vs {expected}"
+    );
+    assert!((zncc_score(&b, &a, width, height).unwrap() - score).abs() < 1e-5);
+    assert!((zncc_score(&a, &a, width, height).unwrap() - 1.0).abs() < 1e-5);
+
+    let inverted: Vec<u8> = a.iter().map(|&v| 255 - v).collect();
+    assert!((zncc_score(&a, &inverted, width, height).unwrap() + 1.0).abs() < 1e-5);
+    let flat = vec![7u8; width * height];
+    assert_eq!(
+        zncc_score(&flat, &a, width, height).unwrap(),
+        f32::NEG_INFINITY
+    );
+    assert!(zncc_score(&a, &b[1..], width, height).is_err());
+}
@@ -1,8 +1,12 @@
 use corrmatch::lowlevel::rotate_u8_bilinear_masked;
 use corrmatch::{
-    CompileConfig, CompileConfigNoRot, CompiledTemplate, ImageView, MatchConfig, Matcher, Metric,
-    RotationMode, Template,
+    AngleRefine, AngleSchedule, CompileConfig, CompileConfigNoRot, CompileOptions,
+    CompiledTemplate, CorrMatchError, CorrMatchResult, Downsampler, Ensemble, FillStrategy,
+    ImageView, Match, MatchConfig, Matcher, Metric, OwnedImage, PyramidFilter, RgbMatcher,
+    RotationMode, ScoreAgg, ScoreClamp, Template, Template3, TemplateBatch, TieBreak,
 };
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
 
 fn make_template(width: usize, height: usize) -> Vec<u8> {
     let mut data = Vec::with_capacity(width * height);
@@ -27,6 +31,7 @@ fn angle_diff_deg(a: f32, b: f32) -> f32 {
 }
 
 #[test]
+#[allow(deprecated)]
 fn pipeline_finds_rotated_match() {
     let tpl_width = 64;
     let tpl_height = 48;
@@ -60,6 +65,7 @@ fn pipeline_finds_rotated_match() {
             min_step_deg: 30.0,
             fill_value: 0,
             precompute_coarsest: true,
+            ..CompileConfig::default()
         },
     )
     .unwrap();
@@ -99,6 +105,598 @@ fn pipeline_finds_rotated_match() {
 }
 
 #[test]
+fn golden_section_angle_refine_lands_between_grid_steps() {
+    let tpl_width = 64;
+    let tpl_height = 48;
+    let tpl_data = make_template(tpl_width, tpl_height);
+    let template = Template::new(tpl_data.clone(), tpl_width, tpl_height).unwrap();
+
+    let angle_deg = 33.0f32;
+    let tpl_view = ImageView::from_slice(&tpl_data, tpl_width, tpl_height).unwrap();
+    let (rotated, mask) = rotate_u8_bilinear_masked(tpl_view, angle_deg, 0);
+    let (img_width, img_height) = (128, 112);
+    let (x0, y0) = (34, 30);
+    let mut image = vec![0u8; img_width * img_height];
+    for y in 0..tpl_height {
+        for x in 0..tpl_width {
+            let idx = y * tpl_width + x;
+            if mask[idx] == 1 {
+                image[(y0 + y) * img_width + (x0 + x)] = rotated.data()[idx];
+            }
+        }
+    }
+    let image_view = ImageView::from_slice(&image, img_width, img_height).unwrap();
+
+    let compiled = Arc::new(
+        CompiledTemplate::compile_rotated(
+            &template,
+            CompileConfig {
+                max_levels: 2,
+                coarse_step_deg: 20.0,
+                min_step_deg: 10.0,
+                fill_strategy: FillStrategy::Constant(0),
+                ..CompileConfig::default()
+            },
+        )
+        .unwrap(),
+    );
+    let cfg = MatchConfig {
+        max_image_levels: 2,
+        rotation: RotationMode::Enabled,
+        ..MatchConfig::default()
+    };
+    let quadratic = Matcher::from_shared(Arc::clone(&compiled))
+        .with_config(cfg.clone())
+        .match_image(image_view)
+        .unwrap();
+    let golden = Matcher::from_shared(compiled)
+        .with_config(MatchConfig {
+            angle_refine: AngleRefine::GoldenSection { iters: 16 },
+            ..cfg
+        })
+        .match_image(image_view)
+        .unwrap();
+
+    let quadratic_err = angle_diff_deg(quadratic.angle_deg, angle_deg);
+    let golden_err = angle_diff_deg(golden.angle_deg, angle_deg);
+    assert!(golden_err < 0.5, "golden angle {}", golden.angle_deg);
+    assert!(
+        golden_err <= quadratic_err,
+        "{golden_err} vs {quadratic_err}"
+    );
+    assert_eq!(golden.score, quadratic.score);
+}
+
+#[test]
+fn angle_prior_selects_instance_within_window() {
+    let size = 48;
+    let tpl_data = make_template(size, size);
+    let template = Template::new(tpl_data.clone(), size, size).unwrap();
+    let tpl_view = ImageView::from_slice(&tpl_data, size, size).unwrap();
+    let (rotated, mask) = rotate_u8_bilinear_masked(tpl_view, 90.0, 0);
+
+    let img_width = 200;
+    let img_height = 140;
+    let mut image = vec![0u8; img_width * img_height];
+    let (ux, uy) = (12usize, 16usize);
+    let (rx, ry) = (120usize, 70usize);
+    for y in 0..size {
+        for x in 0..size {
+            let idx = y * size + x;
+            image[(uy + y) * img_width + ux + x] = tpl_data[idx];
+            if mask[idx] == 1 {
+                image[(ry + y) * img_width + rx + x] = rotated.data()[idx];
+            }
+        }
+    }
+
+    let compiled = CompiledTemplate::compile_rotated(
+        &template,
+        CompileConfig {
+            max_levels: 1,
+            coarse_step_deg: 30.0,
+            min_step_deg: 30.0,
+            ..CompileConfig::default()
+        },
+    )
+    .unwrap();
+    let matcher = Matcher::new(compiled).with_config(MatchConfig {
+        max_image_levels: 1,
+        rotation: RotationMode::Enabled,
+        ..MatchConfig::default()
+    });
+    let view = ImageView::from_slice(&image, img_width, img_height).unwrap();
+
+    let upright = matcher.match_image_with_prior(view, 5.0, 20.0).unwrap();
+    assert!((upright.x - ux as f32).abs() <= 1.0 && (upright.y - uy as f32).abs() <= 1.0);
+    assert!(angle_diff_deg(upright.angle_deg, 0.0) <= 30.0);
+
+    // A window narrower than the grid step still scans the nearest angle.
+    let turned = matcher.match_image_with_prior(view, 85.0, 2.0).unwrap();
+    assert!((turned.x - rx as f32).abs() <= 1.0 && (turned.y - ry as f32).abs() <= 1.0);
+    assert!(angle_diff_deg(turned.angle_deg, 90.0) <= 30.0);
+
+    assert!(matches!(
+        matcher.match_image_with_prior(view, 0.0, -1.0),
+        Err(CorrMatchError::InvalidConfig { .. })
+    ));
+}
+
+#[test]
+fn match_at_positions_scores_only_the_given_positions() {
+    let size = 48;
+    let tpl_data = make_template(size, size);
+    let template = Template::new(tpl_data.clone(), size, size).unwrap();
+    let tpl_view = ImageView::from_slice(&tpl_data, size, size).unwrap();
+    let (rotated, mask) = rotate_u8_bilinear_masked(tpl_view, 60.0, 0);
+
+    let img_width = 200;
+    let img_height = 140;
+    let mut image = vec![0u8; img_width * img_height];
+    let (ux, uy) = (12usize, 16usize);
+    let (rx, ry) = (120usize, 70usize);
+    for y in 0..size {
+        for x in 0..size {
+            let idx = y * size + x;
+            image[(uy + y) * img_width + ux + x] = tpl_data[idx];
+            if mask[idx] == 1 {
+                image[(ry + y) * img_width + rx + x] = rotated.data()[idx];
+            }
+        }
+    }
+    let view = ImageView::from_slice(&image, img_width, img_height).unwrap();
+
+    let compiled = CompiledTemplate::compile_rotated(
+        &template,
+        CompileConfig {
+            max_levels: 2,
+            coarse_step_deg: 30.0,
+            min_step_deg: 10.0,
+            ..CompileConfig::default()
+        },
+    )
+    .unwrap();
+    let matcher = Matcher::new(compiled).with_config(MatchConfig {
+        rotation: RotationMode::Enabled,
+        min_score: 0.5,
+        ..MatchConfig::default()
+    });
+
+    // The empty region at (80, 10) has no texture and is skipped.
+    let matches = matcher
+        .match_at_positions(view, &[(80, 10), (rx, ry), (ux, uy)])
+        .unwrap();
+    assert_eq!(matches.len(), 2, "{matches:?}");
+    assert!(matches[0].score >= matches[1].score);
+    let upright = matches
+        .iter()
+        .find(|m| m.x < 60.0)
+        .expect("upright instance");
+    assert!((upright.x - ux as f32).abs() < 1.0 && (upright.y - uy as f32).abs() < 1.0);
+    assert!(angle_diff_deg(upright.angle_deg, 0.0) < 2.0, "{upright:?}");
+    let turned = matches
+        .iter()
+        .find(|m| m.x > 60.0)
+        .expect("rotated instance");
+    assert!((turned.x - rx as f32).abs() < 1.0 && (turned.y - ry as f32).abs() < 1.0);
+    assert!(angle_diff_deg(turned.angle_deg, 60.0) < 2.0, "{turned:?}");
+
+    assert!(matches!(
+        matcher.match_at_positions(view, &[(img_width - size + 1, 0)]),
+        Err(CorrMatchError::RoiOutOfBounds { .. })
+    ));
+}
+
+#[test]
+fn allow_inverted_finds_polarity_inverted_instances() {
+    let (tpl_width, tpl_height) = (40, 32);
+    let tpl_data = make_template(tpl_width, tpl_height);
+    let template = Template::new(tpl_data.clone(), tpl_width, tpl_height).unwrap();
+
+    let img_width = 200;
+    let img_height = 140;
+    let mut image = vec![128u8; img_width * img_height];
+    let (ux, uy) = (17usize, 21usize);
+    let (ix, iy) = (131usize, 84usize);
+    for y in 0..tpl_height {
+        for x in 0..tpl_width {
+            let value = tpl_data[y * tpl_width + x];
+            image[(uy + y) * img_width + ux + x] = value;
+            image[(iy + y) * img_width + ix + x] = 255 - value;
+        }
+    }
+    let view = ImageView::from_slice(&image, img_width, img_height).unwrap();
+
+    for rotation in [RotationMode::Disabled, RotationMode::Enabled] {
+        let compiled = match rotation {
+            RotationMode::Disabled => {
+                CompiledTemplate::compile_unrotated(&template, CompileConfigNoRot { max_levels: 3 })
+            }
+            RotationMode::Enabled => CompiledTemplate::compile_rotated(
+                &template,
+                CompileConfig {
+                    max_levels: 3,
+                    coarse_step_deg: 30.0,
+                    min_step_deg: 10.0,
+                    ..CompileConfig::default()
+                },
+            ),
+        }
+        .unwrap();
+        let cfg = MatchConfig {
+            rotation,
+            min_score: 0.5,
+            ..MatchConfig::default()
+        };
+        let compiled = Arc::new(compiled);
+
+        // Without the option only the upright copy passes min_score.
+        let upright_only = Matcher::from_shared(compiled.clone())
+            .with_config(cfg.clone())
+            .match_image_topk(view, 2)
+            .unwrap();
+        assert_eq!(upright_only.len(), 1, "{rotation:?}: {upright_only:?}");
+        assert!((upright_only[0].x - ux as f32).abs() < 0.5);
+
+        let matches = Matcher::from_shared(compiled)
+            .with_config(MatchConfig {
+                allow_inverted: true,
+                ..cfg
+            })
+            .match_image_topk(view, 2)
+            .unwrap();
+        assert_eq!(matches.len(), 2, "{rotation:?}: {matches:?}");
+        let upright = matches.iter().find(|m| m.score > 0.0).expect("upright");
+        let inverted = matches.iter().find(|m| m.score < 0.0).expect("inverted");
+        assert!((upright.x - ux as f32).abs() < 0.5 && (upright.y - uy as f32).abs() < 0.5);
+        assert!((inverted.x - ix as f32).abs() < 0.5 && (inverted.y - iy as f32).abs() < 0.5);
+        assert!(inverted.score < -0.99, "{inverted:?}");
+        assert!(
+            angle_diff_deg(inverted.angle_deg, 0.0) < 1.0,
+            "{inverted:?}"
+        );
+    }
+
+    let invalid = MatchConfig {
+        metric: Metric::Ssd,
+        allow_inverted: true,
+        ..MatchConfig::default()
+    };
+    assert!(invalid.validate().is_err());
+}
+
+#[test]
+fn match_image_topk_drops_seeds_that_converge() {
+    // A smooth blob gives a broad peak, so neighboring seeds refine onto it.
+    let size = 24;
+    let tpl_data: Vec<u8> = (0..size * size)
+        .map(|i| {
+            let x = (i % size) as f32 - 11.3;
+            let y = (i / size) as f32 - 12.1;
+            (40.0 + 180.0 * (-(x * x + y * y) / 40.0).exp()) as u8
+        })
+        .collect();
+    let (img_width, img_height) = (96, 80);
+    let (x0, y0) = (41usize, 30usize);
+    let mut image = vec![40u8; img_width * img_height];
+    for y in 0..size {
+        let row = (y0 + y) * img_width + x0;
+        image[row..row + size].copy_from_slice(&tpl_data[y * size..(y + 1) * size]);
+    }
+    let view = ImageView::from_slice(&image, img_width, img_height).unwrap();
+
+    let template = Template::new(tpl_data, size, size).unwrap();
+    let compiled =
+        CompiledTemplate::compile_unrotated(&template, CompileConfigNoRot { max_levels: 3 })
+            .unwrap();
+    // Without spatial suppression the search keeps several seeds on the peak.
+    let matcher = Matcher::new(compiled).with_config(MatchConfig {
+        nms_radius: 0,
+        ..MatchConfig::default()
+    });
+
+    let seeds: Vec<Match> = matcher.match_iter(view).map(Result::unwrap).collect();
+    assert!(seeds.len() > 1, "{seeds:?}");
+    assert!(seeds
+        .iter()
+        .all(|m| (m.x - x0 as f32).abs() < 0.5 && (m.y - y0 as f32).abs() < 0.5));
+
+    let matches = matcher.match_image_topk(view, 4).unwrap();
+    assert_eq!(matches.len(), 1, "{matches:?}");
+    assert!((matches[0].x - x0 as f32).abs() < 0.5 && (matches[0].y - y0 as f32).abs() < 0.5);
+}
+
+#[test]
+fn result_min_separation_merges_nearby_instances() {
+    let tpl_width = 32;
+    let tpl_height = 24;
+    let tpl_data = make_template(tpl_width, tpl_height);
+    let template = Template::new(tpl_data.clone(), tpl_width, tpl_height).unwrap();
+
+    let (img_width, img_height) = (112, 64);
+    let copies = [(8usize, 20usize), (48usize, 20usize)];
+    let mut image = vec![0u8; img_width * img_height];
+    for (x0, y0) in copies {
+        for y in 0..tpl_height {
+            let row = (y0 + y) * img_width + x0;
+            image[row..row + tpl_width]
+                .copy_from_slice(&tpl_data[y * tpl_width..(y + 1) * tpl_width]);
+        }
+    }
+    let view = ImageView::from_slice(&image, img_width, img_height).unwrap();
+
+    let compiled = Arc::new(
+        CompiledTemplate::compile_unrotated(&template, CompileConfigNoRot { max_levels: 2 })
+            .unwrap(),
+    );
+    let topk = |result_min_separation| {
+        Matcher::from_shared(Arc::clone(&compiled))
+            .with_config(MatchConfig {
+                max_image_levels: 2,
+                result_min_separation,
+                ..MatchConfig::default()
+            })
+            .match_image_topk(view, 4)
+            .unwrap()
+    };
+
+    // The search NMS radius alone keeps both copies, 40 pixels apart.
+    for separation in [None, Some(39)] {
+        let matches = topk(separation);
+        assert_eq!(matches.len(), 2, "{separation:?}: {matches:?}");
+        assert!(matches.iter().all(|m| m.score > 0.99));
+    }
+    let matches = topk(Some(40));
+    assert_eq!(matches.len(), 1, "{matches:?}");
+    assert!(copies.iter().any(|&(x0, y0)| {
+        (matches[0].x - x0 as f32).abs() < 0.5 && (matches[0].y - y0 as f32).abs() < 0.5
+    }));
+}
+
+#[test]
+fn score_pose_scores_a_single_placement() {
+    let size = 48;
+    let tpl_data = make_template(size, size);
+    let template = Template::new(tpl_data.clone(), size, size).unwrap();
+    let tpl_view = ImageView::from_slice(&tpl_data, size, size).unwrap();
+    let (rotated, mask) = rotate_u8_bilinear_masked(tpl_view, 60.0, 0);
+
+    let img_width = 160;
+    let img_height = 100;
+    let mut image = vec![0u8; img_width * img_height];
+    let (x0, y0) = (70usize, 30usize);
+    for y in 0..size {
+        for x in 0..size {
+            let idx = y * size + x;
+            if mask[idx] == 1 {
+                image[(y0 + y) * img_width + x0 + x] = rotated.data()[idx];
+            }
+        }
+    }
+    let view = ImageView::from_slice(&image, img_width, img_height).unwrap();
+
+    let compiled = CompiledTemplate::compile_rotated(
+        &template,
+        CompileConfig {
+            max_levels: 2,
+            coarse_step_deg: 30.0,
+            min_step_deg: 10.0,
+            ..CompileConfig::default()
+        },
+    )
+    .unwrap();
+    let matcher = Matcher::new(compiled).with_config(MatchConfig {
+        rotation: RotationMode::Enabled,
+        ..MatchConfig::default()
+    });
+
+    // 62 degrees snaps to the 60 degree grid angle.
+    let hit = matcher.score_pose(view, x0, y0, 62.0).unwrap();
+    assert!(hit > 0.99, "{hit}");
+    let wrong_angle = matcher.score_pose(view, x0, y0, 0.0).unwrap();
+    let wrong_place = matcher.score_pose(view, x0 - 20, y0, 60.0).unwrap();
+    assert!(
+        wrong_angle < 0.5 && wrong_place < 0.5,
+        "{wrong_angle} {wrong_place}"
+    );
+    // The empty corner has no variance to score.
+    assert_eq!(
+        matcher.score_pose(view, 0, 0, 60.0).unwrap(),
+        f32::NEG_INFINITY
+    );
+
+    assert!(matches!(
+        matcher.score_pose(view, img_width - size + 1, 0, 0.0),
+        Err(CorrMatchError::RoiOutOfBounds { .. })
+    ));
+}
+
+#[test]
+fn extract_match_patch_returns_template_frame_pixels() {
+    let size = 48;
+    let tpl_data = make_template(size, size);
+    let template = Template::new(tpl_data.clone(), size, size).unwrap();
+    let tpl_view = ImageView::from_slice(&tpl_data, size, size).unwrap();
+    let (rotated, mask) = rotate_u8_bilinear_masked(tpl_view, 90.0, 0);
+
+    let img_width = 160;
+    let img_height = 120;
+    let (x0, y0) = (57usize, 33usize);
+    let mut image = vec![0u8; img_width * img_height];
+    for y in 0..size {
+        for x in 0..size {
+            let idx = y * size + x;
+            if mask[idx] == 1 {
+                image[(y0 + y) * img_width + x0 + x] = rotated.data()[idx];
+            }
+        }
+    }
+    let view = ImageView::from_slice(&image, img_width, img_height).unwrap();
+
+    let compiled = CompiledTemplate::compile_rotated(
+        &template,
+        CompileConfig {
+            max_levels: 1,
+            coarse_step_deg: 30.0,
+            min_step_deg: 30.0,
+            ..CompileConfig::default()
+        },
+    )
+    .unwrap();
+    let matcher = Matcher::new(compiled).with_config(MatchConfig {
+        max_image_levels: 1,
+        rotation: RotationMode::Enabled,
+        ..MatchConfig::default()
+    });
+    let m = Match {
+        x: x0 as f32 + 0.2,
+        y: y0 as f32 - 0.3,
+        angle_deg: 90.0,
+        score: 1.0,
+        peak_sharpness: None,
+    };
+    let patch = matcher.extract_match_patch(view, &m).unwrap();
+    assert_eq!((patch.width(), patch.height()), (size, size));
+    assert_eq!(patch.data(), tpl_data.as_slice());
+
+    let upright = Match {
+        angle_deg: 0.0,
+        ..m
+    };
+    let raw = matcher.extract_match_patch(view, &upright).unwrap();
+    assert_eq!(raw.data(), rotated.data());
+
+    let outside = Match {
+        x: (img_width - size + 1) as f32,
+        ..upright
+    };
+    assert!(matches!(
+        matcher.extract_match_patch(view, &outside),
+        Err(CorrMatchError::RoiOutOfBounds { .. })
+    ));
+}
+
+#[test]
+fn template_sized_image_is_scored_at_origin() {
+    let size = 48;
+    let tpl_data = make_template(size, size);
+    let template = Template::new(tpl_data.clone(), size, size).unwrap();
+    let tpl_view = ImageView::from_slice(&tpl_data, size, size).unwrap();
+    let (rotated, _) = rotate_u8_bilinear_masked(tpl_view, 90.0, 0);
+    let crop = ImageView::from_slice(rotated.data(), size, size).unwrap();
+
+    let compiled = CompiledTemplate::compile_rotated(
+        &template,
+        CompileConfig {
+            max_levels: 3,
+            coarse_step_deg: 30.0,
+            min_step_deg: 10.0,
+            ..CompileConfig::default()
+        },
+    )
+    .unwrap();
+    let matcher = Matcher::new(compiled).with_config(MatchConfig {
+        rotation: RotationMode::Enabled,
+        ..MatchConfig::default()
+    });
+
+    let best = matcher.match_image(crop).unwrap();
+    assert!(best.x.abs() < 0.5 && best.y.abs() < 0.5, "{best:?}");
+    assert!(angle_diff_deg(best.angle_deg, 90.0) < 1.0, "{best:?}");
+    assert!(best.score > 0.99, "{best:?}");
+
+    // The pyramid is skipped, so only the level-0 seeds are reported.
+    let levels = matcher.debug_levels(crop).unwrap();
+    assert_eq!(levels.len(), 1);
+    assert!(levels[0].iter().all(|m| m.x == 0.0 && m.y == 0.0));
+    assert!(levels[0].len() <= MatchConfig::default().beam_width);
+}
+
+#[test]
+fn edge_clamp_fill_is_scored_by_ssd_but_not_zncc() {
+    let size = 48;
+    let tpl_data = make_template(size, size);
+    let template = Template::new(tpl_data.clone(), size, size).unwrap();
+    let tpl_view = ImageView::from_slice(&tpl_data, size, size).unwrap();
+    let (rotated, mask) = rotate_u8_bilinear_masked(tpl_view, 30.0, 0);
+
+    // Place the rotated template against the top-left image border.
+    let img_width = 96;
+    let img_height = 80;
+    let mut image: Vec<u8> = (0..img_width * img_height)
+        .map(|i| ((i * 37 + (i / img_width) * 11) % 251) as u8)
+        .collect();
+    for y in 0..size {
+        for x in 0..size {
+            let idx = y * size + x;
+            if mask[idx] == 1 {
+                image[y * img_width + x] = rotated.data()[idx];
+            }
+        }
+    }
+    let view = ImageView::from_slice(&image, img_width, img_height).unwrap();
+
+    let compile = |fill_strategy| {
+        CompiledTemplate::compile_rotated(
+            &template,
+            CompileConfig {
+                max_levels: 3,
+                coarse_step_deg: 30.0,
+                min_step_deg: 10.0,
+                fill_strategy,
+                ..CompileConfig::default()
+            },
+        )
+        .unwrap()
+    };
+    let constant = compile(FillStrategy::Constant(0));
+    let edge = compile(FillStrategy::EdgeClamp);
+
+    let idx = constant.angle_grid(0).unwrap().nearest_index(30.0);
+    let (constant_pixels, constant_mask) = constant.rotated_template_image(0, idx).unwrap();
+    let (edge_pixels, edge_mask) = edge.rotated_template_image(0, idx).unwrap();
+    assert_eq!(constant_mask, edge_mask);
+    let mut edge_fill_nonzero = false;
+    for ((&a, &b), &m) in constant_pixels.iter().zip(&edge_pixels).zip(&constant_mask) {
+        if m == 1 {
+            assert_eq!(a, b);
+        } else {
+            assert_eq!(a, 0);
+            edge_fill_nonzero |= b != 0;
+        }
+    }
+    assert!(edge_fill_nonzero);
+
+    let score = |fill_strategy, metric| {
+        Matcher::new(compile(fill_strategy))
+            .with_config(MatchConfig {
+                metric,
+                rotation: RotationMode::Enabled,
+                ..MatchConfig::default()
+            })
+            .score_pose(view, 0, 0, 30.0)
+            .unwrap()
+    };
+
+    // The constant fill is masked out, so SSD only sees the pasted footprint;
+    // the edge-clamped corners are compared against the image background.
+    let constant_ssd = score(FillStrategy::Constant(0), Metric::Ssd);
+    let edge_ssd = score(FillStrategy::EdgeClamp, Metric::Ssd);
+    assert!(constant_ssd > -1e-3, "{constant_ssd}");
+    assert!(
+        edge_ssd < constant_ssd - 1.0,
+        "{edge_ssd} vs {constant_ssd}"
+    );
+
+    // ZNCC keeps the rotation mask for both strategies.
+    assert_eq!(
+        score(FillStrategy::Constant(0), Metric::Zncc),
+        score(FillStrategy::EdgeClamp, Metric::Zncc)
+    );
+}
+
+#[test]
+#[allow(deprecated)]
 fn pipeline_finds_translation_match() {
     let tpl_width = 40;
     let tpl_height = 32;
@@ -124,6 +722,7 @@ fn pipeline_finds_translation_match() {
             min_step_deg: 45.0,
             fill_value: 0,
             precompute_coarsest: true,
+            ..CompileConfig::default()
         },
     )
     .unwrap();
@@ -149,6 +748,7 @@ fn pipeline_finds_translation_match() {
 }
 
 #[test]
+#[allow(deprecated)]
 fn angle_step_schedule_matches_expected() {
     let tpl_width = 16;
     let tpl_height = 16;
@@ -163,6 +763,7 @@ fn angle_step_schedule_matches_expected() {
             min_step_deg: 1.0,
             fill_value: 0,
             precompute_coarsest: false,
+            ..CompileConfig::default()
         },
     )
     .unwrap();
@@ -178,6 +779,77 @@ fn angle_step_schedule_matches_expected() {
 }
 
 #[test]
+fn angle_step_override_replaces_derived_schedule() {
+    let tpl_width = 16;
+    let tpl_height = 16;
+    let tpl_data = make_template(tpl_width, tpl_height);
+    let template = Template::new(tpl_data, tpl_width, tpl_height).unwrap();
+    let cfg = CompileConfig {
+        max_levels: 3,
+        precompute_coarsest: false,
+        angle_steps: Some(vec![0.5, 2.0, 10.0]),
+        ..CompileConfig::default()
+    };
+
+    let compiled = CompiledTemplate::compile_rotated(&template, cfg.clone()).unwrap();
+    for (level, expected) in [0.5f32, 2.0, 10.0].into_iter().enumerate() {
+        let step = compiled.angle_grid(level).unwrap().step_deg();
+        assert!((step - expected).abs() < 1e-6);
+    }
+
+    let short = CompileConfig {
+        angle_steps: Some(vec![1.0, 4.0]),
+        ..cfg.clone()
+    };
+    assert!(CompiledTemplate::compile_rotated(&template, short).is_err());
+
+    let negative = CompileConfig {
+        angle_steps: Some(vec![1.0, -2.0, 4.0]),
+        ..cfg
+    };
+    assert!(negative.validate().is_err());
+}
+
+#[test]
+fn geometric_angle_schedule_shrinks_by_ratio() {
+    let tpl_width = 16;
+    let tpl_height = 16;
+    let tpl_data = make_template(tpl_width, tpl_height);
+    let template = Template::new(tpl_data, tpl_width, tpl_height).unwrap();
+    let cfg = CompileConfig {
+        max_levels: 3,
+        min_template_dim: 4,
+        coarse_step_deg: 16.0,
+        min_step_deg: 1.0,
+        angle_schedule: AngleSchedule::Geometric { ratio: 4.0 },
+        precompute_coarsest: false,
+        ..CompileConfig::default()
+    };
+
+    let compiled = CompiledTemplate::compile_rotated(&template, cfg.clone()).unwrap();
+    for (level, expected) in [1.0f32, 4.0, 16.0].into_iter().enumerate() {
+        let step = compiled.angle_grid(level).unwrap().step_deg();
+        assert!((step - expected).abs() < 1e-6, "level {level} step {step}");
+    }
+
+    let overshoot = CompileConfig {
+        angle_schedule: AngleSchedule::Geometric { ratio: 5.0 },
+        ..cfg.clone()
+    };
+    assert!(matches!(
+        CompiledTemplate::compile_rotated(&template, overshoot),
+        Err(CorrMatchError::InvalidConfig { .. })
+    ));
+
+    let shrinking = CompileConfig {
+        angle_schedule: AngleSchedule::Geometric { ratio: 1.0 },
+        ..cfg
+    };
+    assert!(shrinking.validate().is_err());
+}
+
+#[test]
+#[allow(deprecated)]
 fn pipeline_finds_translation_match_rotation_disabled() {
     let tpl_width = 32;
     let tpl_height = 24;
@@ -219,24 +891,251 @@ fn pipeline_finds_translation_match_rotation_disabled() {
 }
 
 #[test]
-fn pipeline_finds_translation_match_ssd_rotation_disabled() {
-    let tpl_width = 24;
-    let tpl_height = 20;
+fn tie_break_chooses_between_identical_instances() {
+    let tpl_width = 32;
+    let tpl_height = 24;
     let tpl_data = make_template(tpl_width, tpl_height);
     let template = Template::new(tpl_data.clone(), tpl_width, tpl_height).unwrap();
 
-    let img_width = 120;
-    let img_height = 90;
-    let x0 = 17;
-    let y0 = 21;
+    // Both copies sit on the coarsest grid, so every level scores them equally.
+    // The placement range is 0..=112 x 0..=80, centered on the second copy.
+    let img_width = 144;
+    let img_height = 104;
+    let copies = [(8, 8), (56, 40)];
     let mut image = vec![0u8; img_width * img_height];
-    for y in 0..tpl_height {
-        for x in 0..tpl_width {
-            image[(y0 + y) * img_width + (x0 + x)] = tpl_data[y * tpl_width + x];
+    for (x0, y0) in copies {
+        for y in 0..tpl_height {
+            for x in 0..tpl_width {
+                image[(y0 + y) * img_width + (x0 + x)] = tpl_data[y * tpl_width + x];
+            }
         }
     }
 
-    let compiled =
+    let compiled = Arc::new(
+        CompiledTemplate::compile_unrotated(&template, CompileConfigNoRot { max_levels: 3 })
+            .unwrap(),
+    );
+    let cfg = MatchConfig {
+        max_image_levels: 3,
+        rotation: RotationMode::Disabled,
+        ..MatchConfig::default()
+    };
+    let image_view = ImageView::from_slice(&image, img_width, img_height).unwrap();
+
+    for (tie_break, (x0, y0)) in [
+        (TieBreak::TopLeftFirst, copies[0]),
+        (TieBreak::NearestToCenter, copies[1]),
+    ] {
+        let matcher = Matcher::from_shared(Arc::clone(&compiled)).with_config(MatchConfig {
+            tie_break,
+            ..cfg.clone()
+        });
+        let best = matcher.match_image(image_view).unwrap();
+        assert!(
+            (best.x - x0 as f32).abs() <= 0.5 && (best.y - y0 as f32).abs() <= 0.5,
+            "{tie_break:?}: {best:?}"
+        );
+        assert!(best.score > 0.99);
+    }
+}
+
+#[test]
+fn symmetric_template_reports_the_pose_nearest_zero() {
+    // Values depend only on the sorted |offsets| from the center, so the
+    // template is unchanged by 90° rotations and ±90° and 180° tie with 0°.
+    let size = 32;
+    let center = (size as f32 - 1.0) * 0.5;
+    let mut tpl_data = Vec::with_capacity(size * size);
+    for y in 0..size {
+        for x in 0..size {
+            let dx = (x as f32 - center).abs() as usize;
+            let dy = (y as f32 - center).abs() as usize;
+            let (lo, hi) = (dx.min(dy), dx.max(dy));
+            tpl_data.push(((lo * 37 + hi * 11 + lo * hi * 5) % 251) as u8);
+        }
+    }
+    let template = Template::new(tpl_data.clone(), size, size).unwrap();
+
+    let img_width = 96;
+    let img_height = 96;
+    let (x0, y0) = (32, 28);
+    let mut image = vec![0u8; img_width * img_height];
+    for y in 0..size {
+        let row = (y0 + y) * img_width + x0;
+        image[row..row + size].copy_from_slice(&tpl_data[y * size..(y + 1) * size]);
+    }
+    let image_view = ImageView::from_slice(&image, img_width, img_height).unwrap();
+
+    let compiled = Arc::new(
+        CompiledTemplate::compile_rotated(
+            &template,
+            CompileConfig {
+                max_levels: 2,
+                coarse_step_deg: 30.0,
+                min_step_deg: 15.0,
+                ..CompileConfig::default()
+            },
+        )
+        .unwrap(),
+    );
+    for tie_break in [TieBreak::TopLeftFirst, TieBreak::MostCentralAngle] {
+        let best = Matcher::from_shared(Arc::clone(&compiled))
+            .with_config(MatchConfig {
+                max_image_levels: 2,
+                rotation: RotationMode::Enabled,
+                tie_break,
+                ..MatchConfig::default()
+            })
+            .match_image(image_view)
+            .unwrap();
+        assert!(
+            (best.x - x0 as f32).abs() <= 0.5 && (best.y - y0 as f32).abs() <= 0.5,
+            "{tie_break:?}: {best:?}"
+        );
+        assert!(best.angle_deg.abs() < 1e-3, "{tie_break:?}: {best:?}");
+        assert!(best.score > 0.99);
+    }
+}
+
+#[test]
+fn compute_psr_flags_repeated_instances() {
+    let tpl_width = 32;
+    let tpl_height = 24;
+    let tpl_data = make_template(tpl_width, tpl_height);
+    let template = Template::new(tpl_data.clone(), tpl_width, tpl_height).unwrap();
+    let compiled = Arc::new(
+        CompiledTemplate::compile_unrotated(&template, CompileConfigNoRot { max_levels: 3 })
+            .unwrap(),
+    );
+
+    let (img_width, img_height) = (144, 104);
+    let render = |copies: &[(usize, usize)]| {
+        let mut image = vec![0u8; img_width * img_height];
+        for &(x0, y0) in copies {
+            for y in 0..tpl_height {
+                for x in 0..tpl_width {
+                    image[(y0 + y) * img_width + (x0 + x)] = tpl_data[y * tpl_width + x];
+                }
+            }
+        }
+        image
+    };
+    let unique = render(&[(56, 40)]);
+    let repeated = render(&[(8, 8), (56, 40)]);
+
+    let cfg = MatchConfig {
+        max_image_levels: 3,
+        rotation: RotationMode::Disabled,
+        ..MatchConfig::default()
+    };
+    let plain = Matcher::from_shared(Arc::clone(&compiled)).with_config(cfg.clone());
+    let with_psr = Matcher::from_shared(compiled).with_config(MatchConfig {
+        compute_psr: true,
+        ..cfg
+    });
+
+    let unique_view = ImageView::from_slice(&unique, img_width, img_height).unwrap();
+    assert_eq!(plain.match_image(unique_view).unwrap().peak_sharpness, None);
+    let sharp = with_psr.match_image(unique_view).unwrap();
+    assert!(sharp.peak_sharpness.unwrap() > 1.5, "{sharp:?}");
+
+    let repeated_view = ImageView::from_slice(&repeated, img_width, img_height).unwrap();
+    let ambiguous = with_psr.match_image(repeated_view).unwrap();
+    assert!(
+        (ambiguous.peak_sharpness.unwrap() - 1.0).abs() < 1e-3,
+        "{ambiguous:?}"
+    );
+
+    let narrow = MatchConfig {
+        compute_psr: true,
+        beam_width: 1,
+        ..MatchConfig::default()
+    };
+    assert!(matches!(
+        narrow.validate(),
+        Err(CorrMatchError::InvalidConfig { .. })
+    ));
+}
+
+#[test]
+fn min_contrast_skips_faint_windows() {
+    let tpl_width = 32;
+    let tpl_height = 24;
+    let tpl_data = make_template(tpl_width, tpl_height);
+    let template = Template::new(tpl_data.clone(), tpl_width, tpl_height).unwrap();
+
+    // A faint copy (intensity range 0..8) and a full-contrast copy.
+    let img_width = 140;
+    let img_height = 110;
+    let (fx, fy) = (15usize, 12usize);
+    let (bx, by) = (90usize, 70usize);
+    let mut faint_only = vec![0u8; img_width * img_height];
+    for y in 0..tpl_height {
+        for x in 0..tpl_width {
+            faint_only[(fy + y) * img_width + fx + x] = tpl_data[y * tpl_width + x] / 32;
+        }
+    }
+    let mut both = faint_only.clone();
+    for y in 0..tpl_height {
+        for x in 0..tpl_width {
+            both[(by + y) * img_width + bx + x] = tpl_data[y * tpl_width + x];
+        }
+    }
+
+    let compiled =
+        CompiledTemplate::compile_unrotated(&template, CompileConfigNoRot { max_levels: 2 })
+            .unwrap();
+    let matcher = Matcher::new(compiled).with_config(MatchConfig {
+        max_image_levels: 2,
+        ..MatchConfig::default()
+    });
+    let view = ImageView::from_slice(&faint_only, img_width, img_height).unwrap();
+    let best = matcher.match_image(view).unwrap();
+    assert!((best.x - fx as f32).abs() <= 1.0 && (best.y - fy as f32).abs() <= 1.0);
+
+    let gated = matcher.clone().with_config(MatchConfig {
+        max_image_levels: 2,
+        min_contrast: Some(5.0),
+        ..MatchConfig::default()
+    });
+    assert!(matches!(
+        gated.match_image(view),
+        Err(CorrMatchError::NoCandidates { .. })
+    ));
+    let view = ImageView::from_slice(&both, img_width, img_height).unwrap();
+    let topk = gated.match_image_topk(view, 4).unwrap();
+    assert!((topk[0].x - bx as f32).abs() <= 1.0 && (topk[0].y - by as f32).abs() <= 1.0);
+    for m in &topk {
+        assert!((m.x - fx as f32).abs() > 4.0 || (m.y - fy as f32).abs() > 4.0);
+    }
+
+    let invalid = MatchConfig {
+        min_contrast: Some(-1.0),
+        ..MatchConfig::default()
+    };
+    assert!(invalid.validate().is_err());
+}
+
+#[test]
+#[allow(deprecated)]
+fn pipeline_finds_translation_match_ssd_rotation_disabled() {
+    let tpl_width = 24;
+    let tpl_height = 20;
+    let tpl_data = make_template(tpl_width, tpl_height);
+    let template = Template::new(tpl_data.clone(), tpl_width, tpl_height).unwrap();
+
+    let img_width = 120;
+    let img_height = 90;
+    let x0 = 17;
+    let y0 = 21;
+    let mut image = vec![0u8; img_width * img_height];
+    for y in 0..tpl_height {
+        for x in 0..tpl_width {
+            image[(y0 + y) * img_width + (x0 + x)] = tpl_data[y * tpl_width + x];
+        }
+    }
+
+    let compiled =
         CompiledTemplate::compile_unrotated(&template, CompileConfigNoRot { max_levels: 3 })
             .unwrap();
 
@@ -297,3 +1196,2080 @@ fn pipeline_topk_returns_best_first() {
     assert!((topk[0].angle_deg - best.angle_deg).abs() < 1e-6);
     assert!((topk[0].score - best.score).abs() < 1e-6);
 }
+
+#[test]
+fn match_image_topk_into_reuses_the_buffer() {
+    let tpl_width = 24;
+    let tpl_height = 18;
+    let tpl_data = make_template(tpl_width, tpl_height);
+    let template = Template::new(tpl_data.clone(), tpl_width, tpl_height).unwrap();
+
+    let (img_width, img_height) = (120, 90);
+    let mut image = vec![0u8; img_width * img_height];
+    for (x0, y0) in [(19, 23), (80, 55)] {
+        for y in 0..tpl_height {
+            let row = (y0 + y) * img_width + x0;
+            image[row..row + tpl_width]
+                .copy_from_slice(&tpl_data[y * tpl_width..(y + 1) * tpl_width]);
+        }
+    }
+    let image_view = ImageView::from_slice(&image, img_width, img_height).unwrap();
+
+    let compiled =
+        CompiledTemplate::compile_unrotated(&template, CompileConfigNoRot { max_levels: 3 })
+            .unwrap();
+    let matcher = Matcher::new(compiled).with_config(MatchConfig {
+        max_image_levels: 3,
+        ..MatchConfig::default()
+    });
+
+    let expected = matcher.match_image_topk(image_view, 2).unwrap();
+    assert_eq!(expected.len(), 2);
+    let mut out = Vec::with_capacity(8);
+    out.push(expected[1]);
+    let buffer = out.as_ptr();
+    for _ in 0..2 {
+        matcher
+            .match_image_topk_into(image_view, 2, &mut out)
+            .unwrap();
+        assert_eq!(out, expected);
+        assert_eq!(out.as_ptr(), buffer);
+    }
+
+    matcher
+        .match_image_topk_into(image_view, 0, &mut out)
+        .unwrap();
+    assert!(out.is_empty());
+
+    let invalid = matcher.clone().with_config(MatchConfig {
+        beam_width: 0,
+        ..MatchConfig::default()
+    });
+    out.push(expected[0]);
+    assert!(invalid
+        .match_image_topk_into(image_view, 2, &mut out)
+        .is_err());
+    assert!(out.is_empty());
+}
+
+#[test]
+fn shared_compiled_template_supports_multiple_matchers() {
+    let tpl_width = 24;
+    let tpl_height = 18;
+    let tpl_data = make_template(tpl_width, tpl_height);
+    let template = Template::new(tpl_data.clone(), tpl_width, tpl_height).unwrap();
+
+    let img_width = 120;
+    let img_height = 90;
+    let x0 = 31;
+    let y0 = 27;
+    let mut image = vec![0u8; img_width * img_height];
+    for y in 0..tpl_height {
+        for x in 0..tpl_width {
+            image[(y0 + y) * img_width + (x0 + x)] = tpl_data[y * tpl_width + x];
+        }
+    }
+
+    let compiled = Arc::new(
+        CompiledTemplate::compile_unrotated(&template, CompileConfigNoRot { max_levels: 3 })
+            .unwrap(),
+    );
+    let zncc = Matcher::from_shared(Arc::clone(&compiled)).with_config(MatchConfig {
+        max_image_levels: 3,
+        ..MatchConfig::default()
+    });
+    let ssd = Matcher::from_shared(Arc::clone(&compiled)).with_config(MatchConfig {
+        metric: Metric::Ssd,
+        max_image_levels: 3,
+        ..MatchConfig::default()
+    });
+    let cloned = zncc.clone();
+    assert!(std::ptr::eq(zncc.compiled(), ssd.compiled()));
+
+    let image_view = ImageView::from_slice(&image, img_width, img_height).unwrap();
+    let best_zncc = zncc.match_image(image_view).unwrap();
+    let best_ssd = ssd.match_image(image_view).unwrap();
+    let best_cloned = cloned.match_image(image_view).unwrap();
+
+    assert!((best_zncc.x - x0 as f32).abs() <= 1.0);
+    assert!((best_zncc.y - y0 as f32).abs() <= 1.0);
+    assert!((best_ssd.x - x0 as f32).abs() <= 1.0);
+    assert!((best_ssd.y - y0 as f32).abs() <= 1.0);
+    assert_eq!(best_zncc, best_cloned);
+}
+
+#[test]
+fn level0_radii_keep_nearby_instances_at_coarse_levels() {
+    let tpl_width = 16;
+    let tpl_height = 16;
+    let tpl_data = make_template(tpl_width, tpl_height);
+    let template = Template::new(tpl_data.clone(), tpl_width, tpl_height).unwrap();
+
+    let img_width = 96;
+    let img_height = 64;
+    let positions = [(20usize, 24usize), (44usize, 24usize)];
+    let mut image = vec![0u8; img_width * img_height];
+    for &(x0, y0) in positions.iter() {
+        for y in 0..tpl_height {
+            for x in 0..tpl_width {
+                image[(y0 + y) * img_width + (x0 + x)] = tpl_data[y * tpl_width + x];
+            }
+        }
+    }
+    let image_view = ImageView::from_slice(&image, img_width, img_height).unwrap();
+
+    let base_cfg = MatchConfig {
+        max_image_levels: 3,
+        beam_width: 4,
+        nms_radius: 8,
+        roi_radius: 8,
+        rotation: RotationMode::Disabled,
+        ..MatchConfig::default()
+    };
+    let found_both = |matches: &[corrmatch::Match]| {
+        positions.iter().all(|&(x0, y0)| {
+            matches.iter().any(|m| {
+                (m.x - x0 as f32).abs() <= 1.0 && (m.y - y0 as f32).abs() <= 1.0 && m.score > 0.99
+            })
+        })
+    };
+
+    let per_level = Matcher::new(
+        CompiledTemplate::compile_unrotated(&template, CompileConfigNoRot { max_levels: 3 })
+            .unwrap(),
+    )
+    .with_config(base_cfg.clone());
+    let matches = per_level.match_image_topk(image_view, 2).unwrap();
+    assert!(!found_both(&matches));
+
+    let level0 = Matcher::new(
+        CompiledTemplate::compile_unrotated(&template, CompileConfigNoRot { max_levels: 3 })
+            .unwrap(),
+    )
+    .with_config(MatchConfig {
+        radius_in_level0_pixels: true,
+        ..base_cfg
+    });
+    let matches = level0.match_image_topk(image_view, 2).unwrap();
+    assert!(
+        found_both(&matches),
+        "expected both instances, got {matches:?}"
+    );
+}
+
+#[test]
+fn match_iter_yields_topk_lazily() {
+    let tpl_width = 24;
+    let tpl_height = 18;
+    let tpl_data = make_template(tpl_width, tpl_height);
+    let template = Template::new(tpl_data.clone(), tpl_width, tpl_height).unwrap();
+
+    let img_width = 120;
+    let img_height = 90;
+    let x0 = 19;
+    let y0 = 23;
+    let mut image = vec![0u8; img_width * img_height];
+    for y in 0..tpl_height {
+        for x in 0..tpl_width {
+            image[(y0 + y) * img_width + (x0 + x)] = tpl_data[y * tpl_width + x];
+        }
+    }
+
+    let compiled =
+        CompiledTemplate::compile_unrotated(&template, CompileConfigNoRot { max_levels: 3 })
+            .unwrap();
+    let matcher = Matcher::new(compiled).with_config(MatchConfig {
+        max_image_levels: 3,
+        rotation: RotationMode::Disabled,
+        ..MatchConfig::default()
+    });
+    let image_view = ImageView::from_slice(&image, img_width, img_height).unwrap();
+
+    let topk = matcher.match_image_topk(image_view, 3).unwrap();
+    let lazy: Vec<_> = matcher
+        .match_iter(image_view)
+        .take(3)
+        .collect::<Result<_, _>>()
+        .unwrap();
+    assert_eq!(topk, lazy);
+
+    let first = matcher.match_iter(image_view).next().unwrap().unwrap();
+    assert_eq!(first, matcher.match_image(image_view).unwrap());
+
+    let invalid = matcher.clone().with_config(MatchConfig {
+        beam_width: 0,
+        ..MatchConfig::default()
+    });
+    let mut iter = invalid.match_iter(image_view);
+    assert!(iter.next().unwrap().is_err());
+    assert!(iter.next().is_none());
+}
+
+#[test]
+fn score_clamp_maps_anticorrelation_to_zero() {
+    let tpl_width = 24;
+    let tpl_height = 24;
+    let tpl_data = make_template(tpl_width, tpl_height);
+    let template = Template::new(tpl_data.clone(), tpl_width, tpl_height).unwrap();
+    let inverted: Vec<u8> = tpl_data.iter().map(|&v| 255 - v).collect();
+    let image = ImageView::from_slice(&inverted, tpl_width, tpl_height).unwrap();
+
+    let compiled = Arc::new(
+        CompiledTemplate::compile_unrotated(&template, CompileConfigNoRot { max_levels: 1 })
+            .unwrap(),
+    );
+    let cfg = MatchConfig {
+        max_image_levels: 1,
+        ..MatchConfig::default()
+    };
+    let raw = Matcher::from_shared(Arc::clone(&compiled))
+        .with_config(cfg.clone())
+        .match_image(image)
+        .unwrap();
+    assert!(raw.score < -0.99, "raw score {}", raw.score);
+
+    let clamped = Matcher::from_shared(compiled)
+        .with_config(MatchConfig {
+            score_clamp: ScoreClamp::ZeroToOne,
+            ..cfg
+        })
+        .match_image(image)
+        .unwrap();
+    assert_eq!(clamped.score, 0.0);
+    assert_eq!((clamped.x, clamped.y), (raw.x, raw.y));
+}
+
+#[test]
+fn debug_levels_records_beam_from_coarsest_to_finest() {
+    let tpl_width = 32;
+    let tpl_height = 32;
+    let tpl_data = make_template(tpl_width, tpl_height);
+    let template = Template::new(tpl_data.clone(), tpl_width, tpl_height).unwrap();
+
+    let width = 128;
+    let height = 96;
+    let x0 = 40usize;
+    let y0 = 24usize;
+    let mut image = vec![0u8; width * height];
+    for y in 0..tpl_height {
+        let row = (y0 + y) * width + x0;
+        image[row..row + tpl_width].copy_from_slice(&tpl_data[y * tpl_width..(y + 1) * tpl_width]);
+    }
+    let image_view = ImageView::from_slice(&image, width, height).unwrap();
+
+    let compiled =
+        CompiledTemplate::compile_unrotated(&template, CompileConfigNoRot { max_levels: 3 })
+            .unwrap();
+    let matcher = Matcher::new(compiled).with_config(MatchConfig {
+        max_image_levels: 3,
+        beam_width: 4,
+        ..MatchConfig::default()
+    });
+
+    let levels = matcher.debug_levels(image_view).unwrap();
+    assert_eq!(levels.len(), 3);
+    for beam in &levels {
+        assert!(!beam.is_empty() && beam.len() <= 4);
+        let best = beam[0];
+        assert!((best.x - x0 as f32).abs() <= 4.0);
+        assert!((best.y - y0 as f32).abs() <= 4.0);
+    }
+    let finest = levels.last().unwrap()[0];
+    assert_eq!((finest.x, finest.y), (x0 as f32, y0 as f32));
+
+    let best = matcher.match_image(image_view).unwrap();
+    assert!((best.x - x0 as f32).abs() < 0.5 && (best.y - y0 as f32).abs() < 0.5);
+}
+
+#[test]
+fn ensemble_reports_best_template_index() {
+    let tpl_width = 24;
+    let tpl_height = 24;
+    let img_width = 96;
+    let img_height = 80;
+    let mut image = vec![0u8; img_width * img_height];
+    for y in 0..img_height {
+        for x in 0..img_width {
+            image[y * img_width + x] = ((x * 7 + y * 3 + (x * y) % 5) & 0xFF) as u8;
+        }
+    }
+    let tpl_data = make_template(tpl_width, tpl_height);
+    let (x0, y0) = (36usize, 28usize);
+    for y in 0..tpl_height {
+        let row = (y0 + y) * img_width + x0;
+        image[row..row + tpl_width].copy_from_slice(&tpl_data[y * tpl_width..(y + 1) * tpl_width]);
+    }
+    let image_view = ImageView::from_slice(&image, img_width, img_height).unwrap();
+
+    let decoy: Vec<u8> = (0..tpl_width * tpl_height)
+        .map(|i| ((i * 31 + (i / tpl_width) * 17) % 253) as u8)
+        .collect();
+    let cfg = MatchConfig {
+        max_image_levels: 3,
+        ..MatchConfig::default()
+    };
+    let matchers: Vec<Matcher> = [decoy, tpl_data]
+        .into_iter()
+        .map(|data| {
+            let template = Template::new(data, tpl_width, tpl_height).unwrap();
+            let compiled = CompiledTemplate::compile_unrotated(
+                &template,
+                CompileConfigNoRot { max_levels: 3 },
+            )
+            .unwrap();
+            Matcher::new(compiled).with_config(cfg.clone())
+        })
+        .collect();
+    let expected = matchers[1].match_image(image_view).unwrap();
+
+    let ensemble = Ensemble::new(matchers).unwrap();
+    let result = ensemble.match_image(image_view).unwrap();
+    assert_eq!(result.template_index, 1);
+    assert_eq!(result.best, expected);
+    assert!((result.best.x - x0 as f32).abs() < 0.5);
+    assert!((result.best.y - y0 as f32).abs() < 0.5);
+
+    assert!(Ensemble::new(Vec::new()).is_err());
+}
+
+#[test]
+fn explain_contributions_sum_to_zncc_numerator() {
+    let tpl_width = 20;
+    let tpl_height = 16;
+    let tpl_data = make_template(tpl_width, tpl_height);
+    let template = Template::new(tpl_data.clone(), tpl_width, tpl_height).unwrap();
+
+    let img_width = 64;
+    let img_height = 48;
+    let (x0, y0) = (22usize, 14usize);
+    let mut image = vec![0u8; img_width * img_height];
+    for y in 0..tpl_height {
+        let row = (y0 + y) * img_width + x0;
+        image[row..row + tpl_width].copy_from_slice(&tpl_data[y * tpl_width..(y + 1) * tpl_width]);
+    }
+    let image_view = ImageView::from_slice(&image, img_width, img_height).unwrap();
+
+    let compiled =
+        CompiledTemplate::compile_unrotated(&template, CompileConfigNoRot { max_levels: 2 })
+            .unwrap();
+    let matcher = Matcher::new(compiled).with_config(MatchConfig {
+        max_image_levels: 2,
+        ..MatchConfig::default()
+    });
+    let best = matcher.match_image(image_view).unwrap();
+    let contributions = matcher.explain(image_view, &best).unwrap();
+    assert_eq!(contributions.len(), tpl_width * tpl_height);
+
+    // At a perfect placement the numerator equals the template variance term.
+    let var_t = matcher.compiled().unmasked_zncc_plan(0).unwrap().var_t();
+    let sum: f32 = contributions.iter().sum();
+    assert!(
+        (sum - var_t).abs() <= 1e-3 * var_t,
+        "sum {sum} vs var_t {var_t}"
+    );
+}
+
+#[test]
+fn initial_downscale_reports_full_resolution_coordinates() {
+    let tpl_width = 32;
+    let tpl_height = 24;
+    let tpl_data = make_template(tpl_width, tpl_height);
+    let template = Template::new(tpl_data.clone(), tpl_width, tpl_height).unwrap();
+
+    let img_width = 160;
+    let img_height = 120;
+    let x0 = 72;
+    let y0 = 46;
+    let mut image = vec![0u8; img_width * img_height];
+    for y in 0..tpl_height {
+        let row = (y0 + y) * img_width + x0;
+        image[row..row + tpl_width].copy_from_slice(&tpl_data[y * tpl_width..(y + 1) * tpl_width]);
+    }
+    let image_view = ImageView::from_slice(&image, img_width, img_height).unwrap();
+
+    let compiled = Arc::new(
+        CompiledTemplate::compile_unrotated(&template, CompileConfigNoRot { max_levels: 3 })
+            .unwrap(),
+    );
+    let cfg = MatchConfig {
+        max_image_levels: 3,
+        initial_downscale: 2,
+        ..MatchConfig::default()
+    };
+    let matcher = Matcher::from_shared(compiled.clone()).with_config(cfg.clone());
+    let best = matcher.match_image(image_view).unwrap();
+    assert!((best.x - x0 as f32).abs() <= 1.0, "x {}", best.x);
+    assert!((best.y - y0 as f32).abs() <= 1.0, "y {}", best.y);
+    assert!(best.score > 0.9);
+
+    let levels = matcher.debug_levels(image_view).unwrap();
+    assert_eq!(levels.len(), 2);
+
+    let details = matcher.match_image_detailed(image_view).unwrap();
+    assert_eq!(details.best, best);
+    assert_eq!(details.score_level, 1);
+    let full_res = Matcher::from_shared(compiled.clone())
+        .with_config(MatchConfig::default())
+        .match_image_detailed(image_view)
+        .unwrap();
+    assert_eq!(full_res.score_level, 0);
+    let verified = Matcher::from_shared(compiled.clone())
+        .with_config(MatchConfig {
+            verify_full_res: true,
+            ..cfg.clone()
+        })
+        .match_image_detailed(image_view)
+        .unwrap();
+    assert_eq!(verified.score_level, 0);
+
+    for bad in [0, 3] {
+        let cfg = MatchConfig {
+            initial_downscale: bad,
+            ..cfg.clone()
+        };
+        assert!(cfg.validate().is_err());
+    }
+    let too_deep = MatchConfig {
+        initial_downscale: 8,
+        ..cfg
+    };
+    assert!(too_deep.validate().is_err());
+}
+
+#[test]
+fn verify_full_res_rescores_at_level0() {
+    let tpl_width = 32;
+    let tpl_height = 24;
+    let tpl_data = make_template(tpl_width, tpl_height);
+    let template = Template::new(tpl_data.clone(), tpl_width, tpl_height).unwrap();
+
+    // Pixel noise lowers the level-0 score more than the averaged level-1
+    // score, so the two scores disagree.
+    let img_width = 160;
+    let img_height = 120;
+    let (x0, y0) = (72, 46);
+    let mut image = vec![0u8; img_width * img_height];
+    let mut state = 0x2545_f491u32;
+    for y in 0..tpl_height {
+        for x in 0..tpl_width {
+            state = state.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+            let noise = (state >> 24) as i32 % 61 - 30;
+            let value = tpl_data[y * tpl_width + x] as i32 + noise;
+            image[(y0 + y) * img_width + x0 + x] = value.clamp(0, 255) as u8;
+        }
+    }
+    let image_view = ImageView::from_slice(&image, img_width, img_height).unwrap();
+
+    let compiled = Arc::new(
+        CompiledTemplate::compile_unrotated(&template, CompileConfigNoRot { max_levels: 3 })
+            .unwrap(),
+    );
+    let cfg = MatchConfig {
+        max_image_levels: 3,
+        initial_downscale: 2,
+        ..MatchConfig::default()
+    };
+    let coarse = Matcher::from_shared(compiled.clone())
+        .with_config(cfg.clone())
+        .match_image(image_view)
+        .unwrap();
+    let matcher = Matcher::from_shared(compiled).with_config(MatchConfig {
+        verify_full_res: true,
+        ..cfg
+    });
+    let verified = matcher.match_image(image_view).unwrap();
+
+    assert_eq!((verified.x, verified.y), (coarse.x, coarse.y));
+    let expected = matcher
+        .score_pose(
+            image_view,
+            verified.x.round() as usize,
+            verified.y.round() as usize,
+            verified.angle_deg,
+        )
+        .unwrap();
+    assert_eq!(verified.score, expected);
+    assert!(
+        (verified.score - coarse.score).abs() > 0.01,
+        "verified {} vs coarse {}",
+        verified.score,
+        coarse.score
+    );
+
+    let topk = matcher.match_image_topk(image_view, 1).unwrap();
+    assert_eq!(topk[0].score, expected);
+}
+
+#[test]
+fn retry_on_empty_relaxes_a_level_that_finds_nothing() {
+    let tpl_width = 32;
+    let tpl_height = 24;
+    let tpl_data = make_template(tpl_width, tpl_height);
+    let template = Template::new(tpl_data.clone(), tpl_width, tpl_height).unwrap();
+
+    // Odd offsets misalign the fine texture with the coarse pixel grid, so
+    // coarse scores drop well below the exact level-0 match.
+    let img_width = 160;
+    let img_height = 120;
+    let (x0, y0) = (73, 47);
+    let mut image = vec![0u8; img_width * img_height];
+    for y in 0..tpl_height {
+        let row = (y0 + y) * img_width + x0;
+        image[row..row + tpl_width].copy_from_slice(&tpl_data[y * tpl_width..(y + 1) * tpl_width]);
+    }
+    let image_view = ImageView::from_slice(&image, img_width, img_height).unwrap();
+
+    let compiled = Arc::new(
+        CompiledTemplate::compile_unrotated(&template, CompileConfigNoRot { max_levels: 3 })
+            .unwrap(),
+    );
+    let cfg = MatchConfig {
+        max_image_levels: 3,
+        min_score: 0.9,
+        ..MatchConfig::default()
+    };
+    let strict = Matcher::from_shared(compiled.clone()).with_config(cfg.clone());
+    assert!(matches!(
+        strict.match_image(image_view),
+        Err(CorrMatchError::NoCandidates { .. })
+    ));
+
+    let retrying = Matcher::from_shared(compiled).with_config(MatchConfig {
+        retry_on_empty: true,
+        ..cfg
+    });
+    let best = retrying.match_image(image_view).unwrap();
+    assert!((best.x - x0 as f32).abs() < 0.5, "x {}", best.x);
+    assert!((best.y - y0 as f32).abs() < 0.5, "y {}", best.y);
+    assert!(best.score > 0.99);
+}
+
+/// Box-filter downsampler that counts how often it runs.
+#[derive(Debug, Default)]
+struct CountingDownsampler {
+    calls: AtomicUsize,
+}
+
+impl Downsampler for CountingDownsampler {
+    fn downsample(&self, src: ImageView<'_, u8>) -> CorrMatchResult<OwnedImage> {
+        self.calls.fetch_add(1, Ordering::Relaxed);
+        PyramidFilter::Box2x2.downsample(src)
+    }
+}
+
+#[test]
+fn matcher_builds_the_image_pyramid_with_the_template_downsampler() {
+    let tpl_width = 32;
+    let tpl_height = 24;
+    let tpl_data = make_template(tpl_width, tpl_height);
+    let template = Template::new(tpl_data.clone(), tpl_width, tpl_height).unwrap();
+
+    let img_width = 128;
+    let img_height = 96;
+    let (x0, y0) = (52, 36);
+    let mut image = vec![0u8; img_width * img_height];
+    for y in 0..tpl_height {
+        let row = (y0 + y) * img_width + x0;
+        image[row..row + tpl_width].copy_from_slice(&tpl_data[y * tpl_width..(y + 1) * tpl_width]);
+    }
+    let image_view = ImageView::from_slice(&image, img_width, img_height).unwrap();
+
+    let downsampler = Arc::new(CountingDownsampler::default());
+    let compiled = CompiledTemplate::compile_unrotated_with(
+        &template,
+        CompileConfigNoRot { max_levels: 3 },
+        CompileOptions::new().with_downsampler(downsampler.clone()),
+    )
+    .unwrap();
+    assert_eq!(downsampler.calls.load(Ordering::Relaxed), 2);
+
+    let matcher = Matcher::new(compiled).with_config(MatchConfig {
+        max_image_levels: 3,
+        ..MatchConfig::default()
+    });
+    let best = matcher.match_image(image_view).unwrap();
+    assert_eq!(downsampler.calls.load(Ordering::Relaxed), 4);
+    assert!((best.x - x0 as f32).abs() < 0.5, "x {}", best.x);
+    assert!((best.y - y0 as f32).abs() < 0.5, "y {}", best.y);
+
+    let mask = vec![1u8; img_width * img_height];
+    assert!(matches!(
+        matcher.match_image_masked(image_view, &mask),
+        Err(CorrMatchError::InvalidConfig { .. })
+    ));
+}
+
+#[test]
+fn detect_reports_presence_and_keeps_sub_threshold_matches() {
+    let tpl_width = 32;
+    let tpl_height = 24;
+    let tpl_data = make_template(tpl_width, tpl_height);
+    let template = Template::new(tpl_data.clone(), tpl_width, tpl_height).unwrap();
+
+    let img_width = 128;
+    let img_height = 96;
+    let (x0, y0) = (40, 30);
+    let mut image = vec![0u8; img_width * img_height];
+    for y in 0..tpl_height {
+        let row = (y0 + y) * img_width + x0;
+        image[row..row + tpl_width].copy_from_slice(&tpl_data[y * tpl_width..(y + 1) * tpl_width]);
+    }
+    let image_view = ImageView::from_slice(&image, img_width, img_height).unwrap();
+
+    let compiled =
+        CompiledTemplate::compile_unrotated(&template, CompileConfigNoRot { max_levels: 3 })
+            .unwrap();
+    let matcher = Matcher::new(compiled).with_config(MatchConfig {
+        max_image_levels: 3,
+        ..MatchConfig::default()
+    });
+
+    let found = matcher.detect(image_view, 0.9).unwrap();
+    assert!(found.present);
+    let best = found.best.unwrap();
+    assert!((best.x - x0 as f32).abs() < 0.5 && (best.y - y0 as f32).abs() < 0.5);
+
+    let missed = matcher.detect(image_view, 1.5).unwrap();
+    assert!(!missed.present);
+    assert_eq!(missed.best, Some(best));
+
+    let blank = vec![0u8; img_width * img_height];
+    let blank_view = ImageView::from_slice(&blank, img_width, img_height).unwrap();
+    let empty = matcher.detect(blank_view, 0.5).unwrap();
+    assert!(!empty.present);
+    assert!(empty.best.is_none());
+}
+
+#[test]
+fn min_instances_keeps_every_instance_past_beam_width() {
+    let tpl_width = 24;
+    let tpl_height = 24;
+    let tpl_data = make_template(tpl_width, tpl_height);
+    let template = Template::new(tpl_data.clone(), tpl_width, tpl_height).unwrap();
+
+    let img_width = 224;
+    let img_height = 160;
+    let positions = [(8, 8), (96, 16), (176, 8), (40, 112), (152, 120)];
+    let mut image = vec![0u8; img_width * img_height];
+    for &(x0, y0) in &positions {
+        for y in 0..tpl_height {
+            let row = (y0 + y) * img_width + x0;
+            image[row..row + tpl_width]
+                .copy_from_slice(&tpl_data[y * tpl_width..(y + 1) * tpl_width]);
+        }
+    }
+    let image_view = ImageView::from_slice(&image, img_width, img_height).unwrap();
+
+    let compiled = Arc::new(
+        CompiledTemplate::compile_unrotated(&template, CompileConfigNoRot { max_levels: 3 })
+            .unwrap(),
+    );
+    let cfg = MatchConfig {
+        max_image_levels: 3,
+        beam_width: 3,
+        min_score: 0.9,
+        ..MatchConfig::default()
+    };
+    let capped = Matcher::from_shared(compiled.clone())
+        .with_config(cfg.clone())
+        .match_image_topk(image_view, positions.len())
+        .unwrap();
+    assert_eq!(capped.len(), 3);
+
+    let counting = Matcher::from_shared(compiled).with_config(MatchConfig {
+        min_instances: positions.len(),
+        ..cfg
+    });
+    let found = counting
+        .match_image_topk(image_view, positions.len())
+        .unwrap();
+    assert_eq!(found.len(), positions.len());
+    for &(x0, y0) in &positions {
+        assert!(
+            found
+                .iter()
+                .any(|m| (m.x - x0 as f32).abs() < 0.5 && (m.y - y0 as f32).abs() < 0.5),
+            "missing instance at ({x0}, {y0})"
+        );
+    }
+}
+
+#[test]
+fn score_aggregation_combines_coarse_level_scores() {
+    let tpl_width = 32;
+    let tpl_height = 24;
+    let tpl_data = make_template(tpl_width, tpl_height);
+    let template = Template::new(tpl_data.clone(), tpl_width, tpl_height).unwrap();
+
+    // Odd offsets alias the fine texture at coarse levels, so the exact
+    // level-0 match scores well above its coarse-level ancestors.
+    let img_width = 160;
+    let img_height = 120;
+    let (x0, y0) = (73, 47);
+    let mut image = vec![0u8; img_width * img_height];
+    for y in 0..tpl_height {
+        let row = (y0 + y) * img_width + x0;
+        image[row..row + tpl_width].copy_from_slice(&tpl_data[y * tpl_width..(y + 1) * tpl_width]);
+    }
+    let image_view = ImageView::from_slice(&image, img_width, img_height).unwrap();
+
+    let compiled = Arc::new(
+        CompiledTemplate::compile_unrotated(&template, CompileConfigNoRot { max_levels: 3 })
+            .unwrap(),
+    );
+    let run = |score_aggregation| {
+        Matcher::from_shared(compiled.clone())
+            .with_config(MatchConfig {
+                max_image_levels: 3,
+                score_aggregation,
+                ..MatchConfig::default()
+            })
+            .match_image(image_view)
+            .unwrap()
+    };
+    let finest = run(ScoreAgg::Finest);
+    let min = run(ScoreAgg::MinAcrossLevels);
+    let mean = run(ScoreAgg::MeanAcrossLevels);
+
+    assert!(finest.score > 0.99, "finest {}", finest.score);
+    assert!(min.score < finest.score - 0.05, "min {}", min.score);
+    assert!(
+        mean.score > min.score && mean.score < finest.score,
+        "mean {}",
+        mean.score
+    );
+    for m in [min, mean] {
+        assert_eq!((m.x, m.y), (finest.x, finest.y));
+    }
+
+    let ssd = MatchConfig {
+        metric: Metric::Ssd,
+        score_aggregation: ScoreAgg::MeanAcrossLevels,
+        ..MatchConfig::default()
+    };
+    assert!(matches!(
+        ssd.validate(),
+        Err(CorrMatchError::InvalidConfig { .. })
+    ));
+}
+
+#[test]
+fn allow_partial_matches_a_template_past_the_image_border() {
+    let tpl_width = 32;
+    let tpl_height = 24;
+    let tpl_data = make_template(tpl_width, tpl_height);
+    let template = Template::new(tpl_data.clone(), tpl_width, tpl_height).unwrap();
+
+    // The instance enters from the left: its first 10 columns are off-image.
+    let img_width = 128;
+    let img_height = 96;
+    let (cut, y0) = (10, 40);
+    let mut image = vec![0u8; img_width * img_height];
+    for y in 0..tpl_height {
+        let row = (y0 + y) * img_width;
+        image[row..row + tpl_width - cut]
+            .copy_from_slice(&tpl_data[y * tpl_width + cut..(y + 1) * tpl_width]);
+    }
+    let image_view = ImageView::from_slice(&image, img_width, img_height).unwrap();
+
+    let compiled = Arc::new(
+        CompiledTemplate::compile_unrotated(&template, CompileConfigNoRot { max_levels: 3 })
+            .unwrap(),
+    );
+    let cfg = MatchConfig {
+        max_image_levels: 3,
+        ..MatchConfig::default()
+    };
+    let contained = Matcher::from_shared(compiled.clone())
+        .with_config(cfg.clone())
+        .match_image(image_view);
+    if let Ok(m) = contained {
+        assert!(m.x >= 0.0 && m.score < 0.99, "contained match {m:?}");
+    }
+
+    let partial = Matcher::from_shared(compiled).with_config(MatchConfig {
+        allow_partial: true,
+        ..cfg
+    });
+    let best = partial.match_image(image_view).unwrap();
+    assert!((best.x + cut as f32).abs() < 0.5, "x {}", best.x);
+    assert!((best.y - y0 as f32).abs() < 0.5, "y {}", best.y);
+    assert!(best.score > 0.99, "score {}", best.score);
+
+    // The partial placement has no template-sized region inside the image.
+    let above = Match {
+        x: 20.0,
+        y: -6.0,
+        ..best
+    };
+    for m in [best, above] {
+        assert!(matches!(
+            partial.explain(image_view, &m),
+            Err(CorrMatchError::RoiOutOfBounds { .. })
+        ));
+        assert!(matches!(
+            partial.extract_match_patch(image_view, &m),
+            Err(CorrMatchError::RoiOutOfBounds { .. })
+        ));
+    }
+
+    let ssd = MatchConfig {
+        metric: Metric::Ssd,
+        allow_partial: true,
+        ..MatchConfig::default()
+    };
+    assert!(matches!(
+        ssd.validate(),
+        Err(CorrMatchError::InvalidConfig { .. })
+    ));
+}
+
+#[test]
+fn masked_searches_reject_options_they_lack() {
+    let template = Template::new(make_template(16, 16), 16, 16).unwrap();
+    let image = vec![0u8; 64 * 64];
+    let image_mask = vec![1u8; 64 * 64];
+    let image_view = ImageView::from_slice(&image, 64, 64).unwrap();
+    let compiled = Arc::new(
+        CompiledTemplate::compile_unrotated(&template, CompileConfigNoRot::default()).unwrap(),
+    );
+    let partial = MatchConfig {
+        allow_partial: true,
+        ..MatchConfig::default()
+    };
+    assert!(partial.validate().is_ok());
+    let unsupported = [
+        MatchConfig {
+            retry_on_empty: true,
+            ..partial.clone()
+        },
+        MatchConfig {
+            verify_full_res: true,
+            ..partial.clone()
+        },
+        MatchConfig {
+            beam_width: 4,
+            compute_psr: true,
+            ..partial.clone()
+        },
+        MatchConfig {
+            angle_refine: AngleRefine::GoldenSection { iters: 8 },
+            ..partial.clone()
+        },
+        MatchConfig {
+            coarse_variance_ratio: Some(4.0),
+            ..partial.clone()
+        },
+    ];
+    for cfg in unsupported {
+        assert!(cfg.validate().is_ok());
+        let matcher = Matcher::from_shared(Arc::clone(&compiled)).with_config(cfg.clone());
+        assert!(matches!(
+            matcher.match_image(image_view),
+            Err(CorrMatchError::InvalidConfig { .. })
+        ));
+        // The masked entry point rejects the same options without allow_partial.
+        let masked = Matcher::from_shared(Arc::clone(&compiled)).with_config(MatchConfig {
+            allow_partial: false,
+            ..cfg
+        });
+        assert!(matches!(
+            masked.match_image_masked(image_view, &image_mask),
+            Err(CorrMatchError::InvalidConfig { .. })
+        ));
+    }
+
+    let matcher = Matcher::from_shared(Arc::clone(&compiled)).with_config(partial.clone());
+    assert!(matches!(
+        matcher.match_image_topk(image_view, 2),
+        Err(CorrMatchError::InvalidConfig { .. })
+    ));
+    assert!(matches!(
+        TemplateBatch::compile_unrotated(&[template], CompileConfigNoRot::default(), partial),
+        Err(CorrMatchError::InvalidConfig { .. })
+    ));
+}
+
+#[test]
+fn template_batch_matches_each_template_alone() {
+    let img_width = 160;
+    let img_height = 120;
+    let image = make_template(img_width, img_height);
+    let image_view = ImageView::from_slice(&image, img_width, img_height).unwrap();
+
+    let size = 16;
+    let templates: Vec<Template> = [(21, 13), (90, 40), (130, 95)]
+        .iter()
+        .map(|&(x0, y0)| {
+            let mut data = Vec::with_capacity(size * size);
+            for y in 0..size {
+                let row = (y0 + y) * img_width + x0;
+                data.extend_from_slice(&image[row..row + size]);
+            }
+            Template::new(data, size, size).unwrap()
+        })
+        .collect();
+
+    let compile = CompileConfigNoRot { max_levels: 2 };
+    let cfg = MatchConfig {
+        max_image_levels: 2,
+        ..MatchConfig::default()
+    };
+    let batch = TemplateBatch::compile_unrotated(&templates, compile.clone(), cfg.clone()).unwrap();
+    assert_eq!(batch.len(), templates.len());
+    let results = batch.match_image(image_view).unwrap();
+    assert_eq!(results.len(), templates.len());
+
+    for (template, result) in templates.iter().zip(&results) {
+        let compiled = CompiledTemplate::compile_unrotated(template, compile.clone()).unwrap();
+        let alone = Matcher::new(compiled)
+            .with_config(cfg.clone())
+            .match_image(image_view)
+            .unwrap();
+        assert_eq!(result.as_ref(), Some(&alone));
+    }
+
+    assert!(matches!(
+        TemplateBatch::compile_unrotated(&[], compile, cfg),
+        Err(CorrMatchError::InvalidConfig { .. })
+    ));
+}
+
+fn make_barcode(width: usize, height: usize) -> Vec<u8> {
+    let mut state = 0x1234_5678u32;
+    let mut columns = Vec::with_capacity(width);
+    let mut dark = true;
+    while columns.len() < width {
+        state = state.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+        let run = 1 + (state >> 28) as usize % 4;
+        let value = if dark { 30u8 } else { 220u8 };
+        columns.extend(std::iter::repeat_n(value, run));
+        dark = !dark;
+    }
+    columns.truncate(width);
+    (0..height).flat_map(|_| columns.iter().copied()).collect()
+}
+
+#[test]
+fn elongated_template_keeps_levels_and_matches() {
+    let tpl_width = 256;
+    let tpl_height = 16;
+    let tpl_data = make_barcode(tpl_width, tpl_height);
+    let template = Template::new(tpl_data.clone(), tpl_width, tpl_height).unwrap();
+
+    let img_width = 480;
+    let img_height = 160;
+    let x0 = 152;
+    let y0 = 68;
+    let mut image = vec![128u8; img_width * img_height];
+    for y in 0..tpl_height {
+        let row = (y0 + y) * img_width + x0;
+        image[row..row + tpl_width].copy_from_slice(&tpl_data[y * tpl_width..(y + 1) * tpl_width]);
+    }
+    let image_view = ImageView::from_slice(&image, img_width, img_height).unwrap();
+
+    // The 16-pixel side no longer caps the pyramid at two levels.
+    let compiled = CompiledTemplate::compile_rotated(&template, CompileConfig::default()).unwrap();
+    assert_eq!(compiled.num_levels(), 3);
+    assert_eq!(compiled.level_size(2), Some((64, 4)));
+
+    let matcher = Matcher::new(compiled).with_config(MatchConfig {
+        rotation: RotationMode::Enabled,
+        ..MatchConfig::default()
+    });
+    let best = matcher.match_image(image_view).unwrap();
+    assert!((best.x - x0 as f32).abs() <= 1.0);
+    assert!((best.y - y0 as f32).abs() <= 1.0);
+    assert!(angle_diff_deg(best.angle_deg, 0.0) <= 1.0);
+    assert!(best.score > 0.99);
+
+    let compiled =
+        CompiledTemplate::compile_unrotated(&template, CompileConfigNoRot::default()).unwrap();
+    let matcher = Matcher::new(compiled);
+    let best = matcher.match_image(image_view).unwrap();
+    assert!((best.x - x0 as f32).abs() <= 1.0);
+    assert!((best.y - y0 as f32).abs() <= 1.0);
+    assert!(best.score > 0.99);
+}
+
+#[test]
+fn shared_matcher_is_sync_and_deterministic_across_threads() {
+    fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<Matcher>();
+    assert_send_sync::<CompiledTemplate>();
+    assert_send_sync::<Ensemble>();
+
+    let tpl_width = 24;
+    let tpl_height = 20;
+    let tpl_data = make_template(tpl_width, tpl_height);
+    let template = Template::new(tpl_data.clone(), tpl_width, tpl_height).unwrap();
+
+    let img_width = 96;
+    let img_height = 80;
+    let tpl_view = ImageView::from_slice(&tpl_data, tpl_width, tpl_height).unwrap();
+    let (rotated, mask) = rotate_u8_bilinear_masked(tpl_view, 20.0, 0);
+    let mut image = vec![0u8; img_width * img_height];
+    let (x0, y0) = (37usize, 29usize);
+    for y in 0..tpl_height {
+        for x in 0..tpl_width {
+            if mask[y * tpl_width + x] != 0 {
+                image[(y0 + y) * img_width + x0 + x] = rotated.view().get(x, y).copied().unwrap();
+            }
+        }
+    }
+
+    // Leave every rotation slot empty so the threads race to fill the cache.
+    let compiled = CompiledTemplate::compile_rotated(
+        &template,
+        CompileConfig {
+            max_levels: 2,
+            min_template_dim: 4,
+            precompute_coarsest: false,
+            ..CompileConfig::default()
+        },
+    )
+    .unwrap();
+    let matcher = Arc::new(Matcher::new(compiled).with_config(MatchConfig {
+        rotation: RotationMode::Enabled,
+        max_image_levels: 2,
+        ..MatchConfig::default()
+    }));
+
+    let results: Vec<_> = std::thread::scope(|scope| {
+        let handles: Vec<_> = (0..8)
+            .map(|_| {
+                let matcher = Arc::clone(&matcher);
+                let image = &image;
+                scope.spawn(move || {
+                    let view = ImageView::from_slice(image, img_width, img_height).unwrap();
+                    matcher.match_image(view).unwrap()
+                })
+            })
+            .collect();
+        handles.into_iter().map(|h| h.join().unwrap()).collect()
+    });
+
+    let view = ImageView::from_slice(&image, img_width, img_height).unwrap();
+    let expected = matcher.match_image(view).unwrap();
+    for result in results {
+        assert_eq!(result, expected);
+    }
+}
+
+/// Two blocky textures with different layouts, used as swapped color channels.
+fn color_textures(x: usize, y: usize) -> (u8, u8) {
+    let (bx, by) = (x / 4, y / 4);
+    let t = ((bx * 7 + by * 13) % 5) * 50;
+    let s = ((bx * 11 + by * 3) % 7) * 35;
+    (t as u8, s as u8)
+}
+
+#[test]
+fn rgb_matcher_separates_colors_with_equal_luma() {
+    let tpl_size = 24;
+    let mut tpl_data = Vec::with_capacity(tpl_size * tpl_size * 3);
+    for y in 0..tpl_size {
+        for x in 0..tpl_size {
+            let (t, s) = color_textures(x, y);
+            tpl_data.extend_from_slice(&[t, s, 60]);
+        }
+    }
+    let template = Template3::new(tpl_data, tpl_size, tpl_size).unwrap();
+    let compiled = template
+        .compile(CompileConfigNoRot { max_levels: 3 })
+        .unwrap();
+    assert_eq!(compiled.num_levels(), 3);
+
+    // The decoy swaps the red and green channels, so its channel sum (and any
+    // luma with equal red/green weights) is identical to the true placement.
+    let width = 96;
+    let height = 72;
+    let (true_x, true_y) = (60, 40);
+    let (decoy_x, decoy_y) = (8, 8);
+    let mut image = vec![128u8; width * height * 3];
+    for y in 0..tpl_size {
+        for x in 0..tpl_size {
+            let (t, s) = color_textures(x, y);
+            let at = |px: usize, py: usize| ((py + y) * width + px + x) * 3;
+            image[at(true_x, true_y)..at(true_x, true_y) + 3].copy_from_slice(&[t, s, 60]);
+            image[at(decoy_x, decoy_y)..at(decoy_x, decoy_y) + 3].copy_from_slice(&[s, t, 60]);
+        }
+    }
+
+    let matcher = RgbMatcher::new(compiled).with_config(MatchConfig {
+        max_image_levels: 3,
+        ..MatchConfig::default()
+    });
+    let best = matcher.match_image(&image, width, height).unwrap();
+    assert!((best.x - true_x as f32).abs() < 0.5, "x = {}", best.x);
+    assert!((best.y - true_y as f32).abs() < 0.5, "y = {}", best.y);
+    assert!(best.score > 0.99, "score = {}", best.score);
+
+    let rotated = matcher.clone().with_config(MatchConfig {
+        rotation: RotationMode::Enabled,
+        ..MatchConfig::default()
+    });
+    assert!(matches!(
+        rotated.match_image(&image, width, height),
+        Err(CorrMatchError::InvalidConfig { .. })
+    ));
+}
+
+#[test]
+fn strided_views_match_contiguous_images() {
+    let tpl_width = 24;
+    let tpl_height = 20;
+    let tpl_data = make_template(tpl_width, tpl_height);
+    let template = Template::new(tpl_data.clone(), tpl_width, tpl_height).unwrap();
+
+    // The template touches the right edge, so any read past the logical
+    // width would land in the padding, which holds template texture.
+    let img_width = 96;
+    let img_height = 72;
+    let (x0, y0) = (img_width - tpl_width, 30);
+    let mut image = vec![0u8; img_width * img_height];
+    for y in 0..tpl_height {
+        for x in 0..tpl_width {
+            image[(y0 + y) * img_width + (x0 + x)] = tpl_data[y * tpl_width + x];
+        }
+    }
+    let stride = img_width + 13;
+    let mut padded = Vec::with_capacity(stride * img_height);
+    for y in 0..img_height {
+        padded.extend_from_slice(&image[y * img_width..(y + 1) * img_width]);
+        let tpl_row = &tpl_data[(y % tpl_height) * tpl_width..];
+        padded.extend_from_slice(&tpl_row[..stride - img_width]);
+    }
+    let contiguous = ImageView::from_slice(&image, img_width, img_height).unwrap();
+    let strided = ImageView::new(&padded, img_width, img_height, stride).unwrap();
+
+    let unrotated = Arc::new(
+        CompiledTemplate::compile_unrotated(&template, CompileConfigNoRot { max_levels: 3 })
+            .unwrap(),
+    );
+    let rotated = Arc::new(
+        CompiledTemplate::compile_rotated(
+            &template,
+            CompileConfig {
+                // A third level would shrink the template to 6x5, too small
+                // to rank the true pose among the rotated slots.
+                max_levels: 2,
+                coarse_step_deg: 20.0,
+                min_step_deg: 5.0,
+                ..CompileConfig::default()
+            },
+        )
+        .unwrap(),
+    );
+    let base = MatchConfig {
+        max_image_levels: 3,
+        ..MatchConfig::default()
+    };
+    let cases = [
+        (&unrotated, base.clone()),
+        (
+            &unrotated,
+            MatchConfig {
+                metric: Metric::Ssd,
+                ..base.clone()
+            },
+        ),
+        (
+            &unrotated,
+            MatchConfig {
+                subpixel_resample: true,
+                ..base.clone()
+            },
+        ),
+        (
+            &rotated,
+            MatchConfig {
+                rotation: RotationMode::Enabled,
+                ..base.clone()
+            },
+        ),
+    ];
+
+    for (compiled, cfg) in cases {
+        let matcher = Matcher::from_shared(Arc::clone(compiled)).with_config(cfg);
+        let expected = matcher.match_image(contiguous).unwrap();
+        assert_eq!(matcher.match_image(strided).unwrap(), expected);
+        assert!((expected.x - x0 as f32).abs() <= 1.0);
+        assert!((expected.y - y0 as f32).abs() <= 1.0);
+        assert_eq!(
+            matcher.match_image_topk(strided, 3).unwrap(),
+            matcher.match_image_topk(contiguous, 3).unwrap()
+        );
+    }
+}
+
+#[test]
+fn nms_keep_ratio_keeps_adjacent_equal_instances() {
+    let tpl_size = 16;
+    let tpl_data = make_template(tpl_size, tpl_size);
+    let template = Template::new(tpl_data.clone(), tpl_size, tpl_size).unwrap();
+
+    // Two identical, non-overlapping instances closer than the NMS radius.
+    let img_width = 80;
+    let img_height = 48;
+    let positions = [(20usize, 16usize), (38, 16)];
+    let mut image = vec![0u8; img_width * img_height];
+    for &(x0, y0) in &positions {
+        for y in 0..tpl_size {
+            for x in 0..tpl_size {
+                image[(y0 + y) * img_width + x0 + x] = tpl_data[y * tpl_size + x];
+            }
+        }
+    }
+    let image_view = ImageView::from_slice(&image, img_width, img_height).unwrap();
+
+    let compiled = Arc::new(
+        CompiledTemplate::compile_unrotated(&template, CompileConfigNoRot { max_levels: 1 })
+            .unwrap(),
+    );
+    let cfg = MatchConfig {
+        max_image_levels: 1,
+        nms_radius: 24,
+        ..MatchConfig::default()
+    };
+
+    let plain = Matcher::from_shared(Arc::clone(&compiled)).with_config(cfg.clone());
+    let found = plain.match_image_topk(image_view, 4).unwrap();
+    assert_eq!(found.len(), 1);
+
+    let ratio = Matcher::from_shared(compiled).with_config(MatchConfig {
+        nms_keep_ratio: 0.9,
+        ..cfg
+    });
+    let found = ratio.match_image_topk(image_view, 4).unwrap();
+    assert_eq!(found.len(), 2);
+    for (m, &(x0, y0)) in found.iter().zip(&positions) {
+        assert!((m.x - x0 as f32).abs() < 0.5 && (m.y - y0 as f32).abs() < 0.5);
+        assert!(m.score > 0.99);
+    }
+}
+
+#[test]
+fn match_image_with_timings_matches_plain_result() {
+    let tpl_width = 24;
+    let tpl_height = 20;
+    let tpl_data = make_template(tpl_width, tpl_height);
+    let template = Template::new(tpl_data.clone(), tpl_width, tpl_height).unwrap();
+
+    let img_width = 120;
+    let img_height = 90;
+    let (x0, y0) = (45, 33);
+    let mut image = vec![0u8; img_width * img_height];
+    for y in 0..tpl_height {
+        for x in 0..tpl_width {
+            image[(y0 + y) * img_width + (x0 + x)] = tpl_data[y * tpl_width + x];
+        }
+    }
+    let image_view = ImageView::from_slice(&image, img_width, img_height).unwrap();
+
+    let compiled =
+        CompiledTemplate::compile_unrotated(&template, CompileConfigNoRot { max_levels: 3 })
+            .unwrap();
+    let matcher = Matcher::new(compiled).with_config(MatchConfig {
+        max_image_levels: 3,
+        ..MatchConfig::default()
+    });
+
+    let (best, timings) = matcher.match_image_with_timings(image_view).unwrap();
+    assert_eq!(best, matcher.match_image(image_view).unwrap());
+    assert_eq!(
+        timings.total(),
+        timings.pyramid + timings.coarse + timings.refinement + timings.final_refinement
+    );
+    assert!(timings.total() > std::time::Duration::ZERO);
+}
+
+#[test]
+fn level_stride_skips_levels_and_finds_the_same_match() {
+    let tpl_width = 64;
+    let tpl_height = 48;
+    let tpl_data = make_template(tpl_width, tpl_height);
+    let template = Template::new(tpl_data.clone(), tpl_width, tpl_height).unwrap();
+
+    let img_width = 256;
+    let img_height = 192;
+    let x0 = 117;
+    let y0 = 83;
+    let mut image = vec![0u8; img_width * img_height];
+    for y in 0..tpl_height {
+        let row = (y0 + y) * img_width + x0;
+        image[row..row + tpl_width].copy_from_slice(&tpl_data[y * tpl_width..(y + 1) * tpl_width]);
+    }
+    let image_view = ImageView::from_slice(&image, img_width, img_height).unwrap();
+
+    let compiled = Arc::new(
+        CompiledTemplate::compile_unrotated(&template, CompileConfigNoRot { max_levels: 4 })
+            .unwrap(),
+    );
+    let base = MatchConfig {
+        max_image_levels: 4,
+        ..MatchConfig::default()
+    };
+    let dense = Matcher::from_shared(compiled.clone()).with_config(base.clone());
+    let strided = Matcher::from_shared(compiled).with_config(MatchConfig {
+        level_stride: 2,
+        ..base
+    });
+
+    let expected = dense.match_image(image_view).unwrap();
+    let best = strided.match_image(image_view).unwrap();
+    assert!((best.x - x0 as f32).abs() <= 0.5, "x {}", best.x);
+    assert!((best.y - y0 as f32).abs() <= 0.5, "y {}", best.y);
+    assert!((best.x - expected.x).abs() < 1e-3 && (best.y - expected.y).abs() < 1e-3);
+    assert!((best.score - expected.score).abs() < 1e-4);
+
+    // Levels 3 -> 1 -> 0 instead of 3 -> 2 -> 1 -> 0.
+    assert_eq!(dense.debug_levels(image_view).unwrap().len(), 4);
+    assert_eq!(strided.debug_levels(image_view).unwrap().len(), 3);
+}
+
+#[test]
+fn search_starts_at_the_coarsest_shared_level() {
+    let tpl_width = 64;
+    let tpl_height = 48;
+    let tpl_data = make_template(tpl_width, tpl_height);
+    let template = Template::new(tpl_data.clone(), tpl_width, tpl_height).unwrap();
+
+    let img_width = 256;
+    let img_height = 192;
+    let x0 = 117;
+    let y0 = 83;
+    let mut image = vec![0u8; img_width * img_height];
+    for y in 0..tpl_height {
+        let row = (y0 + y) * img_width + x0;
+        image[row..row + tpl_width].copy_from_slice(&tpl_data[y * tpl_width..(y + 1) * tpl_width]);
+    }
+    let image_view = ImageView::from_slice(&image, img_width, img_height).unwrap();
+
+    let compiled = Arc::new(
+        CompiledTemplate::compile_unrotated(&template, CompileConfigNoRot { max_levels: 4 })
+            .unwrap(),
+    );
+    assert_eq!(compiled.num_levels(), 4);
+
+    // One image level fewer, equal, and one more than the template has.
+    for (max_image_levels, searched) in [(3, 3), (4, 4), (5, 4)] {
+        let matcher = Matcher::from_shared(Arc::clone(&compiled)).with_config(MatchConfig {
+            max_image_levels,
+            ..MatchConfig::default()
+        });
+        let levels = matcher.debug_levels(image_view).unwrap();
+        assert_eq!(
+            levels.len(),
+            searched,
+            "max_image_levels {max_image_levels}"
+        );
+
+        let best = matcher.match_image(image_view).unwrap();
+        assert!((best.x - x0 as f32).abs() <= 0.5, "x {}", best.x);
+        assert!((best.y - y0 as f32).abs() <= 0.5, "y {}", best.y);
+    }
+}
+
+#[test]
+fn image_mask_excludes_corrupted_pixels() {
+    let tpl_width = 48;
+    let tpl_height = 40;
+    let tpl_data = make_template(tpl_width, tpl_height);
+    let template = Template::new(tpl_data.clone(), tpl_width, tpl_height).unwrap();
+
+    let img_width = 200;
+    let img_height = 160;
+    let x0 = 83;
+    let y0 = 61;
+    let mut image: Vec<u8> = (0..img_width * img_height)
+        .map(|i| ((i * 37 + (i / img_width) * 11) % 251) as u8)
+        .collect();
+    for y in 0..tpl_height {
+        let row = (y0 + y) * img_width + x0;
+        image[row..row + tpl_width].copy_from_slice(&tpl_data[y * tpl_width..(y + 1) * tpl_width]);
+    }
+    let mut mask = vec![1u8; img_width * img_height];
+    let image_view = ImageView::from_slice(&image, img_width, img_height).unwrap();
+
+    let compiled = CompiledTemplate::compile_rotated(
+        &template,
+        CompileConfig {
+            max_levels: 3,
+            coarse_step_deg: 20.0,
+            min_step_deg: 5.0,
+            ..CompileConfig::default()
+        },
+    )
+    .unwrap();
+    let matcher = Matcher::new(compiled).with_config(MatchConfig {
+        rotation: RotationMode::Enabled,
+        max_image_levels: 3,
+        ..MatchConfig::default()
+    });
+
+    // An all-valid mask reproduces the plain search.
+    let plain = matcher.match_image(image_view).unwrap();
+    let full = matcher.match_image_masked(image_view, &mask).unwrap();
+    assert!((full.x - plain.x).abs() < 0.05 && (full.y - plain.y).abs() < 0.05);
+    assert!(angle_diff_deg(full.angle_deg, plain.angle_deg) < 0.1);
+    assert!((full.score - plain.score).abs() < 1e-3);
+
+    // A saturated reflection over a third of the instance.
+    let mut corrupted = image.clone();
+    for y in y0 + 4..y0 + 30 {
+        for x in x0 + 10..x0 + 28 {
+            corrupted[y * img_width + x] = 255;
+            mask[y * img_width + x] = 0;
+        }
+    }
+    let corrupted_view = ImageView::from_slice(&corrupted, img_width, img_height).unwrap();
+    let unmasked = matcher.match_image(corrupted_view).unwrap();
+    assert!(unmasked.score < 0.9, "score {}", unmasked.score);
+
+    let masked = matcher.match_image_masked(corrupted_view, &mask).unwrap();
+    assert!((masked.x - x0 as f32).abs() < 0.5, "x {}", masked.x);
+    assert!((masked.y - y0 as f32).abs() < 0.5, "y {}", masked.y);
+    assert!(angle_diff_deg(masked.angle_deg, 0.0) < 1.0);
+    assert!(masked.score > 0.99, "score {}", masked.score);
+
+    assert!(matches!(
+        matcher.match_image_masked(corrupted_view, &mask[1..]),
+        Err(CorrMatchError::BufferTooSmall { .. })
+    ));
+    let ssd = matcher.clone().with_config(MatchConfig {
+        metric: Metric::Ssd,
+        ..MatchConfig::default()
+    });
+    assert!(matches!(
+        ssd.match_image_masked(corrupted_view, &mask),
+        Err(CorrMatchError::InvalidConfig { .. })
+    ));
+}
+
+#[test]
+fn template_mask_ignores_occluded_pixels_without_rotation() {
+    let tpl_width = 32;
+    let tpl_height = 32;
+    let tpl_data = make_template(tpl_width, tpl_height);
+    let template = Template::new(tpl_data.clone(), tpl_width, tpl_height).unwrap();
+
+    // The instance's bottom-right quarter is covered by a flat occluder.
+    let img_width = 128;
+    let img_height = 96;
+    let (x0, y0) = (40, 30);
+    let mut image = vec![0u8; img_width * img_height];
+    let mut mask = vec![1u8; tpl_width * tpl_height];
+    for y in 0..tpl_height {
+        for x in 0..tpl_width {
+            let occluded = x >= tpl_width / 2 && y >= tpl_height / 2;
+            image[(y0 + y) * img_width + x0 + x] = if occluded {
+                200
+            } else {
+                tpl_data[y * tpl_width + x]
+            };
+            if occluded {
+                mask[y * tpl_width + x] = 0;
+            }
+        }
+    }
+    let image_view = ImageView::from_slice(&image, img_width, img_height).unwrap();
+
+    let compile = CompileConfigNoRot { max_levels: 3 };
+    let cfg = MatchConfig {
+        max_image_levels: 3,
+        ..MatchConfig::default()
+    };
+    let plain = CompiledTemplate::compile_unrotated(&template, compile.clone()).unwrap();
+    let masked = Arc::new(
+        CompiledTemplate::compile_unrotated_with(
+            &template,
+            compile,
+            CompileOptions::new().with_mask(mask),
+        )
+        .unwrap(),
+    );
+    assert_eq!(masked.angle_grid(0).map(|grid| grid.len()), Some(1));
+    assert!(matches!(
+        CompiledTemplate::compile_with(
+            &template,
+            CompileConfig::default(),
+            CompileOptions::new().with_mask(vec![1; tpl_width * tpl_height]),
+        ),
+        Err(CorrMatchError::InvalidConfig { .. })
+    ));
+
+    let unmasked = Matcher::new(plain)
+        .with_config(cfg.clone())
+        .match_image(image_view);
+    if let Ok(m) = unmasked {
+        assert!(m.score < 0.9, "unmasked match {m:?}");
+    }
+    let combined = Metric::Combined {
+        zncc_weight: 0.5,
+        ssd_weight: 0.5,
+    };
+    for metric in [Metric::Zncc, Metric::Ssd, combined] {
+        let best = Matcher::from_shared(masked.clone())
+            .with_config(MatchConfig {
+                metric,
+                ..cfg.clone()
+            })
+            .match_image(image_view)
+            .unwrap();
+        assert!((best.x - x0 as f32).abs() < 0.5, "{metric:?} x {}", best.x);
+        assert!((best.y - y0 as f32).abs() < 0.5, "{metric:?} y {}", best.y);
+        assert_eq!(best.angle_deg, 0.0);
+        match metric {
+            Metric::Zncc => assert!(best.score > 0.99, "score {}", best.score),
+            Metric::Ssd => assert!(best.score > -1e-3, "score {}", best.score),
+            Metric::Combined { .. } => assert!(best.score > 0.99, "score {}", best.score),
+        }
+    }
+}
+
+#[test]
+fn rotation_on_unrotated_template_reports_rotation_not_compiled() {
+    let tpl_data = make_template(16, 16);
+    let template = Template::new(tpl_data, 16, 16).unwrap();
+    let image = vec![0u8; 64 * 64];
+    let image_view = ImageView::from_slice(&image, 64, 64).unwrap();
+
+    let compiled =
+        CompiledTemplate::compile_unrotated(&template, CompileConfigNoRot::default()).unwrap();
+    let matcher = Matcher::new(compiled).with_config(MatchConfig {
+        rotation: RotationMode::Enabled,
+        ..MatchConfig::default()
+    });
+    assert_eq!(
+        matcher.match_image(image_view).unwrap_err(),
+        CorrMatchError::RotationNotCompiled
+    );
+}
+
+#[test]
+fn nms_angle_separation_reports_each_symmetric_orientation() {
+    // Four copies of one tile, each turned by a further 90° about the center.
+    let tile = make_template(16, 16);
+    let size = 32;
+    let tpl_data: Vec<u8> = (0..size * size)
+        .map(|i| {
+            let (mut x, mut y) = (i % size, i / size);
+            while x >= 16 || y >= 16 {
+                (x, y) = (size - 1 - y, x);
+            }
+            tile[y * 16 + x]
+        })
+        .collect();
+    let template = Template::new(tpl_data.clone(), size, size).unwrap();
+
+    let (img_width, img_height, x0, y0) = (96, 96, 32, 32);
+    let mut image = vec![0u8; img_width * img_height];
+    for y in 0..size {
+        let row = (y0 + y) * img_width + x0;
+        image[row..row + size].copy_from_slice(&tpl_data[y * size..(y + 1) * size]);
+    }
+    let view = ImageView::from_slice(&image, img_width, img_height).unwrap();
+
+    let compiled = Arc::new(
+        CompiledTemplate::compile_rotated(
+            &template,
+            CompileConfig {
+                max_levels: 1,
+                coarse_step_deg: 30.0,
+                min_step_deg: 30.0,
+                fill_strategy: FillStrategy::Constant(0),
+                ..CompileConfig::default()
+            },
+        )
+        .unwrap(),
+    );
+    let topk = |nms_angle_separation_deg| {
+        Matcher::from_shared(Arc::clone(&compiled))
+            .with_config(MatchConfig {
+                max_image_levels: 1,
+                rotation: RotationMode::Enabled,
+                nms_angle_separation_deg,
+                ..MatchConfig::default()
+            })
+            .match_image_topk(view, 4)
+            .unwrap()
+    };
+    let at_part = |m: &&Match| (m.x - x0 as f32).abs() < 0.5 && (m.y - y0 as f32).abs() < 0.5;
+
+    let matches = topk(None);
+    assert_eq!(matches.iter().filter(at_part).count(), 1, "{matches:?}");
+
+    let matches = topk(Some(45.0));
+    assert_eq!(matches.len(), 4, "{matches:?}");
+    assert!(matches.iter().all(|m| at_part(&m) && m.score > 0.99));
+    for expected in [-180.0, -90.0, 0.0, 90.0] {
+        assert!(
+            matches
+                .iter()
+                .any(|m| angle_diff_deg(m.angle_deg, expected) < 1.0),
+            "no match near {expected}°: {matches:?}"
+        );
+    }
+    assert!(matches.windows(2).all(|w| w[0].score >= w[1].score));
+}
+
+#[test]
+fn degenerate_sizes_report_dedicated_errors() {
+    let dot = Template::new(vec![7], 1, 1).unwrap();
+    assert!(matches!(
+        CompiledTemplate::compile_unrotated(&dot, CompileConfigNoRot::default()),
+        Err(CorrMatchError::DegenerateTemplate { .. })
+    ));
+    assert!(matches!(
+        CompiledTemplate::compile_rotated(&dot, CompileConfig::default()),
+        Err(CorrMatchError::DegenerateTemplate { .. })
+    ));
+
+    // A one-pixel-high strip still matches without rotation.
+    let strip: Vec<u8> = (0..8u8).map(|i| i * 29 + 3).collect();
+    let template = Template::new(strip.clone(), 8, 1).unwrap();
+    let (img_width, img_height, x0, y0) = (32, 4, 5, 2);
+    let mut image = vec![0u8; img_width * img_height];
+    let row = y0 * img_width + x0;
+    image[row..row + 8].copy_from_slice(&strip);
+    let view = ImageView::from_slice(&image, img_width, img_height).unwrap();
+    let matcher = Matcher::new(
+        CompiledTemplate::compile_unrotated(&template, CompileConfigNoRot::default()).unwrap(),
+    );
+    let best = matcher.match_image(view).unwrap();
+    assert!((best.x - x0 as f32).abs() < 0.5 && (best.y - y0 as f32).abs() < 0.5);
+    assert!(best.score > 0.99);
+
+    let tiny = [9u8];
+    let tiny = ImageView::from_slice(&tiny, 1, 1).unwrap();
+    let expected = CorrMatchError::ImageSmallerThanTemplate {
+        img_width: 1,
+        img_height: 1,
+        tpl_width: 8,
+        tpl_height: 1,
+    };
+    assert_eq!(matcher.match_image(tiny).unwrap_err(), expected);
+    assert_eq!(matcher.match_image_topk(tiny, 3).unwrap_err(), expected);
+
+    let tpl_data = make_template(16, 16);
+    let template = Template::new(tpl_data, 16, 16).unwrap();
+    let short = ImageView::from_slice(&image, 32, 4).unwrap();
+    let expected = CorrMatchError::ImageSmallerThanTemplate {
+        img_width: 32,
+        img_height: 4,
+        tpl_width: 16,
+        tpl_height: 16,
+    };
+    let unrotated = Matcher::new(
+        CompiledTemplate::compile_unrotated(&template, CompileConfigNoRot::default()).unwrap(),
+    );
+    assert_eq!(unrotated.match_image(short).unwrap_err(), expected);
+    let rotated = Matcher::new(
+        CompiledTemplate::compile_rotated(&template, CompileConfig::default()).unwrap(),
+    )
+    .with_config(MatchConfig {
+        rotation: RotationMode::Enabled,
+        ..MatchConfig::default()
+    });
+    assert_eq!(rotated.match_image(short).unwrap_err(), expected);
+}
+
+#[test]
+fn match_angles_at_best_returns_both_orientations_of_a_symmetric_part() {
+    // The bottom half repeats the top half turned by 180°.
+    let size = 32;
+    let half = make_template(size, size / 2);
+    let tpl_data: Vec<u8> = (0..size * size)
+        .map(|i| {
+            let (x, y) = (i % size, i / size);
+            if y < size / 2 {
+                half[y * size + x]
+            } else {
+                half[(size - 1 - y) * size + (size - 1 - x)]
+            }
+        })
+        .collect();
+    let template = Template::new(tpl_data.clone(), size, size).unwrap();
+
+    let (img_width, img_height, x0, y0) = (96, 80, 40, 24);
+    let mut image = vec![0u8; img_width * img_height];
+    for y in 0..size {
+        let row = (y0 + y) * img_width + x0;
+        image[row..row + size].copy_from_slice(&tpl_data[y * size..(y + 1) * size]);
+    }
+    let view = ImageView::from_slice(&image, img_width, img_height).unwrap();
+
+    let compiled = Arc::new(
+        CompiledTemplate::compile_rotated(
+            &template,
+            CompileConfig {
+                max_levels: 1,
+                coarse_step_deg: 15.0,
+                min_step_deg: 15.0,
+                fill_strategy: FillStrategy::Constant(0),
+                ..CompileConfig::default()
+            },
+        )
+        .unwrap(),
+    );
+    let matcher = Matcher::from_shared(compiled).with_config(MatchConfig {
+        max_image_levels: 1,
+        rotation: RotationMode::Enabled,
+        min_score: 0.5,
+        ..MatchConfig::default()
+    });
+
+    let best = matcher.match_image(view).unwrap();
+    let hypotheses = matcher.match_angles_at_best(view, 2).unwrap();
+    assert_eq!(hypotheses.len(), 2, "{hypotheses:?}");
+    assert_eq!(hypotheses[0], best);
+    for m in &hypotheses {
+        assert!((m.x - x0 as f32).abs() < 0.5 && (m.y - y0 as f32).abs() < 0.5);
+        assert!(m.score > 0.99, "{m:?}");
+    }
+    assert!(
+        (angle_diff_deg(hypotheses[0].angle_deg, hypotheses[1].angle_deg) - 180.0).abs() < 1.0,
+        "{hypotheses:?}"
+    );
+
+    assert_eq!(matcher.match_angles_at_best(view, 1).unwrap(), vec![best]);
+    assert!(matcher.match_angles_at_best(view, 0).unwrap().is_empty());
+}
+
+#[test]
+fn require_full_pyramid_rejects_shallow_image_pyramids() {
+    let tpl_data = make_template(32, 32);
+    let template = Template::new(tpl_data.clone(), 32, 32).unwrap();
+    let (img_width, img_height, x0, y0) = (48, 40, 9, 5);
+    let mut image = vec![0u8; img_width * img_height];
+    for y in 0..32 {
+        let row = (y0 + y) * img_width + x0;
+        image[row..row + 32].copy_from_slice(&tpl_data[y * 32..(y + 1) * 32]);
+    }
+    let view = ImageView::from_slice(&image, img_width, img_height).unwrap();
+
+    let compiled = Arc::new(
+        CompiledTemplate::compile_unrotated(&template, CompileConfigNoRot { max_levels: 3 })
+            .unwrap(),
+    );
+    let matcher = |max_image_levels, require_full_pyramid| {
+        Matcher::from_shared(Arc::clone(&compiled)).with_config(MatchConfig {
+            max_image_levels,
+            require_full_pyramid,
+            ..MatchConfig::default()
+        })
+    };
+
+    // Without the flag the search silently starts one level finer.
+    let best = matcher(2, false).match_image(view).unwrap();
+    assert!((best.x - x0 as f32).abs() <= 0.5 && (best.y - y0 as f32).abs() <= 0.5);
+
+    assert_eq!(
+        matcher(2, true).match_image(view).unwrap_err(),
+        CorrMatchError::InsufficientPyramidLevels {
+            available: 2,
+            required: 3,
+        }
+    );
+    assert!(matcher(3, true).match_image(view).is_ok());
+}
+
+#[test]
+fn require_full_pyramid_accepts_template_sized_images() {
+    let tpl_data = make_template(32, 32);
+    let template = Template::new(tpl_data.clone(), 32, 32).unwrap();
+    let compiled = Arc::new(
+        CompiledTemplate::compile_unrotated(&template, CompileConfigNoRot::default()).unwrap(),
+    );
+    let matcher = Matcher::from_shared(Arc::clone(&compiled)).with_config(MatchConfig {
+        require_full_pyramid: true,
+        ..MatchConfig::default()
+    });
+
+    // The tiniest accepted image is the template itself. Its pyramid halves
+    // the same way, so it still supplies every compiled level.
+    let view = ImageView::from_slice(&tpl_data, 32, 32).unwrap();
+    assert!(compiled.num_levels() >= 5);
+    let best = matcher.match_image(view).unwrap();
+    assert!(best.x.abs() <= 0.5 && best.y.abs() <= 0.5, "{best:?}");
+    assert!(best.score > 0.99);
+
+    // Anything smaller fails on size before the level check.
+    let tiny = ImageView::from_slice(&tpl_data[..31 * 32], 32, 31).unwrap();
+    assert!(matches!(
+        matcher.match_image(tiny),
+        Err(CorrMatchError::ImageSmallerThanTemplate { .. })
+    ));
+}
+
+#[test]
+fn combined_metric_finds_rotated_match_and_blends_both_terms() {
+    let (tpl_width, tpl_height) = (48, 40);
+    let tpl_data = make_template(tpl_width, tpl_height);
+    let template = Template::new(tpl_data.clone(), tpl_width, tpl_height).unwrap();
+    let tpl_view = ImageView::from_slice(&tpl_data, tpl_width, tpl_height).unwrap();
+    let (rotated, mask) = rotate_u8_bilinear_masked(tpl_view, 30.0, 0);
+
+    let (img_width, img_height) = (160, 128);
+    let (x0, y0) = (52, 38);
+    let mut image = vec![0u8; img_width * img_height];
+    for y in 0..tpl_height {
+        for x in 0..tpl_width {
+            let idx = y * tpl_width + x;
+            if mask[idx] == 1 {
+                image[(y0 + y) * img_width + x0 + x] = rotated.data()[idx];
+            }
+        }
+    }
+    let image_view = ImageView::from_slice(&image, img_width, img_height).unwrap();
+
+    let compiled = Arc::new(
+        CompiledTemplate::compile_rotated(
+            &template,
+            CompileConfig {
+                max_levels: 2,
+                coarse_step_deg: 30.0,
+                min_step_deg: 15.0,
+                ..CompileConfig::default()
+            },
+        )
+        .unwrap(),
+    );
+    let cfg = MatchConfig {
+        rotation: RotationMode::Enabled,
+        max_image_levels: 2,
+        ..MatchConfig::default()
+    };
+    let combined = Matcher::from_shared(compiled.clone()).with_config(MatchConfig {
+        metric: Metric::Combined {
+            zncc_weight: 0.7,
+            ssd_weight: 0.3,
+        },
+        ..cfg.clone()
+    });
+
+    let best = combined.match_image(image_view).unwrap();
+    assert!((best.x - x0 as f32).abs() < 1.0, "x {}", best.x);
+    assert!((best.y - y0 as f32).abs() < 1.0, "y {}", best.y);
+    assert!(
+        angle_diff_deg(best.angle_deg, 30.0) < 2.0,
+        "angle {}",
+        best.angle_deg
+    );
+    assert!(best.score > 0.99, "score {}", best.score);
+
+    let score_with = |metric| {
+        Matcher::from_shared(compiled.clone())
+            .with_config(MatchConfig {
+                metric,
+                ..cfg.clone()
+            })
+            .score_pose(image_view, x0 + 2, y0 + 1, 30.0)
+            .unwrap()
+    };
+    let zncc = score_with(Metric::Zncc);
+    let sse = -score_with(Metric::Ssd);
+    let valid = mask.iter().filter(|&&m| m != 0).count() as f32;
+    let expected = 0.7 * zncc + 0.3 * (1.0 - sse / (valid * 255.0 * 255.0));
+    let actual = combined
+        .score_pose(image_view, x0 + 2, y0 + 1, 30.0)
+        .unwrap();
+    assert!((actual - expected).abs() < 1e-4, "{actual} vs {expected}");
+
+    for (zncc_weight, ssd_weight, min_score) in [
+        (-0.5, 1.0, f32::NEG_INFINITY),
+        (0.0, 0.0, f32::NEG_INFINITY),
+        (f32::NAN, 1.0, f32::NEG_INFINITY),
+        (0.5, 0.5, 1.5),
+    ] {
+        let cfg = MatchConfig {
+            metric: Metric::Combined {
+                zncc_weight,
+                ssd_weight,
+            },
+            min_score,
+            ..MatchConfig::default()
+        };
+        assert!(matches!(
+            cfg.validate(),
+            Err(CorrMatchError::InvalidConfig { .. })
+        ));
+    }
+}
+
+#[test]
+fn deterministic_accumulation_matches_default_scores() {
+    let (tpl_width, tpl_height) = (48, 40);
+    let tpl_data = make_template(tpl_width, tpl_height);
+    let template = Template::new(tpl_data.clone(), tpl_width, tpl_height).unwrap();
+    let tpl_view = ImageView::from_slice(&tpl_data, tpl_width, tpl_height).unwrap();
+    let (rotated, mask) = rotate_u8_bilinear_masked(tpl_view, 30.0, 0);
+
+    let (img_width, img_height) = (160, 128);
+    let (x0, y0) = (52, 38);
+    let mut image = vec![0u8; img_width * img_height];
+    for y in 0..tpl_height {
+        for x in 0..tpl_width {
+            let idx = y * tpl_width + x;
+            if mask[idx] == 1 {
+                image[(y0 + y) * img_width + x0 + x] = rotated.data()[idx];
+            }
+        }
+    }
+    let image_view = ImageView::from_slice(&image, img_width, img_height).unwrap();
+
+    let compiled = Arc::new(
+        CompiledTemplate::compile_rotated(
+            &template,
+            CompileConfig {
+                max_levels: 2,
+                coarse_step_deg: 30.0,
+                min_step_deg: 15.0,
+                ..CompileConfig::default()
+            },
+        )
+        .unwrap(),
+    );
+    for metric in [
+        Metric::Zncc,
+        Metric::Ssd,
+        Metric::Combined {
+            zncc_weight: 0.5,
+            ssd_weight: 0.5,
+        },
+    ] {
+        let cfg = MatchConfig {
+            metric,
+            rotation: RotationMode::Enabled,
+            max_image_levels: 2,
+            ..MatchConfig::default()
+        };
+        let plain = Matcher::from_shared(compiled.clone())
+            .with_config(cfg.clone())
+            .match_image(image_view)
+            .unwrap();
+        let deterministic = Matcher::from_shared(compiled.clone())
+            .with_config(MatchConfig {
+                deterministic_accumulation: true,
+                ..cfg
+            })
+            .match_image(image_view)
+            .unwrap();
+        assert!(
+            (plain.x - deterministic.x).abs() < 1e-3
+                && (plain.y - deterministic.y).abs() < 1e-3
+                && angle_diff_deg(plain.angle_deg, deterministic.angle_deg) < 1e-3,
+            "{metric:?}: {plain:?} vs {deterministic:?}"
+        );
+        let tolerance = if matches!(metric, Metric::Ssd) {
+            1.0
+        } else {
+            1e-4
+        };
+        assert!(
+            (plain.score - deterministic.score).abs() <= tolerance,
+            "{metric:?}: {} vs {}",
+            plain.score,
+            deterministic.score
+        );
+    }
+}
+
+#[test]
+fn coarse_variance_ratio_keeps_match_on_flat_background() {
+    let (tpl_width, tpl_height) = (32, 24);
+    let tpl_data = make_template(tpl_width, tpl_height);
+    let template = Template::new(tpl_data.clone(), tpl_width, tpl_height).unwrap();
+
+    let (img_width, img_height) = (160, 120);
+    let (x0, y0) = (97, 41);
+    let mut image: Vec<u8> = (0..img_width * img_height)
+        .map(|i| 128 + ((i * 7 + i / img_width * 3) % 5) as u8)
+        .collect();
+    for y in 0..tpl_height {
+        let row = (y0 + y) * img_width + x0;
+        image[row..row + tpl_width].copy_from_slice(&tpl_data[y * tpl_width..(y + 1) * tpl_width]);
+    }
+    let image_view = ImageView::from_slice(&image, img_width, img_height).unwrap();
+
+    let compiled = Arc::new(
+        CompiledTemplate::compile_unrotated(&template, CompileConfigNoRot { max_levels: 3 })
+            .unwrap(),
+    );
+    let cfg = MatchConfig {
+        max_image_levels: 3,
+        ..MatchConfig::default()
+    };
+    let plain = Matcher::from_shared(compiled.clone())
+        .with_config(cfg.clone())
+        .match_image(image_view)
+        .unwrap();
+    let gated_matcher = Matcher::from_shared(compiled.clone()).with_config(MatchConfig {
+        coarse_variance_ratio: Some(4.0),
+        ..cfg.clone()
+    });
+    let gated = gated_matcher.match_image(image_view).unwrap();
+    assert!((gated.x - x0 as f32).abs() < 0.5, "x {}", gated.x);
+    assert!((gated.y - y0 as f32).abs() < 0.5, "y {}", gated.y);
+    assert_eq!(
+        (gated.x, gated.y, gated.score),
+        (plain.x, plain.y, plain.score)
+    );
+
+    for invalid in [
+        MatchConfig {
+            coarse_variance_ratio: Some(0.5),
+            ..cfg.clone()
+        },
+        MatchConfig {
+            coarse_variance_ratio: Some(f32::NAN),
+            ..cfg.clone()
+        },
+        MatchConfig {
+            metric: Metric::Ssd,
+            coarse_variance_ratio: Some(4.0),
+            ..cfg
+        },
+    ] {
+        assert!(matches!(
+            invalid.validate(),
+            Err(CorrMatchError::InvalidConfig { .. })
+        ));
+    }
+}
+
+#[test]
+fn match_angles_at_best_returns_a_partial_best_match_alone() {
+    let size = 32;
+    let tpl_data = make_template(size, size);
+    let template = Template::new(tpl_data.clone(), size, size).unwrap();
+    // The image shows the template's left 24 columns, so it is narrower than
+    // the template and the best match cannot fit inside it.
+    let cropped_width = size - 8;
+    let cropped: Vec<u8> = (0..size * cropped_width)
+        .map(|i| tpl_data[(i / cropped_width) * size + i % cropped_width])
+        .collect();
+    let view = ImageView::from_slice(&cropped, cropped_width, size).unwrap();
+
+    let compiled = CompiledTemplate::compile_rotated(
+        &template,
+        CompileConfig {
+            max_levels: 1,
+            coarse_step_deg: 15.0,
+            min_step_deg: 15.0,
+            ..CompileConfig::default()
+        },
+    )
+    .unwrap();
+    let matcher = Matcher::new(compiled).with_config(MatchConfig {
+        max_image_levels: 1,
+        rotation: RotationMode::Enabled,
+        allow_partial: true,
+        ..MatchConfig::default()
+    });
+
+    let best = matcher.match_image(view).unwrap();
+    assert!(best.x.abs() < 0.5 && best.y.abs() < 0.5, "{best:?}");
+    assert_eq!(matcher.match_angles_at_best(view, 2).unwrap(), vec![best]);
+}
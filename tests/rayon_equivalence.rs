@@ -2,8 +2,8 @@
 
 use corrmatch::lowlevel::rotate_u8_bilinear_masked;
 use corrmatch::{
-    CompileConfig, CompiledTemplate, ImageView, MatchConfig, Matcher, Metric, RotationMode,
-    Template,
+    CompileConfig, CompiledTemplate, Ensemble, FillStrategy, ImagePyramid, ImageView, MatchConfig,
+    Matcher, Metric, PyramidFilter, RotationMode, Template, TemplateBatch,
 };
 
 fn make_template(width: usize, height: usize) -> Vec<u8> {
@@ -18,6 +18,7 @@ fn make_template(width: usize, height: usize) -> Vec<u8> {
 }
 
 #[test]
+#[allow(deprecated)]
 fn parallel_matches_sequential_rotation_enabled() {
     let tpl_width = 48;
     let tpl_height = 36;
@@ -50,6 +51,7 @@ fn parallel_matches_sequential_rotation_enabled() {
             min_step_deg: 15.0,
             fill_value: 0,
             precompute_coarsest: true,
+            ..CompileConfig::default()
         },
     )
     .unwrap();
@@ -85,6 +87,7 @@ fn parallel_matches_sequential_rotation_enabled() {
                 min_step_deg: 15.0,
                 fill_value: 0,
                 precompute_coarsest: true,
+                ..CompileConfig::default()
             },
         )
         .unwrap(),
@@ -100,3 +103,222 @@ fn parallel_matches_sequential_rotation_enabled() {
     assert!((seq.angle_deg - par.angle_deg).abs() <= tol);
     assert!((seq.score - par.score).abs() <= tol);
 }
+
+#[test]
+fn parallel_precompute_matches_sequential() {
+    let tpl_width = 40;
+    let tpl_height = 32;
+    let template =
+        Template::new(make_template(tpl_width, tpl_height), tpl_width, tpl_height).unwrap();
+    let compile = |parallel| {
+        CompiledTemplate::compile_rotated(
+            &template,
+            CompileConfig {
+                max_levels: 2,
+                coarse_step_deg: 5.0,
+                min_step_deg: 2.5,
+                parallel,
+                ..CompileConfig::default()
+            },
+        )
+        .unwrap()
+    };
+    let seq = compile(false);
+    let par = compile(true);
+
+    let coarsest = seq.num_levels() - 1;
+    let grid = seq.angle_grid(coarsest).unwrap();
+    for idx in 0..grid.len() {
+        let a = seq.rotated_zncc_plan(coarsest, idx).unwrap();
+        let b = par.rotated_zncc_plan(coarsest, idx).unwrap();
+        assert_eq!(a.mask(), b.mask());
+        assert_eq!(a.t_prime(), b.t_prime());
+    }
+}
+
+#[test]
+fn parallel_ensemble_matches_sequential() {
+    let img_width = 120;
+    let img_height = 90;
+    let image: Vec<u8> = (0..img_width * img_height)
+        .map(|i| (((i % img_width) * 5 + (i / img_width) * 9) & 0xFF) as u8)
+        .collect();
+    let image_view = ImageView::from_slice(&image, img_width, img_height).unwrap();
+
+    let matchers: Vec<Matcher> = [(20, 16), (36, 30), (50, 40)]
+        .into_iter()
+        .map(|(x0, y0)| {
+            let (w, h) = (24usize, 20usize);
+            let mut data = Vec::with_capacity(w * h);
+            for y in 0..h {
+                let row = (y0 + y) * img_width + x0;
+                data.extend_from_slice(&image[row..row + w]);
+            }
+            let template = Template::new(data, w, h).unwrap();
+            let compiled = CompiledTemplate::compile_rotated(
+                &template,
+                CompileConfig {
+                    max_levels: 2,
+                    coarse_step_deg: 30.0,
+                    min_step_deg: 15.0,
+                    ..CompileConfig::default()
+                },
+            )
+            .unwrap();
+            Matcher::new(compiled).with_config(MatchConfig {
+                rotation: RotationMode::Enabled,
+                max_image_levels: 2,
+                ..MatchConfig::default()
+            })
+        })
+        .collect();
+
+    let ensemble = Ensemble::new(matchers).unwrap();
+    let seq = ensemble.match_image(image_view).unwrap();
+    let par = ensemble
+        .clone()
+        .with_parallel(true)
+        .match_image(image_view)
+        .unwrap();
+    assert_eq!(seq, par);
+}
+
+#[test]
+fn parallel_pyramid_is_byte_identical() {
+    // Odd sizes and a padded stride exercise the truncated last row/column.
+    let (width, height, stride) = (333, 215, 340);
+    let data: Vec<u8> = (0..stride * height)
+        .map(|i| (((i % stride) * 31) ^ ((i / stride) * 17) ^ (i / 7)) as u8)
+        .collect();
+    let view = ImageView::new(&data, width, height, stride).unwrap();
+
+    for filter in [PyramidFilter::Box2x2, PyramidFilter::OpenCvCompatible] {
+        let serial = ImagePyramid::build_u8_filtered(view, 6, filter).unwrap();
+        let parallel = ImagePyramid::build_u8_filtered_par(view, 6, filter).unwrap();
+        assert_eq!(serial.levels().len(), parallel.levels().len());
+        for (a, b) in serial.levels().iter().zip(parallel.levels()) {
+            assert_eq!((a.width(), a.height()), (b.width(), b.height()));
+            assert_eq!(a.data(), b.data(), "{filter:?}");
+        }
+    }
+
+    let serial = ImagePyramid::build_u8(view, 6).unwrap();
+    let parallel = ImagePyramid::build_u8_par(view, 6).unwrap();
+    for (a, b) in serial.levels().iter().zip(parallel.levels()) {
+        assert_eq!(a.data(), b.data());
+    }
+}
+
+#[test]
+fn parallel_template_batch_matches_sequential() {
+    let img_width = 128;
+    let img_height = 96;
+    let image = make_template(img_width, img_height);
+    let image_view = ImageView::from_slice(&image, img_width, img_height).unwrap();
+
+    let size = 16;
+    let templates: Vec<Template> = (0..8)
+        .map(|i| {
+            let (x0, y0) = (7 + 13 * i, 5 + 9 * i);
+            let mut data = Vec::with_capacity(size * size);
+            for y in 0..size {
+                let row = (y0 + y) * img_width + x0;
+                data.extend_from_slice(&image[row..row + size]);
+            }
+            Template::new(data, size, size).unwrap()
+        })
+        .collect();
+
+    let compile = CompileConfig {
+        max_levels: 2,
+        coarse_step_deg: 30.0,
+        min_step_deg: 15.0,
+        ..CompileConfig::default()
+    };
+    let cfg = MatchConfig {
+        rotation: RotationMode::Enabled,
+        max_image_levels: 2,
+        ..MatchConfig::default()
+    };
+    let seq = TemplateBatch::compile_rotated(&templates, compile.clone(), cfg.clone())
+        .unwrap()
+        .match_image(image_view)
+        .unwrap();
+    let par = TemplateBatch::compile_rotated(
+        &templates,
+        compile,
+        MatchConfig {
+            parallel: true,
+            ..cfg
+        },
+    )
+    .unwrap()
+    .match_image(image_view)
+    .unwrap();
+    assert_eq!(seq, par);
+}
+
+#[test]
+fn parallel_matches_sequential_ssd_rotation_enabled() {
+    let tpl_width = 48;
+    let tpl_height = 36;
+    let tpl_data = make_template(tpl_width, tpl_height);
+    let template = Template::new(tpl_data.clone(), tpl_width, tpl_height).unwrap();
+
+    let tpl_view = ImageView::from_slice(&tpl_data, tpl_width, tpl_height).unwrap();
+    let (rotated, mask) = rotate_u8_bilinear_masked(tpl_view, 30.0, 0);
+
+    let img_width = 180;
+    let img_height = 140;
+    let (x0, y0) = (50, 40);
+    let mut image = vec![0u8; img_width * img_height];
+    for y in 0..tpl_height {
+        for x in 0..tpl_width {
+            let idx = y * tpl_width + x;
+            if mask[idx] == 1 {
+                image[(y0 + y) * img_width + (x0 + x)] = rotated.data()[idx];
+            }
+        }
+    }
+    let image_view = ImageView::from_slice(&image, img_width, img_height).unwrap();
+
+    let compile = CompileConfig {
+        max_levels: 2,
+        coarse_step_deg: 30.0,
+        min_step_deg: 15.0,
+        fill_strategy: FillStrategy::Constant(0),
+        precompute_coarsest: true,
+        ..CompileConfig::default()
+    };
+    let cfg = MatchConfig {
+        metric: Metric::Ssd,
+        rotation: RotationMode::Enabled,
+        max_image_levels: 2,
+        beam_width: 6,
+        coarse_per_angle_topk: 3,
+        refine_per_angle_topk: 3,
+        roi_radius: 6,
+        nms_radius: 4,
+        angle_half_range_steps: 1,
+        ..MatchConfig::default()
+    };
+    let seq_matcher =
+        Matcher::new(CompiledTemplate::compile_rotated(&template, compile.clone()).unwrap())
+            .with_config(cfg.clone());
+    let par_matcher = Matcher::new(CompiledTemplate::compile_rotated(&template, compile).unwrap())
+        .with_config(MatchConfig {
+            parallel: true,
+            ..cfg
+        });
+
+    // Masked SSD sums are accumulated per angle, so splitting the scan over
+    // angles must reproduce the sequential result exactly.
+    assert_eq!(
+        seq_matcher.match_image(image_view).unwrap(),
+        par_matcher.match_image(image_view).unwrap()
+    );
+    assert_eq!(
+        seq_matcher.match_image_topk(image_view, 4).unwrap(),
+        par_matcher.match_image_topk(image_view, 4).unwrap()
+    );
+}
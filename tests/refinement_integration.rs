@@ -1,6 +1,6 @@
 use corrmatch::{
-    CompileConfig, CompileConfigNoRot, CompiledTemplate, ImageView, MatchConfig, Matcher,
-    RotationMode, Template,
+    CompileConfig, CompileConfigNoRot, CompiledTemplate, CorrMatchError, ImageView, Match,
+    MatchConfig, Matcher, RotationMode, Template,
 };
 
 fn make_template(width: usize, height: usize) -> Vec<u8> {
@@ -39,6 +39,7 @@ fn angle_diff_deg(a: f32, b: f32) -> f32 {
 }
 
 #[test]
+#[allow(deprecated)]
 fn refinement_handles_border_candidates() {
     let tpl_width = 12;
     let tpl_height = 10;
@@ -78,6 +79,7 @@ fn refinement_handles_border_candidates() {
 }
 
 #[test]
+#[allow(deprecated)]
 fn refinement_keeps_center_angle_on_symmetric_template() {
     let tpl_width = 17;
     let tpl_height = 13;
@@ -103,6 +105,7 @@ fn refinement_keeps_center_angle_on_symmetric_template() {
             min_step_deg: 30.0,
             fill_value: 0,
             precompute_coarsest: true,
+            ..CompileConfig::default()
         },
     )
     .unwrap();
@@ -123,3 +126,348 @@ fn refinement_keeps_center_angle_on_symmetric_template() {
 
     assert!(angle_diff_deg(best.angle_deg, 0.0) <= step * 0.25);
 }
+
+#[test]
+fn refine_polishes_external_seed_without_search() {
+    let tpl_width = 17;
+    let tpl_height = 13;
+    let tpl_data = make_template(tpl_width, tpl_height);
+    let template = Template::new(tpl_data.clone(), tpl_width, tpl_height).unwrap();
+
+    let img_width = 60;
+    let img_height = 50;
+    let x0 = 21;
+    let y0 = 18;
+    let mut image = vec![0u8; img_width * img_height];
+    for y in 0..tpl_height {
+        for x in 0..tpl_width {
+            image[(y0 + y) * img_width + (x0 + x)] = tpl_data[y * tpl_width + x];
+        }
+    }
+    let image_view = ImageView::from_slice(&image, img_width, img_height).unwrap();
+
+    let compiled = CompiledTemplate::compile_rotated(
+        &template,
+        CompileConfig {
+            max_levels: 1,
+            coarse_step_deg: 10.0,
+            min_step_deg: 10.0,
+            precompute_coarsest: false,
+            ..CompileConfig::default()
+        },
+    )
+    .unwrap();
+    let step = compiled.angle_grid(0).unwrap().step_deg();
+    let matcher = Matcher::new(compiled).with_config(MatchConfig {
+        max_image_levels: 1,
+        rotation: RotationMode::Enabled,
+        ..MatchConfig::default()
+    });
+
+    let seed = Match {
+        x: x0 as f32 + 0.4,
+        y: y0 as f32 - 0.3,
+        angle_deg: 3.0,
+        score: 0.0,
+        peak_sharpness: None,
+    };
+    let refined = matcher.refine(image_view, seed).unwrap();
+    assert!((refined.x - x0 as f32).abs() < 0.5);
+    assert!((refined.y - y0 as f32).abs() < 0.5);
+    assert!(angle_diff_deg(refined.angle_deg, 0.0) <= step * 0.5);
+    assert!(refined.score > 0.99);
+
+    let outside = Match {
+        x: (img_width - 2) as f32,
+        ..seed
+    };
+    let err = matcher.refine(image_view, outside).unwrap_err();
+    assert!(matches!(err, CorrMatchError::RoiOutOfBounds { .. }));
+}
+
+#[test]
+fn disabling_final_refinement_reports_integer_seed() {
+    let tpl_width = 17;
+    let tpl_height = 13;
+    let tpl_data = make_template(tpl_width, tpl_height);
+    let template = Template::new(tpl_data.clone(), tpl_width, tpl_height).unwrap();
+
+    let img_width = 64;
+    let img_height = 48;
+    let x0 = 23;
+    let y0 = 15;
+    let mut image = vec![0u8; img_width * img_height];
+    for y in 0..tpl_height {
+        for x in 0..tpl_width {
+            image[(y0 + y) * img_width + (x0 + x)] = tpl_data[y * tpl_width + x];
+        }
+    }
+    let image_view = ImageView::from_slice(&image, img_width, img_height).unwrap();
+
+    let compiled = CompiledTemplate::compile_rotated(
+        &template,
+        CompileConfig {
+            max_levels: 1,
+            coarse_step_deg: 15.0,
+            min_step_deg: 15.0,
+            precompute_coarsest: false,
+            ..CompileConfig::default()
+        },
+    )
+    .unwrap();
+    let matcher = Matcher::new(compiled).with_config(MatchConfig {
+        max_image_levels: 1,
+        rotation: RotationMode::Enabled,
+        final_refinement: false,
+        ..MatchConfig::default()
+    });
+    let best = matcher.match_image(image_view).unwrap();
+
+    assert_eq!((best.x, best.y), (x0 as f32, y0 as f32));
+    let grid = matcher.compiled().angle_grid(0).unwrap();
+    let snapped = grid.angle_at(grid.nearest_index(best.angle_deg));
+    assert_eq!(best.angle_deg, snapped);
+    assert!(best.score > 0.99);
+}
+
+fn smooth_pattern(u: f32, v: f32) -> f32 {
+    128.0
+        + 60.0 * (u * 0.45).sin()
+        + 40.0 * (v * 0.35 + 0.7).cos()
+        + 15.0 * (u * 0.2 + v * 0.3).sin()
+}
+
+/// Vertex offset of the parabola through scores at `x - 1`, `x` and `x + 1`.
+fn parabola_offset(fm: f64, f0: f64, fp: f64) -> f64 {
+    0.5 * (fm - fp) / (fm - 2.0 * f0 + fp)
+}
+
+#[test]
+fn ssd_final_refinement_fits_negative_sse() {
+    let tpl_width = 24;
+    let tpl_height = 18;
+    let tpl_data: Vec<u8> = (0..tpl_width * tpl_height)
+        .map(|i| smooth_pattern((i % tpl_width) as f32, (i / tpl_width) as f32).round() as u8)
+        .collect();
+    let template = Template::new(tpl_data.clone(), tpl_width, tpl_height).unwrap();
+
+    // A subpixel shift plus a horizontal gain ramp puts the SSD and ZNCC
+    // optima at different subpixel positions.
+    let img_width = 64;
+    let img_height = 48;
+    let (x0, y0, shift) = (20usize, 14usize, 0.2f32);
+    let image: Vec<u8> = (0..img_width * img_height)
+        .map(|i| {
+            let x = (i % img_width) as f32;
+            let y = (i / img_width) as f32;
+            let (u, v) = (x - x0 as f32, y - y0 as f32);
+            if u < -2.0 || v < -2.0 || u > (tpl_width + 2) as f32 || v > (tpl_height + 2) as f32 {
+                return 0;
+            }
+            let gain = 1.0 + 0.02 * u;
+            let value = gain * smooth_pattern(x - x0 as f32 - shift, y - y0 as f32);
+            value.round().clamp(0.0, 255.0) as u8
+        })
+        .collect();
+    let image_view = ImageView::from_slice(&image, img_width, img_height).unwrap();
+
+    let neg_sse = |x: usize| -> f64 {
+        let mut sum = 0.0f64;
+        for ty in 0..tpl_height {
+            for tx in 0..tpl_width {
+                let diff = image[(y0 + ty) * img_width + x + tx] as f64
+                    - tpl_data[ty * tpl_width + tx] as f64;
+                sum += diff * diff;
+            }
+        }
+        -sum
+    };
+    let zncc = |x: usize| -> f64 {
+        let n = (tpl_width * tpl_height) as f64;
+        let (mut st, mut si, mut stt, mut sii, mut sti) = (0.0, 0.0, 0.0, 0.0, 0.0);
+        for ty in 0..tpl_height {
+            for tx in 0..tpl_width {
+                let i = image[(y0 + ty) * img_width + x + tx] as f64;
+                let t = tpl_data[ty * tpl_width + tx] as f64;
+                st += t;
+                si += i;
+                stt += t * t;
+                sii += i * i;
+                sti += t * i;
+            }
+        }
+        (sti - st * si / n) / ((stt - st * st / n) * (sii - si * si / n)).sqrt()
+    };
+    let ssd_x = x0 as f64 + parabola_offset(neg_sse(x0 - 1), neg_sse(x0), neg_sse(x0 + 1));
+    let zncc_x = x0 as f64 + parabola_offset(zncc(x0 - 1), zncc(x0), zncc(x0 + 1));
+    assert!((ssd_x - zncc_x).abs() > 0.01, "optima should differ");
+
+    let compiled =
+        CompiledTemplate::compile_unrotated(&template, CompileConfigNoRot { max_levels: 1 })
+            .unwrap();
+    let matcher = Matcher::new(compiled).with_config(MatchConfig {
+        metric: corrmatch::Metric::Ssd,
+        max_image_levels: 1,
+        rotation: RotationMode::Disabled,
+        ..MatchConfig::default()
+    });
+    let best = matcher.match_image(image_view).unwrap();
+
+    assert_eq!(best.y.round() as usize, y0);
+    assert!(best.score <= 0.0, "SSD scores are negative SSE");
+    assert!(
+        (best.x as f64 - ssd_x).abs() < 1e-3,
+        "refined x {} should sit on the SSD vertex {ssd_x}, not ZNCC {zncc_x}",
+        best.x
+    );
+}
+
+#[test]
+fn refinement_fits_one_sided_at_the_image_edge() {
+    let tpl_width = 24;
+    let tpl_height = 18;
+    let tpl_data: Vec<u8> = (0..tpl_width * tpl_height)
+        .map(|i| smooth_pattern((i % tpl_width) as f32, (i / tpl_width) as f32).round() as u8)
+        .collect();
+    let template = Template::new(tpl_data, tpl_width, tpl_height).unwrap();
+
+    // The true position sits 0.35 px inside the last valid x, so the integer
+    // peak lands exactly on the right edge and its +1 neighbor is outside.
+    let (max_x, max_y) = (30usize, 22usize);
+    let (img_width, img_height) = (max_x + tpl_width, max_y + tpl_height);
+    let (true_x, y0) = (max_x as f32 - 0.35, 12usize);
+    let image: Vec<u8> = (0..img_width * img_height)
+        .map(|i| {
+            let u = (i % img_width) as f32 - true_x;
+            let v = (i / img_width) as f32 - y0 as f32;
+            if u < -2.0 || v < -2.0 || v > (tpl_height + 2) as f32 {
+                return 0;
+            }
+            smooth_pattern(u, v).round().clamp(0.0, 255.0) as u8
+        })
+        .collect();
+    let image_view = ImageView::from_slice(&image, img_width, img_height).unwrap();
+
+    let run = |rotation: RotationMode, final_refinement: bool| {
+        let cfg = MatchConfig {
+            rotation,
+            max_image_levels: 1,
+            final_refinement,
+            ..MatchConfig::default()
+        };
+        let compiled = match rotation {
+            RotationMode::Disabled => {
+                CompiledTemplate::compile_unrotated(&template, CompileConfigNoRot { max_levels: 1 })
+            }
+            RotationMode::Enabled => CompiledTemplate::compile_rotated(
+                &template,
+                CompileConfig {
+                    max_levels: 1,
+                    ..CompileConfig::default()
+                },
+            ),
+        };
+        let matcher = Matcher::new(compiled.unwrap()).with_config(cfg);
+        matcher.match_image(image_view).unwrap()
+    };
+
+    for rotation in [RotationMode::Disabled, RotationMode::Enabled] {
+        let seed = run(rotation, false);
+        assert_eq!((seed.x, seed.y), (max_x as f32, y0 as f32), "{rotation:?}");
+
+        let best = run(rotation, true);
+        assert_eq!(best.y.round() as usize, y0);
+        assert!(best.x <= max_x as f32);
+        assert!(
+            (best.x - true_x).abs() < 0.15,
+            "{rotation:?}: refined x {} should approach {true_x}, not stick to the edge",
+            best.x
+        );
+    }
+}
+
+/// A bright square with soft sigmoid edges, centered at the origin.
+fn smooth_square(u: f32, v: f32, sigma: f32) -> f32 {
+    let sig = |t: f32| 1.0 / (1.0 + (-t / sigma).exp());
+    40.0 + 180.0 * sig(u + 5.0) * sig(5.0 - u) * sig(v + 5.0) * sig(5.0 - v)
+}
+
+#[test]
+fn subpixel_resample_improves_smooth_localization() {
+    let tpl_size = 21;
+    let center = (tpl_size / 2) as f32;
+    let sigma = 2.0;
+    let tpl_data: Vec<u8> = (0..tpl_size * tpl_size)
+        .map(|i| {
+            let u = (i % tpl_size) as f32 - center;
+            let v = (i / tpl_size) as f32 - center;
+            smooth_square(u, v, sigma).round() as u8
+        })
+        .collect();
+    let template = Template::new(tpl_data, tpl_size, tpl_size).unwrap();
+
+    let img_size = 64;
+    let (true_x, true_y) = (20.3f32, 24.7f32);
+    let image: Vec<u8> = (0..img_size * img_size)
+        .map(|i| {
+            let u = (i % img_size) as f32 - true_x - center;
+            let v = (i / img_size) as f32 - true_y - center;
+            smooth_square(u, v, sigma).round() as u8
+        })
+        .collect();
+    let image_view = ImageView::from_slice(&image, img_size, img_size).unwrap();
+
+    let run = |rotation: RotationMode, subpixel_resample: bool| {
+        let cfg = MatchConfig {
+            rotation,
+            max_image_levels: 1,
+            subpixel_resample,
+            ..MatchConfig::default()
+        };
+        let matcher = match rotation {
+            RotationMode::Disabled => {
+                let compiled = CompiledTemplate::compile_unrotated(
+                    &template,
+                    CompileConfigNoRot { max_levels: 1 },
+                )
+                .unwrap();
+                Matcher::new(compiled).with_config(cfg)
+            }
+            RotationMode::Enabled => {
+                let compiled = CompiledTemplate::compile_rotated(
+                    &template,
+                    CompileConfig {
+                        max_levels: 1,
+                        ..CompileConfig::default()
+                    },
+                )
+                .unwrap();
+                Matcher::new(compiled).with_config(cfg)
+            }
+        };
+        let best = matcher.match_image(image_view).unwrap();
+        ((best.x - true_x).powi(2) + (best.y - true_y).powi(2)).sqrt()
+    };
+
+    for rotation in [RotationMode::Disabled, RotationMode::Enabled] {
+        let grid_err = run(rotation, false);
+        let resampled_err = run(rotation, true);
+        assert!(
+            resampled_err < 0.5 * grid_err,
+            "{rotation:?}: resampled error {resampled_err} vs grid error {grid_err}"
+        );
+    }
+}
+
+#[test]
+fn subpixel_resample_rejects_ssd() {
+    let cfg = MatchConfig {
+        metric: corrmatch::Metric::Ssd,
+        subpixel_resample: true,
+        ..MatchConfig::default()
+    };
+    assert!(matches!(
+        cfg.validate(),
+        Err(CorrMatchError::InvalidConfig { .. })
+    ));
+}
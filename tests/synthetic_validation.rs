@@ -4,7 +4,7 @@
 //! produces results within acceptable tolerances.
 
 use corrmatch::{
-    CompileConfig, CompileConfigNoRot, CompiledTemplate, MatchConfig, Matcher, Metric,
+    CompileConfig, CompileConfigNoRot, CompiledTemplate, Match, MatchConfig, Matcher, Metric,
     RotationMode, Template,
 };
 use serde::Deserialize;
@@ -74,6 +74,7 @@ struct CompileConfigJson {
 }
 
 impl Default for CompileConfigJson {
+    #[allow(deprecated)]
     fn default() -> Self {
         let cfg = CompileConfig::default();
         Self {
@@ -137,6 +138,7 @@ struct MatchConfigJson {
 }
 
 impl Default for MatchConfigJson {
+    #[allow(deprecated)]
     fn default() -> Self {
         let cfg = MatchConfig::default();
         Self {
@@ -214,6 +216,7 @@ fn wrap_angle_diff(a: f32, b: f32) -> f32 {
 }
 
 /// Runs a single synthetic test case.
+#[allow(deprecated)]
 fn run_case(case_dir: &Path) -> Result<(), String> {
     // Load meta.json
     let meta_path = case_dir.join("meta.json");
@@ -254,6 +257,7 @@ fn run_case(case_dir: &Path) -> Result<(), String> {
                 min_step_deg: config.compile.min_step_deg,
                 fill_value: config.compile.fill_value,
                 precompute_coarsest: config.compile.precompute_coarsest,
+                ..CompileConfig::default()
             },
         )
         .map_err(|e| format!("Failed to compile template: {}", e))?
@@ -280,6 +284,7 @@ fn run_case(case_dir: &Path) -> Result<(), String> {
         angle_half_range_steps: config.match_cfg.angle_half_range_steps,
         min_var_i: config.match_cfg.min_var_i,
         min_score: config.match_cfg.min_score,
+        ..MatchConfig::default()
     });
 
     // Create image view
@@ -292,12 +297,32 @@ fn run_case(case_dir: &Path) -> Result<(), String> {
         .map_err(|e| format!("Matching failed: {}", e))?;
 
     // Validate results
-    if !meta.present {
+    if meta.present && meta.instances.is_empty() {
+        return Err("No ground truth instances in meta.json".to_string());
+    }
+    let expected = meta.present.then(|| &meta.instances[0]);
+    check_result(
+        &matches,
+        expected,
+        rotation_enabled,
+        matches!(config.match_cfg.metric, MetricConfig::Zncc),
+        score_threshold,
+    )
+}
+
+/// Checks the best match against the expected instance, or for a negative
+/// case (`expected` is `None`) that no ZNCC match clears the threshold.
+fn check_result(
+    matches: &[Match],
+    expected: Option<&Instance>,
+    rotation_enabled: bool,
+    is_zncc: bool,
+    score_threshold: f32,
+) -> Result<(), String> {
+    let Some(expected) = expected else {
         // Negative case: score should be low
         if let Some(result) = matches.first() {
-            if matches!(config.match_cfg.metric, MetricConfig::Zncc)
-                && result.score >= score_threshold
-            {
+            if is_zncc && result.score >= score_threshold {
                 return Err(format!(
                     "Negative case has unexpectedly high score: {:.4}",
                     result.score
@@ -305,19 +330,13 @@ fn run_case(case_dir: &Path) -> Result<(), String> {
             }
         }
         return Ok(());
-    }
+    };
 
     // Positive case: validate against ground truth
-    if meta.instances.is_empty() {
-        return Err("No ground truth instances in meta.json".to_string());
-    }
-
     let result = matches
         .first()
         .ok_or_else(|| "No match found".to_string())?;
 
-    let expected = &meta.instances[0];
-
     // Check position
     let dx = (result.x - expected.x).abs();
     let dy = (result.y - expected.y).abs();
@@ -348,7 +367,7 @@ fn run_case(case_dir: &Path) -> Result<(), String> {
     }
 
     // Check score for ZNCC
-    if matches!(config.match_cfg.metric, MetricConfig::Zncc) && result.score < score_threshold {
+    if is_zncc && result.score < score_threshold {
         return Err(format!(
             "Score {:.4} below threshold {:.4}",
             result.score, score_threshold
@@ -401,3 +420,77 @@ fn test_synthetic_cases() {
         panic!("{} test case(s) failed", failures.len());
     }
 }
+
+/// Seed for the in-memory suites. Every generated case is asserted; the
+/// occlusion and distractor cases depend on where the seed places the
+/// template, so a new seed needs the whole suite re-checked.
+#[cfg(feature = "testutil")]
+const GENERATED_SEED: u64 = 12;
+
+#[cfg(feature = "testutil")]
+fn run_generated_case(case: &corrmatch::testutil::SynthCase) -> Result<(), String> {
+    let compiled = case
+        .compile_template()
+        .map_err(|e| format!("Failed to compile template: {}", e))?;
+    let matches = Matcher::new(compiled)
+        .with_config(case.match_cfg.clone())
+        .match_image_topk(case.image.view(), 1)
+        .map_err(|e| format!("Matching failed: {}", e))?;
+    let expected = case.instances.first().map(|inst| Instance {
+        x: inst.x as f32,
+        y: inst.y as f32,
+        angle_deg: inst.angle_deg,
+    });
+    check_result(
+        &matches,
+        expected.as_ref().filter(|_| case.present),
+        case.match_cfg.rotation == RotationMode::Enabled,
+        case.match_cfg.metric == Metric::Zncc,
+        min_score_threshold(case.case_id),
+    )
+}
+
+#[cfg(feature = "testutil")]
+fn run_generated_suites(suites: &[corrmatch::testutil::Suite], include_slow: bool) {
+    use corrmatch::testutil::generate;
+
+    let mut failures = Vec::new();
+    for &suite in suites {
+        for case in generate(suite, GENERATED_SEED).expect("case generation failed") {
+            // Single-level cases scan the full image at every angle and take
+            // tens of seconds in debug builds.
+            if !include_slow && case.case_id.ends_with("_single_level") {
+                continue;
+            }
+            match run_generated_case(&case) {
+                Ok(()) => println!("PASS: {}", case.case_id),
+                Err(e) => {
+                    println!("FAIL: {} - {}", case.case_id, e);
+                    failures.push(case.case_id);
+                }
+            }
+        }
+    }
+    assert!(failures.is_empty(), "failed cases: {failures:?}");
+}
+
+/// Runs the in-memory suites from `corrmatch::testutil`, which need neither
+/// the Python generator nor PNG files.
+#[test]
+#[cfg(feature = "testutil")]
+fn test_generated_cases() {
+    use corrmatch::testutil::Suite;
+
+    run_generated_suites(&[Suite::Smoke, Suite::Standard], false);
+}
+
+/// Includes the slow single-level cases; run with
+/// `cargo test --release --features testutil -- --ignored`.
+#[test]
+#[ignore]
+#[cfg(feature = "testutil")]
+fn test_generated_cases_full() {
+    use corrmatch::testutil::Suite;
+
+    run_generated_suites(&[Suite::Standard], true);
+}